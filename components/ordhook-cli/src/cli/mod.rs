@@ -1,30 +1,27 @@
 use crate::config::file::ConfigFile;
-use crate::config::generator::generate_config;
+use crate::config::generator::{generate_config, generate_config_template};
 use clap::{Parser, Subcommand};
 use hiro_system_kit;
-use ordhook::chainhook_sdk::bitcoincore_rpc::{Auth, Client, RpcApi};
 use ordhook::chainhook_sdk::chainhooks::types::{BitcoinChainhookSpecification, HttpHook};
 use ordhook::chainhook_sdk::chainhooks::types::{
     BitcoinPredicateType, ChainhookFullSpecification, HookAction, OrdinalOperations,
 };
-use ordhook::chainhook_sdk::indexer::bitcoin::{
-    build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
-};
 use ordhook::chainhook_sdk::observer::BitcoinConfig;
 use ordhook::chainhook_sdk::types::{BitcoinBlockData, TransactionIdentifier};
 use ordhook::chainhook_sdk::utils::BlockHeights;
 use ordhook::chainhook_sdk::utils::Context;
-use ordhook::config::Config;
-use ordhook::core::new_traversals_lazy_cache;
-use ordhook::core::pipeline::download_and_pipeline_blocks;
+use ordhook::config::{Config, LogFormat};
 use ordhook::core::pipeline::processors::block_archiving::start_block_archiving_processor;
 use ordhook::core::pipeline::processors::start_inscription_indexing_processor;
+use ordhook::core::pipeline::{download_and_pipeline_blocks, PostProcessorRegistry};
 use ordhook::core::protocol::inscription_parsing::parse_inscriptions_and_standardize_block;
 use ordhook::core::protocol::satoshi_numbering::compute_satoshi_number;
+use ordhook::core::{check_start_block_against_indexed_height, new_traversals_lazy_cache};
 use ordhook::db::{
     delete_data_in_ordhook_db, find_all_inscription_transfers, find_all_inscriptions_in_block,
     find_all_transfers_in_block, find_block_bytes_at_block_height, find_inscription_with_id,
     find_last_block_inserted, find_latest_inscription_block_height, find_missing_blocks,
+    for_each_inscription_in_block_range, for_each_inscription_transfer_in_block_range,
     get_default_ordhook_db_file_path, initialize_ordhook_db, open_ordhook_db_conn_rocks_db_loop,
     open_readonly_ordhook_db_conn, open_readonly_ordhook_db_conn_rocks_db,
     open_readwrite_ordhook_db_conn, BlockBytesCursor,
@@ -34,7 +31,8 @@ use ordhook::hex;
 use ordhook::scan::bitcoin::scan_bitcoin_chainstate_via_rpc_using_predicate;
 use ordhook::service::observers::initialize_observers_db;
 use ordhook::service::{start_observer_forwarding, Service};
-use reqwest::Client as HttpClient;
+use ordhook::utils::new_json_logger;
+use ordhook::utils::rpc::RateLimitedRpcClient;
 use std::io::{BufReader, Read};
 use std::path::PathBuf;
 use std::process;
@@ -47,6 +45,10 @@ use std::time::Duration;
 struct Opts {
     #[clap(subcommand)]
     command: Command,
+    /// TOML file overlaid on the loaded configuration, e.g. for per-machine settings: only
+    /// the keys it sets are replaced
+    #[clap(long = "override-config", global = true)]
+    override_config: Option<String>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -63,6 +65,97 @@ enum Command {
     /// Perform maintenance operations on local databases
     #[clap(subcommand)]
     Db(OrdhookDbCommand),
+    /// Inspect sats
+    #[clap(subcommand)]
+    Sat(SatCommand),
+    /// Inscription creation helpers (nothing is broadcasted)
+    #[clap(subcommand)]
+    Inscribe(InscribeCommand),
+    /// Debugging tools
+    #[clap(subcommand)]
+    Debug(DebugCommand),
+    /// Decode the data stored locally, without querying bitcoind
+    #[clap(subcommand)]
+    Inspect(InspectCommand),
+    /// Keep indexing the new blocks, polling bitcoind for its chain tip
+    #[clap(name = "watch", bin_name = "watch")]
+    Watch(WatchCommand),
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum DebugCommand {
+    /// Run the sat traversal of a transaction input and print the result as JSON
+    #[clap(name = "traversal", bin_name = "traversal")]
+    Traversal(DebugTraversalCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DebugTraversalCommand {
+    /// Transaction id
+    #[clap(long = "tx-id")]
+    pub tx_id: String,
+    /// Input index
+    #[clap(long = "input")]
+    pub input: usize,
+    /// Block height of the transaction (looked up in hord.sqlite when omitted)
+    #[clap(long = "block-height")]
+    pub block_height: Option<u64>,
+    /// Log every step of the traversal at debug level
+    #[clap(long = "verbose-traversal")]
+    pub verbose_traversal: bool,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum InspectCommand {
+    /// Print the sats consumed and assigned by each transaction of a compacted block, with
+    /// the inscriptions revealed and transferred
+    #[clap(name = "block", bin_name = "block")]
+    Block(InspectBlockCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct InspectBlockCommand {
+    /// Block height
+    pub block_height: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum SatCommand {
+    /// Retrieve the genesis block, epoch, rarity and name of a sat
+    #[clap(name = "info", bin_name = "info")]
+    Info(SatInfoCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct SatInfoCommand {
+    /// Sat number
+    pub sat: u64,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum InscribeCommand {
+    /// Estimate the fee of the reveal transaction of an inscription
+    #[clap(name = "estimate", bin_name = "estimate")]
+    Estimate(InscribeEstimateCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct InscribeEstimateCommand {
+    /// Content type of the inscription (e.g. image/png)
+    #[clap(long = "content-type")]
+    pub content_type: String,
+    /// Path of the file to inscribe
+    #[clap(long = "content-file")]
+    pub content_file: String,
+    /// Fee rate, in sat/vB
+    #[clap(long = "fee-rate", default_value = "1.0")]
+    pub fee_rate: f64,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -76,6 +169,9 @@ enum ScanCommand {
     /// Retrieve activities for a given inscription
     #[clap(name = "transaction", bin_name = "transaction")]
     Transaction(ScanTransactionCommand),
+    /// Show how the sats and inscriptions of a transaction's inputs are assigned to its outputs
+    #[clap(name = "tx", bin_name = "tx")]
+    Tx(ScanTxCommand),
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -86,6 +182,20 @@ struct ScanBlocksCommand {
     /// List of blocks (--blocks 767430,767431,767433,800000)
     #[clap(long = "blocks", conflicts_with = "interval")]
     pub blocks: Option<String>,
+    /// Starting block, when scanning without --interval or --blocks (defaults to genesis)
+    #[clap(
+        long = "from-block",
+        conflicts_with = "interval",
+        conflicts_with = "blocks"
+    )]
+    pub from_block: Option<u64>,
+    /// Ending block, when scanning without --interval or --blocks (defaults to the last block stored)
+    #[clap(
+        long = "to-block",
+        conflicts_with = "interval",
+        conflicts_with = "blocks"
+    )]
+    pub to_block: Option<u64>,
     /// Target Regtest network
     #[clap(
         long = "regtest",
@@ -197,6 +307,24 @@ struct ScanTransactionCommand {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ScanTxCommand {
+    /// Transaction id
+    pub txid: String,
+    /// Raw transaction hex, instead of fetching it from bitcoind
+    #[clap(long = "tx-hex")]
+    pub tx_hex: Option<String>,
+    /// Block height of the transaction (looked up through bitcoind when omitted)
+    #[clap(long = "block-height")]
+    pub block_height: Option<u64>,
+    /// Print the result as JSON
+    #[clap(long = "json")]
+    pub json: bool,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 #[derive(Subcommand, PartialEq, Clone, Debug)]
 enum RepairCommand {
     /// Rewrite blocks data in hord.rocksdb
@@ -230,6 +358,9 @@ struct RepairStorageCommand {
     /// Display debug logs
     #[clap(short, long, action = clap::ArgAction::SetTrue)]
     pub debug: Option<bool>,
+    /// Compute inscriptions data without writing to the databases
+    #[clap(long = "dry-run", action = clap::ArgAction::SetTrue)]
+    pub dry_run: Option<bool>,
 }
 
 impl RepairStorageCommand {
@@ -268,6 +399,12 @@ enum ConfigCommand {
     /// Generate new config
     #[clap(name = "new", bin_name = "new", aliases = &["generate"])]
     New(NewConfig),
+    /// Write a config file listing every setting, with its default and a description
+    #[clap(name = "init", bin_name = "init")]
+    Init(InitConfig),
+    /// Print the resolved config, with defaults applied
+    #[clap(name = "dump", bin_name = "dump")]
+    Dump(DumpConfig),
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -295,6 +432,70 @@ struct NewConfig {
     pub mainnet: bool,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct InitConfig {
+    /// Path of the config file written
+    #[clap(long = "output", default_value = "Ordhook.toml")]
+    pub output: String,
+    /// Target Regtest network
+    #[clap(
+        long = "regtest",
+        conflicts_with = "testnet",
+        conflicts_with = "mainnet"
+    )]
+    pub regtest: bool,
+    /// Target Testnet network
+    #[clap(
+        long = "testnet",
+        conflicts_with = "regtest",
+        conflicts_with = "mainnet"
+    )]
+    pub testnet: bool,
+    /// Target Mainnet network (default)
+    #[clap(
+        long = "mainnet",
+        conflicts_with = "testnet",
+        conflicts_with = "regtest"
+    )]
+    pub mainnet: bool,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DumpConfig {
+    /// Output format (only json is supported)
+    #[clap(long = "format", default_value = "json")]
+    pub format: String,
+    /// Target Regtest network
+    #[clap(
+        long = "regtest",
+        conflicts_with = "testnet",
+        conflicts_with = "mainnet"
+    )]
+    pub regtest: bool,
+    /// Target Testnet network
+    #[clap(
+        long = "testnet",
+        conflicts_with = "regtest",
+        conflicts_with = "mainnet"
+    )]
+    pub testnet: bool,
+    /// Target Mainnet network
+    #[clap(
+        long = "mainnet",
+        conflicts_with = "testnet",
+        conflicts_with = "regtest"
+    )]
+    pub mainnet: bool,
+    /// Load config file path
+    #[clap(
+        long = "config-path",
+        conflicts_with = "mainnet",
+        conflicts_with = "testnet",
+        conflicts_with = "regtest"
+    )]
+    pub config_path: Option<String>,
+}
+
 #[derive(Subcommand, PartialEq, Clone, Debug)]
 enum ServiceCommand {
     /// Start chainhook-cli
@@ -348,6 +549,15 @@ struct StartCommand {
     /// Stream indexing to observers
     #[clap(long = "stream-indexing")]
     pub stream_indexing_to_observers: bool,
+    /// Bitcoin Core ZMQ rawblock publisher (e.g. tcp://127.0.0.1:28332), overrides bitcoind_zmq_url
+    #[clap(long = "zmq-endpoint")]
+    pub zmq_endpoint: Option<String>,
+    /// Log every step of the sat traversals at debug level (requires logs.ordinals_internals)
+    #[clap(long = "verbose-traversal")]
+    pub verbose_traversal: bool,
+    /// Deepest re-org rolled back automatically, overrides max_rollback_depth
+    #[clap(long = "max-rollback-depth")]
+    pub max_rollback_depth: Option<u64>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -364,6 +574,30 @@ enum OrdhookDbCommand {
     /// Check integrity
     #[clap(name = "check", bin_name = "check")]
     Check(CheckDbCommand),
+    /// Print the size of the databases, their row counts and index statistics
+    #[clap(name = "stats", bin_name = "stats")]
+    Stats(StatsDbCommand),
+    /// Verify that no sat is held by two live UTXOs at once
+    #[clap(name = "check-integrity", bin_name = "check-integrity")]
+    CheckIntegrity(CheckIntegrityDbCommand),
+    /// Roll back and re-index inscriptions and transfers for a block range
+    #[clap(name = "reindex", bin_name = "reindex")]
+    Reindex(ReindexDbCommand),
+    /// Export indexed inscriptions
+    #[clap(name = "export", bin_name = "export")]
+    Export(ExportDbCommand),
+    /// Export inscriptions transfers history (CSV)
+    #[clap(name = "export-transfers", bin_name = "export-transfers")]
+    ExportTransfers(ExportTransfersDbCommand),
+    /// Export the sat ranges of the UTXO set at a given height (CBOR)
+    #[clap(name = "export-utxo-set", bin_name = "export-utxo-set")]
+    ExportUtxoSet(ExportUtxoSetDbCommand),
+    /// Write a consistent copy of hord.sqlite and hord.rocksdb, pausing a running indexer
+    #[clap(name = "snapshot", bin_name = "snapshot")]
+    Snapshot(SnapshotDbCommand),
+    /// Delete the compacted blocks stored below a given height
+    #[clap(name = "prune", bin_name = "prune")]
+    Prune(PruneDbCommand),
     /// Db maintenance related commands
     #[clap(subcommand)]
     Repair(RepairCommand),
@@ -470,6 +704,16 @@ struct SyncOrdhookDbCommand {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct WatchCommand {
+    /// Seconds between two checks of the chain tip
+    #[clap(long = "poll-interval-secs", default_value = "15")]
+    pub poll_interval_secs: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 #[derive(Parser, PartialEq, Clone, Debug)]
 struct DropOrdhookDbCommand {
     /// Starting block
@@ -506,6 +750,110 @@ struct CheckDbCommand {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct StatsDbCommand {
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct CheckIntegrityDbCommand {
+    /// Starting block (blocks are still replayed from genesis)
+    #[clap(long = "from-block")]
+    pub from_block: Option<u64>,
+    /// Ending block (defaults to the last block stored)
+    #[clap(long = "to-block")]
+    pub to_block: Option<u64>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ReindexDbCommand {
+    /// Starting block
+    #[clap(long = "from-block")]
+    pub from_block: u64,
+    /// Ending block
+    #[clap(long = "to-block")]
+    pub to_block: u64,
+    /// Network threads
+    #[clap(long = "network-threads")]
+    pub network_threads: Option<usize>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportDbCommand {
+    /// Output file path
+    #[clap(long = "output")]
+    pub output: String,
+    /// Output format: json or ndjson
+    #[clap(long = "format", default_value = "ndjson")]
+    pub format: String,
+    /// Starting block
+    #[clap(long = "from-block")]
+    pub from_block: Option<u64>,
+    /// Ending block
+    #[clap(long = "to-block")]
+    pub to_block: Option<u64>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportTransfersDbCommand {
+    /// Output file path
+    #[clap(long = "output")]
+    pub output: String,
+    /// Starting block
+    #[clap(long = "from-block")]
+    pub from_block: Option<u64>,
+    /// Ending block
+    #[clap(long = "to-block")]
+    pub to_block: Option<u64>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportUtxoSetDbCommand {
+    /// Output file path
+    #[clap(long = "output")]
+    pub output: String,
+    /// Block height of the exported UTXO set (blocks are replayed from genesis)
+    #[clap(long = "at-height")]
+    pub at_height: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct SnapshotDbCommand {
+    /// Output directory
+    #[clap(long = "output-dir")]
+    pub output_dir: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct PruneDbCommand {
+    /// Blocks below this height are deleted
+    #[clap(long = "before-block")]
+    pub before_block: u64,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 pub fn main() {
     let logger = hiro_system_kit::log::setup_logger();
     let _guard = hiro_system_kit::log::setup_global_logger(logger.clone());
@@ -532,14 +880,43 @@ pub fn main() {
 async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
     match opts.command {
         Command::Scan(ScanCommand::Blocks(cmd)) => {
-            let config: Config =
-                ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
+            let config: Config = ConfigFile::default(
+                cmd.regtest,
+                cmd.testnet,
+                cmd.mainnet,
+                &cmd.config_path,
+                &opts.override_config,
+            )?;
             // Download dataset if required
             // If console:
             // - Replay based on SQLite queries
             // If post-to:
             // - Replay that requires connection to bitcoind
-            let block_heights = parse_blocks_heights_spec(&cmd.blocks_interval, &cmd.blocks);
+            let block_heights = match (&cmd.blocks_interval, &cmd.blocks) {
+                (None, None) => {
+                    let blocks_db = open_readonly_ordhook_db_conn_rocks_db(
+                        &config.expected_cache_path(),
+                        &config.resources,
+                        ctx,
+                    )?;
+                    let tip = find_last_block_inserted(&blocks_db) as u64;
+                    let from_block = cmd.from_block.unwrap_or(0);
+                    let to_block = cmd.to_block.unwrap_or(tip);
+                    if from_block > to_block {
+                        return Err(format!(
+                            "invalid block range: {} > {}",
+                            from_block, to_block
+                        ));
+                    }
+                    if to_block > tip {
+                        return Err(format!(
+                            "Unable to scan above the last block stored (#{tip})"
+                        ));
+                    }
+                    BlockHeights::BlockRange(from_block, to_block)
+                }
+                _ => parse_blocks_heights_spec(&cmd.blocks_interval, &cmd.blocks),
+            };
             let mut block_range = block_heights.get_sorted_entries();
             if let Some(ref post_to) = cmd.post_to {
                 info!(ctx.expect_logger(), "A fully synchronized bitcoind node is required for retrieving inscriptions content.");
@@ -579,8 +956,11 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 let mut total_inscriptions = 0;
                 let mut total_transfers = 0;
 
-                let inscriptions_db_conn =
-                    initialize_ordhook_db(&config.expected_cache_path(), ctx);
+                let inscriptions_db_conn = initialize_ordhook_db(
+                    &config.expected_cache_path(),
+                    config.storage.sqlite_wal,
+                    ctx,
+                );
                 while let Some(block_height) = block_range.pop_front() {
                     let inscriptions =
                         find_all_inscriptions_in_block(&block_height, &inscriptions_db_conn, ctx);
@@ -630,8 +1010,13 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             }
         }
         Command::Scan(ScanCommand::Inscription(cmd)) => {
-            let config: Config =
-                ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
+            let config: Config = ConfigFile::default(
+                cmd.regtest,
+                cmd.testnet,
+                cmd.mainnet,
+                &cmd.config_path,
+                &opts.override_config,
+            )?;
 
             let _ = download_ordinals_dataset_if_required(&config, ctx).await;
 
@@ -668,18 +1053,24 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             println!("Number of transfers: {}", transfers.len() - 1);
         }
         Command::Scan(ScanCommand::Transaction(cmd)) => {
-            let config: Config =
-                ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
-            let http_client = build_http_client();
+            let config: Config = ConfigFile::default(
+                cmd.regtest,
+                cmd.testnet,
+                cmd.mainnet,
+                &cmd.config_path,
+                &opts.override_config,
+            )?;
+            let http_client = RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second);
             let block = fetch_and_standardize_block(
                 &http_client,
                 cmd.block_height,
                 &config.get_event_observer_config().get_bitcoin_config(),
+                config.index_annex_envelopes,
                 ctx,
             )
             .await?;
             let transaction_identifier = TransactionIdentifier::new(&cmd.transaction_id);
-            let cache = new_traversals_lazy_cache(100);
+            let cache = new_traversals_lazy_cache(100, &config.resources.cache_eviction_policy);
             let (res, _, mut back_trace) = compute_satoshi_number(
                 &config.get_ordhook_config().db_path,
                 &block.block_identifier,
@@ -687,9 +1078,9 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 cmd.input_index,
                 0,
                 &Arc::new(cache),
-                config.resources.ulimit,
-                config.resources.memory_available,
+                &config.resources,
                 true,
+                false,
                 ctx,
             )?;
             back_trace.reverse();
@@ -698,6 +1089,448 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             }
             println!("{:?}", res);
         }
+        Command::Scan(ScanCommand::Tx(cmd)) => {
+            use ordhook::chainhook_sdk::bitcoincore_rpc::bitcoin::{
+                consensus::deserialize, Address, Transaction, Txid,
+            };
+            use ordhook::chainhook_sdk::types::BlockIdentifier;
+            use ordhook::core::protocol::inscription_sequencing::get_bitcoin_network;
+            use ordhook::core::{compute_next_satpoint_data, SatPosition};
+            use ordhook::db::{
+                find_all_inscriptions_with_ordinal_number,
+                find_inscribed_ordinals_at_wached_outpoint, format_outpoint_to_watch,
+            };
+            use serde_json::json;
+            use std::str::FromStr;
+
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let db_path = config.expected_cache_path();
+            let txid = Txid::from_str(cmd.txid.trim_start_matches("0x"))
+                .map_err(|e| format!("invalid txid {}: {e}", cmd.txid))?;
+
+            let rpc = RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second);
+            let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+            let (tx, block_hash) = match cmd.tx_hex {
+                Some(ref tx_hex) => {
+                    let bytes = hex::decode(tx_hex.trim_start_matches("0x"))
+                        .map_err(|e| format!("invalid transaction hex: {e}"))?;
+                    let tx: Transaction = deserialize(&bytes)
+                        .map_err(|e| format!("unable to decode transaction: {e}"))?;
+                    (tx, None)
+                }
+                None => {
+                    let info = rpc
+                        .get_raw_transaction_info(&txid, &bitcoin_config)
+                        .await
+                        .map_err(|e| format!("unable to retrieve transaction {txid}: {e}"))?;
+                    let tx = info
+                        .transaction()
+                        .map_err(|e| format!("unable to decode transaction: {e}"))?;
+                    (tx, info.blockhash)
+                }
+            };
+            if tx.txid() != txid {
+                return Err(format!("transaction hex does not match txid {txid}"));
+            }
+            let block_height = match cmd.block_height {
+                Some(block_height) => block_height,
+                None => {
+                    let block_hash = match (block_hash, &cmd.tx_hex) {
+                        (Some(block_hash), _) => block_hash,
+                        (None, Some(_)) => rpc
+                            .get_raw_transaction_info(&txid, &bitcoin_config)
+                            .await
+                            .map_err(|e| format!("unable to retrieve transaction {txid}: {e}"))?
+                            .blockhash
+                            .ok_or(format!("transaction {txid} not confirmed yet"))?,
+                        (None, None) => {
+                            return Err(format!("transaction {txid} not confirmed yet"));
+                        }
+                    };
+                    rpc.get_block_header_info(&block_hash, &bitcoin_config)
+                        .await
+                        .map_err(|e| format!("unable to retrieve block {block_hash}: {e}"))?
+                        .height as u64
+                }
+            };
+
+            // Input values are read from the compacted block, along with the first sat of each input
+            let transaction_identifier = TransactionIdentifier::new(&txid.to_string());
+            let blocks_db =
+                open_readonly_ordhook_db_conn_rocks_db(&db_path, &config.resources, ctx)?;
+            let block_bytes =
+                find_block_bytes_at_block_height(block_height as u32, 3, &blocks_db, ctx)
+                    .ok_or(format!("block #{block_height} not in hord.rocksdb"))?;
+            let compacted_tx = BlockBytesCursor::new(&block_bytes)
+                .find_and_serialize_transaction_with_txid(
+                    &transaction_identifier.get_8_hash_bytes(),
+                )
+                .ok_or(format!("transaction {txid} not in block #{block_height}"))?;
+            drop(blocks_db);
+            let block_identifier = BlockIdentifier {
+                index: block_height,
+                hash: String::new(),
+            };
+            let inputs = compacted_tx
+                .inputs
+                .iter()
+                .map(|input| input.txin_value)
+                .collect::<Vec<_>>();
+            let outputs = tx
+                .output
+                .iter()
+                .map(|output| output.value.to_sat())
+                .collect::<Vec<_>>();
+            let network = get_bitcoin_network(&config.network.bitcoin_network);
+            let destination = |input_index: usize, offset: u64| match compute_next_satpoint_data(
+                0,
+                input_index,
+                &inputs,
+                &outputs,
+                offset,
+                None,
+            ) {
+                SatPosition::Output((output_index, offset)) => {
+                    json!({ "output": output_index, "offset": offset })
+                }
+                SatPosition::Fee(offset) => json!({ "fee": offset }),
+            };
+
+            let cache = Arc::new(new_traversals_lazy_cache(
+                100,
+                &config.resources.cache_eviction_policy,
+            ));
+            let inscriptions_db_conn = open_readonly_ordhook_db_conn(&db_path, ctx)?;
+            let mut inputs_movements = vec![];
+            for (input_index, input) in tx.input.iter().enumerate() {
+                // Coinbase inputs are not part of the compacted transactions
+                let input_value = inputs.get(input_index).copied().unwrap_or(0);
+                let first_sat = if input_value == 0 {
+                    None
+                } else {
+                    compute_satoshi_number(
+                        &db_path,
+                        &block_identifier,
+                        &transaction_identifier,
+                        input_index,
+                        0,
+                        &cache,
+                        &config.resources,
+                        false,
+                        false,
+                        ctx,
+                    )
+                    .map(|(traversal, _, _)| traversal.ordinal_number)
+                    .ok()
+                };
+                let outpoint = format_outpoint_to_watch(
+                    &TransactionIdentifier::new(&input.previous_output.txid.to_string()),
+                    input.previous_output.vout as usize,
+                );
+                let inscriptions = find_inscribed_ordinals_at_wached_outpoint(
+                    &outpoint,
+                    &inscriptions_db_conn,
+                    ctx,
+                )
+                .iter()
+                .map(|watched| {
+                    let inscription_ids = find_all_inscriptions_with_ordinal_number(
+                        &watched.ordinal_number,
+                        &inscriptions_db_conn,
+                        ctx,
+                    )
+                    .iter()
+                    .map(|(traversal, _)| traversal.get_inscription_id())
+                    .collect::<Vec<_>>();
+                    json!({
+                        "sat": watched.ordinal_number,
+                        "offset": watched.offset,
+                        "inscription_ids": inscription_ids,
+                        "destination": destination(input_index, watched.offset),
+                    })
+                })
+                .collect::<Vec<_>>();
+                inputs_movements.push(json!({
+                    "input": input_index,
+                    "outpoint": outpoint,
+                    "value": input_value,
+                    "first_sat": first_sat,
+                    "first_sat_destination": destination(input_index, 0),
+                    "inscriptions": inscriptions,
+                }));
+            }
+            let outputs_description = tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(output_index, output)| {
+                    json!({
+                        "output": output_index,
+                        "value": output.value.to_sat(),
+                        "address": Address::from_script(&output.script_pubkey, network)
+                            .map(|address| address.to_string())
+                            .ok(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let fee = inputs
+                .iter()
+                .sum::<u64>()
+                .saturating_sub(outputs.iter().sum());
+
+            if cmd.json {
+                let result = json!({
+                    "txid": txid.to_string(),
+                    "block_height": block_height,
+                    "inputs": inputs_movements,
+                    "outputs": outputs_description,
+                    "fee": fee,
+                });
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+
+            let format_destination =
+                |destination: &serde_json::Value| match destination.get("output") {
+                    Some(output) => format!("output #{output}:{}", destination["offset"]),
+                    None => format!("fee +{}", destination["fee"]),
+                };
+            println!("Transaction {txid} (block #{block_height})");
+            println!("Inputs:");
+            for input in inputs_movements.iter() {
+                println!(
+                    "\t#{}\t{}\t{} sats\tfirst sat {}\t→ {}",
+                    input["input"],
+                    input["outpoint"].as_str().unwrap_or_default(),
+                    input["value"],
+                    input["first_sat"],
+                    format_destination(&input["first_sat_destination"])
+                );
+                for inscription in input["inscriptions"].as_array().into_iter().flatten() {
+                    println!(
+                        "\t\tsat {} (offset {}) carrying {}\t→ {}",
+                        inscription["sat"],
+                        inscription["offset"],
+                        inscription["inscription_ids"],
+                        format_destination(&inscription["destination"])
+                    );
+                }
+            }
+            println!("Outputs:");
+            for output in outputs_description.iter() {
+                println!(
+                    "\t#{}\t{} sats\t{}",
+                    output["output"],
+                    output["value"],
+                    output["address"].as_str().unwrap_or("-")
+                );
+            }
+            println!("Fee: {fee} sats");
+        }
+        Command::Debug(DebugCommand::Traversal(cmd)) => {
+            use ordhook::chainhook_sdk::types::BlockIdentifier;
+            use ordhook::db::{find_block_hash_at_block_height, get_inscriptions_by_tx};
+            use serde_json::json;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let db_path = config.expected_cache_path();
+            let block_height = match cmd.block_height {
+                Some(block_height) => block_height,
+                None => {
+                    let inscriptions_db_conn = open_readonly_ordhook_db_conn(&db_path, ctx)?;
+                    get_inscriptions_by_tx(&cmd.tx_id, 1, 0, &inscriptions_db_conn, ctx)?
+                        .first()
+                        .map(|inscription| inscription.block_height)
+                        .ok_or(format!(
+                            "transaction {} not found in hord.sqlite, use --block-height",
+                            cmd.tx_id
+                        ))?
+                }
+            };
+            let blocks_db =
+                open_readonly_ordhook_db_conn_rocks_db(&db_path, &config.resources, ctx)?;
+            let block_identifier = BlockIdentifier {
+                index: block_height,
+                hash: find_block_hash_at_block_height(block_height, &blocks_db).unwrap_or_default(),
+            };
+            drop(blocks_db);
+            let transaction_identifier = TransactionIdentifier::new(&cmd.tx_id);
+            let cache = Arc::new(new_traversals_lazy_cache(
+                100,
+                &config.resources.cache_eviction_policy,
+            ));
+            let (traversal, _, mut back_trace) = compute_satoshi_number(
+                &db_path,
+                &block_identifier,
+                &transaction_identifier,
+                cmd.input,
+                0,
+                &cache,
+                &config.resources,
+                true,
+                cmd.verbose_traversal,
+                ctx,
+            )?;
+            back_trace.reverse();
+            let hops = back_trace
+                .iter()
+                .map(|(block_height, txid, vout)| {
+                    json!({
+                        "block_height": block_height,
+                        "txid": hex::encode(txid),
+                        "vout": vout,
+                        "transaction": cache
+                            .get(&(*block_height, *txid))
+                            .map(|entry| entry.value().clone()),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let result = json!({
+                "traversal": traversal,
+                "back_trace": hops,
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?
+            );
+        }
+        Command::Inspect(InspectCommand::Block(cmd)) => {
+            use ordhook::core::inspect::inspect_block;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let db_path = config.expected_cache_path();
+            let blocks_db =
+                open_readonly_ordhook_db_conn_rocks_db(&db_path, &config.resources, ctx)?;
+            let inscriptions_db_conn = match open_readonly_ordhook_db_conn(&db_path, ctx) {
+                Ok(conn) => Some(conn),
+                Err(e) => {
+                    warn!(
+                        ctx.expect_logger(),
+                        "Inscriptions and transfers not listed, unable to open hord.sqlite: {e}"
+                    );
+                    None
+                }
+            };
+            let inspection = inspect_block(
+                cmd.block_height,
+                &blocks_db,
+                inscriptions_db_conn.as_ref(),
+                ctx,
+            )?;
+            println!(
+                "Block #{}: {} transactions, subsidy {} sats, fees {} sats",
+                inspection.block_height,
+                inspection.transactions.len(),
+                inspection.subsidy,
+                inspection.fees
+            );
+            println!(
+                "\n{:>5}  {:<16}  {:>16}  {:>16}  {:>12}",
+                "#", "tx_id", "sats in", "sats out", "fee"
+            );
+            for tx in inspection.transactions.iter() {
+                println!(
+                    "{:>5}  {:<16}  {:>16}  {:>16}  {:>12}",
+                    tx.tx_index,
+                    hex::encode(tx.txid),
+                    tx.sats_in,
+                    tx.sats_out,
+                    tx.fee()
+                );
+                if tx.is_coinbase() {
+                    println!(
+                        "{:>7}in   subsidy + fees{:>20}  [0, {})",
+                        "", tx.sats_in, tx.sats_in
+                    );
+                }
+                for (input_index, input) in tx.inputs.iter().enumerate() {
+                    println!(
+                        "{:>7}in   #{:<4} {}:{} (block #{}){:>12}  [{}, {})",
+                        "",
+                        input_index,
+                        hex::encode(input.input.txin),
+                        input.input.vout,
+                        input.input.block_height,
+                        input.input.txin_value,
+                        input.offsets.0,
+                        input.offsets.1
+                    );
+                }
+                for (vout, output) in tx.outputs.iter().enumerate() {
+                    println!(
+                        "{:>7}out  #{:<4}{:>30}  [{}, {})",
+                        "", vout, output.value, output.offsets.0, output.offsets.1
+                    );
+                }
+                for inscription in tx.inscriptions.iter() {
+                    println!(
+                        "{:>7}inscription {} revealed on sat {} (input #{})",
+                        "",
+                        inscription.get_inscription_id(),
+                        inscription.ordinal_number,
+                        inscription.inscription_input_index
+                    );
+                }
+                for (ordinal_number, location) in tx.locations.iter() {
+                    if tx
+                        .inscriptions
+                        .iter()
+                        .any(|inscription| inscription.ordinal_number == *ordinal_number)
+                    {
+                        continue;
+                    }
+                    println!(
+                        "{:>7}transfer of sat {} to output #{}, offset {}",
+                        "",
+                        ordinal_number,
+                        location.output_index,
+                        location.inscription_offset_intra_output
+                    );
+                }
+            }
+        }
+        Command::Sat(SatCommand::Info(cmd)) => {
+            use ordhook::ord::rarity::classify_sat_rarity;
+            use ordhook::ord::sat::{sat_to_epoch, sat_to_genesis_location, Sat};
+            if cmd.sat >= Sat::SUPPLY {
+                return Err(format!("sat {} exceeds the supply", cmd.sat));
+            }
+            let (block_height, offset) = sat_to_genesis_location(cmd.sat);
+            println!("Block: {}", block_height);
+            println!("Offset in coinbase: {}", offset);
+            println!("Epoch: {}", sat_to_epoch(cmd.sat));
+            println!("Rarity: {}", classify_sat_rarity(cmd.sat));
+            println!("Name: {}", Sat(cmd.sat).name());
+        }
+        Command::Inscribe(InscribeCommand::Estimate(cmd)) => {
+            use ordhook::ord::inscription::Inscription;
+            use ordhook::ord::COIN_VALUE;
+            use ordhook::utils::read_file_content_at_path;
+            if !cmd.fee_rate.is_finite() || cmd.fee_rate < 0.0 {
+                return Err(format!("invalid fee rate {}", cmd.fee_rate));
+            }
+            let content = read_file_content_at_path(&PathBuf::from(&cmd.content_file))?;
+            let content_length = content.len();
+            let inscription = Inscription {
+                content_type: Some(cmd.content_type.into_bytes()),
+                body: Some(content),
+                ..Default::default()
+            };
+            let vsize = inscription.reveal_transaction_vsize();
+            let fee = (vsize as f64 * cmd.fee_rate).ceil() as u64;
+            println!("Content size: {} bytes", content_length);
+            println!("Reveal transaction size: {} vB", vsize);
+            println!(
+                "Fee at {} sat/vB: {} sats ({:.8} BTC)",
+                cmd.fee_rate,
+                fee,
+                fee as f64 / COIN_VALUE as f64
+            );
+        }
         Command::Service(subcmd) => match subcmd {
             ServiceCommand::Start(cmd) => {
                 let maintenance_enabled =
@@ -707,10 +1540,39 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     sleep(Duration::from_secs(3600 * 24 * 7))
                 }
 
-                let config =
-                    ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &cmd.config_path)?;
+                let mut config = ConfigFile::default(
+                    cmd.regtest,
+                    cmd.testnet,
+                    cmd.mainnet,
+                    &cmd.config_path,
+                    &opts.override_config,
+                )?;
+                if let Some(ref zmq_endpoint) = cmd.zmq_endpoint {
+                    use ordhook::chainhook_sdk::types::BitcoinBlockSignaling;
+                    config.network.bitcoin_block_signaling =
+                        BitcoinBlockSignaling::ZeroMQ(zmq_endpoint.clone());
+                }
+                if cmd.verbose_traversal {
+                    config.logs.verbose_traversal = true;
+                }
+                if let Some(max_rollback_depth) = cmd.max_rollback_depth {
+                    config.max_rollback_depth = max_rollback_depth;
+                }
 
-                let _ = initialize_ordhook_db(&config.expected_cache_path(), ctx);
+                let ctx = &match config.logs.format {
+                    LogFormat::Json => Context {
+                        logger: Some(new_json_logger()),
+                        tracer: ctx.tracer,
+                    },
+                    LogFormat::Text => ctx.clone(),
+                };
+
+                check_start_block_against_indexed_height(&config, ctx)?;
+                let _ = initialize_ordhook_db(
+                    &config.expected_cache_path(),
+                    config.storage.sqlite_wal,
+                    ctx,
+                );
 
                 let inscriptions_db_conn =
                     open_readonly_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
@@ -721,8 +1583,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     open_ordhook_db_conn_rocks_db_loop(
                         true,
                         &config.expected_cache_path(),
-                        config.resources.ulimit,
-                        config.resources.memory_available,
+                        &config.resources,
                         ctx,
                     );
                 }
@@ -777,7 +1638,13 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             ConfigCommand::New(cmd) => {
                 use std::fs::File;
                 use std::io::Write;
-                let config = ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &None)?;
+                let config = ConfigFile::default(
+                    cmd.regtest,
+                    cmd.testnet,
+                    cmd.mainnet,
+                    &None,
+                    &opts.override_config,
+                )?;
                 let config_content = generate_config(&config.network.bitcoin_network);
                 let mut file_path = PathBuf::new();
                 file_path.push("Ordhook.toml");
@@ -787,27 +1654,107 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     .map_err(|e| format!("unable to write file {}\n{}", file_path.display(), e))?;
                 println!("Created file Ordhook.toml");
             }
+            ConfigCommand::Init(cmd) => {
+                use std::fs::OpenOptions;
+                use std::io::Write;
+                // Defaults of the network, leaving out the environment overrides
+                let config = match (cmd.regtest, cmd.testnet) {
+                    (true, _) => Config::devnet_default(),
+                    (_, true) => Config::testnet_default(),
+                    _ => Config::mainnet_default(),
+                };
+                let config_content = generate_config_template(&config)?;
+                let file_path = PathBuf::from(&cmd.output);
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&file_path)
+                    .map_err(|e| format!("unable to create file {}\n{}", file_path.display(), e))?;
+                file.write_all(config_content.as_bytes())
+                    .map_err(|e| format!("unable to write file {}\n{}", file_path.display(), e))?;
+                println!("Created file {}", file_path.display());
+            }
+            ConfigCommand::Dump(cmd) => {
+                if cmd.format != "json" {
+                    return Err(format!("unsupported format {}", cmd.format));
+                }
+                let config = ConfigFile::default(
+                    cmd.regtest,
+                    cmd.testnet,
+                    cmd.mainnet,
+                    &cmd.config_path,
+                    &opts.override_config,
+                )?;
+                let json = serde_json::to_string_pretty(&config)
+                    .map_err(|e| format!("unable to serialize config: {}", e))?;
+                println!("{}", json);
+            }
         },
         Command::Db(OrdhookDbCommand::New(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
-            initialize_ordhook_db(&config.expected_cache_path(), ctx);
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            initialize_ordhook_db(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                ctx,
+            );
             open_ordhook_db_conn_rocks_db_loop(
                 true,
                 &config.expected_cache_path(),
-                config.resources.ulimit,
-                config.resources.memory_available,
+                &config.resources,
                 ctx,
             );
         }
         Command::Db(OrdhookDbCommand::Sync(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
-            initialize_ordhook_db(&config.expected_cache_path(), ctx);
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            check_start_block_against_indexed_height(&config, ctx)?;
+            initialize_ordhook_db(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                ctx,
+            );
             let service = Service::new(config, ctx.clone());
             service.update_state(None).await?;
         }
+        Command::Watch(cmd) => {
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            check_start_block_against_indexed_height(&config, ctx)?;
+            initialize_ordhook_db(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                ctx,
+            );
+            let service = Service::new(config, ctx.clone());
+            let poll_interval = Duration::from_secs(cmd.poll_interval_secs.max(1));
+            let mut caught_up = false;
+            loop {
+                // Blocks mined since the last check get downloaded and indexed
+                match service.update_state(None).await {
+                    Ok(_) if !caught_up => {
+                        info!(
+                            ctx.expect_logger(),
+                            "Watching the chain tip every {}s",
+                            poll_interval.as_secs()
+                        );
+                        caught_up = true;
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!(ctx.expect_logger(), "Unable to index new blocks: {e}"),
+                }
+                sleep(poll_interval);
+            }
+        }
         Command::Db(OrdhookDbCommand::Repair(subcmd)) => match subcmd {
             RepairCommand::Blocks(cmd) => {
-                let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
+                let config = ConfigFile::default(
+                    false,
+                    false,
+                    false,
+                    &cmd.config_path,
+                    &opts.override_config,
+                )?;
                 let mut ordhook_config = config.get_ordhook_config();
                 if let Some(network_threads) = cmd.network_threads {
                     ordhook_config.resources.bitcoind_rpc_threads = network_threads;
@@ -828,8 +1775,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     let blocks_db = open_ordhook_db_conn_rocks_db_loop(
                         false,
                         &config.get_ordhook_config().db_path,
-                        config.resources.ulimit,
-                        config.resources.memory_available,
+                        &config.resources,
                         ctx,
                     );
                     for i in cmd.get_blocks().into_iter() {
@@ -846,22 +1792,32 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 }
             }
             RepairCommand::Inscriptions(cmd) => {
-                let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
+                let mut config = ConfigFile::default(
+                    false,
+                    false,
+                    false,
+                    &cmd.config_path,
+                    &opts.override_config,
+                )?;
+                config.dry_run = cmd.dry_run.unwrap_or(false);
                 let mut ordhook_config = config.get_ordhook_config();
                 if let Some(network_threads) = cmd.network_threads {
                     ordhook_config.resources.bitcoind_rpc_threads = network_threads;
                 }
-                let block_post_processor = match cmd.repair_observers {
-                    Some(true) => {
-                        let tx_replayer =
-                            start_observer_forwarding(&config.get_event_observer_config(), ctx);
-                        Some(tx_replayer)
-                    }
-                    _ => None,
-                };
+                let mut post_processors = PostProcessorRegistry::new();
+                if let Some(true) = cmd.repair_observers {
+                    post_processors.register_sender(start_observer_forwarding(
+                        &config.get_event_observer_config(),
+                        ctx,
+                    ));
+                }
                 let blocks = cmd.get_blocks();
-                let inscription_indexing_processor =
-                    start_inscription_indexing_processor(&config, ctx, block_post_processor);
+                let inscription_indexing_processor = start_inscription_indexing_processor(
+                    &config,
+                    ctx,
+                    post_processors.into_senders(),
+                    None,
+                );
 
                 download_and_pipeline_blocks(
                     &config,
@@ -874,7 +1830,13 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 .await?;
             }
             RepairCommand::Transfers(cmd) => {
-                let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
+                let config = ConfigFile::default(
+                    false,
+                    false,
+                    false,
+                    &cmd.config_path,
+                    &opts.override_config,
+                )?;
                 let block_post_processor = match cmd.repair_observers {
                     Some(true) => {
                         let tx_replayer =
@@ -898,12 +1860,12 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
             }
         },
         Command::Db(OrdhookDbCommand::Check(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
             {
                 let blocks_db = open_readonly_ordhook_db_conn_rocks_db(
                     &config.expected_cache_path(),
-                    config.resources.ulimit,
-                    config.resources.memory_available,
+                    &config.resources,
                     ctx,
                 )?;
                 let tip = find_last_block_inserted(&blocks_db);
@@ -912,17 +1874,354 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 println!("{:?}", missing_blocks);
             }
         }
+        Command::Db(OrdhookDbCommand::Stats(cmd)) => {
+            use ordhook::db::get_ordhook_db_stats;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let stats = get_ordhook_db_stats(&config.expected_cache_path(), ctx)?;
+            let mb = |size: u64| size as f64 / (1024.0 * 1024.0);
+            println!("hord.rocksdb: {:.1} MB", mb(stats.rocksdb_size));
+            println!("hord.sqlite: {:.1} MB", mb(stats.sqlite_size));
+            println!("\nTables:");
+            for table in stats.tables.iter() {
+                println!("  {:<32} {:>12} rows", table.name, table.rows);
+            }
+            println!("\nIndexes (sqlite_stat1):");
+            for index in stats.indexes.iter() {
+                println!(
+                    "  {:<48} {:<24} {}",
+                    index.name,
+                    index.table,
+                    index.stat.as_deref().unwrap_or("-")
+                );
+            }
+            if stats.indexes.iter().all(|index| index.stat.is_none()) {
+                println!("\nNo index statistics, run ANALYZE on hord.sqlite to collect them");
+            }
+        }
+        Command::Db(OrdhookDbCommand::CheckIntegrity(cmd)) => {
+            use ordhook::core::integrity::check_sat_ranges_integrity;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let blocks_db = open_readonly_ordhook_db_conn_rocks_db(
+                &config.expected_cache_path(),
+                &config.resources,
+                ctx,
+            )?;
+            let tip = find_last_block_inserted(&blocks_db) as u64;
+            let to_block = cmd.to_block.unwrap_or(tip).min(tip);
+            let from_block = cmd.from_block.unwrap_or(0);
+            let conflicts = check_sat_ranges_integrity(from_block, to_block, &blocks_db, ctx)?;
+            for conflict in conflicts.iter() {
+                error!(
+                    ctx.expect_logger(),
+                    "Block #{}: transaction {} conflicts with {}: {}",
+                    conflict.block_height,
+                    conflict.txid,
+                    conflict.conflicting_txid,
+                    conflict.reason
+                );
+            }
+            if !conflicts.is_empty() {
+                return Err(format!(
+                    "{} sat range conflicts found between blocks #{from_block} and #{to_block}",
+                    conflicts.len()
+                ));
+            }
+            info!(
+                ctx.expect_logger(),
+                "No sat range conflict found between blocks #{from_block} and #{to_block}"
+            );
+        }
+        Command::Db(OrdhookDbCommand::Reindex(cmd)) => {
+            use ordhook::db::rollback_block_at_height;
+            if cmd.from_block > cmd.to_block {
+                return Err(format!(
+                    "invalid block range: {} > {}",
+                    cmd.from_block, cmd.to_block
+                ));
+            }
+            let mut config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            if let Some(network_threads) = cmd.network_threads {
+                config.resources.bitcoind_rpc_threads = network_threads;
+            }
+            let ordhook_config = config.get_ordhook_config();
+            {
+                let mut inscriptions_db_conn_rw = open_readwrite_ordhook_db_conn(
+                    &config.expected_cache_path(),
+                    config.storage.sqlite_wal,
+                    ctx,
+                )?;
+                for block_height in cmd.from_block..=cmd.to_block {
+                    rollback_block_at_height(block_height, &mut inscriptions_db_conn_rw, ctx)
+                        .map_err(|e| format!("unable to rollback block #{block_height}: {e}"))?;
+                }
+            }
+            info!(
+                ctx.expect_logger(),
+                "Re-indexing inscriptions and transfers from block #{} to block #{}",
+                cmd.from_block,
+                cmd.to_block
+            );
+            let blocks: Vec<u64> = BlockHeights::BlockRange(cmd.from_block, cmd.to_block)
+                .get_sorted_entries()
+                .into();
+            let inscription_indexing_processor =
+                start_inscription_indexing_processor(&config, ctx, vec![], None);
+            download_and_pipeline_blocks(
+                &config,
+                blocks,
+                ordhook_config.first_inscription_height,
+                Some(&inscription_indexing_processor),
+                10_000,
+                ctx,
+            )
+            .await?;
+        }
+        Command::Db(OrdhookDbCommand::Export(cmd)) => {
+            use serde_json::json;
+            use std::fs::File;
+            use std::io::{BufWriter, Write};
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let ndjson = match cmd.format.as_str() {
+                "ndjson" => true,
+                "json" => false,
+                _ => return Err(format!("format {} not supported", cmd.format)),
+            };
+            let inscriptions_db_conn =
+                open_readonly_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
+            let file = File::create(&cmd.output)
+                .map_err(|e| format!("unable to create file {}: {}", cmd.output, e))?;
+            let mut writer = BufWriter::new(file);
+            let write_err = |e: std::io::Error| format!("unable to write {}: {}", cmd.output, e);
+
+            if !ndjson {
+                writer.write_all(b"[").map_err(write_err)?;
+            }
+            let mut count = 0;
+            for_each_inscription_in_block_range(
+                cmd.from_block.unwrap_or(0),
+                cmd.to_block.unwrap_or(u32::MAX as u64),
+                &inscriptions_db_conn,
+                ctx,
+                |inscription, state| {
+                    let entry = json!({
+                        "inscription_id": inscription.inscription_id,
+                        "inscription_number": inscription.inscription_number.jubilee,
+                        "classic_inscription_number": inscription.inscription_number.classic,
+                        "sat": inscription.ordinal_number,
+                        "genesis_block_height": inscription.block_height,
+                        "content_type": state.content_type,
+                        "content_length": state.content_length,
+                        "current_holder_address": state.current_holder_address,
+                        "satpoint": state.satpoint,
+                    });
+                    if !ndjson && count > 0 {
+                        writer.write_all(b",").map_err(write_err)?;
+                    }
+                    serde_json::to_writer(&mut writer, &entry).map_err(|e| e.to_string())?;
+                    if ndjson {
+                        writer.write_all(b"\n").map_err(write_err)?;
+                    }
+                    count += 1;
+                    Ok(())
+                },
+            )?;
+            if !ndjson {
+                writer.write_all(b"]").map_err(write_err)?;
+            }
+            writer.flush().map_err(write_err)?;
+            info!(
+                ctx.expect_logger(),
+                "{count} inscriptions exported to {}", cmd.output
+            );
+        }
+        Command::Db(OrdhookDbCommand::ExportTransfers(cmd)) => {
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let inscriptions_db_conn =
+                open_readonly_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
+            let mut writer = csv::Writer::from_path(&cmd.output)
+                .map_err(|e| format!("unable to create file {}: {}", cmd.output, e))?;
+            let write_err = |e: csv::Error| format!("unable to write {}: {}", cmd.output, e);
+            writer
+                .write_record(&[
+                    "inscription_id",
+                    "from_address",
+                    "to_address",
+                    "block_height",
+                    "tx_id",
+                    "sat",
+                    "from_satpoint",
+                    "to_satpoint",
+                ])
+                .map_err(write_err)?;
+            let mut count = 0;
+            for_each_inscription_transfer_in_block_range(
+                cmd.from_block.unwrap_or(0),
+                cmd.to_block.unwrap_or(u32::MAX as u64),
+                &inscriptions_db_conn,
+                ctx,
+                |transfer| {
+                    writer
+                        .write_record(&[
+                            transfer.inscription_id,
+                            transfer.from_address.unwrap_or_default(),
+                            transfer.to_address.unwrap_or_default(),
+                            transfer.block_height.to_string(),
+                            transfer.tx_id,
+                            transfer.ordinal_number.to_string(),
+                            transfer.from_satpoint,
+                            transfer.to_satpoint,
+                        ])
+                        .map_err(write_err)?;
+                    count += 1;
+                    Ok(())
+                },
+            )?;
+            writer
+                .flush()
+                .map_err(|e| format!("unable to write {}: {}", cmd.output, e))?;
+            info!(
+                ctx.expect_logger(),
+                "{count} transfers exported to {}", cmd.output
+            );
+        }
+        Command::Db(OrdhookDbCommand::ExportUtxoSet(cmd)) => {
+            use ordhook::core::utxo_set::export_utxo_set;
+            use std::fs::File;
+            use std::io::BufWriter;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let blocks_db = open_readonly_ordhook_db_conn_rocks_db(
+                &config.expected_cache_path(),
+                &config.resources,
+                ctx,
+            )?;
+            let tip = find_last_block_inserted(&blocks_db) as u64;
+            if cmd.at_height > tip {
+                return Err(format!(
+                    "Unable to export the UTXO set above the last block stored (#{tip})"
+                ));
+            }
+            let file = File::create(&cmd.output)
+                .map_err(|e| format!("unable to create file {}: {}", cmd.output, e))?;
+            let mut writer = BufWriter::new(file);
+            let started_at = std::time::Instant::now();
+            let count = export_utxo_set(cmd.at_height, &blocks_db, &mut writer, ctx)?;
+            info!(
+                ctx.expect_logger(),
+                "{count} UTXOs at block #{} exported to {} in {:.1}s",
+                cmd.at_height,
+                cmd.output,
+                started_at.elapsed().as_secs_f64()
+            );
+        }
+        Command::Db(OrdhookDbCommand::Snapshot(cmd)) => {
+            use ordhook::config::PredicatesApi;
+            use ordhook::db::{open_readwrite_ordhook_db_conn_rocks_db, snapshot_ordhook_db};
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            // The snapshot is taken by the indexer itself when running: the path has to be
+            // resolved on this side
+            let output_dir = std::env::current_dir()
+                .map_err(|e| format!("unable to resolve {}: {e}", cmd.output_dir))?
+                .join(&cmd.output_dir);
+            match open_readwrite_ordhook_db_conn_rocks_db(
+                &config.expected_cache_path(),
+                &config.resources,
+                ctx,
+            ) {
+                Ok(blocks_db) => {
+                    let inscriptions_db_conn =
+                        open_readonly_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
+                    snapshot_ordhook_db(&blocks_db, &inscriptions_db_conn, &output_dir, ctx)?;
+                }
+                // hord.rocksdb is locked by a running indexer, which is paused for the snapshot
+                Err(e) => {
+                    let PredicatesApi::On(ref api_config) = config.http_api else {
+                        return Err(format!(
+                            "{e}, enable http_api to snapshot a running indexer"
+                        ));
+                    };
+                    let started_at = std::time::Instant::now();
+                    let url = format!("http://localhost:{}/v1/snapshot", api_config.http_port);
+                    let response: serde_json::Value = reqwest::Client::new()
+                        .post(&url)
+                        .json(&serde_json::json!({ "output_dir": output_dir }))
+                        .send()
+                        .await
+                        .map_err(|e| format!("unable to reach the indexer at {url}: {e}"))?
+                        .json()
+                        .await
+                        .map_err(|e| format!("unable to parse the indexer response: {e}"))?;
+                    let Some(size) = response["result"]["size"].as_u64() else {
+                        return Err(format!(
+                            "unable to take snapshot: {}",
+                            response["message"].as_str().unwrap_or("unknown error")
+                        ));
+                    };
+                    info!(
+                        ctx.expect_logger(),
+                        "Snapshot written by the indexer to {} ({:.1} MB in {:.1}s)",
+                        output_dir.display(),
+                        size as f64 / (1024.0 * 1024.0),
+                        started_at.elapsed().as_secs_f64()
+                    );
+                }
+            }
+        }
+        Command::Db(OrdhookDbCommand::Prune(cmd)) => {
+            use ordhook::db::prune_blocks_before;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
+            let blocks_db = open_ordhook_db_conn_rocks_db_loop(
+                true,
+                &config.expected_cache_path(),
+                &config.resources,
+                ctx,
+            );
+            let tip = find_last_block_inserted(&blocks_db) as u64;
+            if cmd.before_block > tip {
+                return Err(format!(
+                    "Unable to prune above the last block stored (#{tip})"
+                ));
+            }
+
+            warn!(
+                ctx.expect_logger(),
+                "Blocks below #{} will no longer be traversable: sats of new inscriptions tracing back to them can't be numbered, and re-indexing these heights will require a fresh hord.rocksdb sync",
+                cmd.before_block
+            );
+            println!("{} blocks will be pruned. Confirm? [Y/n]", cmd.before_block);
+            let mut buffer = String::new();
+            std::io::stdin().read_line(&mut buffer).unwrap();
+            if buffer.starts_with('n') {
+                return Err("Pruning aborted".to_string());
+            }
+
+            prune_blocks_before(cmd.before_block as u32, &blocks_db, ctx)?;
+            info!(
+                ctx.expect_logger(),
+                "Pruned compacted blocks below #{}", cmd.before_block
+            );
+        }
         Command::Db(OrdhookDbCommand::Drop(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path)?;
+            let config =
+                ConfigFile::default(false, false, false, &cmd.config_path, &opts.override_config)?;
             let blocks_db = open_ordhook_db_conn_rocks_db_loop(
                 true,
                 &config.expected_cache_path(),
-                config.resources.ulimit,
-                config.resources.memory_available,
+                &config.resources,
                 ctx,
             );
-            let inscriptions_db_conn_rw =
-                open_readwrite_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
+            let inscriptions_db_conn_rw = open_readwrite_ordhook_db_conn(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                ctx,
+            )?;
 
             println!(
                 "{} blocks will be deleted. Confirm? [Y/n]",
@@ -967,18 +2266,26 @@ pub fn load_predicate_from_path(
 }
 
 pub async fn fetch_and_standardize_block(
-    http_client: &HttpClient,
+    http_client: &RateLimitedRpcClient,
     block_height: u64,
     bitcoin_config: &BitcoinConfig,
+    index_annex_envelopes: bool,
     ctx: &Context,
 ) -> Result<BitcoinBlockData, String> {
-    let block_hash =
-        retrieve_block_hash_with_retry(http_client, &block_height, bitcoin_config, ctx).await?;
-    let block_breakdown =
-        download_and_parse_block_with_retry(http_client, &block_hash, bitcoin_config, ctx).await?;
+    let block_hash = http_client
+        .retrieve_block_hash_with_retry(&block_height, bitcoin_config, ctx)
+        .await?;
+    let block_breakdown = http_client
+        .download_and_parse_block_with_retry(&block_hash, bitcoin_config, ctx)
+        .await?;
 
-    parse_inscriptions_and_standardize_block(block_breakdown, &bitcoin_config.network, ctx)
-        .map_err(|(e, _)| e)
+    parse_inscriptions_and_standardize_block(
+        block_breakdown,
+        &bitcoin_config.network,
+        index_annex_envelopes,
+        ctx,
+    )
+    .map_err(|(e, _)| e)
 }
 
 pub fn build_predicate_from_cli(
@@ -1022,26 +2329,11 @@ pub fn build_predicate_from_cli(
 }
 
 pub async fn check_bitcoind_connection(config: &Config) -> Result<u64, String> {
-    let auth = Auth::UserPass(
-        config.network.bitcoind_rpc_username.clone(),
-        config.network.bitcoind_rpc_password.clone(),
-    );
-
-    let bitcoin_rpc = match Client::new(&config.network.bitcoind_rpc_url, auth) {
-        Ok(con) => con,
-        Err(message) => {
-            return Err(format!("unable to connect to bitcoind: {}", message));
-        }
-    };
-
-    let end_block = match bitcoin_rpc.get_blockchain_info() {
-        Ok(result) => result.blocks,
-        Err(e) => {
-            return Err(format!("unable to connect to bitcoind: {}", e));
-        }
-    };
-
-    Ok(end_block)
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second)
+        .get_block_count(&bitcoin_config)
+        .await
+        .map_err(|e| format!("unable to connect to bitcoind: {}", e))
 }
 
 fn parse_blocks_heights_spec(
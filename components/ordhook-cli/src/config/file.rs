@@ -4,34 +4,71 @@ use ordhook::chainhook_sdk::types::{
     BitcoinBlockSignaling, BitcoinNetwork, StacksNetwork, StacksNodeConfig,
 };
 use ordhook::config::{
-    Config, LogConfig, PredicatesApi, PredicatesApiConfig, ResourcesConfig, SnapshotConfig,
-    StorageConfig, DEFAULT_BITCOIND_RPC_THREADS, DEFAULT_BITCOIND_RPC_TIMEOUT,
-    DEFAULT_CONTROL_PORT, DEFAULT_MEMORY_AVAILABLE, DEFAULT_ULIMIT,
+    BitcoinRpcConfig, CacheEvictionPolicy, Config, LogConfig, LogFormat, PredicatesApi,
+    PredicatesApiConfig, QueryApi, QueryApiConfig, ResourcesConfig, SnapshotConfig, StorageConfig,
+    WebhookConfig, DEFAULT_BITCOIND_RPC_THREADS, DEFAULT_BITCOIND_RPC_TIMEOUT,
+    DEFAULT_BLOCK_CACHE_SIZE, DEFAULT_CACHE_GC_INTERVAL, DEFAULT_CONTROL_PORT,
+    DEFAULT_MAX_ROLLBACK_DEPTH, DEFAULT_MEMORY_AVAILABLE, DEFAULT_METRICS_PORT,
+    DEFAULT_QUERY_API_HOT_CACHE_SIZE, DEFAULT_QUERY_API_PORT, DEFAULT_QUERY_API_WEBSOCKET_PORT,
+    DEFAULT_ROCKS_BLOCK_CACHE_MB, DEFAULT_ROCKS_MAX_WRITE_BUFFERS, DEFAULT_ROCKS_WRITE_BUFFER_MB,
+    DEFAULT_TRAVERSAL_CACHE_SIZE, DEFAULT_ULIMIT, DEFAULT_WEBHOOK_RETRY_COUNT,
 };
+use ordhook::db::set_slow_query_threshold;
+use ordhook::ord::chain::Chain;
 use std::fs::File;
 use std::io::{BufReader, Read};
 
-#[derive(Deserialize, Debug, Clone)]
+fn read_toml_file(file_path: &str) -> Result<toml::Value, String> {
+    let file =
+        File::open(file_path).map_err(|e| format!("unable to read file {}\n{:?}", file_path, e))?;
+    let mut file_reader = BufReader::new(file);
+    let mut file_buffer = vec![];
+    file_reader
+        .read_to_end(&mut file_buffer)
+        .map_err(|e| format!("unable to read file {}\n{:?}", file_path, e))?;
+    toml::from_slice(&file_buffer)
+        .map_err(|e| format!("Config file {} malformatted {}", file_path, e))
+}
+
+/// Deep merge of `overrides` into `base`: tables are merged key by key, any other value
+/// (arrays included) replaces the base one.
+fn merge_toml(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides.into_iter() {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_toml(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ConfigFile {
     pub storage: StorageConfigFile,
     pub http_api: Option<PredicatesApiConfigFile>,
+    pub api: Option<QueryApiConfigFile>,
+    pub webhooks: Option<Vec<WebhookConfigFile>>,
+    pub metrics: Option<MetricsConfigFile>,
     pub resources: ResourcesConfigFile,
     pub network: NetworkConfigFile,
+    pub bitcoin: Option<BitcoinConfigFile>,
     pub logs: Option<LogConfigFile>,
     pub snapshot: Option<SnapshotConfigFile>,
 }
 
 impl ConfigFile {
     pub fn from_file_path(file_path: &str) -> Result<Config, String> {
-        let file = File::open(file_path)
-            .map_err(|e| format!("unable to read file {}\n{:?}", file_path, e))?;
-        let mut file_reader = BufReader::new(file);
-        let mut file_buffer = vec![];
-        file_reader
-            .read_to_end(&mut file_buffer)
-            .map_err(|e| format!("unable to read file {}\n{:?}", file_path, e))?;
-
-        let config_file: ConfigFile = match toml::from_slice(&file_buffer) {
+        ConfigFile::from_toml_value(read_toml_file(file_path)?)
+    }
+
+    fn from_toml_value(value: toml::Value) -> Result<Config, String> {
+        let config_file: ConfigFile = match value.try_into() {
             Ok(s) => s,
             Err(e) => {
                 return Err(format!("Config file malformatted {}", e));
@@ -40,14 +77,33 @@ impl ConfigFile {
         ConfigFile::from_config_file(config_file)
     }
 
+    /// `config` with the keys of the override file (if any) replaced.
+    fn with_override(config: Config, override_path: Option<&String>) -> Result<Config, String> {
+        let Some(override_path) = override_path else {
+            return Ok(config);
+        };
+        let mut config_file = toml::Value::try_from(ConfigFile::from_config(&config))
+            .map_err(|e| format!("unable to serialize config: {e}"))?;
+        merge_toml(&mut config_file, read_toml_file(override_path)?);
+        ConfigFile::from_toml_value(config_file)
+    }
+
     pub fn from_config_file(config_file: ConfigFile) -> Result<Config, String> {
-        let (stacks_network, bitcoin_network) = match config_file.network.mode.as_str() {
-            "devnet" => (StacksNetwork::Devnet, BitcoinNetwork::Regtest),
-            "testnet" => (StacksNetwork::Testnet, BitcoinNetwork::Testnet),
-            "mainnet" => (StacksNetwork::Mainnet, BitcoinNetwork::Mainnet),
-            "signet" => (StacksNetwork::Testnet, BitcoinNetwork::Signet),
+        let chain = match config_file.network.mode.as_str() {
+            "devnet" => Chain::Regtest,
+            "testnet" => Chain::Testnet,
+            "testnet4" => Chain::Testnet4,
+            "mainnet" => Chain::Mainnet,
+            "signet" => Chain::Signet,
             _ => return Err("network.mode not supported".to_string()),
         };
+        // chainhook-sdk has no testnet4 variant, testnet4 blocks are observed as testnet ones
+        let (stacks_network, bitcoin_network) = match chain {
+            Chain::Regtest => (StacksNetwork::Devnet, BitcoinNetwork::Regtest),
+            Chain::Testnet | Chain::Testnet4 => (StacksNetwork::Testnet, BitcoinNetwork::Testnet),
+            Chain::Mainnet => (StacksNetwork::Mainnet, BitcoinNetwork::Mainnet),
+            Chain::Signet => (StacksNetwork::Testnet, BitcoinNetwork::Signet),
+        };
 
         let snapshot = match config_file.snapshot {
             Some(bootstrap) => match bootstrap.download_url {
@@ -57,9 +113,26 @@ impl ConfigFile {
             None => SnapshotConfig::Build,
         };
 
+        let traversal_cache_size = config_file
+            .resources
+            .traversal_cache_size
+            .unwrap_or(DEFAULT_TRAVERSAL_CACHE_SIZE);
+        if traversal_cache_size == 0 {
+            return Err("resources.traversal_cache_size must be greater than 0".to_string());
+        }
+
         let config = Config {
             storage: StorageConfig {
                 working_dir: config_file.storage.working_dir.unwrap_or("ordhook".into()),
+                sqlite_wal: config_file.storage.sqlite_wal.unwrap_or(true),
+                store_inscription_content: config_file
+                    .storage
+                    .store_inscription_content
+                    .unwrap_or(false),
+                max_stored_content_bytes: config_file.storage.max_stored_content_bytes,
+                compress_content: config_file.storage.compress_content.unwrap_or(false),
+                resolve_ipfs: config_file.storage.resolve_ipfs.unwrap_or(false),
+                sqlite_slow_query_ms: config_file.storage.sqlite_slow_query_ms,
             },
             http_api: match config_file.http_api {
                 None => PredicatesApi::Off,
@@ -71,6 +144,45 @@ impl ConfigFile {
                     }),
                 },
             },
+            query_api: match config_file.api {
+                Some(QueryApiConfigFile {
+                    enabled: Some(true),
+                    host,
+                    port,
+                    websocket_port,
+                    hot_cache_size,
+                }) => QueryApi::On(QueryApiConfig {
+                    host: host.unwrap_or("0.0.0.0".into()),
+                    port: port.unwrap_or(DEFAULT_QUERY_API_PORT),
+                    websocket_port: websocket_port.unwrap_or(DEFAULT_QUERY_API_WEBSOCKET_PORT),
+                    hot_cache_size: hot_cache_size.unwrap_or(DEFAULT_QUERY_API_HOT_CACHE_SIZE),
+                }),
+                _ => QueryApi::Off,
+            },
+            webhooks: config_file
+                .webhooks
+                .unwrap_or_default()
+                .into_iter()
+                .map(|webhook| WebhookConfig {
+                    url: webhook.url,
+                    secret: webhook.secret,
+                    retry_count: webhook.retry_count.unwrap_or(DEFAULT_WEBHOOK_RETRY_COUNT),
+                })
+                .collect(),
+            metrics_port: match config_file.metrics {
+                Some(MetricsConfigFile {
+                    enabled: Some(true),
+                    port,
+                }) => Some(port.unwrap_or(DEFAULT_METRICS_PORT)),
+                _ => None,
+            },
+            dry_run: false,
+            start_block: config_file.network.start_block,
+            max_rollback_depth: config_file
+                .network
+                .max_rollback_depth
+                .unwrap_or(DEFAULT_MAX_ROLLBACK_DEPTH),
+            index_annex_envelopes: config_file.network.index_annex_envelopes.unwrap_or(false),
             snapshot,
             resources: ResourcesConfig {
                 ulimit: config_file.resources.ulimit.unwrap_or(DEFAULT_ULIMIT),
@@ -94,6 +206,40 @@ impl ConfigFile {
                     .resources
                     .expected_observers_count
                     .unwrap_or(1),
+                traversal_cache_size,
+                cache_gc_interval: config_file
+                    .resources
+                    .cache_gc_interval
+                    .unwrap_or(DEFAULT_CACHE_GC_INTERVAL),
+                cache_eviction_policy: match config_file.resources.cache_lru_evict_entries {
+                    Some(entries) => CacheEvictionPolicy::LruEvict(entries),
+                    None => CacheEvictionPolicy::FullClear,
+                },
+                max_cache_bytes: config_file.resources.max_cache_bytes,
+                block_cache_size: config_file
+                    .resources
+                    .block_cache_size
+                    .unwrap_or(DEFAULT_BLOCK_CACHE_SIZE),
+                parallel_block_processing: config_file
+                    .resources
+                    .parallel_block_processing
+                    .unwrap_or(false),
+                rocks_block_cache_mb: config_file
+                    .resources
+                    .rocks_block_cache_mb
+                    .unwrap_or(DEFAULT_ROCKS_BLOCK_CACHE_MB),
+                rocks_write_buffer_mb: config_file
+                    .resources
+                    .rocks_write_buffer_mb
+                    .unwrap_or(DEFAULT_ROCKS_WRITE_BUFFER_MB),
+                rocks_max_write_buffers: config_file
+                    .resources
+                    .rocks_max_write_buffers
+                    .unwrap_or(DEFAULT_ROCKS_MAX_WRITE_BUFFERS),
+                worker_threads: config_file
+                    .resources
+                    .worker_threads
+                    .unwrap_or(num_cpus::get()),
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: config_file.network.bitcoind_rpc_url.to_string(),
@@ -111,6 +257,13 @@ impl ConfigFile {
                 stacks_network,
                 bitcoin_network,
             },
+            chain,
+            bitcoin: BitcoinRpcConfig {
+                rpc_request_per_second: config_file
+                    .bitcoin
+                    .as_ref()
+                    .and_then(|b| b.rpc_request_per_second),
+            },
             logs: LogConfig {
                 ordinals_internals: config_file
                     .logs
@@ -122,40 +275,213 @@ impl ConfigFile {
                     .as_ref()
                     .and_then(|l| l.chainhook_internals)
                     .unwrap_or(true),
+                verbose_traversal: config_file
+                    .logs
+                    .as_ref()
+                    .and_then(|l| l.verbose_traversal)
+                    .unwrap_or(false),
+                format: match config_file.logs.as_ref().and_then(|l| l.format.as_deref()) {
+                    None | Some("text") => LogFormat::Text,
+                    Some("json") => LogFormat::Json,
+                    Some(format) => return Err(format!("logs.format {format} not supported")),
+                },
             },
         };
         Ok(config)
     }
 
+    /// Inverse of `from_config_file`: the file describing `config`, every setting made explicit.
+    pub fn from_config(config: &Config) -> ConfigFile {
+        let (bitcoind_zmq_url, stacks_node_rpc_url, stacks_events_ingestion_port) =
+            match config.network.bitcoin_block_signaling {
+                BitcoinBlockSignaling::ZeroMQ(ref zmq_url) => (Some(zmq_url.clone()), None, None),
+                BitcoinBlockSignaling::Stacks(ref stacks_node) => (
+                    None,
+                    Some(stacks_node.rpc_url.clone()),
+                    Some(stacks_node.ingestion_port),
+                ),
+            };
+        let query_api = match config.query_api {
+            QueryApi::On(ref api) => QueryApiConfigFile {
+                enabled: Some(true),
+                host: Some(api.host.clone()),
+                port: Some(api.port),
+                websocket_port: Some(api.websocket_port),
+                hot_cache_size: Some(api.hot_cache_size),
+            },
+            QueryApi::Off => QueryApiConfigFile {
+                enabled: Some(false),
+                host: Some("0.0.0.0".into()),
+                port: Some(DEFAULT_QUERY_API_PORT),
+                websocket_port: Some(DEFAULT_QUERY_API_WEBSOCKET_PORT),
+                hot_cache_size: Some(DEFAULT_QUERY_API_HOT_CACHE_SIZE),
+            },
+        };
+        ConfigFile {
+            storage: StorageConfigFile {
+                working_dir: Some(config.storage.working_dir.clone()),
+                sqlite_wal: Some(config.storage.sqlite_wal),
+                store_inscription_content: Some(config.storage.store_inscription_content),
+                max_stored_content_bytes: config.storage.max_stored_content_bytes,
+                compress_content: Some(config.storage.compress_content),
+                resolve_ipfs: Some(config.storage.resolve_ipfs),
+                sqlite_slow_query_ms: config.storage.sqlite_slow_query_ms,
+            },
+            http_api: match config.http_api {
+                PredicatesApi::On(ref api) => Some(PredicatesApiConfigFile {
+                    http_port: Some(api.http_port),
+                    database_uri: None,
+                    display_logs: Some(api.display_logs),
+                    disabled: None,
+                }),
+                PredicatesApi::Off => None,
+            },
+            api: Some(query_api),
+            webhooks: match config.webhooks.is_empty() {
+                true => None,
+                false => Some(
+                    config
+                        .webhooks
+                        .iter()
+                        .map(|webhook| WebhookConfigFile {
+                            url: webhook.url.clone(),
+                            secret: webhook.secret.clone(),
+                            retry_count: Some(webhook.retry_count),
+                        })
+                        .collect(),
+                ),
+            },
+            metrics: Some(MetricsConfigFile {
+                enabled: Some(config.metrics_port.is_some()),
+                port: Some(config.metrics_port.unwrap_or(DEFAULT_METRICS_PORT)),
+            }),
+            resources: ResourcesConfigFile {
+                ulimit: Some(config.resources.ulimit),
+                cpu_core_available: Some(config.resources.cpu_core_available),
+                memory_available: Some(config.resources.memory_available),
+                bitcoind_rpc_threads: Some(config.resources.bitcoind_rpc_threads),
+                bitcoind_rpc_timeout: Some(config.resources.bitcoind_rpc_timeout),
+                expected_observers_count: Some(config.resources.expected_observers_count),
+                traversal_cache_size: Some(config.resources.traversal_cache_size),
+                cache_gc_interval: Some(config.resources.cache_gc_interval),
+                cache_lru_evict_entries: match config.resources.cache_eviction_policy {
+                    CacheEvictionPolicy::LruEvict(entries) => Some(entries),
+                    CacheEvictionPolicy::FullClear => None,
+                },
+                max_cache_bytes: config.resources.max_cache_bytes,
+                block_cache_size: Some(config.resources.block_cache_size),
+                parallel_block_processing: Some(config.resources.parallel_block_processing),
+                rocks_block_cache_mb: Some(config.resources.rocks_block_cache_mb),
+                rocks_write_buffer_mb: Some(config.resources.rocks_write_buffer_mb),
+                rocks_max_write_buffers: Some(config.resources.rocks_max_write_buffers),
+                worker_threads: Some(config.resources.worker_threads),
+            },
+            network: NetworkConfigFile {
+                mode: match config.chain {
+                    Chain::Regtest => "devnet",
+                    Chain::Testnet => "testnet",
+                    Chain::Testnet4 => "testnet4",
+                    Chain::Signet => "signet",
+                    Chain::Mainnet => "mainnet",
+                }
+                .into(),
+                bitcoind_rpc_url: config.network.bitcoind_rpc_url.clone(),
+                bitcoind_rpc_username: config.network.bitcoind_rpc_username.clone(),
+                bitcoind_rpc_password: config.network.bitcoind_rpc_password.clone(),
+                bitcoind_zmq_url,
+                stacks_node_rpc_url,
+                stacks_events_ingestion_port,
+                start_block: config.start_block,
+                max_rollback_depth: Some(config.max_rollback_depth),
+                index_annex_envelopes: Some(config.index_annex_envelopes),
+            },
+            bitcoin: Some(BitcoinConfigFile {
+                rpc_request_per_second: config.bitcoin.rpc_request_per_second,
+            }),
+            logs: Some(LogConfigFile {
+                ordinals_internals: Some(config.logs.ordinals_internals),
+                chainhook_internals: Some(config.logs.chainhook_internals),
+                verbose_traversal: Some(config.logs.verbose_traversal),
+                format: Some(
+                    match config.logs.format {
+                        LogFormat::Text => "text",
+                        LogFormat::Json => "json",
+                    }
+                    .into(),
+                ),
+            }),
+            snapshot: Some(SnapshotConfigFile {
+                download_url: match config.snapshot {
+                    SnapshotConfig::Download(ref url) => Some(url.clone()),
+                    SnapshotConfig::Build => None,
+                },
+            }),
+        }
+    }
+
+    /// Loads the preset or the file at `config_path`, with the file at `override_path`
+    /// (`--override-config`) overlaid, then the environment overrides applied.
     pub fn default(
         devnet: bool,
         testnet: bool,
         mainnet: bool,
         config_path: &Option<String>,
+        override_path: &Option<String>,
     ) -> Result<Config, String> {
-        let config = match (devnet, testnet, mainnet, config_path) {
-            (true, false, false, _) => Config::devnet_default(),
-            (false, true, false, _) => Config::testnet_default(),
-            (false, false, true, _) => Config::mainnet_default(),
-            (false, false, false, Some(config_path)) => ConfigFile::from_file_path(config_path)?,
+        let override_path = override_path.as_ref();
+        let mut config = match (devnet, testnet, mainnet, config_path) {
+            (true, false, false, _) => {
+                ConfigFile::with_override(Config::devnet_default(), override_path)?
+            }
+            (false, true, false, _) => {
+                ConfigFile::with_override(Config::testnet_default(), override_path)?
+            }
+            (false, false, true, _) => {
+                ConfigFile::with_override(Config::mainnet_default(), override_path)?
+            }
+            (false, false, false, Some(config_path)) => {
+                let mut config_file = read_toml_file(config_path)?;
+                if let Some(override_path) = override_path {
+                    merge_toml(&mut config_file, read_toml_file(override_path)?);
+                }
+                ConfigFile::from_toml_value(config_file)?
+            }
             _ => Err("Invalid combination of arguments".to_string())?,
         };
+        config.apply_env_overrides()?;
+        config
+            .validate()
+            .map_err(|errors| format!("invalid configuration:\n- {}", errors.join("\n- ")))?;
+        set_slow_query_threshold(config.storage.sqlite_slow_query_ms);
         Ok(config)
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BitcoinConfigFile {
+    pub rpc_request_per_second: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct LogConfigFile {
     pub ordinals_internals: Option<bool>,
     pub chainhook_internals: Option<bool>,
+    pub verbose_traversal: Option<bool>,
+    pub format: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct StorageConfigFile {
     pub working_dir: Option<String>,
+    pub sqlite_wal: Option<bool>,
+    pub store_inscription_content: Option<bool>,
+    pub max_stored_content_bytes: Option<u64>,
+    pub compress_content: Option<bool>,
+    pub resolve_ipfs: Option<bool>,
+    pub sqlite_slow_query_ms: Option<u64>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PredicatesApiConfigFile {
     pub http_port: Option<u16>,
     pub database_uri: Option<String>,
@@ -163,12 +489,34 @@ pub struct PredicatesApiConfigFile {
     pub disabled: Option<bool>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct QueryApiConfigFile {
+    pub enabled: Option<bool>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub websocket_port: Option<u16>,
+    pub hot_cache_size: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WebhookConfigFile {
+    pub url: String,
+    pub secret: String,
+    pub retry_count: Option<u8>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MetricsConfigFile {
+    pub enabled: Option<bool>,
+    pub port: Option<u16>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SnapshotConfigFile {
     pub download_url: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ResourcesConfigFile {
     pub ulimit: Option<usize>,
     pub cpu_core_available: Option<usize>,
@@ -176,9 +524,19 @@ pub struct ResourcesConfigFile {
     pub bitcoind_rpc_threads: Option<usize>,
     pub bitcoind_rpc_timeout: Option<u32>,
     pub expected_observers_count: Option<usize>,
+    pub traversal_cache_size: Option<usize>,
+    pub cache_gc_interval: Option<u32>,
+    pub cache_lru_evict_entries: Option<usize>,
+    pub max_cache_bytes: Option<usize>,
+    pub block_cache_size: Option<usize>,
+    pub parallel_block_processing: Option<bool>,
+    pub rocks_block_cache_mb: Option<u64>,
+    pub rocks_write_buffer_mb: Option<u64>,
+    pub rocks_max_write_buffers: Option<u32>,
+    pub worker_threads: Option<usize>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct NetworkConfigFile {
     pub mode: String,
     pub bitcoind_rpc_url: String,
@@ -187,4 +545,134 @@ pub struct NetworkConfigFile {
     pub bitcoind_zmq_url: Option<String>,
     pub stacks_node_rpc_url: Option<String>,
     pub stacks_events_ingestion_port: Option<u16>,
+    pub start_block: Option<u64>,
+    pub max_rollback_depth: Option<u64>,
+    pub index_annex_envelopes: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toml_value(source: &str) -> toml::Value {
+        toml::from_str(source).expect("invalid toml")
+    }
+
+    #[test]
+    fn test_testnet4_mode() {
+        let config = ConfigFile::from_toml_value(toml_value(
+            r#"
+            [storage]
+            working_dir = "ordhook"
+
+            [network]
+            mode = "testnet4"
+            bitcoind_rpc_url = "http://0.0.0.0:48332"
+            bitcoind_rpc_username = "devnet"
+            bitcoind_rpc_password = "devnet"
+
+            [resources]
+            "#,
+        ))
+        .unwrap();
+        assert_eq!(config.chain, Chain::Testnet4);
+        assert!(matches!(
+            config.network.bitcoin_network,
+            BitcoinNetwork::Testnet
+        ));
+        assert_eq!(ConfigFile::from_config(&config).network.mode, "testnet4");
+    }
+
+    #[test]
+    fn test_merge_toml_merges_nested_tables() {
+        let mut base = toml_value(
+            r#"
+            [network]
+            mode = "mainnet"
+            bitcoind_rpc_url = "http://0.0.0.0:8332"
+
+            [network.extra]
+            a = 1
+            b = 2
+            "#,
+        );
+        let overrides = toml_value(
+            r#"
+            [network]
+            bitcoind_rpc_url = "http://10.0.0.1:8332"
+
+            [network.extra]
+            b = 3
+            "#,
+        );
+        merge_toml(&mut base, overrides);
+        assert_eq!(
+            base,
+            toml_value(
+                r#"
+                [network]
+                mode = "mainnet"
+                bitcoind_rpc_url = "http://10.0.0.1:8332"
+
+                [network.extra]
+                a = 1
+                b = 3
+                "#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_toml_replaces_scalars_and_arrays() {
+        let mut base = toml_value(
+            r#"
+            [resources]
+            worker_threads = 16
+            peers = ["a", "b", "c"]
+            "#,
+        );
+        let overrides = toml_value(
+            r#"
+            [resources]
+            worker_threads = 4
+            peers = ["d"]
+            "#,
+        );
+        merge_toml(&mut base, overrides);
+        assert_eq!(base["resources"]["worker_threads"].as_integer(), Some(4));
+        assert_eq!(
+            base["resources"]["peers"],
+            toml::Value::Array(vec![toml::Value::String("d".into())])
+        );
+    }
+
+    #[test]
+    fn test_merge_toml_keeps_absent_keys() {
+        let mut base = toml_value(
+            r#"
+            [storage]
+            working_dir = "ordhook"
+
+            [logs]
+            ordinals_internals = true
+            "#,
+        );
+        let overrides = toml_value(
+            r#"
+            [logs]
+            chainhook_internals = false
+
+            [bitcoin]
+            rpc_request_per_second = 10
+            "#,
+        );
+        merge_toml(&mut base, overrides);
+        assert_eq!(base["storage"]["working_dir"].as_str(), Some("ordhook"));
+        assert_eq!(base["logs"]["ordinals_internals"].as_bool(), Some(true));
+        assert_eq!(base["logs"]["chainhook_internals"].as_bool(), Some(false));
+        assert_eq!(
+            base["bitcoin"]["rpc_request_per_second"].as_integer(),
+            Some(10)
+        );
+    }
 }
@@ -1,10 +1,34 @@
 use ordhook::chainhook_sdk::types::BitcoinNetwork;
+use ordhook::config::{
+    Config, DEFAULT_BITCOIND_RPC_THREADS, DEFAULT_BITCOIND_RPC_TIMEOUT, DEFAULT_BLOCK_CACHE_SIZE,
+    DEFAULT_CACHE_GC_INTERVAL, DEFAULT_CONTROL_PORT, DEFAULT_INGESTION_PORT,
+    DEFAULT_MAX_ROLLBACK_DEPTH, DEFAULT_MEMORY_AVAILABLE, DEFAULT_METRICS_PORT,
+    DEFAULT_QUERY_API_HOT_CACHE_SIZE, DEFAULT_QUERY_API_PORT, DEFAULT_QUERY_API_WEBSOCKET_PORT,
+    DEFAULT_ROCKS_BLOCK_CACHE_MB, DEFAULT_ROCKS_MAX_WRITE_BUFFERS, DEFAULT_ROCKS_WRITE_BUFFER_MB,
+    DEFAULT_TRAVERSAL_CACHE_SIZE, DEFAULT_ULIMIT, DEFAULT_WEBHOOK_RETRY_COUNT,
+};
+
+use super::file::{BitcoinConfigFile, ConfigFile, PredicatesApiConfigFile, WebhookConfigFile};
 
 pub fn generate_config(network: &BitcoinNetwork) -> String {
     let network = format!("{:?}", network);
     let conf = format!(
         r#"[storage]
 working_dir = "ordhook"
+# Disable if full durability of the inscriptions database is required
+# (rollback journal and synchronous=FULL instead of WAL and synchronous=NORMAL).
+sqlite_wal = true
+# Enable to keep the raw content bytes of the inscriptions in hord.sqlite.
+store_inscription_content = false
+# Skip storing the content of the inscriptions larger than this size (in bytes):
+# max_stored_content_bytes = 102400
+# Enable to compress the stored content with zstd, trading CPU for disk space.
+# compress_content = true
+# Enable to store the content fetched from https://ipfs.io for the inscriptions whose
+# content is only an IPFS CID (the CID is kept when the gateway is unreachable).
+# resolve_ipfs = true
+# Log the query plan of the hord.sqlite queries slower than this duration (in ms):
+# sqlite_slow_query_ms = 500
 
 # The Http Api allows you to register / deregister
 # dynamically predicates.
@@ -13,6 +37,34 @@ working_dir = "ordhook"
 # [http_api]
 # http_port = 20456
 
+# The Query Api exposes the indexed inscriptions
+# over HTTP (read-only).
+# Disable by default.
+#
+# [api]
+# enabled = true
+# host = "0.0.0.0"
+# port = 20457
+# Inscription events are streamed on ws://<host>:<websocket_port>/ws
+# websocket_port = 20458
+# Number of inscriptions served from memory by /inscriptions/<id> (0 to disable):
+# hot_cache_size = 1000
+
+# Inscription events can be POSTed to webhooks after each
+# block, signed with an HMAC-SHA256 X-Ordhook-Signature header.
+#
+# [[webhooks]]
+# url = "http://0.0.0.0:3000/events"
+# secret = "<secret>"
+# retry_count = 3
+
+# Prometheus metrics are exposed on http://0.0.0.0:<port>/metrics
+# Disable by default.
+#
+# [metrics]
+# enabled = true
+# port = 20459
+
 [network]
 mode = "{network}"
 bitcoind_rpc_url = "http://0.0.0.0:8332"
@@ -25,6 +77,19 @@ bitcoind_rpc_password = "devnet"
 bitcoind_zmq_url = "tcp://0.0.0.0:18543"
 # but stacks can also be used:
 # stacks_node_rpc_url = "http://0.0.0.0:20443"
+# Inscriptions revealed before this block are ignored,
+# blocks are still ingested to keep tracking transfers:
+# start_block = 900000
+# Deepest re-org rolled back automatically, deeper ones stop ordhook
+# until an operator intervenes:
+max_rollback_depth = 100
+# Index the inscription envelopes carried in taproot annexes.
+# ord ignores them, enabling this makes inscription numbers diverge from ord:
+# index_annex_envelopes = false
+
+[bitcoin]
+# Throttle the requests sent to bitcoind (e.g. when using a rate-limited RPC provider):
+# rpc_request_per_second = 10
 
 [resources]
 ulimit = 2048
@@ -33,6 +98,20 @@ memory_available = 32
 bitcoind_rpc_threads = 4
 bitcoind_rpc_timeout = 15
 expected_observers_count = 1
+traversal_cache_size = 2048
+cache_gc_interval = 100
+# Evict only the N least recently used entries of the traversals cache
+# on each garbage collection instead of clearing it:
+# cache_lru_evict_entries = 100000
+# Collect the traversals cache garbage early once it holds more than this many bytes:
+# max_cache_bytes = 4000000000
+# Number of blocks read from hord.rocksdb kept in memory by the traversals (0 to disable).
+block_cache_size = 16
+parallel_block_processing = false
+rocks_block_cache_mb = 512
+rocks_write_buffer_mb = 64
+rocks_max_write_buffers = 4
+worker_threads = {worker_threads}
 
 # Disable the following section if the state
 # must be built locally
@@ -42,8 +121,204 @@ download_url = "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-l
 [logs]
 ordinals_internals = true
 chainhook_internals = true
+# Log every step of the sat traversals at debug level (requires ordinals_internals):
+# verbose_traversal = false
+# "text" or "json"
+format = "text"
 "#,
         network = network.to_lowercase(),
+        worker_threads = num_cpus::get(),
     );
     conf
 }
+
+/// Fully commented config file describing `config`, generated by walking the fields of
+/// `ConfigFile`. Settings left unset in `config` are written commented out, with an example value.
+pub fn generate_config_template(config: &Config) -> Result<String, String> {
+    let to_table = |config_file: &ConfigFile| match toml::Value::try_from(config_file) {
+        Ok(toml::Value::Table(table)) => Ok(table),
+        Ok(_) => Err("config is not a toml table".to_string()),
+        Err(e) => Err(format!("unable to serialize config: {}", e)),
+    };
+    let config_file = ConfigFile::from_config(config);
+    let values = to_table(&config_file)?;
+    let examples = to_table(&with_examples(config_file))?;
+
+    let mut template = String::new();
+    for (section, example) in examples.iter() {
+        let value = values.get(section);
+        match example {
+            toml::Value::Table(example_fields) => {
+                let fields = value.and_then(|value| value.as_table());
+                write_section(&mut template, section, false, example_fields, fields);
+            }
+            toml::Value::Array(example_entries) => {
+                let entries = value.and_then(|value| value.as_array());
+                match entries {
+                    Some(entries) => {
+                        for entry in entries.iter().filter_map(|entry| entry.as_table()) {
+                            write_section(&mut template, section, true, entry, Some(entry));
+                        }
+                    }
+                    None => {
+                        for entry in example_entries.iter().filter_map(|entry| entry.as_table()) {
+                            write_section(&mut template, section, true, entry, None);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(template)
+}
+
+fn write_section(
+    template: &mut String,
+    section: &str,
+    is_array: bool,
+    example_fields: &toml::value::Table,
+    fields: Option<&toml::value::Table>,
+) {
+    let prefix = if fields.is_some() { "" } else { "# " };
+    if let Some(description) = describe_section(section) {
+        template.push_str(&format!("# {description}\n"));
+    }
+    match is_array {
+        true => template.push_str(&format!("{prefix}[[{section}]]\n")),
+        false => template.push_str(&format!("{prefix}[{section}]\n")),
+    }
+    for (key, example) in example_fields.iter() {
+        if let Some(description) = describe_field(section, key) {
+            template.push_str(&format!("# {key}: {description}\n"));
+        }
+        match fields.and_then(|fields| fields.get(key)) {
+            Some(value) => template.push_str(&format!("{key} = {value}\n")),
+            None => template.push_str(&format!("# {key} = {example}\n")),
+        }
+    }
+    template.push('\n');
+}
+
+/// Fill the optional settings left unset, for them to be listed in the template.
+fn with_examples(mut config_file: ConfigFile) -> ConfigFile {
+    let storage = &mut config_file.storage;
+    storage.max_stored_content_bytes.get_or_insert(102400);
+    storage.sqlite_slow_query_ms.get_or_insert(500);
+    config_file.http_api.get_or_insert(PredicatesApiConfigFile {
+        http_port: Some(DEFAULT_CONTROL_PORT),
+        database_uri: None,
+        display_logs: Some(true),
+        disabled: None,
+    });
+    config_file.webhooks.get_or_insert_with(|| {
+        vec![WebhookConfigFile {
+            url: "http://0.0.0.0:3000/events".into(),
+            secret: "<secret>".into(),
+            retry_count: Some(DEFAULT_WEBHOOK_RETRY_COUNT),
+        }]
+    });
+    let resources = &mut config_file.resources;
+    resources.cache_lru_evict_entries.get_or_insert(100000);
+    resources.max_cache_bytes.get_or_insert(4_000_000_000);
+    config_file
+        .bitcoin
+        .get_or_insert(BitcoinConfigFile {
+            rpc_request_per_second: None,
+        })
+        .rpc_request_per_second
+        .get_or_insert(10);
+    let network = &mut config_file.network;
+    network
+        .bitcoind_zmq_url
+        .get_or_insert("tcp://0.0.0.0:18543".into());
+    network
+        .stacks_node_rpc_url
+        .get_or_insert("http://0.0.0.0:20443".into());
+    network
+        .stacks_events_ingestion_port
+        .get_or_insert(DEFAULT_INGESTION_PORT);
+    network.start_block.get_or_insert(900000);
+    network.index_annex_envelopes.get_or_insert(false);
+    if let Some(ref mut snapshot) = config_file.snapshot {
+        snapshot.download_url.get_or_insert(
+            "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-latest".into(),
+        );
+    }
+    config_file
+}
+
+fn describe_section(section: &str) -> Option<&'static str> {
+    let description = match section {
+        "storage" => "Location and content of the inscriptions database",
+        "http_api" => "Http Api registering / deregistering predicates dynamically",
+        "api" => "Query Api exposing the indexed inscriptions over HTTP (read-only)",
+        "webhooks" => "Inscription events POSTed after each block, signed with an HMAC-SHA256 X-Ordhook-Signature header",
+        "metrics" => "Prometheus metrics, exposed on http://0.0.0.0:<port>/metrics",
+        "resources" => "Resources allocated to the indexer",
+        "network" => "Bitcoin node to index",
+        "bitcoin" => "Requests sent to bitcoind",
+        "logs" => "Logging",
+        "snapshot" => "Remove download_url to build the state locally instead of downloading a snapshot",
+        _ => return None,
+    };
+    Some(description)
+}
+
+fn describe_field(section: &str, key: &str) -> Option<String> {
+    let description = match (section, key) {
+        ("storage", "working_dir") => "directory holding hord.sqlite and hord.rocksdb (default: ordhook)".into(),
+        ("storage", "sqlite_wal") => "use WAL and synchronous=NORMAL for hord.sqlite, disable if full durability is required (default: true)".into(),
+        ("storage", "store_inscription_content") => "keep the raw content bytes of the inscriptions in hord.sqlite (default: false)".into(),
+        ("storage", "max_stored_content_bytes") => "skip storing the content of the inscriptions larger than this size, in bytes (default: no limit)".into(),
+        ("storage", "compress_content") => "compress the stored content with zstd, trading CPU for disk space (default: false)".into(),
+        ("storage", "resolve_ipfs") => "store the content fetched from an IPFS gateway for the inscriptions whose content is an IPFS CID (default: false)".into(),
+        ("storage", "sqlite_slow_query_ms") => "log the query plan of the hord.sqlite queries slower than this duration, in milliseconds (default: disabled)".into(),
+        ("http_api", "http_port") => format!("port of the predicates Api (default: {DEFAULT_CONTROL_PORT})"),
+        ("http_api", "display_logs") => "log the requests received (default: true)".into(),
+        ("api", "enabled") => "serve the Query Api (default: false)".into(),
+        ("api", "host") => "interface the Query Api listens on (default: 0.0.0.0)".into(),
+        ("api", "port") => format!("port of the Query Api (default: {DEFAULT_QUERY_API_PORT})"),
+        ("api", "websocket_port") => format!("port streaming inscription events on ws://<host>:<websocket_port>/ws (default: {DEFAULT_QUERY_API_WEBSOCKET_PORT})"),
+        ("webhooks", "url") => "endpoint receiving the events".into(),
+        ("webhooks", "secret") => "key signing the payloads".into(),
+        ("webhooks", "retry_count") => format!("deliveries attempted before giving up on a block (default: {DEFAULT_WEBHOOK_RETRY_COUNT})"),
+        ("api", "hot_cache_size") => format!("number of inscriptions served from memory by /inscriptions/<id>, 0 to disable (default: {DEFAULT_QUERY_API_HOT_CACHE_SIZE})"),
+        ("metrics", "enabled") => "serve the Prometheus metrics (default: false)".into(),
+        ("metrics", "port") => format!("port of the metrics endpoint (default: {DEFAULT_METRICS_PORT})"),
+        ("resources", "ulimit") => format!("maximum number of open files (default: {DEFAULT_ULIMIT})"),
+        ("resources", "cpu_core_available") => "number of CPU cores available (default: number of logical CPUs)".into(),
+        ("resources", "memory_available") => format!("memory available, in GB (default: {DEFAULT_MEMORY_AVAILABLE})"),
+        ("resources", "bitcoind_rpc_threads") => format!("number of concurrent block downloads (default: {DEFAULT_BITCOIND_RPC_THREADS})"),
+        ("resources", "bitcoind_rpc_timeout") => format!("timeout of the requests sent to bitcoind, in seconds (default: {DEFAULT_BITCOIND_RPC_TIMEOUT})"),
+        ("resources", "expected_observers_count") => "number of observers expected (default: 1)".into(),
+        ("resources", "traversal_cache_size") => format!("number of sat traversals kept in cache (default: {DEFAULT_TRAVERSAL_CACHE_SIZE})"),
+        ("resources", "cache_gc_interval") => format!("number of blocks between two garbage collections of the traversals cache (default: {DEFAULT_CACHE_GC_INTERVAL})"),
+        ("resources", "cache_lru_evict_entries") => "evict only the N least recently used entries of the traversals cache on each garbage collection (default: clear the cache)".into(),
+        ("resources", "max_cache_bytes") => "memory used by the traversals cache triggering a garbage collection before the next cache_gc_interval (default: unbounded)".into(),
+        ("resources", "block_cache_size") => format!("number of blocks read from hord.rocksdb kept in memory by the traversals, 0 to disable (default: {DEFAULT_BLOCK_CACHE_SIZE})"),
+        ("resources", "parallel_block_processing") => "compute the inscriptions data of independent blocks of a batch all at once (default: false)".into(),
+        ("resources", "rocks_block_cache_mb") => format!("block cache of hord.rocksdb, in MB (default: {DEFAULT_ROCKS_BLOCK_CACHE_MB})"),
+        ("resources", "rocks_write_buffer_mb") => format!("write buffer size of hord.rocksdb, in MB (default: {DEFAULT_ROCKS_WRITE_BUFFER_MB})"),
+        ("resources", "rocks_max_write_buffers") => format!("maximum number of write buffers of hord.rocksdb (default: {DEFAULT_ROCKS_MAX_WRITE_BUFFERS})"),
+        ("resources", "worker_threads") => "number of CPU threads for traversal (default: number of logical CPUs)".into(),
+        ("network", "mode") => "devnet, testnet, testnet4, signet or mainnet".into(),
+        ("network", "bitcoind_rpc_url") => "RPC endpoint of bitcoind".into(),
+        ("network", "bitcoind_rpc_username") => "RPC username of bitcoind".into(),
+        ("network", "bitcoind_rpc_password") => "RPC password of bitcoind".into(),
+        ("network", "bitcoind_zmq_url") => "ZeroMQ interface of bitcoind notifying new blocks (default: blocks received from the Stacks node)".into(),
+        ("network", "stacks_node_rpc_url") => "Stacks node notifying new blocks, when bitcoind_zmq_url is not set".into(),
+        ("network", "stacks_events_ingestion_port") => format!("port receiving the events of the Stacks node (default: {DEFAULT_INGESTION_PORT})"),
+        ("network", "max_rollback_depth") => format!("deepest re-org rolled back automatically, deeper ones stop ordhook until an operator intervenes (default: {DEFAULT_MAX_ROLLBACK_DEPTH})"),
+        ("network", "index_annex_envelopes") => "index the inscription envelopes carried in taproot annexes, which ord ignores: inscription numbers diverge from ord once one is numbered (default: false)".into(),
+        ("network", "start_block") => "inscriptions revealed before this block are ignored, blocks are still ingested to keep tracking transfers".into(),
+        ("bitcoin", "rpc_request_per_second") => "throttle the requests sent to bitcoind, e.g. for rate-limited RPC providers (default: unbounded)".into(),
+        ("logs", "ordinals_internals") => "log the ordinals indexing (default: true)".into(),
+        ("logs", "chainhook_internals") => "log the chainhook internals (default: true)".into(),
+        ("logs", "verbose_traversal") => "log every step of the sat traversals at debug level, requires ordinals_internals (default: false)".into(),
+        ("logs", "format") => "text or json (default: text)".into(),
+        ("snapshot", "download_url") => "archive of hord.sqlite bootstrapping the index".into(),
+        _ => return None,
+    };
+    Some(description)
+}
@@ -0,0 +1,76 @@
+//! Throughput of the traversals L2 cache under concurrent access, the way the worker threads
+//! of a batch hit it: mostly lookups, an insertion on each miss.
+//!
+//!     cargo bench -p ordhook --bench cache_contention
+
+use std::{sync::Arc, thread, time::Instant};
+
+use ordhook::{
+    config::CacheEvictionPolicy,
+    core::{new_traversals_lazy_cache, TraversalsCache},
+    db::TransactionBytesCursor,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const KEYS: usize = 200_000;
+const OPERATIONS_PER_THREAD: usize = 500_000;
+const THREADS: [usize; 4] = [4, 8, 16, 32];
+
+fn transaction(txid: [u8; 8]) -> TransactionBytesCursor {
+    TransactionBytesCursor {
+        txid,
+        inputs: vec![],
+        outputs: vec![546, 10_000],
+    }
+}
+
+/// Keys of the transactions of 50 consecutive blocks.
+fn keys() -> Vec<(u32, [u8; 8])> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..KEYS)
+        .map(|i| (800_000 + (i / 4_000) as u32, rng.gen()))
+        .collect()
+}
+
+/// Operations per second with `threads` threads sharing `cache`.
+fn run(cache: Arc<TraversalsCache>, keys: Arc<Vec<(u32, [u8; 8])>>, threads: usize) -> f64 {
+    let started_at = Instant::now();
+    let handles = (0..threads)
+        .map(|thread_index| {
+            let cache = cache.clone();
+            let keys = keys.clone();
+            thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(thread_index as u64);
+                for _ in 0..OPERATIONS_PER_THREAD {
+                    let key = keys[rng.gen_range(0..keys.len())];
+                    if cache.get(&key).is_none() {
+                        cache.insert(key, transaction(key.1));
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    (threads * OPERATIONS_PER_THREAD) as f64 / started_at.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let keys = Arc::new(keys());
+    for eviction_policy in [
+        CacheEvictionPolicy::FullClear,
+        CacheEvictionPolicy::LruEvict(10_000),
+    ] {
+        for threads in THREADS {
+            let cache = Arc::new(new_traversals_lazy_cache(0, &eviction_policy));
+            let ops_per_sec = run(cache, keys.clone(), threads);
+            println!(
+                "{:<24} {:>2} threads: {:>12.0} ops/s",
+                format!("{:?}", eviction_policy),
+                threads,
+                ops_per_sec
+            );
+        }
+    }
+}
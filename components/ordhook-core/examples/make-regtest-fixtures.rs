@@ -0,0 +1,337 @@
+//! Dev-only example generating the regtest fixtures used by the integration tests.
+//!
+//! Spins up a throwaway `bitcoind -regtest`, mines a handful of blocks revealing and
+//! transferring inscriptions, exports every block as the JSON returned by `getblock <hash> 3`
+//! along with a manifest of the expected inscriptions, then tears the node down.
+//!
+//! Usage: `cargo run -p ordhook --example make-regtest-fixtures -- [--bitcoind <path>] [--output <dir>]`
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+use chainhook_sdk::bitcoin::{
+    absolute,
+    blockdata::{opcodes, script},
+    secp256k1::{Keypair, Message, Secp256k1},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{self, LeafVersion, TapLeafHash, TaprootBuilder},
+    transaction, Address, Amount, Network, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
+    Txid, Witness,
+};
+use chainhook_sdk::bitcoincore_rpc::{Auth, Client, RpcApi};
+use ordhook::ord::inscription::Inscription;
+use serde_json::{json, Value as JsonValue};
+
+const RPC_PORT: u16 = 28443;
+const P2P_PORT: u16 = 28444;
+const RPC_USERNAME: &str = "ordhook";
+const RPC_PASSWORD: &str = "ordhook";
+const POSTAGE: Amount = Amount::from_sat(10_000);
+const FEE_RATE: u64 = 2;
+
+/// Kills the node and wipes its datadir on the way out, including on errors.
+struct RegtestNode {
+    process: Child,
+    datadir: PathBuf,
+}
+
+impl RegtestNode {
+    fn spawn(bitcoind: &str) -> Result<RegtestNode, String> {
+        let mut datadir = std::env::temp_dir();
+        datadir.push(format!("ordhook-regtest-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&datadir)
+            .map_err(|e| format!("unable to create {}: {e}", datadir.display()))?;
+        let process = Command::new(bitcoind)
+            .arg("-regtest")
+            .arg(format!("-datadir={}", datadir.display()))
+            .arg(format!("-rpcport={RPC_PORT}"))
+            .arg(format!("-port={P2P_PORT}"))
+            .arg(format!("-rpcuser={RPC_USERNAME}"))
+            .arg(format!("-rpcpassword={RPC_PASSWORD}"))
+            .arg("-server=1")
+            .arg("-listen=0")
+            .arg("-txindex=1")
+            .arg("-fallbackfee=0.0001")
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("unable to spawn {bitcoind}: {e}"))?;
+        Ok(RegtestNode { process, datadir })
+    }
+
+    fn rpc(&self) -> Result<Client, String> {
+        let auth = Auth::UserPass(RPC_USERNAME.into(), RPC_PASSWORD.into());
+        let rpc = Client::new(&format!("http://127.0.0.1:{RPC_PORT}"), auth)
+            .map_err(|e| format!("Bitcoin RPC error: {e}"))?;
+        for _ in 0..30 {
+            if rpc.get_blockchain_info().is_ok() {
+                return Ok(rpc);
+            }
+            sleep(Duration::from_secs(1));
+        }
+        Err("bitcoind did not become ready within 30s".into())
+    }
+}
+
+impl Drop for RegtestNode {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.datadir);
+    }
+}
+
+struct Reveal {
+    inscription_id: String,
+    content_type: String,
+    outpoint: OutPoint,
+    value: Amount,
+}
+
+fn main() {
+    let mut bitcoind = "bitcoind".to_string();
+    let mut output = PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/integration/fixtures/regtest"
+    ));
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--bitcoind", Some(value)) => bitcoind = value,
+            ("--output", Some(value)) => output = PathBuf::from(value),
+            _ => {
+                eprintln!("usage: make-regtest-fixtures [--bitcoind <path>] [--output <dir>]");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = make_fixtures(&bitcoind, &output) {
+        eprintln!("unable to make regtest fixtures: {e}");
+        std::process::exit(1);
+    }
+    println!("Regtest fixtures written to {}", output.display());
+}
+
+fn make_fixtures(bitcoind: &str, output: &PathBuf) -> Result<(), String> {
+    let node = RegtestNode::spawn(bitcoind)?;
+    let rpc = node.rpc()?;
+    rpc.create_wallet("fixtures", None, None, None, None)
+        .map_err(|e| format!("unable to create wallet: {e}"))?;
+    let miner = new_address(&rpc)?;
+    // Coinbase outputs need 100 confirmations before being spendable
+    rpc.generate_to_address(101, &miner)
+        .map_err(|e| format!("unable to mine blocks: {e}"))?;
+
+    let inscriptions = [
+        (
+            "text/plain;charset=utf-8",
+            b"ordhook regtest fixture".to_vec(),
+        ),
+        (
+            "application/json",
+            br#"{"p":"brc-20","op":"deploy","tick":"rgts","max":"21000000","lim":"1000"}"#.to_vec(),
+        ),
+        ("text/html;charset=utf-8", b"<h1>ordhook</h1>".to_vec()),
+    ];
+    let mut reveals = vec![];
+    for (index, (content_type, body)) in inscriptions.into_iter().enumerate() {
+        let inscription = Inscription {
+            content_type: Some(content_type.as_bytes().to_vec()),
+            body: Some(body),
+            ..Default::default()
+        };
+        reveals.push(inscribe(&rpc, &inscription, content_type, index as u8 + 1)?);
+    }
+    let reveal_block_hash = mine_block(&rpc, &miner)?;
+    let reveal_block_height = get_block_height(&rpc, &reveal_block_hash)?;
+
+    // Move the first inscription, so that transfers are covered as well
+    transfer(&rpc, &reveals[0])?;
+    let transfer_block_hash = mine_block(&rpc, &miner)?;
+    let transfer_block_height = get_block_height(&rpc, &transfer_block_hash)?;
+
+    fs::create_dir_all(output)
+        .map_err(|e| format!("unable to create {}: {e}", output.display()))?;
+    let tip = rpc
+        .get_block_count()
+        .map_err(|e| format!("unable to retrieve chain tip: {e}"))?;
+    for block_height in 1..=tip {
+        let block_hash = rpc
+            .get_block_hash(block_height)
+            .map_err(|e| format!("unable to retrieve block hash #{block_height}: {e}"))?;
+        let block = rpc
+            .call::<JsonValue>("getblock", &[json!(block_hash.to_string()), json!(3)])
+            .map_err(|e| format!("unable to retrieve block #{block_height}: {e}"))?;
+        write_json(
+            output,
+            &format!("{block_height}.json"),
+            &json!({ "result": block }),
+        )?;
+    }
+
+    let manifest = json!({
+        "tip": tip,
+        "inscriptions": reveals.iter().map(|reveal| json!({
+            "inscription_id": reveal.inscription_id,
+            "content_type": reveal.content_type,
+            "block_height": reveal_block_height,
+        })).collect::<Vec<_>>(),
+        "transfers": [{
+            "inscription_id": reveals[0].inscription_id,
+            "block_height": transfer_block_height,
+        }],
+    });
+    write_json(output, "manifest.json", &manifest)?;
+
+    let _ = rpc.stop();
+    Ok(())
+}
+
+/// Commit to a tapscript embedding the inscription envelope, then reveal it by spending the
+/// commit output through the script path. Both transactions are left in the mempool.
+fn inscribe(
+    rpc: &Client,
+    inscription: &Inscription,
+    content_type: &str,
+    seed: u8,
+) -> Result<Reveal, String> {
+    let secp = Secp256k1::new();
+    let keypair = Keypair::from_seckey_slice(&secp, &[seed; 32])
+        .map_err(|e| format!("invalid secret key: {e}"))?;
+    let (public_key, _) = keypair.x_only_public_key();
+
+    let builder = script::Builder::new()
+        .push_x_only_key(&public_key)
+        .push_opcode(opcodes::all::OP_CHECKSIG);
+    let reveal_script = inscription
+        .append_reveal_script_to_builder(builder)
+        .into_script();
+    let spend_info = TaprootBuilder::new()
+        .add_leaf(0, reveal_script.clone())
+        .map_err(|e| format!("unable to build taproot tree: {e}"))?
+        .finalize(&secp, public_key)
+        .map_err(|_| "unable to finalize taproot tree".to_string())?;
+    let control_block = spend_info
+        .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+        .ok_or("unable to compute control block")?;
+    let commit_address = Address::p2tr_tweaked(spend_info.output_key(), Network::Regtest);
+
+    let commit_txid = rpc
+        .send_to_address(&commit_address, POSTAGE, None, None, None, None, None, None)
+        .map_err(|e| format!("unable to send commit transaction: {e}"))?;
+    let commit_tx = rpc
+        .get_raw_transaction(&commit_txid, None)
+        .map_err(|e| format!("unable to retrieve commit transaction: {e}"))?;
+    let (vout, commit_output) = commit_tx
+        .output
+        .iter()
+        .enumerate()
+        .find(|(_, output)| output.script_pubkey == commit_address.script_pubkey())
+        .ok_or("commit output not found")?;
+
+    let destination = new_address(rpc)?;
+    let mut reveal_tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(commit_txid, vout as u32),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: POSTAGE
+                - Amount::from_sat(inscription.reveal_transaction_vsize() as u64 * FEE_RATE),
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let sighash = SighashCache::new(&reveal_tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[commit_output.clone()]),
+            TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+            TapSighashType::Default,
+        )
+        .map_err(|e| format!("unable to compute sighash: {e}"))?;
+    let message = Message::from_digest_slice(sighash.as_ref())
+        .map_err(|e| format!("invalid sighash: {e}"))?;
+    let signature = taproot::Signature {
+        sig: secp.sign_schnorr_no_aux_rand(&message, &keypair),
+        hash_ty: TapSighashType::Default,
+    };
+    let mut witness = Witness::new();
+    witness.push(signature.to_vec());
+    witness.push(reveal_script);
+    witness.push(control_block.serialize());
+    reveal_tx.input[0].witness = witness;
+
+    let reveal_txid = rpc
+        .send_raw_transaction(&reveal_tx)
+        .map_err(|e| format!("unable to send reveal transaction: {e}"))?;
+    Ok(Reveal {
+        inscription_id: format!("{reveal_txid}i0"),
+        content_type: content_type.to_string(),
+        outpoint: OutPoint::new(reveal_txid, 0),
+        value: reveal_tx.output[0].value,
+    })
+}
+
+fn transfer(rpc: &Client, reveal: &Reveal) -> Result<Txid, String> {
+    let destination = new_address(rpc)?;
+    let transfer_tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: reveal.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: reveal.value - Amount::from_sat(1_000),
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let signed_tx = rpc
+        .sign_raw_transaction_with_wallet(&transfer_tx, None, None)
+        .map_err(|e| format!("unable to sign transfer transaction: {e}"))?
+        .transaction()
+        .map_err(|e| format!("unable to decode signed transfer transaction: {e}"))?;
+    rpc.send_raw_transaction(&signed_tx)
+        .map_err(|e| format!("unable to send transfer transaction: {e}"))
+}
+
+fn new_address(rpc: &Client) -> Result<Address, String> {
+    rpc.get_new_address(None, None)
+        .map(|address| address.assume_checked())
+        .map_err(|e| format!("unable to generate address: {e}"))
+}
+
+fn mine_block(rpc: &Client, miner: &Address) -> Result<String, String> {
+    rpc.generate_to_address(1, miner)
+        .map_err(|e| format!("unable to mine block: {e}"))?
+        .pop()
+        .map(|block_hash| block_hash.to_string())
+        .ok_or("no block mined".into())
+}
+
+fn get_block_height(rpc: &Client, block_hash: &str) -> Result<usize, String> {
+    let block_hash = block_hash
+        .parse()
+        .map_err(|e| format!("invalid block hash: {e}"))?;
+    rpc.get_block_info(&block_hash)
+        .map(|info| info.height)
+        .map_err(|e| format!("unable to retrieve block {block_hash}: {e}"))
+}
+
+fn write_json(dir: &PathBuf, file_name: &str, value: &JsonValue) -> Result<(), String> {
+    let mut path = dir.clone();
+    path.push(file_name);
+    let content = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| format!("unable to write {}: {e}", path.display()))
+}
@@ -1,71 +1,211 @@
 use crate::core::OrdhookConfig;
+use crate::ord::chain::Chain;
 pub use chainhook_sdk::indexer::IndexerConfig;
 use chainhook_sdk::observer::EventObserverConfig;
 use chainhook_sdk::types::{
     BitcoinBlockSignaling, BitcoinNetwork, StacksNetwork, StacksNodeConfig,
 };
+use serde::ser::{SerializeStruct, Serializer};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 const DEFAULT_MAINNET_ORDINALS_SQLITE_ARCHIVE: &str =
     "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-latest";
 
 pub const DEFAULT_INGESTION_PORT: u16 = 20455;
 pub const DEFAULT_CONTROL_PORT: u16 = 20456;
+pub const DEFAULT_QUERY_API_PORT: u16 = 20457;
+pub const DEFAULT_QUERY_API_WEBSOCKET_PORT: u16 = 20458;
+pub const DEFAULT_QUERY_API_HOT_CACHE_SIZE: usize = 1000;
+pub const DEFAULT_METRICS_PORT: u16 = 20459;
+pub const DEFAULT_WEBHOOK_RETRY_COUNT: u8 = 3;
 pub const DEFAULT_ULIMIT: usize = 2048;
 pub const DEFAULT_MEMORY_AVAILABLE: usize = 8;
 pub const DEFAULT_BITCOIND_RPC_THREADS: usize = 4;
 pub const DEFAULT_BITCOIND_RPC_TIMEOUT: u32 = 15;
+pub const DEFAULT_TRAVERSAL_CACHE_SIZE: usize = 2048;
+pub const DEFAULT_CACHE_GC_INTERVAL: u32 = 100;
+pub const DEFAULT_BLOCK_CACHE_SIZE: usize = 16;
+pub const DEFAULT_MAX_ROLLBACK_DEPTH: u64 = 100;
+pub const DEFAULT_ROCKS_BLOCK_CACHE_MB: u64 = 512;
+pub const DEFAULT_ROCKS_WRITE_BUFFER_MB: u64 = 64;
+pub const DEFAULT_ROCKS_MAX_WRITE_BUFFERS: u32 = 4;
 
-#[derive(Clone, Debug)]
+/// Resolved configuration. Once loaded, fields can be overridden through environment variables
+/// (see `Config::apply_env_overrides`):
+///
+/// | Variable                                   | Field                                        |
+/// |--------------------------------------------|----------------------------------------------|
+/// | `ORDHOOK_CACHE_PATH`                       | `storage.working_dir`                        |
+/// | `ORDHOOK_SQLITE_WAL`                       | `storage.sqlite_wal`                         |
+/// | `ORDHOOK_STORE_INSCRIPTION_CONTENT`        | `storage.store_inscription_content`          |
+/// | `ORDHOOK_MAX_STORED_CONTENT_BYTES`         | `storage.max_stored_content_bytes`           |
+/// | `ORDHOOK_COMPRESS_CONTENT`                 | `storage.compress_content`                   |
+/// | `ORDHOOK_RESOLVE_IPFS`                     | `storage.resolve_ipfs`                       |
+/// | `ORDHOOK_SQLITE_SLOW_QUERY_MS`             | `storage.sqlite_slow_query_ms`               |
+/// | `ORDHOOK_HTTP_API_ENABLED`                 | `http_api`                                   |
+/// | `ORDHOOK_HTTP_API_PORT`                    | `http_api.http_port`                         |
+/// | `ORDHOOK_API_ENABLED`                      | `query_api`                                  |
+/// | `ORDHOOK_API_HOST`                         | `query_api.host`                             |
+/// | `ORDHOOK_API_PORT`                         | `query_api.port`                             |
+/// | `ORDHOOK_API_WEBSOCKET_PORT`               | `query_api.websocket_port`                   |
+/// | `ORDHOOK_API_HOT_CACHE_SIZE`               | `query_api.hot_cache_size`                   |
+/// | `ORDHOOK_METRICS_PORT`                     | `metrics_port`                               |
+/// | `ORDHOOK_START_BLOCK`                      | `start_block`                                |
+/// | `ORDHOOK_MAX_ROLLBACK_DEPTH`               | `max_rollback_depth`                         |
+/// | `ORDHOOK_INDEX_ANNEX_ENVELOPES`            | `index_annex_envelopes`                      |
+/// | `ORDHOOK_BITCOIN_RPC_URL`                  | `network.bitcoind_rpc_url`                   |
+/// | `ORDHOOK_BITCOIN_RPC_USERNAME`             | `network.bitcoind_rpc_username`              |
+/// | `ORDHOOK_BITCOIN_RPC_PASSWORD`             | `network.bitcoind_rpc_password`              |
+/// | `ORDHOOK_BITCOIN_ZMQ_URL`                  | `network.bitcoin_block_signaling`            |
+/// | `ORDHOOK_BITCOIN_RPC_REQUEST_PER_SECOND`   | `bitcoin.rpc_request_per_second`             |
+/// | `ORDHOOK_SNAPSHOT_DOWNLOAD_URL`            | `snapshot`                                   |
+/// | `ORDHOOK_LOGS_ORDINALS_INTERNALS`          | `logs.ordinals_internals`                    |
+/// | `ORDHOOK_LOGS_CHAINHOOK_INTERNALS`         | `logs.chainhook_internals`                   |
+/// | `ORDHOOK_LOGS_VERBOSE_TRAVERSAL`           | `logs.verbose_traversal`                     |
+/// | `ORDHOOK_LOGS_FORMAT`                      | `logs.format` (`text` or `json`)             |
+/// | `ORDHOOK_ULIMIT`                           | `resources.ulimit`                           |
+/// | `ORDHOOK_CPU_CORE_AVAILABLE`               | `resources.cpu_core_available`               |
+/// | `ORDHOOK_MEMORY_AVAILABLE`                 | `resources.memory_available`                 |
+/// | `ORDHOOK_BITCOIND_RPC_THREADS`             | `resources.bitcoind_rpc_threads`             |
+/// | `ORDHOOK_BITCOIND_RPC_TIMEOUT`             | `resources.bitcoind_rpc_timeout`             |
+/// | `ORDHOOK_EXPECTED_OBSERVERS_COUNT`         | `resources.expected_observers_count`         |
+/// | `ORDHOOK_TRAVERSAL_CACHE_SIZE`             | `resources.traversal_cache_size`             |
+/// | `ORDHOOK_CACHE_GC_INTERVAL`                | `resources.cache_gc_interval`                |
+/// | `ORDHOOK_CACHE_LRU_EVICT_ENTRIES`          | `resources.cache_eviction_policy`            |
+/// | `ORDHOOK_MAX_CACHE_BYTES`                  | `resources.max_cache_bytes`                  |
+/// | `ORDHOOK_BLOCK_CACHE_SIZE`                 | `resources.block_cache_size`                 |
+/// | `ORDHOOK_PARALLEL_BLOCK_PROCESSING`        | `resources.parallel_block_processing`        |
+/// | `ORDHOOK_ROCKS_BLOCK_CACHE_MB`             | `resources.rocks_block_cache_mb`             |
+/// | `ORDHOOK_ROCKS_WRITE_BUFFER_MB`            | `resources.rocks_write_buffer_mb`            |
+/// | `ORDHOOK_ROCKS_MAX_WRITE_BUFFERS`          | `resources.rocks_max_write_buffers`          |
+/// | `ORDHOOK_WORKER_THREADS`                   | `resources.worker_threads`                   |
+#[derive(Clone, Debug, Serialize)]
 pub struct Config {
     pub storage: StorageConfig,
     pub http_api: PredicatesApi,
+    pub query_api: QueryApi,
+    pub webhooks: Vec<WebhookConfig>,
+    /// Port of the Prometheus metrics endpoint, disabled when `None`
+    pub metrics_port: Option<u16>,
+    /// Compute the ordinals data of the blocks processed without writing anything to hord.sqlite / hord.rocksdb
+    pub dry_run: bool,
+    /// Inscriptions revealed before this height are not indexed, transfers still are
+    pub start_block: Option<u64>,
+    /// Deepest re-org rolled back automatically, deeper ones stop the indexer
+    pub max_rollback_depth: u64,
+    /// Index the inscription envelopes carried in taproot annexes. ord ignores them: once such an
+    /// envelope is numbered, the inscription numbers diverge from ord's
+    pub index_annex_envelopes: bool,
     pub resources: ResourcesConfig,
+    /// Chain indexed, `network.bitcoin_network` being the closest network known to chainhook-sdk
+    pub chain: Chain,
+    #[serde(serialize_with = "serialize_indexer_config")]
     pub network: IndexerConfig,
+    pub bitcoin: BitcoinRpcConfig,
     pub snapshot: SnapshotConfig,
     pub logs: LogConfig,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+pub struct BitcoinRpcConfig {
+    /// Maximum number of requests per second sent to bitcoind, unbounded when `None`
+    pub rpc_request_per_second: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct LogConfig {
     pub ordinals_internals: bool,
     pub chainhook_internals: bool,
+    /// Log every step of the sat traversals at debug level (requires `ordinals_internals`)
+    pub verbose_traversal: bool,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, for log ingestion pipelines
+    Json,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct StorageConfig {
     pub working_dir: String,
+    pub sqlite_wal: bool,
+    /// Keep the raw content bytes of the inscriptions in hord.sqlite
+    pub store_inscription_content: bool,
+    /// Skip storing the content of the inscriptions larger than this size, metadata is still stored
+    pub max_stored_content_bytes: Option<u64>,
+    /// Compress the stored content of the inscriptions with zstd
+    pub compress_content: bool,
+    /// Store the content fetched from an IPFS gateway for the inscriptions whose content is a CID
+    pub resolve_ipfs: bool,
+    /// Log the query plan of the hord.sqlite queries slower than this duration, in milliseconds
+    pub sqlite_slow_query_ms: Option<u64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum PredicatesApi {
     Off,
     On(PredicatesApiConfig),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PredicatesApiConfig {
     pub http_port: u16,
     pub display_logs: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+pub enum QueryApi {
+    Off,
+    On(QueryApiConfig),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryApiConfig {
+    pub host: String,
+    pub port: u16,
+    pub websocket_port: u16,
+    /// Number of inscriptions served from memory by `/inscriptions/<id>`, 0 to disable
+    pub hot_cache_size: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(serialize_with = "serialize_redacted")]
+    pub secret: String,
+    pub retry_count: u8,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub enum SnapshotConfig {
     Build,
     Download(String),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PathConfig {
     pub file_path: PathBuf,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct UrlConfig {
     pub file_url: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// How the traversals cache (L2) is trimmed at each garbage collection cycle.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum CacheEvictionPolicy {
+    /// Drop every entry
+    FullClear,
+    /// Evict the given number of least recently used entries
+    LruEvict(usize),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ResourcesConfig {
     pub ulimit: usize,
     pub cpu_core_available: usize,
@@ -73,6 +213,21 @@ pub struct ResourcesConfig {
     pub bitcoind_rpc_threads: usize,
     pub bitcoind_rpc_timeout: u32,
     pub expected_observers_count: usize,
+    pub traversal_cache_size: usize,
+    pub cache_gc_interval: u32,
+    pub cache_eviction_policy: CacheEvictionPolicy,
+    /// Memory used by the traversals cache (L2) triggering a garbage collection before the
+    /// next `cache_gc_interval`, unbounded when `None`
+    pub max_cache_bytes: Option<usize>,
+    /// Number of blocks read from hord.rocksdb kept in memory by the traversals, 0 to disable
+    pub block_cache_size: usize,
+    /// Compute the inscriptions data of independent blocks of a batch all at once
+    pub parallel_block_processing: bool,
+    pub rocks_block_cache_mb: u64,
+    pub rocks_write_buffer_mb: u64,
+    pub rocks_max_write_buffers: u32,
+    /// Size of the pool computing inscriptions data
+    pub worker_threads: usize,
 }
 
 impl ResourcesConfig {
@@ -92,17 +247,287 @@ impl Config {
         }
     }
 
+    pub fn is_query_api_enabled(&self) -> bool {
+        match self.query_api {
+            QueryApi::Off => false,
+            QueryApi::On(_) => true,
+        }
+    }
+
+    /// Override the fields listed in the `Config` documentation with the `ORDHOOK_*`
+    /// environment variables set, so that they win over the config file.
+    pub fn apply_env_overrides(&mut self) -> Result<(), String> {
+        self.apply_overrides(|key| std::env::var(key).ok())
+    }
+
+    fn apply_overrides<F>(&mut self, var: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let read = |key: &str| var(key).filter(|value| !value.is_empty());
+
+        if let Some(value) = read("ORDHOOK_CACHE_PATH") {
+            self.storage.working_dir = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_SQLITE_WAL")? {
+            self.storage.sqlite_wal = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_STORE_INSCRIPTION_CONTENT")? {
+            self.storage.store_inscription_content = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_MAX_STORED_CONTENT_BYTES")? {
+            self.storage.max_stored_content_bytes = Some(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_COMPRESS_CONTENT")? {
+            self.storage.compress_content = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_RESOLVE_IPFS")? {
+            self.storage.resolve_ipfs = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_SQLITE_SLOW_QUERY_MS")? {
+            self.storage.sqlite_slow_query_ms = Some(value);
+        }
+
+        match parse_override(&read, "ORDHOOK_HTTP_API_ENABLED")? {
+            Some(false) => self.http_api = PredicatesApi::Off,
+            Some(true) if !self.is_http_api_enabled() => {
+                self.http_api = PredicatesApi::On(PredicatesApiConfig {
+                    http_port: DEFAULT_CONTROL_PORT,
+                    display_logs: true,
+                })
+            }
+            _ => {}
+        }
+        if let PredicatesApi::On(ref mut api) = self.http_api {
+            if let Some(value) = parse_override(&read, "ORDHOOK_HTTP_API_PORT")? {
+                api.http_port = value;
+            }
+        }
+
+        match parse_override(&read, "ORDHOOK_API_ENABLED")? {
+            Some(false) => self.query_api = QueryApi::Off,
+            Some(true) if !self.is_query_api_enabled() => {
+                self.query_api = QueryApi::On(QueryApiConfig {
+                    host: "0.0.0.0".into(),
+                    port: DEFAULT_QUERY_API_PORT,
+                    websocket_port: DEFAULT_QUERY_API_WEBSOCKET_PORT,
+                    hot_cache_size: DEFAULT_QUERY_API_HOT_CACHE_SIZE,
+                })
+            }
+            _ => {}
+        }
+        if let QueryApi::On(ref mut api) = self.query_api {
+            if let Some(value) = read("ORDHOOK_API_HOST") {
+                api.host = value;
+            }
+            if let Some(value) = parse_override(&read, "ORDHOOK_API_PORT")? {
+                api.port = value;
+            }
+            if let Some(value) = parse_override(&read, "ORDHOOK_API_WEBSOCKET_PORT")? {
+                api.websocket_port = value;
+            }
+            if let Some(value) = parse_override(&read, "ORDHOOK_API_HOT_CACHE_SIZE")? {
+                api.hot_cache_size = value;
+            }
+        }
+
+        if let Some(value) = parse_override(&read, "ORDHOOK_METRICS_PORT")? {
+            self.metrics_port = Some(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_START_BLOCK")? {
+            self.start_block = Some(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_MAX_ROLLBACK_DEPTH")? {
+            self.max_rollback_depth = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_INDEX_ANNEX_ENVELOPES")? {
+            self.index_annex_envelopes = value;
+        }
+
+        if let Some(value) = read("ORDHOOK_BITCOIN_RPC_URL") {
+            self.network.bitcoind_rpc_url = value;
+        }
+        if let Some(value) = read("ORDHOOK_BITCOIN_RPC_USERNAME") {
+            self.network.bitcoind_rpc_username = value;
+        }
+        if let Some(value) = read("ORDHOOK_BITCOIN_RPC_PASSWORD") {
+            self.network.bitcoind_rpc_password = value;
+        }
+        if let Some(value) = read("ORDHOOK_BITCOIN_ZMQ_URL") {
+            self.network.bitcoin_block_signaling = BitcoinBlockSignaling::ZeroMQ(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_BITCOIN_RPC_REQUEST_PER_SECOND")? {
+            self.bitcoin.rpc_request_per_second = Some(value);
+        }
+        if let Some(value) = read("ORDHOOK_SNAPSHOT_DOWNLOAD_URL") {
+            self.snapshot = SnapshotConfig::Download(value);
+        }
+
+        if let Some(value) = parse_override(&read, "ORDHOOK_LOGS_ORDINALS_INTERNALS")? {
+            self.logs.ordinals_internals = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_LOGS_CHAINHOOK_INTERNALS")? {
+            self.logs.chainhook_internals = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_LOGS_VERBOSE_TRAVERSAL")? {
+            self.logs.verbose_traversal = value;
+        }
+        match read("ORDHOOK_LOGS_FORMAT").as_deref() {
+            None => {}
+            Some("text") => self.logs.format = LogFormat::Text,
+            Some("json") => self.logs.format = LogFormat::Json,
+            Some(format) => return Err(format!("ORDHOOK_LOGS_FORMAT {format} not supported")),
+        }
+
+        let resources = &mut self.resources;
+        if let Some(value) = parse_override(&read, "ORDHOOK_ULIMIT")? {
+            resources.ulimit = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_CPU_CORE_AVAILABLE")? {
+            resources.cpu_core_available = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_MEMORY_AVAILABLE")? {
+            resources.memory_available = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_BITCOIND_RPC_THREADS")? {
+            resources.bitcoind_rpc_threads = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_BITCOIND_RPC_TIMEOUT")? {
+            resources.bitcoind_rpc_timeout = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_EXPECTED_OBSERVERS_COUNT")? {
+            resources.expected_observers_count = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_TRAVERSAL_CACHE_SIZE")? {
+            resources.traversal_cache_size = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_CACHE_GC_INTERVAL")? {
+            resources.cache_gc_interval = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_CACHE_LRU_EVICT_ENTRIES")? {
+            resources.cache_eviction_policy = CacheEvictionPolicy::LruEvict(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_MAX_CACHE_BYTES")? {
+            resources.max_cache_bytes = Some(value);
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_BLOCK_CACHE_SIZE")? {
+            resources.block_cache_size = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_PARALLEL_BLOCK_PROCESSING")? {
+            resources.parallel_block_processing = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_ROCKS_BLOCK_CACHE_MB")? {
+            resources.rocks_block_cache_mb = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_ROCKS_WRITE_BUFFER_MB")? {
+            resources.rocks_write_buffer_mb = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_ROCKS_MAX_WRITE_BUFFERS")? {
+            resources.rocks_max_write_buffers = value;
+        }
+        if let Some(value) = parse_override(&read, "ORDHOOK_WORKER_THREADS")? {
+            resources.worker_threads = value;
+        }
+        Ok(())
+    }
+
+    /// Check the configuration before anything gets started, reporting every error at once.
+    /// `start_block` is checked against the indexed height by
+    /// `check_start_block_against_indexed_height`, once the database can be read.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.resources.worker_threads == 0 {
+            errors.push("resources.worker_threads must be greater than 0".to_string());
+        }
+        if self.resources.cpu_core_available == 0 {
+            errors.push("resources.cpu_core_available must be greater than 0".to_string());
+        }
+        if self.resources.bitcoind_rpc_threads == 0 {
+            errors.push("resources.bitcoind_rpc_threads must be greater than 0".to_string());
+        }
+        if self.bitcoin.rpc_request_per_second == Some(0) {
+            errors.push("bitcoin.rpc_request_per_second must be greater than 0".to_string());
+        }
+        if self.resources.traversal_cache_size == 0 {
+            errors.push("resources.traversal_cache_size must be greater than 0".to_string());
+        }
+        if self.max_rollback_depth == 0 {
+            errors.push("max_rollback_depth must be greater than 0".to_string());
+        }
+        if Chain::from_bitcoin_network(&self.network.bitcoin_network).network()
+            != self.chain.network()
+        {
+            errors.push(format!(
+                "chain {} can't be observed as bitcoin network {:?}",
+                self.chain, self.network.bitcoin_network
+            ));
+        }
+        if self.resources.max_cache_bytes == Some(0) {
+            errors.push("resources.max_cache_bytes must be greater than 0".to_string());
+        }
+        if self.resources.cache_gc_interval == 0 {
+            errors.push("resources.cache_gc_interval must be greater than 0".to_string());
+        }
+        if let CacheEvictionPolicy::LruEvict(0) = self.resources.cache_eviction_policy {
+            errors.push("resources.cache_lru_evict_entries must be greater than 0".to_string());
+        }
+
+        let mut ports = vec![("the events ingestion port", DEFAULT_INGESTION_PORT)];
+        if let PredicatesApi::On(ref api) = self.http_api {
+            ports.push(("http_api.http_port", api.http_port));
+        }
+        if let QueryApi::On(ref api) = self.query_api {
+            ports.push(("query_api.port", api.port));
+            ports.push(("query_api.websocket_port", api.websocket_port));
+        }
+        if let Some(port) = self.metrics_port {
+            ports.push(("metrics.port", port));
+        }
+        for (index, (name, port)) in ports.iter().enumerate() {
+            if *port == 0 {
+                errors.push(format!("{name} must be between 1 and 65535"));
+            } else if let Some((other, _)) = ports[..index].iter().find(|(_, p)| p == port) {
+                errors.push(format!("{name} conflicts with {other} (port {port})"));
+            }
+        }
+
+        // The working dir is created on first run, its parent has to exist
+        let working_dir = self.expected_cache_path();
+        if self.storage.working_dir.is_empty() {
+            errors.push("storage.working_dir must not be empty".to_string());
+        } else if !working_dir.is_dir() {
+            match working_dir.parent() {
+                Some(parent) if parent.as_os_str().is_empty() || parent.is_dir() => {}
+                _ => errors.push(format!(
+                    "storage.working_dir {} does not exist",
+                    working_dir.display()
+                )),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn get_ordhook_config(&self) -> OrdhookConfig {
         OrdhookConfig {
             resources: self.resources.clone(),
             db_path: self.expected_cache_path(),
-            first_inscription_height: match self.network.bitcoin_network {
-                BitcoinNetwork::Mainnet => 767430,
-                BitcoinNetwork::Regtest => 1,
-                BitcoinNetwork::Testnet => 2413343,
-                BitcoinNetwork::Signet => 112402,
-            },
+            chain: self.chain,
+            // The genesis coinbase can't be spent, no inscription can be revealed before block 1
+            first_inscription_height: self.chain.first_inscription_height().max(1),
             logs: self.logs.clone(),
+            dry_run: self.dry_run,
+            start_block: self.start_block,
+            max_rollback_depth: self.max_rollback_depth,
+            index_annex_envelopes: self.index_annex_envelopes,
+            store_inscription_content: self.storage.store_inscription_content,
+            max_stored_content_bytes: self.storage.max_stored_content_bytes,
+            compress_content: self.storage.compress_content,
+            resolve_ipfs: self.storage.resolve_ipfs,
         }
     }
 
@@ -162,8 +587,21 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                sqlite_wal: true,
+                store_inscription_content: false,
+                max_stored_content_bytes: None,
+                compress_content: false,
+                resolve_ipfs: false,
+                sqlite_slow_query_ms: None,
             },
             http_api: PredicatesApi::Off,
+            query_api: QueryApi::Off,
+            webhooks: vec![],
+            metrics_port: None,
+            dry_run: false,
+            start_block: None,
+            max_rollback_depth: DEFAULT_MAX_ROLLBACK_DEPTH,
+            index_annex_envelopes: false,
             snapshot: SnapshotConfig::Build,
             resources: ResourcesConfig {
                 cpu_core_available: num_cpus::get(),
@@ -172,6 +610,16 @@ impl Config {
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
                 expected_observers_count: 1,
+                traversal_cache_size: DEFAULT_TRAVERSAL_CACHE_SIZE,
+                cache_gc_interval: DEFAULT_CACHE_GC_INTERVAL,
+                cache_eviction_policy: CacheEvictionPolicy::FullClear,
+                max_cache_bytes: None,
+                block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
+                parallel_block_processing: false,
+                rocks_block_cache_mb: DEFAULT_ROCKS_BLOCK_CACHE_MB,
+                rocks_write_buffer_mb: DEFAULT_ROCKS_WRITE_BUFFER_MB,
+                rocks_max_write_buffers: DEFAULT_ROCKS_MAX_WRITE_BUFFERS,
+                worker_threads: num_cpus::get(),
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18443".into(),
@@ -183,9 +631,15 @@ impl Config {
                 stacks_network: StacksNetwork::Devnet,
                 bitcoin_network: BitcoinNetwork::Regtest,
             },
+            chain: Chain::Regtest,
+            bitcoin: BitcoinRpcConfig {
+                rpc_request_per_second: None,
+            },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                verbose_traversal: false,
+                format: LogFormat::Text,
             },
         }
     }
@@ -194,8 +648,21 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                sqlite_wal: true,
+                store_inscription_content: false,
+                max_stored_content_bytes: None,
+                compress_content: false,
+                resolve_ipfs: false,
+                sqlite_slow_query_ms: None,
             },
             http_api: PredicatesApi::Off,
+            query_api: QueryApi::Off,
+            webhooks: vec![],
+            metrics_port: None,
+            dry_run: false,
+            start_block: None,
+            max_rollback_depth: DEFAULT_MAX_ROLLBACK_DEPTH,
+            index_annex_envelopes: false,
             snapshot: SnapshotConfig::Build,
             resources: ResourcesConfig {
                 cpu_core_available: num_cpus::get(),
@@ -204,6 +671,16 @@ impl Config {
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
                 expected_observers_count: 1,
+                traversal_cache_size: DEFAULT_TRAVERSAL_CACHE_SIZE,
+                cache_gc_interval: DEFAULT_CACHE_GC_INTERVAL,
+                cache_eviction_policy: CacheEvictionPolicy::FullClear,
+                max_cache_bytes: None,
+                block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
+                parallel_block_processing: false,
+                rocks_block_cache_mb: DEFAULT_ROCKS_BLOCK_CACHE_MB,
+                rocks_write_buffer_mb: DEFAULT_ROCKS_WRITE_BUFFER_MB,
+                rocks_max_write_buffers: DEFAULT_ROCKS_MAX_WRITE_BUFFERS,
+                worker_threads: num_cpus::get(),
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:18332".into(),
@@ -215,9 +692,15 @@ impl Config {
                 stacks_network: StacksNetwork::Testnet,
                 bitcoin_network: BitcoinNetwork::Testnet,
             },
+            chain: Chain::Testnet,
+            bitcoin: BitcoinRpcConfig {
+                rpc_request_per_second: None,
+            },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                verbose_traversal: false,
+                format: LogFormat::Text,
             },
         }
     }
@@ -226,8 +709,21 @@ impl Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
+                sqlite_wal: true,
+                store_inscription_content: false,
+                max_stored_content_bytes: None,
+                compress_content: false,
+                resolve_ipfs: false,
+                sqlite_slow_query_ms: None,
             },
             http_api: PredicatesApi::Off,
+            query_api: QueryApi::Off,
+            webhooks: vec![],
+            metrics_port: None,
+            dry_run: false,
+            start_block: None,
+            max_rollback_depth: DEFAULT_MAX_ROLLBACK_DEPTH,
+            index_annex_envelopes: false,
             snapshot: SnapshotConfig::Download(DEFAULT_MAINNET_ORDINALS_SQLITE_ARCHIVE.to_string()),
             resources: ResourcesConfig {
                 cpu_core_available: num_cpus::get(),
@@ -236,6 +732,16 @@ impl Config {
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
                 expected_observers_count: 1,
+                traversal_cache_size: DEFAULT_TRAVERSAL_CACHE_SIZE,
+                cache_gc_interval: DEFAULT_CACHE_GC_INTERVAL,
+                cache_eviction_policy: CacheEvictionPolicy::FullClear,
+                max_cache_bytes: None,
+                block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
+                parallel_block_processing: false,
+                rocks_block_cache_mb: DEFAULT_ROCKS_BLOCK_CACHE_MB,
+                rocks_write_buffer_mb: DEFAULT_ROCKS_WRITE_BUFFER_MB,
+                rocks_max_write_buffers: DEFAULT_ROCKS_MAX_WRITE_BUFFERS,
+                worker_threads: num_cpus::get(),
             },
             network: IndexerConfig {
                 bitcoind_rpc_url: "http://0.0.0.0:8332".into(),
@@ -247,9 +753,15 @@ impl Config {
                 stacks_network: StacksNetwork::Mainnet,
                 bitcoin_network: BitcoinNetwork::Mainnet,
             },
+            chain: Chain::Mainnet,
+            bitcoin: BitcoinRpcConfig {
+                rpc_request_per_second: None,
+            },
             logs: LogConfig {
                 ordinals_internals: true,
                 chainhook_internals: false,
+                verbose_traversal: false,
+                format: LogFormat::Text,
             },
         }
     }
@@ -260,3 +772,147 @@ pub fn default_cache_path() -> String {
     cache_path.push("ordhook");
     format!("{}", cache_path.display())
 }
+
+fn parse_override<T, F>(read: &F, key: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    F: Fn(&str) -> Option<String>,
+{
+    match read(key) {
+        Some(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("{key}: unable to parse value {value}")),
+        None => Ok(None),
+    }
+}
+
+const REDACTED: &str = "********";
+
+fn serialize_redacted<S: Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(REDACTED)
+}
+
+/// `IndexerConfig` is owned by chainhook-sdk and can't derive `Serialize` from here.
+fn serialize_indexer_config<S: Serializer>(
+    config: &IndexerConfig,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("IndexerConfig", 6)?;
+    state.serialize_field(
+        "bitcoin_network",
+        &format!("{:?}", config.bitcoin_network).to_lowercase(),
+    )?;
+    state.serialize_field(
+        "stacks_network",
+        &format!("{:?}", config.stacks_network).to_lowercase(),
+    )?;
+    state.serialize_field("bitcoind_rpc_url", &config.bitcoind_rpc_url)?;
+    state.serialize_field("bitcoind_rpc_username", &config.bitcoind_rpc_username)?;
+    state.serialize_field("bitcoind_rpc_password", REDACTED)?;
+    match config.bitcoin_block_signaling {
+        BitcoinBlockSignaling::ZeroMQ(ref url) => {
+            state.serialize_field("bitcoind_zmq_url", url)?;
+        }
+        BitcoinBlockSignaling::Stacks(ref stacks_node) => {
+            state.serialize_field("stacks_node_rpc_url", &stacks_node.rpc_url)?;
+        }
+    }
+    state.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_every_error() {
+        assert_eq!(Config::devnet_default().validate(), Ok(()));
+
+        let mut config = Config::devnet_default();
+        config.resources.worker_threads = 0;
+        config.resources.traversal_cache_size = 0;
+        config.max_rollback_depth = 0;
+        config.metrics_port = Some(DEFAULT_INGESTION_PORT);
+        config.storage.working_dir = "/nonexistent/ordhook/cache".into();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 5);
+        assert_eq!(errors[2], "max_rollback_depth must be greater than 0");
+        assert!(errors[3].starts_with("metrics.port conflicts"));
+    }
+
+    #[test]
+    fn test_testnet4_is_observed_as_testnet() {
+        let mut config = Config::testnet_default();
+        config.chain = Chain::Testnet4;
+        assert_eq!(config.validate(), Ok(()));
+        let ordhook_config = config.get_ordhook_config();
+        assert_eq!(ordhook_config.chain, Chain::Testnet4);
+        assert_eq!(ordhook_config.first_inscription_height, 1);
+        assert_eq!(ordhook_config.chain.jubilee_height(), 0);
+        assert_eq!(serde_json::to_value(&config).unwrap()["chain"], "testnet4");
+
+        config.network.bitcoin_network = BitcoinNetwork::Mainnet;
+        let errors = config.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["chain testnet4 can't be observed as bitcoin network Mainnet".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_win_over_config() {
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("ORDHOOK_CACHE_PATH", "/data/ordhook"),
+            ("ORDHOOK_BITCOIN_RPC_URL", "http://bitcoind:8332"),
+            ("ORDHOOK_API_ENABLED", "true"),
+            ("ORDHOOK_API_PORT", "3000"),
+            ("ORDHOOK_CACHE_LRU_EVICT_ENTRIES", "1000"),
+            ("ORDHOOK_LOGS_FORMAT", ""),
+        ]);
+        let mut config = Config::devnet_default();
+        config
+            .apply_overrides(|key| env.get(key).map(|v| v.to_string()))
+            .unwrap();
+        assert_eq!(config.storage.working_dir, "/data/ordhook");
+        assert_eq!(config.network.bitcoind_rpc_url, "http://bitcoind:8332");
+        match config.query_api {
+            QueryApi::On(ref api) => {
+                assert_eq!(api.port, 3000);
+                assert_eq!(api.websocket_port, DEFAULT_QUERY_API_WEBSOCKET_PORT);
+            }
+            QueryApi::Off => panic!("query api should be enabled"),
+        }
+        assert_eq!(
+            config.resources.cache_eviction_policy,
+            CacheEvictionPolicy::LruEvict(1000)
+        );
+        assert_eq!(config.logs.format, LogFormat::Text);
+
+        let mut config = Config::devnet_default();
+        let err = config
+            .apply_overrides(|key| (key == "ORDHOOK_WORKER_THREADS").then(|| "many".to_string()))
+            .unwrap_err();
+        assert_eq!(err, "ORDHOOK_WORKER_THREADS: unable to parse value many");
+    }
+
+    #[test]
+    fn test_serialized_config_redacts_secrets() {
+        let mut config = Config::devnet_default();
+        config.webhooks.push(WebhookConfig {
+            url: "http://localhost:3000".into(),
+            secret: "webhook-secret".into(),
+            retry_count: DEFAULT_WEBHOOK_RETRY_COUNT,
+        });
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["network"]["bitcoin_network"], "regtest");
+        assert_eq!(json["network"]["bitcoind_rpc_password"], REDACTED);
+        assert_eq!(json["webhooks"][0]["secret"], REDACTED);
+        assert_eq!(
+            json["resources"]["bitcoind_rpc_threads"],
+            DEFAULT_BITCOIND_RPC_THREADS
+        );
+    }
+}
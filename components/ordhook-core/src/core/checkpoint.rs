@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chainhook_sdk::{types::BlockIdentifier, utils::Context};
+
+use crate::{
+    config::Config,
+    core::pipeline::processors::block_archiving::recover_compacted_blocks_journal,
+    db::{
+        delete_block_hash, find_last_block_hash, find_latest_inscription_block_height,
+        find_latest_transfers_block_height, open_ordhook_db_conn_rocks_db_loop,
+        open_readwrite_ordhook_db_conn, rollback_blocks_above_height,
+    },
+    utils::{read_file_content_at_path, write_file_content_at_path},
+};
+
+/// Last block fully committed to hord.sqlite, persisted after each block so that a crash
+/// in the middle of a batch can be detected and reconciled on the next start.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub last_committed_height: u64,
+    pub last_committed_block_hash: String,
+    pub timestamp: u64,
+}
+
+pub fn get_checkpoint_path(cache_path: &PathBuf) -> PathBuf {
+    let mut checkpoint_path = cache_path.clone();
+    checkpoint_path.push("checkpoint.json");
+    checkpoint_path
+}
+
+pub fn read_checkpoint(cache_path: &PathBuf) -> Result<Option<Checkpoint>, String> {
+    let checkpoint_path = get_checkpoint_path(cache_path);
+    if !checkpoint_path.exists() {
+        return Ok(None);
+    }
+    let bytes = read_file_content_at_path(&checkpoint_path)?;
+    let checkpoint = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("unable to parse {}: {}", checkpoint_path.display(), e))?;
+    Ok(Some(checkpoint))
+}
+
+pub fn write_checkpoint(
+    cache_path: &PathBuf,
+    block_identifier: &BlockIdentifier,
+) -> Result<(), String> {
+    let checkpoint = Checkpoint {
+        last_committed_height: block_identifier.index,
+        last_committed_block_hash: block_identifier.hash.clone(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+    let bytes = serde_json::to_vec(&checkpoint).map_err(|e| e.to_string())?;
+    // Write then rename, so that a crash never leaves a truncated checkpoint behind
+    let checkpoint_path = get_checkpoint_path(cache_path);
+    let tmp_path = checkpoint_path.with_extension("json.tmp");
+    write_file_content_at_path(&tmp_path, &bytes)?;
+    std::fs::rename(&tmp_path, &checkpoint_path)
+        .map_err(|e| format!("unable to write {}: {}", checkpoint_path.display(), e))
+}
+
+/// Same as `write_checkpoint`, without moving the checkpoint backward (e.g. when repairing
+/// a range of blocks below the tip).
+pub fn advance_checkpoint(
+    cache_path: &PathBuf,
+    block_identifier: &BlockIdentifier,
+) -> Result<(), String> {
+    match read_checkpoint(cache_path)? {
+        Some(checkpoint) if checkpoint.last_committed_height > block_identifier.index => Ok(()),
+        _ => write_checkpoint(cache_path, block_identifier),
+    }
+}
+
+/// Recover the compacted blocks batch interrupted by a crash, if any, then discard the data
+/// written to hord.sqlite after the last checkpoint. hord.rocksdb can legitimately be ahead of
+/// hord.sqlite (e.g. after `db repair blocks`): only the blocks of the interrupted batch and the
+/// block hashes above the checkpoint are discarded, the other compacted blocks are kept.
+pub fn reconcile_dbs_with_checkpoint(config: &Config, ctx: &Context) -> Result<(), String> {
+    let cache_path = config.expected_cache_path();
+    let checkpoint = read_checkpoint(&cache_path)?;
+    let checkpoint_height = checkpoint.map(|checkpoint| checkpoint.last_committed_height);
+    let blocks_db_rw =
+        open_ordhook_db_conn_rocks_db_loop(true, &cache_path, &config.resources, ctx);
+    recover_compacted_blocks_journal(&blocks_db_rw, checkpoint_height, ctx)?;
+
+    let Some(checkpoint_height) = checkpoint_height else {
+        return Ok(());
+    };
+
+    let mut inscriptions_db_conn_rw =
+        open_readwrite_ordhook_db_conn(&cache_path, config.storage.sqlite_wal, ctx)?;
+    // Blocks without reveals only write transfers: both tips have to be checked
+    let sqlite_tip = find_latest_inscription_block_height(&inscriptions_db_conn_rw, ctx)?.max(
+        find_latest_transfers_block_height(&inscriptions_db_conn_rw, ctx),
+    );
+    if let Some(sqlite_tip) = sqlite_tip {
+        if sqlite_tip > checkpoint_height {
+            ctx.try_log(|logger| {
+                warn!(
+                    logger,
+                    "hord.sqlite tip (#{sqlite_tip}) is ahead of checkpoint (#{checkpoint_height}), rolling back"
+                )
+            });
+            rollback_blocks_above_height(checkpoint_height, &mut inscriptions_db_conn_rw, ctx)
+                .map_err(|e| {
+                    format!("unable to rollback hord.sqlite to #{checkpoint_height}: {e}")
+                })?;
+        }
+    }
+
+    // Block hashes are only indexed once committed to hord.sqlite
+    let mut last_block_hash = find_last_block_hash(&blocks_db_rw);
+    while let Some((block_height, _)) =
+        last_block_hash.filter(|(block_height, _)| *block_height > checkpoint_height)
+    {
+        delete_block_hash(block_height, &blocks_db_rw, ctx);
+        last_block_hash = find_last_block_hash(&blocks_db_rw);
+        if matches!(last_block_hash, Some((height, _)) if height == block_height) {
+            return Err(format!(
+                "unable to delete the hash of block #{block_height} from hord.rocksdb"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::initialize_ordhook_db;
+    use crate::utils::test_dir::TestWorkingDir;
+
+    #[test]
+    fn test_reconcile_rolls_back_transfers_above_checkpoint() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut config = Config::devnet_default();
+        config.storage.working_dir = working_dir.display().to_string();
+        let cache_path = config.expected_cache_path();
+        let conn = initialize_ordhook_db(&cache_path, true, &ctx);
+        let locations_at = |block_height: u64| -> u64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM locations WHERE block_height = ?1",
+                rusqlite::params![block_height],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // Inscription revealed in #100, transferred in #102: only #101 was checkpointed
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, 0, 0, 0, 100, 0)",
+            rusqlite::params![format!("{:064x}i0", 0)],
+        )
+        .unwrap();
+        for block_height in [100u64, 102] {
+            conn.execute(
+                "INSERT INTO locations (ordinal_number, block_height, tx_index, outpoint_to_watch, offset) VALUES (0, ?1, 0, ?2, 0)",
+                rusqlite::params![block_height, format!("{:064x}:0", block_height)],
+            )
+            .unwrap();
+        }
+        let checkpoint = BlockIdentifier {
+            index: 101,
+            hash: format!("0x{:064x}", 101),
+        };
+        write_checkpoint(&cache_path, &checkpoint).unwrap();
+
+        reconcile_dbs_with_checkpoint(&config, &ctx).unwrap();
+        assert_eq!(locations_at(100), 1);
+        assert_eq!(locations_at(102), 0);
+    }
+}
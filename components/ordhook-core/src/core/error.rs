@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum ProcessingError {
+    /// A hord.sqlite statement failed
+    DatabaseError(rusqlite::Error),
+    /// The sat of an inscription could not be traced back to its coinbase
+    TraversalFailed { tx_id: String, reason: String },
+    /// Data could not be encoded for storage
+    SerializationError(String),
+    /// hord.sqlite or hord.rocksdb could not be opened or written outside of a statement
+    StorageError(String),
+    /// A re-org would revert more blocks than `max_rollback_depth` allows
+    RollbackTooDeep {
+        block_height: u64,
+        depth: u64,
+        max_rollback_depth: u64,
+    },
+    /// Any of the above, raised while processing or rolling back the block at `block_height`
+    BlockError {
+        block_height: u64,
+        error: Box<ProcessingError>,
+    },
+}
+
+impl ProcessingError {
+    /// Attaches the height of the block being processed to the error.
+    pub fn at_block(self, block_height: u64) -> Self {
+        Self::BlockError {
+            block_height,
+            error: Box::new(self),
+        }
+    }
+}
+
+impl Display for ProcessingError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::DatabaseError(err) => write!(f, "hord.sqlite error: {err}"),
+            Self::TraversalFailed { tx_id, reason } => {
+                write!(f, "traversal of transaction {tx_id} failed: {reason}")
+            }
+            Self::SerializationError(reason) => write!(f, "serialization error: {reason}"),
+            Self::StorageError(reason) => write!(f, "storage error: {reason}"),
+            Self::RollbackTooDeep {
+                block_height,
+                depth,
+                max_rollback_depth,
+            } => write!(
+                f,
+                "rolling back to block #{block_height} would revert {depth} blocks, more than max_rollback_depth ({max_rollback_depth})"
+            ),
+            Self::BlockError {
+                block_height,
+                error,
+            } => write!(f, "block #{block_height}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::DatabaseError(err) => Some(err),
+            Self::BlockError { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ProcessingError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::DatabaseError(err)
+    }
+}
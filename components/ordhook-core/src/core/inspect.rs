@@ -0,0 +1,202 @@
+use chainhook_sdk::utils::Context;
+use rocksdb::DB;
+use rusqlite::Connection;
+
+use crate::{
+    db::{
+        find_all_inscriptions_in_block, find_all_transfers_in_block,
+        find_pinned_block_bytes_at_block_height, BlockBytesCursor, TransactionInputBytesCursor,
+        TransferData, TraversalResult,
+    },
+    ord::height::Height,
+};
+
+/// Sats flowing through a transaction, as offsets `[start, end)` in the concatenation of
+/// its inputs (the subsidy, then the fees, for the coinbase).
+#[derive(Debug, Clone)]
+pub struct InputInspection {
+    pub input: TransactionInputBytesCursor,
+    pub offsets: (u64, u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputInspection {
+    pub value: u64,
+    pub offsets: (u64, u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionInspection {
+    pub tx_index: usize,
+    pub txid: [u8; 8],
+    pub inputs: Vec<InputInspection>,
+    pub outputs: Vec<OutputInspection>,
+    pub sats_in: u64,
+    pub sats_out: u64,
+    /// Inscriptions revealed by the transaction
+    pub inscriptions: Vec<TraversalResult>,
+    /// Inscribed sats landing in the outputs of the transaction, with their ordinal number
+    pub locations: Vec<(u64, TransferData)>,
+}
+
+impl TransactionInspection {
+    pub fn is_coinbase(&self) -> bool {
+        self.tx_index == 0
+    }
+
+    /// Sats left to the miner, zero for the coinbase.
+    pub fn fee(&self) -> u64 {
+        if self.is_coinbase() {
+            0
+        } else {
+            self.sats_in.saturating_sub(self.sats_out)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockInspection {
+    pub block_height: u64,
+    pub subsidy: u64,
+    pub fees: u64,
+    /// Coinbase first
+    pub transactions: Vec<TransactionInspection>,
+}
+
+/// Decodes a compacted block: the sats consumed by each input and assigned to each output.
+/// Absolute ordinal numbers would need a replay from genesis, only the offsets of the sats
+/// within each transaction are derived from the block itself.
+pub fn inspect_block_bytes(block_height: u64, block: &BlockBytesCursor) -> BlockInspection {
+    let mut transactions = vec![];
+    let mut fees = 0;
+    for (tx_index, tx) in block.iter_tx().enumerate() {
+        let mut sats_in = 0;
+        let mut inputs = Vec::with_capacity(tx.inputs.len());
+        for input in tx.inputs.iter() {
+            inputs.push(InputInspection {
+                input: input.clone(),
+                offsets: (sats_in, sats_in + input.txin_value),
+            });
+            sats_in += input.txin_value;
+        }
+        let mut sats_out = 0;
+        let mut outputs = Vec::with_capacity(tx.outputs.len());
+        for value in tx.outputs.iter() {
+            outputs.push(OutputInspection {
+                value: *value,
+                offsets: (sats_out, sats_out + value),
+            });
+            sats_out += value;
+        }
+        let tx = TransactionInspection {
+            tx_index,
+            txid: tx.txid,
+            inputs,
+            outputs,
+            sats_in,
+            sats_out,
+            inscriptions: vec![],
+            locations: vec![],
+        };
+        fees += tx.fee();
+        transactions.push(tx);
+    }
+    let subsidy = Height(block_height).subsidy();
+    if let Some(coinbase) = transactions.first_mut() {
+        coinbase.sats_in = subsidy + fees;
+    }
+    BlockInspection {
+        block_height,
+        subsidy,
+        fees,
+        transactions,
+    }
+}
+
+/// Loads the compacted block `block_height` from hord.rocksdb and decodes it, along with the
+/// inscriptions revealed and moved in the block when `inscriptions_db_conn` is provided.
+/// Bitcoin Core is never queried.
+pub fn inspect_block(
+    block_height: u64,
+    blocks_db: &DB,
+    inscriptions_db_conn: Option<&Connection>,
+    ctx: &Context,
+) -> Result<BlockInspection, String> {
+    let block_bytes =
+        find_pinned_block_bytes_at_block_height(block_height as u32, 1, blocks_db, ctx)
+            .ok_or(format!("block #{block_height} missing from hord.rocksdb"))?;
+    let block = BlockBytesCursor::new(&block_bytes.as_ref());
+    let mut inspection = inspect_block_bytes(block_height, &block);
+
+    let Some(inscriptions_db_conn) = inscriptions_db_conn else {
+        return Ok(inspection);
+    };
+    let inscriptions = find_all_inscriptions_in_block(&block_height, inscriptions_db_conn, ctx);
+    let locations = find_all_transfers_in_block(&block_height, inscriptions_db_conn, ctx);
+    for tx in inspection.transactions.iter_mut() {
+        tx.inscriptions = inscriptions
+            .values()
+            .filter(|inscription| {
+                inscription
+                    .transaction_identifier_inscription
+                    .get_8_hash_bytes()
+                    == tx.txid
+            })
+            .cloned()
+            .collect();
+        for (ordinal_number, transfers) in locations.iter() {
+            for transfer in transfers.iter() {
+                if transfer.transaction_identifier_location.get_8_hash_bytes() == tx.txid {
+                    tx.locations.push((*ordinal_number, transfer.clone()));
+                }
+            }
+        }
+    }
+    Ok(inspection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chainhook_sdk::indexer::bitcoin::parse_downloaded_block;
+
+    #[test]
+    fn sats_in_balance_sats_out_and_fees() {
+        let block = include_str!("../db/fixtures/blocks_json/279671.json");
+        let decoded_block =
+            parse_downloaded_block(block.as_bytes().to_vec()).expect("unable to decode block");
+        let bytes = BlockBytesCursor::from_full_block(&decoded_block).expect("unable to serialize");
+        let inspection = inspect_block_bytes(279671, &BlockBytesCursor::new(&bytes));
+
+        assert_eq!(inspection.transactions.len(), decoded_block.tx.len());
+        assert_eq!(inspection.subsidy, 25 * 100_000_000);
+        let mut fees = 0;
+        for (tx, src) in inspection.transactions.iter().zip(decoded_block.tx.iter()) {
+            assert_eq!(
+                tx.sats_out,
+                src.vout.iter().map(|o| o.value.to_sat()).sum::<u64>()
+            );
+            if tx.is_coinbase() {
+                continue;
+            }
+            // Inputs and outputs cover the sats of the transaction without gaps
+            let mut bound = 0;
+            for input in tx.inputs.iter() {
+                assert_eq!(input.offsets.0, bound);
+                bound = input.offsets.1;
+            }
+            assert_eq!(bound, tx.sats_in);
+            bound = 0;
+            for output in tx.outputs.iter() {
+                assert_eq!(output.offsets, (bound, bound + output.value));
+                bound = output.offsets.1;
+            }
+            assert_eq!(tx.sats_in, tx.sats_out + tx.fee());
+            fees += tx.fee();
+        }
+        assert_eq!(inspection.fees, fees);
+        let coinbase = &inspection.transactions[0];
+        assert_eq!(coinbase.sats_in, inspection.subsidy + fees);
+        assert!(coinbase.sats_out <= coinbase.sats_in);
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chainhook_sdk::utils::Context;
+use rocksdb::DB;
+
+use crate::{
+    db::{find_pinned_block_bytes_at_block_height, BlockBytesCursor},
+    ord::height::Height,
+};
+
+pub(crate) type Outpoint = ([u8; 8], u16);
+
+#[derive(Clone, Debug)]
+pub struct SatRangeConflict {
+    pub block_height: u64,
+    pub txid: String,
+    pub conflicting_txid: String,
+    pub reason: String,
+}
+
+#[derive(Default)]
+pub(crate) struct LiveSatRanges {
+    pub(crate) utxos: HashMap<Outpoint, Vec<(u64, u64)>>,
+    ranges: BTreeMap<u64, (u64, Outpoint)>,
+}
+
+impl LiveSatRanges {
+    fn spend(&mut self, outpoint: &Outpoint) -> Option<Vec<(u64, u64)>> {
+        let sat_ranges = self.utxos.remove(outpoint)?;
+        for (start, _) in sat_ranges.iter() {
+            self.ranges.remove(start);
+        }
+        Some(sat_ranges)
+    }
+
+    fn create(&mut self, outpoint: Outpoint, sat_ranges: Vec<(u64, u64)>) -> Vec<Outpoint> {
+        let mut conflicts = vec![];
+        for (start, end) in sat_ranges.iter() {
+            if let Some((_, (previous_end, owner))) = self.ranges.range(..=*start).next_back() {
+                if previous_end > start {
+                    conflicts.push(*owner);
+                }
+            }
+            if let Some((next_start, (_, owner))) = self.ranges.range(*start..).next() {
+                if next_start < end && !conflicts.contains(owner) {
+                    conflicts.push(*owner);
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            return conflicts;
+        }
+        for (start, end) in sat_ranges.iter() {
+            self.ranges.insert(*start, (*end, outpoint));
+        }
+        self.utxos.insert(outpoint, sat_ranges);
+        conflicts
+    }
+}
+
+fn take_sat_ranges(available: &mut VecDeque<(u64, u64)>, mut value: u64) -> Vec<(u64, u64)> {
+    let mut sat_ranges = vec![];
+    while value > 0 {
+        let Some((start, end)) = available.pop_front() else {
+            break;
+        };
+        let len = end - start;
+        if len > value {
+            sat_ranges.push((start, start + value));
+            available.push_front((start + value, end));
+            value = 0;
+        } else {
+            sat_ranges.push((start, end));
+            value -= len;
+        }
+    }
+    sat_ranges
+}
+
+/// Replays the compacted blocks stored in hord.rocksdb from genesis up to `to_block`,
+/// rebuilding the sat ranges of every live UTXO, and reports any sat assigned to two
+/// live UTXOs at once (or any input spending an outpoint unknown at that point) for
+/// blocks in `[from_block, to_block]`.
+pub fn check_sat_ranges_integrity(
+    from_block: u64,
+    to_block: u64,
+    blocks_db: &DB,
+    ctx: &Context,
+) -> Result<Vec<SatRangeConflict>, String> {
+    let mut conflicts = vec![];
+    replay_sat_ranges(to_block, blocks_db, ctx, |conflict| {
+        if conflict.block_height >= from_block {
+            conflicts.push(conflict);
+        }
+    })?;
+    Ok(conflicts)
+}
+
+/// Live UTXOs and their sat ranges once the block `to_block` is applied, replayed from
+/// genesis. `on_conflict` is called for every inconsistency met along the way.
+pub(crate) fn replay_sat_ranges<F>(
+    to_block: u64,
+    blocks_db: &DB,
+    ctx: &Context,
+    mut on_conflict: F,
+) -> Result<LiveSatRanges, String>
+where
+    F: FnMut(SatRangeConflict),
+{
+    let mut live = LiveSatRanges::default();
+
+    for block_height in 0..=to_block {
+        let block_bytes =
+            match find_pinned_block_bytes_at_block_height(block_height as u32, 1, blocks_db, ctx) {
+                Some(block_bytes) => block_bytes,
+                // The genesis coinbase is unspendable, it's fine if it was never stored.
+                None if block_height == 0 => continue,
+                None => return Err(format!("block #{block_height} missing from hord.rocksdb")),
+            };
+        let block = BlockBytesCursor::new(&block_bytes.as_ref());
+
+        let mut fees = VecDeque::new();
+        let mut coinbase = None;
+        for tx in block.iter_tx() {
+            if coinbase.is_none() {
+                coinbase = Some(tx);
+                continue;
+            }
+            let txid = hex::encode(tx.txid);
+            let mut available = VecDeque::new();
+            for input in tx.inputs.iter() {
+                match live.spend(&(input.txin, input.vout)) {
+                    Some(sat_ranges) => available.extend(sat_ranges),
+                    None => on_conflict(SatRangeConflict {
+                        block_height,
+                        txid: txid.clone(),
+                        conflicting_txid: hex::encode(input.txin),
+                        reason: format!(
+                            "input {}:{} is not a live UTXO",
+                            hex::encode(input.txin),
+                            input.vout
+                        ),
+                    }),
+                }
+            }
+            for (vout, value) in tx.outputs.iter().enumerate() {
+                let sat_ranges = take_sat_ranges(&mut available, *value);
+                for owner in live.create((tx.txid, vout as u16), sat_ranges) {
+                    on_conflict(SatRangeConflict {
+                        block_height,
+                        txid: txid.clone(),
+                        conflicting_txid: hex::encode(owner.0),
+                        reason: format!(
+                            "output {}:{} overlaps sats held by {}:{}",
+                            txid,
+                            vout,
+                            hex::encode(owner.0),
+                            owner.1
+                        ),
+                    });
+                }
+            }
+            fees.extend(available);
+        }
+
+        // The coinbase collects the block subsidy, then the fees in transaction order.
+        if let Some(tx) = coinbase {
+            let height = Height(block_height);
+            let first_sat = height.starting_sat().0;
+            let mut available = VecDeque::from(vec![(first_sat, first_sat + height.subsidy())]);
+            available.extend(fees);
+            for (vout, value) in tx.outputs.iter().enumerate() {
+                let outpoint = (tx.txid, vout as u16);
+                // Duplicate coinbase txids (BIP30) overwrite the previous outputs.
+                live.spend(&outpoint);
+                let sat_ranges = take_sat_ranges(&mut available, *value);
+                for owner in live.create(outpoint, sat_ranges) {
+                    on_conflict(SatRangeConflict {
+                        block_height,
+                        txid: hex::encode(tx.txid),
+                        conflicting_txid: hex::encode(owner.0),
+                        reason: format!(
+                            "coinbase output {}:{} overlaps sats held by {}:{}",
+                            hex::encode(tx.txid),
+                            vout,
+                            hex::encode(owner.0),
+                            owner.1
+                        ),
+                    });
+                }
+            }
+        }
+
+        if block_height % 10_000 == 0 {
+            ctx.try_log(|logger| {
+                info!(
+                    logger,
+                    "Sat ranges replayed up to block #{block_height} ({} live UTXOs)",
+                    live.utxos.len()
+                )
+            });
+        }
+    }
+    Ok(live)
+}
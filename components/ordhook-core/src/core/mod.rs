@@ -1,35 +1,58 @@
+pub mod checkpoint;
+pub mod error;
+pub mod inspect;
+pub mod integrity;
 pub mod pipeline;
 pub mod protocol;
+pub mod utxo_set;
 
-use dashmap::DashMap;
+use dashmap::{mapref::one::MappedRef, DashMap};
 use fxhash::{FxBuildHasher, FxHasher};
+use lru::LruCache;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rocksdb::{DBPinnableSlice, DB};
+use std::collections::{HashMap, VecDeque};
 use std::hash::BuildHasherDefault;
+use std::num::NonZeroUsize;
 use std::ops::Div;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use chainhook_sdk::{
-    bitcoincore_rpc::{Auth, Client, RpcApi},
-    utils::Context,
-};
+use chainhook_sdk::{types::BitcoinTransactionData, utils::Context};
 
 use crate::{
-    config::{Config, LogConfig, ResourcesConfig},
+    config::{CacheEvictionPolicy, Config, LogConfig, ResourcesConfig},
     db::{find_pinned_block_bytes_at_block_height, open_ordhook_db_conn_rocks_db_loop},
+    ord::chain::Chain,
+    utils::rpc::RateLimitedRpcClient,
 };
 
 use crate::db::{
-    find_last_block_inserted, find_latest_inscription_block_height, initialize_ordhook_db,
+    find_last_block_inserted, find_latest_inscription_block_height,
+    find_latest_transfers_block_height, get_default_ordhook_db_file_path, initialize_ordhook_db,
     open_readonly_ordhook_db_conn,
 };
 
 use crate::db::TransactionBytesCursor;
 
+use self::error::ProcessingError;
+
 #[derive(Clone, Debug)]
 pub struct OrdhookConfig {
     pub resources: ResourcesConfig,
     pub db_path: PathBuf,
+    pub chain: Chain,
     pub first_inscription_height: u64,
     pub logs: LogConfig,
+    pub dry_run: bool,
+    pub start_block: Option<u64>,
+    pub max_rollback_depth: u64,
+    pub index_annex_envelopes: bool,
+    pub store_inscription_content: bool,
+    pub max_stored_content_bytes: Option<u64>,
+    pub compress_content: bool,
+    pub resolve_ipfs: bool,
 }
 
 pub fn new_traversals_cache(
@@ -39,15 +62,310 @@ pub fn new_traversals_cache(
     DashMap::with_hasher(hasher)
 }
 
+type TraversalsCacheKey = (u32, [u8; 8]);
+
+/// L2 cache of the transactions visited while computing satoshi numbers. With
+/// `CacheEvictionPolicy::LruEvict`, entries are evicted in insertion order unless they were
+/// read since the last garbage collection (CLOCK, an approximation of LRU): a hit only sets a
+/// flag on its entry instead of serializing every worker on a shared recency list.
+pub struct TraversalsCache {
+    entries: DashMap<TraversalsCacheKey, TraversalsCacheEntry, BuildHasherDefault<FxHasher>>,
+    /// Keys in insertion order, the hand of the clock sits at the front
+    clock: Option<Mutex<VecDeque<TraversalsCacheKey>>>,
+    eviction_policy: CacheEvictionPolicy,
+    blocks: BlockCache,
+    /// Memory held by the entries, see `TransactionBytesCursor::byte_size`
+    bytes: AtomicUsize,
+    max_bytes: Option<usize>,
+}
+
+pub struct TraversalsCacheEntry {
+    transaction: TransactionBytesCursor,
+    /// Read since the clock hand last passed over the entry
+    referenced: AtomicBool,
+}
+
+impl TraversalsCache {
+    pub fn get(
+        &self,
+        key: &TraversalsCacheKey,
+    ) -> Option<
+        MappedRef<
+            '_,
+            TraversalsCacheKey,
+            TraversalsCacheEntry,
+            TransactionBytesCursor,
+            BuildHasherDefault<FxHasher>,
+        >,
+    > {
+        let entry = self.entries.get(key)?;
+        // Only write the flag once per sweep, to keep hits on a shared cache line read-only
+        if self.clock.is_some() && !entry.referenced.load(Ordering::Relaxed) {
+            entry.referenced.store(true, Ordering::Relaxed);
+        }
+        Some(entry.map(|entry| &entry.transaction))
+    }
+
+    pub fn insert(&self, key: TraversalsCacheKey, value: TransactionBytesCursor) {
+        self.bytes.fetch_add(value.byte_size(), Ordering::Relaxed);
+        let entry = TraversalsCacheEntry {
+            transaction: value,
+            referenced: AtomicBool::new(false),
+        };
+        match self.entries.insert(key, entry) {
+            Some(previous) => {
+                self.bytes
+                    .fetch_sub(previous.transaction.byte_size(), Ordering::Relaxed);
+            }
+            None => {
+                if let Some(clock) = &self.clock {
+                    clock.lock().unwrap().push_back(key);
+                }
+            }
+        }
+    }
+
+    fn remove(&self, key: &TraversalsCacheKey) {
+        if let Some((_, entry)) = self.entries.remove(key) {
+            self.bytes
+                .fetch_sub(entry.transaction.byte_size(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&self) {
+        // Hold the clock while clearing, so that concurrent insertions stay tracked
+        let clock = self.clock.as_ref().map(|clock| clock.lock().unwrap());
+        self.entries.clear();
+        self.bytes.store(0, Ordering::Relaxed);
+        if let Some(mut clock) = clock {
+            clock.clear();
+        }
+    }
+
+    pub fn byte_size(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Collect the garbage once the entries hold more than `max_cache_bytes`.
+    pub fn with_max_bytes(mut self, max_cache_bytes: Option<usize>) -> TraversalsCache {
+        self.max_bytes = max_cache_bytes;
+        self
+    }
+
+    /// Whether the entries hold more memory than allowed, calling for an early garbage
+    /// collection.
+    pub fn exceeds_max_bytes(&self) -> bool {
+        match self.max_bytes {
+            Some(max_bytes) => self.byte_size() > max_bytes,
+            None => false,
+        }
+    }
+
+    /// Keep the last `block_cache_size` blocks read by the traversals in memory.
+    pub fn with_block_cache(mut self, block_cache_size: usize) -> TraversalsCache {
+        self.blocks = BlockCache::new(block_cache_size);
+        self
+    }
+
+    /// Compacted bytes of the block at `block_height`, from the block cache or hord.rocksdb.
+    pub fn find_block_bytes<'a>(
+        &self,
+        block_height: u32,
+        retry: u8,
+        blocks_db: &'a DB,
+        ctx: &Context,
+    ) -> Option<BlockBytes<'a>> {
+        self.blocks.get_or_load(block_height, retry, blocks_db, ctx)
+    }
+
+    /// Keep compacted blocks that are not stored in hord.rocksdb (dry runs) in the block cache,
+    /// for the traversals of their batch to find them without waiting on hord.rocksdb.
+    pub fn insert_blocks(&self, compacted_blocks: &[(u64, Vec<u8>)]) {
+        self.blocks.insert_unstored(compacted_blocks);
+    }
+
+    /// Drop the cached bytes of a block about to be rewritten in hord.rocksdb (reorgs).
+    pub fn forget_block(&self, block_height: u32) {
+        self.blocks.remove(block_height);
+    }
+
+    /// Applies the eviction policy, returning the number of entries removed.
+    pub fn collect_garbage(&self) -> usize {
+        match (&self.eviction_policy, &self.clock) {
+            (CacheEvictionPolicy::LruEvict(n), Some(clock)) => {
+                let mut clock = clock.lock().unwrap();
+                let mut evicted = 0;
+                // Two turns of the clock: every entry read since the last sweep gets a
+                // second chance during the first one.
+                let mut remaining_steps = 2 * clock.len();
+                // Keep evicting past `n` entries until the cache fits in `max_bytes`
+                while (evicted < *n || self.exceeds_max_bytes()) && remaining_steps > 0 {
+                    remaining_steps -= 1;
+                    let Some(key) = clock.pop_front() else {
+                        break;
+                    };
+                    let referenced = match self.entries.get(&key) {
+                        Some(entry) => entry.referenced.swap(false, Ordering::Relaxed),
+                        // Already removed by a clear
+                        None => continue,
+                    };
+                    if referenced {
+                        clock.push_back(key);
+                    } else {
+                        self.remove(&key);
+                        evicted += 1;
+                    }
+                }
+                evicted
+            }
+            _ => {
+                let evicted = self.entries.len();
+                self.clear();
+                evicted
+            }
+        }
+    }
+}
+
 pub fn new_traversals_lazy_cache(
     cache_size: usize,
-) -> DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>> {
+    eviction_policy: &CacheEvictionPolicy,
+) -> TraversalsCache {
     let hasher = FxBuildHasher::default();
-    DashMap::with_capacity_and_hasher(
+    let entries = DashMap::with_capacity_and_hasher(
         ((cache_size.saturating_sub(500)) * 1000 * 1000)
             .div(TransactionBytesCursor::get_average_bytes_size()),
         hasher,
-    )
+    );
+    let clock = match eviction_policy {
+        CacheEvictionPolicy::FullClear => None,
+        CacheEvictionPolicy::LruEvict(_) => Some(Mutex::new(VecDeque::new())),
+    };
+    TraversalsCache {
+        entries,
+        clock,
+        eviction_policy: eviction_policy.clone(),
+        blocks: BlockCache::new(0),
+        bytes: AtomicUsize::new(0),
+        max_bytes: None,
+    }
+}
+
+/// Compacted block bytes (see `BlockBytesCursor`), pinned in hord.rocksdb or shared with the
+/// block cache.
+pub enum BlockBytes<'a> {
+    Pinned(DBPinnableSlice<'a>),
+    Cached(Arc<Vec<u8>>),
+}
+
+impl AsRef<[u8]> for BlockBytes<'_> {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            BlockBytes::Pinned(bytes) => bytes.as_ref(),
+            BlockBytes::Cached(bytes) => bytes.as_slice(),
+        }
+    }
+}
+
+/// The most recently read blocks of hord.rocksdb, so that the blocks of a batch, read by
+/// the prefetching and then by each of their traversals, are only fetched once.
+pub struct BlockCache {
+    blocks: Option<Mutex<LruCache<u32, Arc<Vec<u8>>>>>,
+}
+
+impl BlockCache {
+    /// A cache of `capacity` 0 stores nothing, blocks stay pinned in hord.rocksdb.
+    pub fn new(capacity: usize) -> BlockCache {
+        BlockCache {
+            blocks: NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    pub fn get(&self, block_height: u32) -> Option<Arc<Vec<u8>>> {
+        let blocks = self.blocks.as_ref()?;
+        blocks.lock().unwrap().get(&block_height).cloned()
+    }
+
+    pub fn insert(&self, block_height: u32, block_bytes: Arc<Vec<u8>>) {
+        if let Some(blocks) = &self.blocks {
+            blocks.lock().unwrap().put(block_height, block_bytes);
+        }
+    }
+
+    /// Inserts blocks missing from hord.rocksdb, growing the cache if needed for none of them
+    /// to evict another: they can't be loaded again.
+    pub fn insert_unstored(&self, compacted_blocks: &[(u64, Vec<u8>)]) {
+        let Some(blocks) = &self.blocks else {
+            return;
+        };
+        let mut blocks = blocks.lock().unwrap();
+        if let Some(capacity) = NonZeroUsize::new(compacted_blocks.len()) {
+            if capacity > blocks.cap() {
+                blocks.resize(capacity);
+            }
+        }
+        for (block_height, block_bytes) in compacted_blocks.iter() {
+            blocks.put(*block_height as u32, Arc::new(block_bytes.clone()));
+        }
+    }
+
+    pub fn remove(&self, block_height: u32) {
+        if let Some(blocks) = &self.blocks {
+            blocks.lock().unwrap().pop(&block_height);
+        }
+    }
+
+    pub fn get_or_load<'a>(
+        &self,
+        block_height: u32,
+        retry: u8,
+        blocks_db: &'a DB,
+        ctx: &Context,
+    ) -> Option<BlockBytes<'a>> {
+        if self.blocks.is_none() {
+            return find_pinned_block_bytes_at_block_height(block_height, retry, blocks_db, ctx)
+                .map(BlockBytes::Pinned);
+        }
+        if let Some(block_bytes) = self.get(block_height) {
+            return Some(BlockBytes::Cached(block_bytes));
+        }
+        let block_bytes = Arc::new(
+            find_pinned_block_bytes_at_block_height(block_height, retry, blocks_db, ctx)?.to_vec(),
+        );
+        self.insert(block_height, block_bytes.clone());
+        Some(BlockBytes::Cached(block_bytes))
+    }
+}
+
+lazy_static! {
+    static ref WORKER_POOLS: Mutex<HashMap<usize, Arc<ThreadPool>>> = Mutex::new(HashMap::new());
+}
+
+/// Work-stealing pool shared by the inscriptions data computations, one per `worker_threads`
+/// value: processors configured with different sizes don't share threads.
+pub fn get_worker_pool(worker_threads: usize) -> Arc<ThreadPool> {
+    let worker_threads = worker_threads.max(1);
+    let mut worker_pools = WORKER_POOLS.lock().unwrap();
+    worker_pools
+        .entry(worker_threads)
+        .or_insert_with(|| {
+            Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(worker_threads)
+                    .thread_name(|_| "Worker".to_string())
+                    .build()
+                    .expect("unable to build worker pool"),
+            )
+        })
+        .clone()
 }
 
 #[derive(PartialEq, Debug)]
@@ -115,12 +433,57 @@ pub fn compute_next_satpoint_data(
     SatPosition::Output((selected_output_index, relative_offset_in_selected_output))
 }
 
+fn get_compact_size_len(n: usize) -> usize {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffffffff => 5,
+        _ => 9,
+    }
+}
+
+fn get_hex_bytes_len(hex: &str) -> usize {
+    hex.trim_start_matches("0x").len() / 2
+}
+
+/// Virtual size of a transaction (BIP141), recomputed from its standardized inputs and outputs.
+pub fn compute_transaction_vbytes(tx: &BitcoinTransactionData) -> u64 {
+    let inputs = &tx.metadata.inputs;
+    let outputs = &tx.metadata.outputs;
+    // version + locktime
+    let mut base_size = 8;
+    base_size += get_compact_size_len(inputs.len());
+    for input in inputs.iter() {
+        let script_sig_len = get_hex_bytes_len(&input.script_sig);
+        // outpoint + script_sig + sequence
+        base_size += 36 + get_compact_size_len(script_sig_len) + script_sig_len + 4;
+    }
+    base_size += get_compact_size_len(outputs.len());
+    for output in outputs.iter() {
+        let script_pubkey_len = get_hex_bytes_len(&output.get_script_pubkey_hex());
+        base_size += 8 + get_compact_size_len(script_pubkey_len) + script_pubkey_len;
+    }
+    let mut witness_size = 0;
+    if inputs.iter().any(|input| !input.witness.is_empty()) {
+        // marker + flag
+        witness_size += 2;
+        for input in inputs.iter() {
+            witness_size += get_compact_size_len(input.witness.len());
+            for item in input.witness.iter() {
+                let item_len = get_hex_bytes_len(item);
+                witness_size += get_compact_size_len(item_len) + item_len;
+            }
+        }
+    }
+    let weight = (base_size * 4 + witness_size) as u64;
+    (weight + 3) / 4
+}
+
 pub fn should_sync_rocks_db(config: &Config, ctx: &Context) -> Result<Option<(u64, u64)>, String> {
     let blocks_db = open_ordhook_db_conn_rocks_db_loop(
         true,
         &config.expected_cache_path(),
-        config.resources.ulimit,
-        config.resources.memory_available,
+        &config.resources,
         &ctx,
     );
     let inscriptions_db_conn = open_readonly_ordhook_db_conn(&config.expected_cache_path(), &ctx)?;
@@ -139,33 +502,75 @@ pub fn should_sync_rocks_db(config: &Config, ctx: &Context) -> Result<Option<(u6
     Ok(res)
 }
 
-pub fn should_sync_ordhook_db(
+/// Refuses to roll back from `block_height` when `tip_height` sits more than `max_rollback_depth`
+/// blocks above it: such a re-org needs an operator to look at it.
+pub fn check_rollback_depth(
+    block_height: u64,
+    tip_height: u64,
+    max_rollback_depth: u64,
+) -> Result<(), ProcessingError> {
+    let depth = (tip_height + 1).saturating_sub(block_height);
+    if depth > max_rollback_depth {
+        return Err(ProcessingError::RollbackTooDeep {
+            block_height,
+            depth,
+            max_rollback_depth,
+        });
+    }
+    Ok(())
+}
+
+/// Refuses a `start_block` above the height indexed in an existing hord.sqlite: the inscriptions
+/// revealed in between would never be indexed, and the numbers assigned after them would be off.
+pub fn check_start_block_against_indexed_height(
     config: &Config,
     ctx: &Context,
-) -> Result<Option<(u64, u64, usize)>, String> {
-    let auth = Auth::UserPass(
-        config.network.bitcoind_rpc_username.clone(),
-        config.network.bitcoind_rpc_password.clone(),
+) -> Result<(), String> {
+    let Some(start_block) = config.start_block else {
+        return Ok(());
+    };
+    if !get_default_ordhook_db_file_path(&config.expected_cache_path()).exists() {
+        return Ok(());
+    }
+    let inscriptions_db_conn = open_readonly_ordhook_db_conn(&config.expected_cache_path(), ctx)?;
+    let indexed_height = find_latest_inscription_block_height(&inscriptions_db_conn, ctx)?.max(
+        find_latest_transfers_block_height(&inscriptions_db_conn, ctx),
     );
+    match indexed_height {
+        Some(indexed_height) if start_block > indexed_height + 1 => Err(format!(
+            "start_block (#{start_block}) conflicts with hord.sqlite, indexed up to #{indexed_height}: the inscriptions revealed in between would not be indexed"
+        )),
+        _ => Ok(()),
+    }
+}
 
-    let bitcoin_rpc = match Client::new(&config.network.bitcoind_rpc_url, auth) {
-        Ok(con) => con,
-        Err(message) => {
-            return Err(format!("Bitcoin RPC error: {}", message.to_string()));
-        }
-    };
+/// Height of the chain tip of bitcoind.
+pub async fn get_chain_tip_height(config: &Config) -> Result<u64, String> {
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second)
+        .get_block_count(&bitcoin_config)
+        .await
+        .map_err(|e| format!("unable to retrieve Bitcoin chain tip ({e})"))
+}
 
+pub async fn should_sync_ordhook_db(
+    config: &Config,
+    ctx: &Context,
+) -> Result<Option<(u64, u64, usize)>, String> {
     let blocks_db = open_ordhook_db_conn_rocks_db_loop(
         true,
         &config.expected_cache_path(),
-        config.resources.ulimit,
-        config.resources.memory_available,
+        &config.resources,
         &ctx,
     );
     let mut start_block = find_last_block_inserted(&blocks_db) as u64;
 
     if start_block == 0 {
-        let _ = initialize_ordhook_db(&config.expected_cache_path(), &ctx);
+        let _ = initialize_ordhook_db(
+            &config.expected_cache_path(),
+            config.storage.sqlite_wal,
+            &ctx,
+        );
     }
 
     let inscriptions_db_conn = open_readonly_ordhook_db_conn(&config.expected_cache_path(), &ctx)?;
@@ -185,15 +590,7 @@ pub fn should_sync_ordhook_db(
         }
     };
 
-    let end_block = match bitcoin_rpc.get_blockchain_info() {
-        Ok(result) => result.blocks,
-        Err(e) => {
-            return Err(format!(
-                "unable to retrieve Bitcoin chain tip ({})",
-                e.to_string()
-            ));
-        }
-    };
+    let end_block = get_chain_tip_height(config).await?;
 
     // TODO: Gracefully handle Regtest, Testnet and Signet
     let (mut end_block, speed) = if start_block < 200_000 {
@@ -272,3 +669,186 @@ fn test_identify_next_output_index_destination() {
         SatPosition::Fee(260377)
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_dir::TestWorkingDir;
+
+    #[test]
+    fn test_resolve_absolute_pointer() {
+        let inputs = vec![1_000, 546, 10_000];
+        // No pointer, or pointer within the first input
+        assert_eq!(resolve_absolute_pointer(&inputs, 0), (0, 0));
+        assert_eq!(resolve_absolute_pointer(&inputs, 999), (0, 999));
+        // Pointer shifting the inscription to the following inputs
+        assert_eq!(resolve_absolute_pointer(&inputs, 1_000), (1, 0));
+        assert_eq!(resolve_absolute_pointer(&inputs, 1_546), (2, 0));
+        assert_eq!(resolve_absolute_pointer(&inputs, 2_000), (2, 454));
+        // Out of range pointers are ignored
+        assert_eq!(resolve_absolute_pointer(&inputs, 100_000), (0, 0));
+    }
+
+    #[test]
+    fn test_check_rollback_depth() {
+        // Rolling back the tip itself reverts a single block
+        assert!(check_rollback_depth(100, 100, 1).is_ok());
+        assert!(check_rollback_depth(95, 100, 6).is_ok());
+        assert!(matches!(
+            check_rollback_depth(95, 100, 5),
+            Err(ProcessingError::RollbackTooDeep { depth: 6, .. })
+        ));
+        // Nothing to revert above the tip
+        assert!(check_rollback_depth(101, 100, 1).is_ok());
+    }
+
+    #[test]
+    fn test_check_start_block_against_indexed_height() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut config = Config::devnet_default();
+        config.storage.working_dir = working_dir.display().to_string();
+        config.start_block = Some(200);
+        // Nothing indexed yet
+        assert!(check_start_block_against_indexed_height(&config, &ctx).is_ok());
+
+        let conn = initialize_ordhook_db(&config.expected_cache_path(), true, &ctx);
+        assert!(check_start_block_against_indexed_height(&config, &ctx).is_ok());
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, 0, 0, 0, 150, 0)",
+            rusqlite::params![format!("{:064x}i0", 0)],
+        )
+        .unwrap();
+        assert!(check_start_block_against_indexed_height(&config, &ctx).is_err());
+        config.start_block = Some(151);
+        assert!(check_start_block_against_indexed_height(&config, &ctx).is_ok());
+        config.start_block = None;
+        assert!(check_start_block_against_indexed_height(&config, &ctx).is_ok());
+    }
+
+    #[test]
+    fn test_block_cache_keeps_the_last_blocks_read() {
+        let cache = BlockCache::new(2);
+        for block_height in 1..=3 {
+            cache.insert(block_height, Arc::new(vec![block_height as u8]));
+        }
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(3).as_deref(), Some(&vec![3]));
+        cache.remove(3);
+        assert_eq!(cache.get(3), None);
+
+        let cache = BlockCache::new(0);
+        cache.insert(1, Arc::new(vec![1]));
+        assert_eq!(cache.get(1), None);
+    }
+
+    #[test]
+    fn test_unstored_blocks_are_served_from_the_block_cache() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+        let cache =
+            new_traversals_lazy_cache(0, &CacheEvictionPolicy::LruEvict(1)).with_block_cache(1);
+
+        // A batch larger than the cache, missing from hord.rocksdb
+        cache.insert_blocks(&[(800000, vec![0]), (800001, vec![1])]);
+        let started_at = std::time::Instant::now();
+        for (block_height, block_bytes) in [(800000, [0]), (800001, [1])] {
+            let cached = cache.find_block_bytes(block_height, 3, &blocks_db, &ctx);
+            assert_eq!(
+                cached.as_ref().map(|bytes| bytes.as_ref()),
+                Some(&block_bytes[..])
+            );
+        }
+        // Served without waiting on hord.rocksdb retries
+        assert!(started_at.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_traversals_cache_evicts_entries_exceeding_max_bytes() {
+        let tx = |outputs: usize| TransactionBytesCursor {
+            txid: [0; 8],
+            inputs: vec![],
+            outputs: vec![546; outputs],
+        };
+        let entry_size = tx(10).byte_size();
+        let cache = new_traversals_lazy_cache(0, &CacheEvictionPolicy::LruEvict(1))
+            .with_max_bytes(Some(2 * entry_size));
+        for i in 0..4u8 {
+            cache.insert((1, [i; 8]), tx(10));
+        }
+        // Replacing an entry doesn't count it twice
+        cache.insert((1, [0; 8]), tx(10));
+        assert_eq!(cache.byte_size(), 4 * entry_size);
+        assert!(cache.exceeds_max_bytes());
+
+        // One entry evicted by the policy, one more to fit in the budget
+        assert_eq!(cache.collect_garbage(), 2);
+        assert_eq!(cache.byte_size(), 2 * entry_size);
+        assert!(!cache.exceeds_max_bytes());
+
+        cache.clear();
+        assert_eq!(cache.byte_size(), 0);
+    }
+
+    #[test]
+    fn test_worker_pools_are_sized_with_each_config() {
+        assert_eq!(get_worker_pool(2).current_num_threads(), 2);
+        assert_eq!(get_worker_pool(3).current_num_threads(), 3);
+        assert!(Arc::ptr_eq(&get_worker_pool(2), &get_worker_pool(2)));
+    }
+
+    #[test]
+    fn test_traversals_cache_keeps_the_entries_read_since_the_last_collection() {
+        let tx = TransactionBytesCursor {
+            txid: [0; 8],
+            inputs: vec![],
+            outputs: vec![546],
+        };
+        let cache = new_traversals_lazy_cache(0, &CacheEvictionPolicy::LruEvict(1));
+        for i in 0..3u8 {
+            cache.insert((1, [i; 8]), tx.clone());
+        }
+        assert!(cache.get(&(1, [0; 8])).is_some());
+
+        // The oldest entry was read: the next one is evicted instead
+        assert_eq!(cache.collect_garbage(), 1);
+        assert!(cache.get(&(1, [0; 8])).is_some());
+        assert!(cache.get(&(1, [1; 8])).is_none());
+
+        // Both remaining entries were read: the one under the hand goes after a full turn
+        assert!(cache.get(&(1, [2; 8])).is_some());
+        assert_eq!(cache.collect_garbage(), 1);
+        assert!(cache.get(&(1, [2; 8])).is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_traversals_cache_keys_spread_evenly_across_shards() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use std::collections::HashMap;
+
+        // Batches traverse the transactions of a few consecutive blocks at once: the txid
+        // bytes, hashed last, have to spread them over the shards.
+        let entries: DashMap<TraversalsCacheKey, (), BuildHasherDefault<FxHasher>> =
+            DashMap::with_hasher_and_shard_amount(FxBuildHasher::default(), 64);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut entries_per_shard: HashMap<usize, usize> = HashMap::new();
+        let keys = 64_000;
+        for i in 0..keys {
+            let key: TraversalsCacheKey = (800_000 + (i / 4_000) as u32, rng.gen());
+            *entries_per_shard
+                .entry(entries.determine_map(&key))
+                .or_default() += 1;
+        }
+        assert_eq!(entries_per_shard.len(), 64);
+        let mean = keys / 64;
+        for (shard, count) in entries_per_shard.iter() {
+            assert!(
+                count.abs_diff(mean) * 4 < mean,
+                "shard {shard} holds {count} entries, expected about {mean}"
+            );
+        }
+    }
+}
@@ -1,31 +1,105 @@
+pub mod processing_duration;
+pub mod processing_rate;
 pub mod processors;
 
 use chainhook_sdk::observer::BitcoinConfig;
 use chainhook_sdk::types::BitcoinBlockData;
 use chainhook_sdk::utils::Context;
-use crossbeam_channel::bounded;
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
 use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::thread::{sleep, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::task::JoinSet;
 
 use crate::config::Config;
 use crate::db::BlockBytesCursor;
+use crate::utils::rpc::RateLimitedRpcClient;
 
-use chainhook_sdk::indexer::bitcoin::{
-    build_http_client, parse_downloaded_block, try_download_block_bytes_with_retry,
-};
+use chainhook_sdk::indexer::bitcoin::parse_downloaded_block;
 
+use super::error::ProcessingError;
 use super::protocol::inscription_parsing::parse_inscriptions_and_standardize_block;
 
 pub enum PostProcessorCommand {
     ProcessBlocks(Vec<(u64, Vec<u8>)>, Vec<BitcoinBlockData>),
+    /// Height of the last block of the sync in progress, for reporting its ETA
+    SetTargetHeight(u64),
+    /// Height of the bitcoind chain tip, for reporting when the indexer catches up with it
+    SetChainTip(u64),
+    /// Process all the blocks already enqueued, then exit
+    Drain,
     Terminate,
+    /// Stop processing the commands, left enqueued, until `Resume` is received on this channel
+    /// or the channel is dropped. Processors that can't be paused drop the channel right away.
+    Pause(Receiver<PausedProcessorCommand>),
+}
+
+/// Commands served by a paused processor, see `PostProcessorCommand::Pause`.
+pub enum PausedProcessorCommand {
+    /// Snapshot hord.sqlite and hord.rocksdb into this directory from the handles of the
+    /// processor, replying with the size of the snapshot
+    Snapshot(PathBuf, Sender<Result<u64, String>>),
+    Resume,
 }
 
 pub enum PostProcessorEvent {
     Terminated,
+    Drained,
     Expired,
+    /// Changes for the block at this height were committed to hord.sqlite
+    BlockCommitted(u64),
+    /// The indexer caught up with the chain tip set with `SetChainTip`, sent once per processor
+    TipReached(u64),
+    /// The processor stopped on a condition requiring an operator, e.g. a re-org deeper than
+    /// `max_rollback_depth`
+    Aborted(ProcessingError),
+}
+
+/// Processors receiving each block once indexed, e.g. the observers forwarding or the
+/// inscription events publishing. Each processor has its own bounded channel: blocks queue
+/// up independently for each of them, and the indexer only waits once a channel is full.
+#[derive(Clone, Default)]
+pub struct PostProcessorRegistry {
+    senders: Vec<Sender<BitcoinBlockData>>,
+}
+
+impl PostProcessorRegistry {
+    pub fn new() -> PostProcessorRegistry {
+        PostProcessorRegistry::default()
+    }
+
+    /// Register a processor receiving the blocks through a channel of `capacity` blocks.
+    pub fn register(&mut self, capacity: usize) -> Receiver<BitcoinBlockData> {
+        let (block_tx, block_rx) = bounded(capacity);
+        self.senders.push(block_tx);
+        block_rx
+    }
+
+    /// Register a processor already listening on `sender`.
+    pub fn register_sender(&mut self, sender: Sender<BitcoinBlockData>) {
+        self.senders.push(sender);
+    }
+
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    pub fn into_senders(self) -> Vec<Sender<BitcoinBlockData>> {
+        self.senders
+    }
+}
+
+/// Send `block` to every post-processor, skipping the ones that hung up.
+pub fn broadcast_block(block: &BitcoinBlockData, post_processors: &[Sender<BitcoinBlockData>]) {
+    for post_processor in post_processors.iter() {
+        let _ = post_processor.send(block.clone());
+    }
 }
 
 pub struct PostProcessorController {
@@ -34,6 +108,99 @@ pub struct PostProcessorController {
     pub thread_handle: JoinHandle<()>,
 }
 
+impl PostProcessorController {
+    pub fn pause(&self) -> Result<PausedProcessor, String> {
+        PausedProcessor::pause(&self.commands_tx)
+    }
+
+    /// Block until the processor runs out of blocks to process (`Expired` or `Drained`),
+    /// i.e. once the blocks sent so far are committed to hord.sqlite.
+    pub fn wait_until_synced(&self, timeout: Duration) -> Result<(), WaitError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events_rx.recv_timeout(remaining) {
+                Ok(PostProcessorEvent::Expired) | Ok(PostProcessorEvent::Drained) => return Ok(()),
+                Ok(PostProcessorEvent::Terminated) => return Err(WaitError::Terminated),
+                Ok(PostProcessorEvent::Aborted(reason)) => return Err(WaitError::Aborted(reason)),
+                Ok(PostProcessorEvent::BlockCommitted(_))
+                | Ok(PostProcessorEvent::TipReached(_)) => continue,
+                Err(RecvTimeoutError::Timeout) => return Err(WaitError::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(WaitError::Disconnected),
+            }
+        }
+    }
+}
+
+/// Processor paused with `PostProcessorCommand::Pause`, resumed when dropped.
+pub struct PausedProcessor {
+    control_tx: Sender<PausedProcessorCommand>,
+}
+
+impl PausedProcessor {
+    /// Pause the processor listening on `commands_tx`, once done with the commands already sent.
+    pub fn pause(commands_tx: &Sender<PostProcessorCommand>) -> Result<PausedProcessor, String> {
+        let (control_tx, control_rx) = unbounded();
+        commands_tx
+            .send(PostProcessorCommand::Pause(control_rx))
+            .map_err(|_| "post processor stopped".to_string())?;
+        Ok(PausedProcessor { control_tx })
+    }
+
+    /// Snapshot hord.sqlite and hord.rocksdb into `output_dir` and return the size of the
+    /// snapshot, once the processor is paused.
+    pub fn snapshot(&self, output_dir: &PathBuf) -> Result<u64, String> {
+        let (reply_tx, reply_rx) = bounded(1);
+        let not_paused = || "post processor stopped without being paused".to_string();
+        self.control_tx
+            .send(PausedProcessorCommand::Snapshot(
+                output_dir.clone(),
+                reply_tx,
+            ))
+            .map_err(|_| not_paused())?;
+        reply_rx.recv().map_err(|_| not_paused())?
+    }
+
+    pub fn resume(self) {}
+}
+
+impl Drop for PausedProcessor {
+    fn drop(&mut self) {
+        let _ = self.control_tx.send(PausedProcessorCommand::Resume);
+    }
+}
+
+#[derive(Debug)]
+pub enum WaitError {
+    Timeout,
+    /// The processor was terminated before running out of blocks
+    Terminated,
+    /// The processor thread exited
+    Disconnected,
+    /// The processor stopped before syncing, see `PostProcessorEvent::Aborted`
+    Aborted(ProcessingError),
+}
+
+impl Display for WaitError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timed out waiting for the post processor to sync"),
+            Self::Terminated => write!(f, "post processor terminated before syncing"),
+            Self::Disconnected => write!(f, "post processor disconnected"),
+            Self::Aborted(reason) => write!(f, "post processor aborted: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for WaitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Aborted(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 pub async fn download_and_pipeline_blocks(
     config: &Config,
     blocks: Vec<u64>,
@@ -61,7 +228,7 @@ pub async fn download_and_pipeline_blocks(
     let number_of_blocks_to_process = blocks.len() as u64;
 
     let (block_compressed_tx, block_compressed_rx) = crossbeam_channel::bounded(speed);
-    let http_client = build_http_client();
+    let http_client = RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second);
 
     let moved_config = bitcoin_config.clone();
     let moved_ctx = ctx.clone();
@@ -73,6 +240,12 @@ pub async fn download_and_pipeline_blocks(
     let end_block = *blocks.last().expect("no blocks to pipeline");
     let mut block_heights = VecDeque::from(blocks);
 
+    if let Some(post_processor) = blocks_post_processor {
+        let _ = post_processor
+            .commands_tx
+            .send(PostProcessorCommand::SetTargetHeight(end_block));
+    }
+
     // All the requests are being processed on the same thread.
     // As soon as we are getting the bytes back from wire, the
     // processing is moved to a thread pool, to defer the parsing, quite expensive.
@@ -95,17 +268,13 @@ pub async fn download_and_pipeline_blocks(
             let http_client = moved_http_client.clone();
             // We interleave the initial requests to avoid DDOSing bitcoind from the get go.
             sleep(Duration::from_millis(500));
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
-                block_height,
-                config,
-                ctx,
-            ));
+            set.spawn(http_client.try_download_block_bytes_with_retry(block_height, config, ctx));
         }
     }
 
     let moved_ctx: Context = ctx.clone();
     let moved_bitcoin_network = bitcoin_config.network.clone();
+    let index_annex_envelopes = ordhook_config.index_annex_envelopes;
 
     let mut tx_thread_pool = vec![];
     let mut rx_thread_pool = vec![];
@@ -134,6 +303,7 @@ pub async fn download_and_pipeline_blocks(
                         let block_data = parse_inscriptions_and_standardize_block(
                             raw_block_data,
                             &moved_bitcoin_network,
+                            index_annex_envelopes,
                             &moved_ctx,
                         )
                         .expect("unable to deserialize block");
@@ -176,7 +346,7 @@ pub async fn download_and_pipeline_blocks(
                         )
                     });
                     if let Some(ref blocks_tx) = blocks_post_processor_commands_tx {
-                        let _ = blocks_tx.send(PostProcessorCommand::Terminate);
+                        let _ = blocks_tx.send(PostProcessorCommand::Drain);
                     }
                     break;
                 }
@@ -280,12 +450,7 @@ pub async fn download_and_pipeline_blocks(
             let config = moved_config.clone();
             let ctx = ctx.clone();
             let http_client = moved_http_client.clone();
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
-                block_height,
-                config,
-                ctx,
-            ));
+            set.spawn(http_client.try_download_block_bytes_with_retry(block_height, config, ctx));
         }
     }
 
@@ -308,14 +473,44 @@ pub async fn download_and_pipeline_blocks(
 
     ctx.try_log(|logger| debug!(logger, "Pipeline successfully terminated"));
 
+    let mut abort_reason = None;
     if let Some(post_processor) = blocks_post_processor {
+        let mut last_block_committed = None;
         loop {
             if let Ok(signal) = post_processor.events_rx.recv() {
                 match signal {
-                    PostProcessorEvent::Terminated | PostProcessorEvent::Expired => break,
+                    PostProcessorEvent::BlockCommitted(block_height) => {
+                        ctx.try_log(|logger| {
+                            debug!(logger, "Block #{block_height} committed by post processor")
+                        });
+                        last_block_committed = Some(block_height);
+                    }
+                    PostProcessorEvent::TipReached(block_height) => {
+                        ctx.try_log(|logger| {
+                            info!(
+                                logger,
+                                "Indexer caught up with the chain tip #{block_height}"
+                            )
+                        });
+                    }
+                    PostProcessorEvent::Aborted(reason) => {
+                        abort_reason = Some(reason);
+                        break;
+                    }
+                    PostProcessorEvent::Terminated
+                    | PostProcessorEvent::Drained
+                    | PostProcessorEvent::Expired => break,
                 }
             }
         }
+        if let Some(block_height) = last_block_committed {
+            ctx.try_log(|logger| {
+                info!(
+                    logger,
+                    "Post processor committed blocks up to #{block_height}"
+                )
+            });
+        }
     }
 
     let _ = block_compressed_tx.send(None);
@@ -323,6 +518,10 @@ pub async fn download_and_pipeline_blocks(
     let _ = storage_thread.join();
     let _ = set.shutdown();
 
+    if let Some(reason) = abort_reason {
+        return Err(reason.to_string());
+    }
+
     ctx.try_log(|logger| {
         info!(
             logger,
@@ -347,3 +546,59 @@ pub async fn download_and_pipeline_blocks(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_until_synced_returns_once_the_queue_is_empty() {
+        let (commands_tx, _commands_rx) = crossbeam_channel::bounded(2);
+        let (events_tx, events_rx) = crossbeam_channel::unbounded();
+        let controller = PostProcessorController {
+            commands_tx,
+            events_rx,
+            thread_handle: std::thread::spawn(|| {}),
+        };
+
+        assert!(matches!(
+            controller.wait_until_synced(Duration::from_millis(10)),
+            Err(WaitError::Timeout)
+        ));
+
+        events_tx
+            .send(PostProcessorEvent::BlockCommitted(1))
+            .unwrap();
+        events_tx.send(PostProcessorEvent::Expired).unwrap();
+        assert!(controller.wait_until_synced(Duration::from_secs(1)).is_ok());
+
+        drop(events_tx);
+        assert!(matches!(
+            controller.wait_until_synced(Duration::from_secs(1)),
+            Err(WaitError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn blocks_are_broadcast_to_every_post_processor() {
+        use chainhook_sdk::{indexer::bitcoin::standardize_bitcoin_block, types::BitcoinNetwork};
+
+        let ctx = Context::empty();
+        let block = include_str!("../../db/fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+
+        let mut post_processors = PostProcessorRegistry::new();
+        let first_rx = post_processors.register(1);
+        let second_rx = post_processors.register(1);
+        // A processor that stopped doesn't hold back the others
+        drop(post_processors.register(1));
+        assert_eq!(post_processors.len(), 3);
+
+        broadcast_block(&block, &post_processors.into_senders());
+        for block_rx in [first_rx, second_rx] {
+            let received = block_rx.try_recv().unwrap();
+            assert_eq!(received.block_identifier, block.block_identifier);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{collections::VecDeque, fmt, time::Duration};
+
+const DEFAULT_WINDOW_SIZE: usize = 1000;
+
+/// Time spent processing each of the last blocks, for reporting the distribution of the
+/// processing durations.
+pub struct ProcessingDurationWindow {
+    /// Oldest first
+    durations: VecDeque<Duration>,
+    window_size: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DurationSummary {
+    pub min: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl ProcessingDurationWindow {
+    pub fn new() -> ProcessingDurationWindow {
+        ProcessingDurationWindow::with_window_size(DEFAULT_WINDOW_SIZE)
+    }
+
+    pub fn with_window_size(window_size: usize) -> ProcessingDurationWindow {
+        ProcessingDurationWindow {
+            durations: VecDeque::with_capacity(window_size),
+            window_size: window_size.max(1),
+        }
+    }
+
+    pub fn record(&mut self, duration: Duration) {
+        if self.durations.len() == self.window_size {
+            self.durations.pop_front();
+        }
+        self.durations.push_back(duration);
+    }
+
+    pub fn len(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.durations.is_empty()
+    }
+
+    /// Nearest-rank percentiles of the durations in the window.
+    pub fn summary(&self) -> Option<DurationSummary> {
+        if self.durations.is_empty() {
+            return None;
+        }
+        let mut sorted = self.durations.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Some(DurationSummary {
+            min: sorted[0],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl Default for ProcessingDurationWindow {
+    fn default() -> Self {
+        ProcessingDurationWindow::new()
+    }
+}
+
+impl fmt::Display for DurationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {:.1?}, p50 {:.1?}, p95 {:.1?}, p99 {:.1?}, max {:.1?}",
+            self.min, self.p50, self.p95, self.p99, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_covers_the_last_blocks() {
+        let mut window = ProcessingDurationWindow::with_window_size(100);
+        assert_eq!(window.summary(), None);
+
+        // A slow block, then evicted by 100 blocks taking 1ms to 100ms
+        window.record(Duration::from_secs(10));
+        for ms in 1..=100 {
+            window.record(Duration::from_millis(ms));
+        }
+        assert_eq!(window.len(), 100);
+        let summary = window.summary().unwrap();
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+    }
+}
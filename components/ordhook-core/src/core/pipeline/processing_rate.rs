@@ -0,0 +1,108 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_WINDOW_SIZE: usize = 10;
+
+/// Rolling average of the number of blocks processed per second, computed over the last
+/// completed batches, used for reporting the progress of a sync.
+pub struct ProcessingRateTracker {
+    /// Completion time and size of the batches in the window, oldest first
+    batches: VecDeque<(Instant, u64)>,
+    window_size: usize,
+    /// Completion time of the batch preceding the window (creation time initially)
+    window_start: Instant,
+}
+
+impl ProcessingRateTracker {
+    pub fn new() -> ProcessingRateTracker {
+        ProcessingRateTracker::with_window_size(DEFAULT_WINDOW_SIZE, Instant::now())
+    }
+
+    pub fn with_window_size(window_size: usize, started_at: Instant) -> ProcessingRateTracker {
+        ProcessingRateTracker {
+            batches: VecDeque::with_capacity(window_size),
+            window_size: window_size.max(1),
+            window_start: started_at,
+        }
+    }
+
+    /// Record a batch of `block_count` blocks completed at `completed_at`.
+    pub fn record(&mut self, block_count: u64, completed_at: Instant) {
+        if self.batches.len() == self.window_size {
+            if let Some((evicted_at, _)) = self.batches.pop_front() {
+                self.window_start = evicted_at;
+            }
+        }
+        self.batches.push_back((completed_at, block_count));
+    }
+
+    pub fn blocks_per_second(&self) -> Option<f64> {
+        let (last_completed_at, _) = self.batches.back()?;
+        let elapsed = last_completed_at
+            .saturating_duration_since(self.window_start)
+            .as_secs_f64();
+        if elapsed == 0.0 {
+            return None;
+        }
+        let block_count: u64 = self.batches.iter().map(|(_, count)| count).sum();
+        Some(block_count as f64 / elapsed)
+    }
+
+    /// Time left to process `remaining_blocks` at the current rate.
+    pub fn eta(&self, remaining_blocks: u64) -> Option<Duration> {
+        let rate = self.blocks_per_second()?;
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(remaining_blocks as f64 / rate))
+    }
+}
+
+impl Default for ProcessingRateTracker {
+    fn default() -> Self {
+        ProcessingRateTracker::new()
+    }
+}
+
+/// `3h 22m`, `12m 5s`, `42s`
+pub fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, _) => format!("{minutes}m {seconds}s"),
+        _ => format!("{hours}h {minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolling_rate_only_covers_the_last_batches() {
+        let started_at = Instant::now();
+        let mut tracker = ProcessingRateTracker::with_window_size(2, started_at);
+        assert_eq!(tracker.blocks_per_second(), None);
+
+        // 10 blocks in 10s, then 100 blocks in 1s twice: the slow batch is evicted
+        tracker.record(10, started_at + Duration::from_secs(10));
+        assert_eq!(tracker.blocks_per_second(), Some(1.0));
+        tracker.record(100, started_at + Duration::from_secs(11));
+        tracker.record(100, started_at + Duration::from_secs(12));
+        assert_eq!(tracker.blocks_per_second(), Some(100.0));
+        assert_eq!(tracker.eta(1_000), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn eta_formatting() {
+        assert_eq!(format_eta(Duration::from_secs(42)), "42s");
+        assert_eq!(format_eta(Duration::from_secs(725)), "12m 5s");
+        assert_eq!(
+            format_eta(Duration::from_secs(3 * 3600 + 22 * 60 + 7)),
+            "3h 22m"
+        );
+    }
+}
@@ -2,14 +2,24 @@ use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
 use crossbeam_channel::{Sender, TryRecvError};
 use rocksdb::DB;
 use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
     thread::{sleep, JoinHandle},
     time::Duration,
 };
 
 use crate::{
     config::Config,
-    core::pipeline::{PostProcessorCommand, PostProcessorController, PostProcessorEvent},
-    db::{insert_entry_in_blocks, open_ordhook_db_conn_rocks_db_loop},
+    core::{
+        error::ProcessingError,
+        pipeline::{PostProcessorCommand, PostProcessorController, PostProcessorEvent},
+    },
+    db::{
+        find_last_block_inserted, insert_entries_in_blocks, open_ordhook_db_conn_rocks_db_loop,
+        remove_entry_from_blocks,
+    },
+    utils::read_file_content_at_path,
 };
 
 pub fn start_block_archiving_processor(
@@ -28,14 +38,14 @@ pub fn start_block_archiving_processor(
             let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
                 true,
                 &config.expected_cache_path(),
-                config.resources.ulimit,
-                config.resources.memory_available,
+                &config.resources,
                 &ctx,
             );
             let mut processed_blocks = 0;
+            let mut draining = false;
 
             loop {
-                let (compacted_blocks, _) = match commands_rx.try_recv() {
+                let (compacted_blocks, blocks) = match commands_rx.try_recv() {
                     Ok(PostProcessorCommand::ProcessBlocks(compacted_blocks, blocks)) => {
                         (compacted_blocks, blocks)
                     }
@@ -43,8 +53,20 @@ pub fn start_block_archiving_processor(
                         let _ = events_tx.send(PostProcessorEvent::Terminated);
                         break;
                     }
+                    Ok(PostProcessorCommand::SetTargetHeight(_))
+                    | Ok(PostProcessorCommand::SetChainTip(_)) => continue,
+                    // Only the inscription indexing processor can be paused
+                    Ok(PostProcessorCommand::Pause(_)) => continue,
+                    Ok(PostProcessorCommand::Drain) => {
+                        draining = true;
+                        continue;
+                    }
                     Err(e) => match e {
                         TryRecvError::Empty => {
+                            if draining {
+                                let _ = events_tx.send(PostProcessorEvent::Drained);
+                                break;
+                            }
                             sleep(Duration::from_secs(1));
                             continue;
                         }
@@ -54,7 +76,19 @@ pub fn start_block_archiving_processor(
                     },
                 };
                 processed_blocks += compacted_blocks.len();
-                store_compacted_blocks(compacted_blocks, update_tip, &blocks_db_rw, &ctx);
+                if let Err(e) = store_compacted_blocks(
+                    compacted_blocks,
+                    &blocks,
+                    update_tip,
+                    &blocks_db_rw,
+                    &ctx,
+                ) {
+                    ctx.try_log(|logger| error!(logger, "Block archiving: {e}, aborting"));
+                    let _ = events_tx.send(PostProcessorEvent::Aborted(
+                        ProcessingError::StorageError(e),
+                    ));
+                    break;
+                }
 
                 if processed_blocks % 10_000 == 0 {
                     let _ = blocks_db_rw.flush_wal(true);
@@ -78,20 +112,46 @@ pub fn start_block_archiving_processor(
 
 pub fn store_compacted_blocks(
     mut compacted_blocks: Vec<(u64, Vec<u8>)>,
+    blocks: &Vec<BitcoinBlockData>,
     update_tip: bool,
     blocks_db_rw: &DB,
     ctx: &Context,
-) {
+) -> Result<(), String> {
     compacted_blocks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    // Compacted blocks don't embed their hash: only the blocks fully transmitted are indexed.
+    let block_hashes = blocks
+        .iter()
+        .map(|block| {
+            (
+                block.block_identifier.index,
+                block.block_identifier.hash.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let heights = compacted_blocks
+        .iter()
+        .map(|(block_height, _)| *block_height)
+        .chain(block_hashes.iter().map(|(block_height, _)| *block_height));
+    let (Some(start_block), Some(end_block)) = (heights.clone().min(), heights.max()) else {
+        return Ok(());
+    };
 
-    for (block_height, compacted_block) in compacted_blocks.into_iter() {
-        insert_entry_in_blocks(
-            block_height as u32,
-            &compacted_block,
-            update_tip,
-            &blocks_db_rw,
-            &ctx,
-        );
+    let journal = CompactedBlocksJournal {
+        update_tip,
+        start_block,
+        end_block,
+    };
+    let journal_path = get_compacted_blocks_journal_path(blocks_db_rw.path());
+    journal.commit(&journal_path)?;
+
+    insert_entries_in_blocks(
+        &compacted_blocks,
+        &block_hashes,
+        update_tip,
+        blocks_db_rw,
+        ctx,
+    )?;
+    for (block_height, _) in compacted_blocks.iter() {
         ctx.try_log(|logger| {
             info!(logger, "Block #{block_height} saved to disk");
         });
@@ -102,4 +162,215 @@ pub fn store_compacted_blocks(
             error!(logger, "{}", e.to_string());
         });
     }
+    let _ = std::fs::remove_file(&journal_path);
+    Ok(())
+}
+
+/// Journal file written next to hord.rocksdb before each batch of compacted blocks.
+pub fn get_compacted_blocks_journal_path(blocks_db_path: &Path) -> PathBuf {
+    blocks_db_path.with_extension("rocksdb.journal")
+}
+
+/// Range of heights written by the batch of `store_compacted_blocks` in progress. The batch
+/// itself is atomic: the journal only tells which blocks may have been stored ahead of
+/// hord.sqlite when the process stopped.
+#[derive(Debug, PartialEq)]
+struct CompactedBlocksJournal {
+    update_tip: bool,
+    start_block: u64,
+    end_block: u64,
+}
+
+impl CompactedBlocksJournal {
+    fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![self.update_tip as u8];
+        bytes.extend(self.start_block.to_be_bytes());
+        bytes.extend(self.end_block.to_be_bytes());
+        bytes
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<CompactedBlocksJournal, String> {
+        let mut cursor = Cursor::new(bytes);
+        let mut update_tip = [0u8; 1];
+        cursor
+            .read_exact(&mut update_tip)
+            .map_err(|e| e.to_string())?;
+        Ok(CompactedBlocksJournal {
+            update_tip: update_tip[0] == 1,
+            start_block: read_u64(&mut cursor)?,
+            end_block: read_u64(&mut cursor)?,
+        })
+    }
+
+    /// Durably writes the journal, then renames it in place: a journal at `journal_path`
+    /// is always complete.
+    fn commit(&self, journal_path: &PathBuf) -> Result<(), String> {
+        let tmp_path = journal_path.with_extension("journal.tmp");
+        let mut file = File::create(&tmp_path)
+            .map_err(|e| format!("unable to create {}: {e}", tmp_path.display()))?;
+        file.write_all(&self.serialize())
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("unable to write {}: {e}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, journal_path)
+            .map_err(|e| format!("unable to write {}: {e}", journal_path.display()))
+    }
+}
+
+fn read_u64(cursor: &mut Cursor<&[u8]>) -> Result<u64, String> {
+    let mut buffer = [0u8; 8];
+    cursor.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+    Ok(u64::from_be_bytes(buffer))
+}
+
+/// Recovers from the batch of compacted blocks that was being written when the process
+/// stopped, if any. The batch was either stored entirely or not at all: its blocks above
+/// `discard_above` (the last block committed to hord.sqlite) are discarded, to be downloaded
+/// again, the other ones are kept. Blocks written outside of the interrupted batch are left
+/// untouched. A journal that was never renamed in place means that hord.rocksdb was not
+/// touched yet: it is discarded.
+pub fn recover_compacted_blocks_journal(
+    blocks_db_rw: &DB,
+    discard_above: Option<u64>,
+    ctx: &Context,
+) -> Result<(), String> {
+    let journal_path = get_compacted_blocks_journal_path(blocks_db_rw.path());
+    let _ = std::fs::remove_file(journal_path.with_extension("journal.tmp"));
+    if !journal_path.exists() {
+        return Ok(());
+    }
+    let bytes = read_file_content_at_path(&journal_path)?;
+    let journal = CompactedBlocksJournal::deserialize(&bytes)
+        .map_err(|e| format!("unable to parse {}: {e}", journal_path.display()))?;
+    let lowest_discarded = discard_above
+        .map(|height| journal.start_block.max(height.saturating_add(1)))
+        .filter(|lowest_discarded| *lowest_discarded <= journal.end_block);
+    ctx.try_log(|logger| {
+        warn!(
+            logger,
+            "Recovering the compacted blocks #{} to #{} left uncommitted in hord.rocksdb: {} discarded",
+            journal.start_block,
+            journal.end_block,
+            lowest_discarded.map_or(0, |lowest| journal.end_block - lowest + 1)
+        )
+    });
+    if let Some(lowest_discarded) = lowest_discarded {
+        for block_height in lowest_discarded..=journal.end_block {
+            remove_entry_from_blocks(block_height as u32, blocks_db_rw, ctx);
+        }
+        if journal.update_tip && find_last_block_inserted(blocks_db_rw) as u64 >= lowest_discarded {
+            blocks_db_rw
+                .put(
+                    b"metadata::last_insert",
+                    (lowest_discarded as u32).saturating_sub(1).to_be_bytes(),
+                )
+                .map_err(|e| format!("unable to update hord.rocksdb tip: {e}"))?;
+        }
+    }
+    blocks_db_rw
+        .flush()
+        .map_err(|e| format!("unable to flush hord.rocksdb: {e}"))?;
+    std::fs::remove_file(&journal_path)
+        .map_err(|e| format!("unable to remove {}: {e}", journal_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{find_block_hash_at_block_height, insert_entry_in_blocks};
+    use crate::utils::test_dir::TestWorkingDir;
+
+    #[test]
+    fn journal_roundtrip() {
+        let journal = CompactedBlocksJournal {
+            update_tip: true,
+            start_block: 800000,
+            end_block: 800001,
+        };
+        let bytes = journal.serialize();
+        assert_eq!(CompactedBlocksJournal::deserialize(&bytes), Ok(journal));
+        assert!(CompactedBlocksJournal::deserialize(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn store_compacted_blocks_writes_the_batch() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+
+        store_compacted_blocks(
+            vec![(800001, vec![1]), (800000, vec![0])],
+            &vec![],
+            true,
+            &blocks_db,
+            &ctx,
+        )
+        .unwrap();
+        assert_eq!(
+            blocks_db.get(800000u32.to_be_bytes()).unwrap(),
+            Some(vec![0])
+        );
+        assert_eq!(
+            blocks_db.get(800001u32.to_be_bytes()).unwrap(),
+            Some(vec![1])
+        );
+        assert_eq!(find_last_block_inserted(&blocks_db), 800001);
+        assert_eq!(find_block_hash_at_block_height(800001, &blocks_db), None);
+        assert!(!get_compacted_blocks_journal_path(blocks_db.path()).exists());
+    }
+
+    #[test]
+    fn journal_recovery_discards_only_the_interrupted_batch() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+        let stored = |block_height: u32| blocks_db.get(block_height.to_be_bytes()).unwrap();
+
+        // #800000 committed to hord.sqlite, #800005 ingested without indexing
+        insert_entry_in_blocks(800000, &[0], true, &blocks_db, &ctx);
+        insert_entry_in_blocks(800005, &[5], false, &blocks_db, &ctx);
+        // Batch #800001-#800002 stored, the process stopped before indexing it
+        insert_entry_in_blocks(800001, &[1], true, &blocks_db, &ctx);
+        insert_entry_in_blocks(800002, &[2], true, &blocks_db, &ctx);
+        let journal = CompactedBlocksJournal {
+            update_tip: true,
+            start_block: 800001,
+            end_block: 800002,
+        };
+        journal
+            .commit(&get_compacted_blocks_journal_path(blocks_db.path()))
+            .unwrap();
+
+        recover_compacted_blocks_journal(&blocks_db, Some(800000), &ctx).unwrap();
+        assert_eq!(stored(800000), Some(vec![0]));
+        assert_eq!(stored(800001), None);
+        assert_eq!(stored(800002), None);
+        assert_eq!(stored(800005), Some(vec![5]));
+        assert_eq!(find_last_block_inserted(&blocks_db), 800000);
+        assert!(!get_compacted_blocks_journal_path(blocks_db.path()).exists());
+    }
+
+    #[test]
+    fn journal_recovery_without_checkpoint_keeps_the_batch() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+
+        insert_entry_in_blocks(1, &[1], true, &blocks_db, &ctx);
+        let journal = CompactedBlocksJournal {
+            update_tip: true,
+            start_block: 0,
+            end_block: 1,
+        };
+        journal
+            .commit(&get_compacted_blocks_journal_path(blocks_db.path()))
+            .unwrap();
+
+        recover_compacted_blocks_journal(&blocks_db, None, &ctx).unwrap();
+        assert_eq!(blocks_db.get(1u32.to_be_bytes()).unwrap(), Some(vec![1]));
+        assert_eq!(find_last_block_inserted(&blocks_db), 1);
+        assert!(!get_compacted_blocks_journal_path(blocks_db.path()).exists());
+    }
 }
@@ -1,59 +1,87 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     sync::Arc,
     thread::{sleep, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use chainhook_sdk::{
-    types::{BitcoinBlockData, TransactionIdentifier},
+    types::{BitcoinBlockData, BlockIdentifier, OrdinalOperation, TransactionIdentifier},
     utils::Context,
 };
-use crossbeam_channel::{Sender, TryRecvError};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use rocksdb::DB;
 use rusqlite::Transaction;
 
-use dashmap::DashMap;
-use fxhash::FxHasher;
 use rusqlite::Connection;
-use std::hash::BuildHasherDefault;
 
 use crate::{
     core::{
-        pipeline::processors::block_archiving::store_compacted_blocks,
+        check_rollback_depth,
+        checkpoint::advance_checkpoint,
+        error::ProcessingError,
+        pipeline::{
+            processing_rate::{format_eta, ProcessingRateTracker},
+            processors::block_archiving::store_compacted_blocks,
+        },
         protocol::{
+            brc20::parse_brc20_operation,
             inscription_parsing::{
                 get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
             },
             inscription_sequencing::{
                 augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx,
-                get_bitcoin_network, get_jubilee_block_height,
-                parallelize_inscription_data_computations, SequenceCursor,
+                get_blocks_without_intra_batch_dependencies,
+                parallelize_inscription_data_computations, prefetch_inscriptions_data_in_blocks,
+                SequenceCursor,
             },
             satoshi_tracking::augment_block_with_ordinals_transfer_data,
         },
-        OrdhookConfig,
+        OrdhookConfig, TraversalsCache,
     },
     db::{
-        get_any_entry_in_ordinal_activities, open_ordhook_db_conn_rocks_db_loop,
-        open_readonly_ordhook_db_conn,
+        delete_block_hash, find_block_hash_at_block_height, find_last_block_hash,
+        get_any_entry_in_ordinal_activities, get_conflicting_activity, insert_block_hash,
+        insert_entry_in_brc20_operations, open_ordhook_db_conn_rocks_db_loop,
+        rollback_block_at_height_tx, run_compaction_slice, snapshot_ordhook_db,
+        store_inscription_content, verify_sequence_integrity, ContentSource,
+    },
+    metrics,
+    utils::{
+        hot_cache::InscriptionsHotCache,
+        ipfs::{parse_ipfs_cid, resolve_ipfs_contents},
     },
 };
 
-use crate::db::{TransactionBytesCursor, TraversalResult};
+use crate::db::TraversalResult;
 
 use crate::{
     config::Config,
     core::{
         new_traversals_lazy_cache,
-        pipeline::{PostProcessorCommand, PostProcessorController, PostProcessorEvent},
+        pipeline::{
+            broadcast_block, PausedProcessorCommand, PostProcessorCommand, PostProcessorController,
+            PostProcessorEvent,
+        },
     },
     db::open_readwrite_ordhook_db_conn,
 };
 
+const SEQUENCE_INTEGRITY_CHECK_INTERVAL: usize = 10_000;
+const SEQUENCE_GAPS_LOGGED: usize = 10;
+const PROCESSING_RATE_LOG_INTERVAL: u64 = 100;
+const PROCESSING_DURATION_LOG_INTERVAL: u64 = 100;
+/// Number of consecutive idle cycles (~1s each) after which the processor considers itself caught up.
+const IDLE_CYCLES_BEFORE_COMPACTION: usize = 10;
+const ROCKSDB_COMPACTION_INTERVAL: Duration = Duration::from_secs(3600);
+/// Number of blocks compacted per idle cycle, so that incoming blocks wait for one slice at most.
+const ROCKSDB_COMPACTION_SLICE: u32 = 10_000;
+
 pub fn start_inscription_indexing_processor(
     config: &Config,
     ctx: &Context,
-    post_processor: Option<Sender<BitcoinBlockData>>,
+    post_processors: Vec<Sender<BitcoinBlockData>>,
+    inscriptions_hot_cache: Option<Arc<InscriptionsHotCache>>,
 ) -> PostProcessorController {
     let (commands_tx, commands_rx) = crossbeam_channel::bounded::<PostProcessorCommand>(2);
     let (events_tx, events_rx) = crossbeam_channel::unbounded::<PostProcessorEvent>();
@@ -62,18 +90,63 @@ pub fn start_inscription_indexing_processor(
     let ctx = ctx.clone();
     let handle: JoinHandle<()> = hiro_system_kit::thread_named("Inscription indexing runloop")
         .spawn(move || {
-            let cache_l2 = Arc::new(new_traversals_lazy_cache(2048));
-            let garbage_collect_every_n_blocks = 100;
+            let traversal_cache_size = config.resources.traversal_cache_size;
+            if traversal_cache_size < 256 {
+                ctx.try_log(|logger| {
+                    warn!(
+                        logger,
+                        "Traversal cache size ({traversal_cache_size}) is below 256, expect degraded performance"
+                    )
+                });
+            }
+            let cache_l2 = Arc::new(
+                new_traversals_lazy_cache(
+                    traversal_cache_size,
+                    &config.resources.cache_eviction_policy,
+                )
+                // Dry runs keep the blocks of each batch in the block cache instead of
+                // hord.rocksdb: it can't be disabled
+                .with_block_cache(match config.dry_run {
+                    true => config.resources.block_cache_size.max(1),
+                    false => config.resources.block_cache_size,
+                })
+                .with_max_bytes(config.resources.max_cache_bytes),
+            );
+            let garbage_collect_every_n_blocks = config.resources.cache_gc_interval as usize;
             let mut garbage_collect_nth_block = 0;
+            let mut inscriptions_indexed_since_integrity_check = 0;
+            let mut integrity_check_start_block: Option<u64> = None;
 
-            let mut inscriptions_db_conn_rw =
-                open_readwrite_ordhook_db_conn(&config.expected_cache_path(), &ctx).unwrap();
+            let mut inscriptions_db_conn_rw = match open_readwrite_ordhook_db_conn(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                &ctx,
+            ) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    ctx.try_log(|logger| error!(logger, "Unable to open hord.sqlite: {e}"));
+                    let _ = events_tx
+                        .send(PostProcessorEvent::Aborted(ProcessingError::StorageError(e)));
+                    return;
+                }
+            };
             let ordhook_config = config.get_ordhook_config();
+            let blocks_db = open_ordhook_db_conn_rocks_db_loop(
+                !ordhook_config.dry_run,
+                &config.expected_cache_path(),
+                &config.resources,
+                &ctx,
+            );
             let mut empty_cycles = 0;
-
-            let inscriptions_db_conn =
-                open_readonly_ordhook_db_conn(&config.expected_cache_path(), &ctx).unwrap();
-            let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_conn);
+            let mut draining = false;
+            let mut chain_tip = None;
+            let mut last_compaction = Instant::now();
+            let mut compaction_cursor: Option<u32> = None;
+            let mut compaction_started_at = Instant::now();
+            let mut processing_rate = ProcessingRateTracker::new();
+            let mut blocks_processed_since_rate_log = 0;
+            let mut target_block_height = None;
+            let mut live_chain_tip = None;
 
             loop {
                 let (compacted_blocks, mut blocks) = match commands_rx.try_recv() {
@@ -85,9 +158,61 @@ pub fn start_inscription_indexing_processor(
                         let _ = events_tx.send(PostProcessorEvent::Terminated);
                         break;
                     }
+                    Ok(PostProcessorCommand::SetTargetHeight(block_height)) => {
+                        target_block_height = Some(block_height);
+                        continue;
+                    }
+                    Ok(PostProcessorCommand::SetChainTip(block_height)) => {
+                        live_chain_tip = Some(block_height);
+                        continue;
+                    }
+                    Ok(PostProcessorCommand::Drain) => {
+                        draining = true;
+                        continue;
+                    }
+                    Ok(PostProcessorCommand::Pause(control_rx)) => {
+                        run_paused(&control_rx, &blocks_db, &inscriptions_db_conn_rw, &ctx);
+                        continue;
+                    }
                     Err(e) => match e {
                         TryRecvError::Empty => {
+                            if draining {
+                                let _ = events_tx.send(PostProcessorEvent::Drained);
+                                break;
+                            }
                             empty_cycles += 1;
+                            if empty_cycles == IDLE_CYCLES_BEFORE_COMPACTION
+                                && !ordhook_config.dry_run
+                                && compaction_cursor.is_none()
+                                && last_compaction.elapsed() >= ROCKSDB_COMPACTION_INTERVAL
+                            {
+                                ctx.try_log(|logger| {
+                                    info!(logger, "Block processor idle, compacting hord.rocksdb")
+                                });
+                                compaction_cursor = Some(0);
+                                compaction_started_at = Instant::now();
+                            }
+                            // Compaction goes through this thread's handle, one slice per idle
+                            // cycle, and resumes where it left off after incoming blocks
+                            if let Some(start) = compaction_cursor
+                                .filter(|_| empty_cycles >= IDLE_CYCLES_BEFORE_COMPACTION)
+                            {
+                                compaction_cursor = run_compaction_slice(
+                                    &blocks_db,
+                                    start,
+                                    ROCKSDB_COMPACTION_SLICE,
+                                );
+                                if compaction_cursor.is_none() {
+                                    last_compaction = Instant::now();
+                                    ctx.try_log(|logger| {
+                                        info!(
+                                            logger,
+                                            "hord.rocksdb compaction completed in {:.1}s",
+                                            compaction_started_at.elapsed().as_secs_f64()
+                                        )
+                                    });
+                                }
+                            }
                             if empty_cycles == 180 {
                                 ctx.try_log(|logger| {
                                     info!(logger, "Block processor reached expiration")
@@ -104,20 +229,32 @@ pub fn start_inscription_indexing_processor(
                     },
                 };
 
-                {
-                    let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
-                        true,
-                        &config.expected_cache_path(),
-                        config.resources.ulimit,
-                        config.resources.memory_available,
-                        &ctx,
-                    );
-                    store_compacted_blocks(
+                if ordhook_config.dry_run {
+                    ctx.try_log(|logger| {
+                        info!(
+                            logger,
+                            "Dry run: skipping the storage of {} compacted blocks",
+                            compacted_blocks.len()
+                        )
+                    });
+                    cache_l2.insert_blocks(&compacted_blocks);
+                } else {
+                    for (block_height, _) in compacted_blocks.iter() {
+                        cache_l2.forget_block(*block_height as u32);
+                    }
+                    // Block hashes are indexed by process_blocks, once committed to hord.sqlite
+                    if let Err(e) = store_compacted_blocks(
                         compacted_blocks,
+                        &vec![],
                         true,
-                        &blocks_db_rw,
+                        &blocks_db,
                         &Context::empty(),
-                    );
+                    ) {
+                        ctx.try_log(|logger| error!(logger, "Block archiving: {e}, aborting"));
+                        let _ = events_tx
+                            .send(PostProcessorEvent::Aborted(ProcessingError::StorageError(e)));
+                        break;
+                    }
                 }
 
                 // Early return
@@ -126,30 +263,137 @@ pub fn start_inscription_indexing_processor(
                 }
 
                 ctx.try_log(|logger| info!(logger, "Processing {} blocks", blocks.len()));
-                blocks = process_blocks(
+                blocks = match process_blocks(
                     &mut blocks,
-                    &mut sequence_cursor,
+                    &mut chain_tip,
                     &cache_l2,
                     &mut inscriptions_db_conn_rw,
+                    &blocks_db,
                     &ordhook_config,
-                    &post_processor,
+                    &post_processors,
+                    inscriptions_hot_cache.as_deref(),
+                    &events_tx,
                     &ctx,
-                );
+                ) {
+                    Ok(blocks) => blocks,
+                    Err(e) => {
+                        ctx.try_log(|logger| error!(logger, "Block processing: {e}, aborting"));
+                        let _ = events_tx.send(PostProcessorEvent::Aborted(e));
+                        break;
+                    }
+                };
+
+                if let (Some(live_tip), Some(tip)) = (live_chain_tip, &chain_tip) {
+                    if tip.index >= live_tip {
+                        let _ = events_tx.send(PostProcessorEvent::TipReached(tip.index));
+                        live_chain_tip = None;
+                    }
+                }
+
+                processing_rate.record(blocks.len() as u64, Instant::now());
+                blocks_processed_since_rate_log += blocks.len() as u64;
+                if let Some(rate) = processing_rate.blocks_per_second() {
+                    metrics::BLOCKS_PROCESSED_PER_SECOND.set(rate);
+                    if blocks_processed_since_rate_log >= PROCESSING_RATE_LOG_INTERVAL {
+                        let eta = match (target_block_height, &chain_tip) {
+                            (Some(target), Some(tip)) => processing_rate
+                                .eta(target.saturating_sub(tip.index))
+                                .map(|eta| format!(", ETA: {}", format_eta(eta))),
+                            _ => None,
+                        };
+                        ctx.try_log(|logger| {
+                            info!(
+                                logger,
+                                "Processing rate: {rate:.1} blocks/s{}",
+                                eta.unwrap_or_default()
+                            )
+                        });
+                        blocks_processed_since_rate_log = 0;
+                    }
+                }
+
+                inscriptions_indexed_since_integrity_check += blocks
+                    .iter()
+                    .map(|b| get_inscriptions_revealed_in_block(b, None).len())
+                    .sum::<usize>();
+                if let Some(first_block) = blocks.iter().map(|b| b.block_identifier.index).min() {
+                    integrity_check_start_block = Some(
+                        integrity_check_start_block.map_or(first_block, |h| h.min(first_block)),
+                    );
+                }
+                if inscriptions_indexed_since_integrity_check >= SEQUENCE_INTEGRITY_CHECK_INTERVAL {
+                    // Only the blocks indexed since the last check are verified
+                    if let (Some(start_block), Some(tip)) = (integrity_check_start_block, &chain_tip)
+                    {
+                        match verify_sequence_integrity(
+                            start_block,
+                            tip.index,
+                            &inscriptions_db_conn_rw,
+                            &ctx,
+                        ) {
+                            Ok(gaps) if !gaps.is_empty() => {
+                                let missing_numbers: u64 = gaps
+                                    .iter()
+                                    .map(|(first, last)| first.abs_diff(*last) + 1)
+                                    .sum();
+                                ctx.try_log(|logger| {
+                                    warn!(
+                                        logger,
+                                        "Inscription sequence integrity check failed between #{start_block} and #{}, {missing_numbers} numbers missing in {} gaps, starting with {:?}",
+                                        tip.index,
+                                        gaps.len(),
+                                        &gaps[..gaps.len().min(SEQUENCE_GAPS_LOGGED)]
+                                    )
+                                });
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                ctx.try_log(|logger| {
+                                    error!(logger, "Unable to verify inscription sequence: {e}")
+                                });
+                            }
+                        }
+                    }
+                    inscriptions_indexed_since_integrity_check = 0;
+                    integrity_check_start_block = None;
+                }
 
                 garbage_collect_nth_block += blocks.len();
-                if garbage_collect_nth_block > garbage_collect_every_n_blocks {
+                if garbage_collect_nth_block > garbage_collect_every_n_blocks
+                    || cache_l2.exceeds_max_bytes()
+                {
                     ctx.try_log(|logger| info!(logger, "Performing garbage collecting"));
 
-                    // Clear L2 cache on a regular basis
+                    // Trim L2 cache on a regular basis, or as soon as it outgrows its budget
+                    let cache_l2_len = cache_l2.len();
+                    let cache_l2_bytes = cache_l2.byte_size();
+                    let evicted = cache_l2.collect_garbage();
                     ctx.try_log(|logger| {
-                        info!(logger, "Clearing cache L2 ({} entries)", cache_l2.len())
+                        info!(
+                            logger,
+                            "Evicted {evicted} of {cache_l2_len} cache L2 entries ({:.1} MB) accumulated over the last {garbage_collect_nth_block} blocks ({:?})",
+                            cache_l2_bytes as f64 / (1024.0 * 1024.0),
+                            config.resources.cache_eviction_policy
+                        )
                     });
-                    cache_l2.clear();
 
                     // Recreate sqlite db connection on a regular basis
-                    inscriptions_db_conn_rw =
-                        open_readwrite_ordhook_db_conn(&config.expected_cache_path(), &ctx)
-                            .unwrap();
+                    inscriptions_db_conn_rw = match open_readwrite_ordhook_db_conn(
+                        &config.expected_cache_path(),
+                        config.storage.sqlite_wal,
+                        &ctx,
+                    ) {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            ctx.try_log(|logger| {
+                                error!(logger, "Unable to reopen hord.sqlite: {e}")
+                            });
+                            let _ = events_tx.send(PostProcessorEvent::Aborted(
+                                ProcessingError::StorageError(e),
+                            ));
+                            break;
+                        }
+                    };
                     inscriptions_db_conn_rw.flush_prepared_statement_cache();
                     garbage_collect_nth_block = 0;
                 }
@@ -164,52 +408,169 @@ pub fn start_inscription_indexing_processor(
     }
 }
 
+/// Serves the commands of the paused processor until it is resumed. Snapshots are taken from
+/// the processor's own handles, in between two batches of blocks.
+fn run_paused(
+    control_rx: &Receiver<PausedProcessorCommand>,
+    blocks_db: &DB,
+    inscriptions_db_conn: &Connection,
+    ctx: &Context,
+) {
+    let paused_at = Instant::now();
+    ctx.try_log(|logger| info!(logger, "Block processor paused"));
+    while let Ok(command) = control_rx.recv() {
+        match command {
+            PausedProcessorCommand::Snapshot(output_dir, reply_tx) => {
+                let res = snapshot_ordhook_db(blocks_db, inscriptions_db_conn, &output_dir, ctx);
+                if let Err(ref e) = res {
+                    ctx.try_log(|logger| error!(logger, "Unable to take snapshot: {e}"));
+                }
+                let _ = reply_tx.send(res);
+            }
+            PausedProcessorCommand::Resume => break,
+        }
+    }
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Block processor resumed after {:.1}s",
+            paused_at.elapsed().as_secs_f64()
+        )
+    });
+}
+
+/// Indexes `next_blocks`, rolling back first the blocks orphaned by each of them. Fails when
+/// the re-org goes deeper than `max_rollback_depth`, when the orphaned blocks can't be rolled
+/// back or when a block can't be processed, leaving this block and the remaining ones unprocessed.
 pub fn process_blocks(
     next_blocks: &mut Vec<BitcoinBlockData>,
-    sequence_cursor: &mut SequenceCursor,
-    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    chain_tip: &mut Option<BlockIdentifier>,
+    cache_l2: &Arc<TraversalsCache>,
     inscriptions_db_conn_rw: &mut Connection,
+    blocks_db: &DB,
     ordhook_config: &OrdhookConfig,
-    post_processor: &Option<Sender<BitcoinBlockData>>,
+    post_processors: &[Sender<BitcoinBlockData>],
+    inscriptions_hot_cache: Option<&InscriptionsHotCache>,
+    events_tx: &Sender<PostProcessorEvent>,
     ctx: &Context,
-) -> Vec<BitcoinBlockData> {
+) -> Result<Vec<BitcoinBlockData>, ProcessingError> {
     let mut cache_l1 = BTreeMap::new();
 
     let mut updated_blocks = vec![];
 
-    for _cursor in 0..next_blocks.len() {
-        let inscriptions_db_tx: rusqlite::Transaction<'_> =
-            inscriptions_db_conn_rw.transaction().unwrap();
+    if ordhook_config.resources.parallel_block_processing && next_blocks.len() > 1 {
+        let independent_blocks = get_blocks_without_intra_batch_dependencies(next_blocks);
+        prefetch_inscriptions_data_in_blocks(
+            &independent_blocks,
+            &mut cache_l1,
+            cache_l2,
+            inscriptions_db_conn_rw,
+            ordhook_config,
+            ctx,
+        );
+    }
 
+    for _cursor in 0..next_blocks.len() {
         let mut block = next_blocks.remove(0);
 
+        // Discard the data written for the indexed blocks the incoming block is not building on
+        if !ordhook_config.dry_run {
+            if let Some((tip_height, orphaned_heights)) =
+                find_orphaned_block_heights(&block, blocks_db)
+            {
+                let lowest_height = orphaned_heights[orphaned_heights.len() - 1];
+                ctx.try_log(|logger| {
+                    warn!(
+                        logger,
+                        "Re-org detected: block {} is not building on the blocks indexed from #{lowest_height} to #{tip_height}",
+                        block.block_identifier
+                    )
+                });
+                check_rollback_depth(lowest_height, tip_height, ordhook_config.max_rollback_depth)?;
+                // Orphaned blocks are unwound all at once: a partial rollback is never persisted
+                let rollback_tx = inscriptions_db_conn_rw
+                    .transaction()
+                    .map_err(|e| ProcessingError::from(e).at_block(lowest_height))?;
+                for block_height in orphaned_heights.iter() {
+                    rollback_block_at_height_tx(*block_height, &rollback_tx, ctx)
+                        .map_err(|e| e.at_block(*block_height))?;
+                }
+                rollback_tx
+                    .commit()
+                    .map_err(|e| ProcessingError::from(e).at_block(lowest_height))?;
+                for block_height in orphaned_heights.into_iter() {
+                    delete_block_hash(block_height, blocks_db, ctx);
+                    cache_l2.forget_block(block_height as u32);
+                }
+                // The query API may be serving inscriptions revealed in the orphaned blocks
+                if let Some(hot_cache) = inscriptions_hot_cache {
+                    hot_cache.clear();
+                }
+            }
+        }
+
+        // Fetched before opening the transaction, for hord.sqlite not to stay locked while
+        // waiting on the gateway
+        let ipfs_contents = resolve_ipfs_contents_in_block(&block, ordhook_config, ctx);
+
+        let inscriptions_db_tx: rusqlite::Transaction<'_> =
+            inscriptions_db_conn_rw
+                .transaction()
+                .map_err(|e| ProcessingError::from(e).at_block(block.block_identifier.index))?;
+        let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_tx, ordhook_config.chain);
+
         // We check before hand if some data were pre-existing, before processing
         // Always discard if we have some existing content at this block height (inscription or transfers)
-        let any_existing_activity = get_any_entry_in_ordinal_activities(
-            &block.block_identifier.index,
+        let conflicting_activity = match get_conflicting_activity(
+            block.block_identifier.index,
             &inscriptions_db_tx,
             ctx,
-        );
+        ) {
+            Ok(conflicting_activity) => conflicting_activity,
+            Err(e) => {
+                ctx.try_log(|logger| warn!(logger, "Unable to look up existing activities: {e}"));
+                get_any_entry_in_ordinal_activities(
+                    &block.block_identifier.index,
+                    &inscriptions_db_tx,
+                    ctx,
+                )
+                .then(|| "unknown".to_string())
+            }
+        };
 
         // Invalidate and recompute cursor when crossing the jubilee height
-        let jubilee_height =
-            get_jubilee_block_height(&get_bitcoin_network(&block.metadata.network));
-        if block.block_identifier.index == jubilee_height {
-            sequence_cursor.reset();
+        if block.block_identifier.index == ordhook_config.chain.jubilee_height() {
+            sequence_cursor
+                .invalidate(ctx)
+                .map_err(|e| e.at_block(block.block_identifier.index))?;
         }
 
-        let _ = process_block(
+        let processing_started_at = Instant::now();
+        let processing_result = process_block(
             &mut block,
             &next_blocks,
-            sequence_cursor,
+            &mut sequence_cursor,
             &mut cache_l1,
             cache_l2,
             &inscriptions_db_tx,
+            &ipfs_contents,
             ordhook_config,
             ctx,
         );
+        metrics::BLOCK_PROCESSING_DURATION.observe(processing_started_at.elapsed());
+        if metrics::BLOCK_PROCESSING_DURATION.count() % PROCESSING_DURATION_LOG_INTERVAL == 0 {
+            if let Some(durations) = metrics::BLOCK_PROCESSING_DURATION.summary() {
+                ctx.try_log(|logger| info!(logger, "Block processing durations: {durations}"));
+            }
+        }
+        // A partially processed block is neither committed nor checkpointed, for the next run to
+        // process it again
+        if let Err(e) = processing_result {
+            let _ = inscriptions_db_tx.rollback();
+            return Err(e.at_block(block.block_identifier.index));
+        }
 
-        let inscriptions_revealed = get_inscriptions_revealed_in_block(&block)
+        let inscriptions_revealed = get_inscriptions_revealed_in_block(&block, None)
             .iter()
             .map(|d| d.get_inscription_number().to_string())
             .collect::<Vec<String>>();
@@ -222,15 +583,26 @@ pub fn process_blocks(
                 "Block #{} processed, revealed {} inscriptions [{}] and {inscriptions_transferred} transfers",
                 block.block_identifier.index,
                 inscriptions_revealed.len(),
-                inscriptions_revealed.join(", ")
+                inscriptions_revealed.join(", ");
+                "block_height" => block.block_identifier.index
             )
         });
 
-        if any_existing_activity {
+        if ordhook_config.dry_run {
+            ctx.try_log(|logger| {
+                info!(
+                    logger,
+                    "Dry run: discarding updates for block #{} ({} inscriptions revealed, {inscriptions_transferred} transfers)",
+                    block.block_identifier.index,
+                    inscriptions_revealed.len(),
+                )
+            });
+            let _ = inscriptions_db_tx.rollback();
+        } else if let Some(ref inscription_id) = conflicting_activity {
             ctx.try_log(|logger| {
                 error!(
                     logger,
-                    "Dropping updates for block #{}, activities present in database",
+                    "Dropping updates for block #{}, activities present in database (inscription {inscription_id})",
                     block.block_identifier.index,
                 )
             });
@@ -244,26 +616,124 @@ pub fn process_blocks(
                     //         "Updates saved for block {}", block.block_identifier.index,
                     //     )
                     // });
+                    insert_block_hash(
+                        block.block_identifier.index,
+                        &block.block_identifier.hash,
+                        blocks_db,
+                        ctx,
+                    );
+                    let _ = events_tx.send(PostProcessorEvent::BlockCommitted(
+                        block.block_identifier.index,
+                    ));
+                    if let Err(e) =
+                        advance_checkpoint(&ordhook_config.db_path, &block.block_identifier)
+                    {
+                        ctx.try_log(|logger| error!(logger, "Unable to write checkpoint: {e}"));
+                    }
                 }
                 Err(e) => {
-                    ctx.try_log(|logger| {
-                        error!(
-                            logger,
-                            "Unable to update changes in block #{}: {}",
-                            block.block_identifier.index,
-                            e.to_string()
-                        )
-                    });
+                    return Err(ProcessingError::from(e).at_block(block.block_identifier.index));
                 }
             }
         }
 
-        if let Some(post_processor_tx) = post_processor {
-            let _ = post_processor_tx.send(block.clone());
-        }
+        *chain_tip = Some(block.block_identifier.clone());
+
+        broadcast_block(&block, post_processors);
         updated_blocks.push(block);
     }
-    updated_blocks
+    Ok(updated_blocks)
+}
+
+/// Heights of the indexed blocks orphaned by `block`, highest first, along with the height
+/// of the indexed tip. Walking down from the tip, every block indexed at or above the height
+/// of `block` is orphaned, down to its parent's height: the block indexed there is kept when
+/// it is the parent of `block`, or when its hash is unknown.
+fn find_orphaned_block_heights(
+    block: &BitcoinBlockData,
+    blocks_db: &DB,
+) -> Option<(u64, Vec<u64>)> {
+    let (tip_height, _) = find_last_block_hash(blocks_db)?;
+    let block_height = block.block_identifier.index;
+    // Block already indexed, e.g. replayed after a crash
+    if find_block_hash_at_block_height(block_height, blocks_db).as_ref()
+        == Some(&block.block_identifier.hash)
+    {
+        return None;
+    }
+    let mut orphaned_heights = vec![];
+    for height in (block.parent_block_identifier.index..=tip_height).rev() {
+        match find_block_hash_at_block_height(height, blocks_db) {
+            Some(hash) if height == block.parent_block_identifier.index => {
+                if hash != block.parent_block_identifier.hash {
+                    orphaned_heights.push(height);
+                }
+            }
+            Some(_) => orphaned_heights.push(height),
+            None => {}
+        }
+    }
+    if orphaned_heights.is_empty() {
+        return None;
+    }
+    Some((tip_height, orphaned_heights))
+}
+
+/// Contents fetched from the IPFS gateway, by inscription id: the inscribed CID and the
+/// content it references.
+pub type IpfsContents = HashMap<String, (String, Vec<u8>)>;
+
+/// Fetches the IPFS contents referenced by the inscriptions revealed in `block`, when
+/// `storage.resolve_ipfs` is enabled. The CIDs of a block are fetched concurrently, within
+/// `IPFS_BLOCK_DEADLINE`. Inscriptions whose content can't be fetched in time are left out,
+/// their inscribed CID is stored instead.
+pub fn resolve_ipfs_contents_in_block(
+    block: &BitcoinBlockData,
+    ordhook_config: &OrdhookConfig,
+    ctx: &Context,
+) -> IpfsContents {
+    let mut ipfs_contents = HashMap::new();
+    if !ordhook_config.store_inscription_content || !ordhook_config.resolve_ipfs {
+        return ipfs_contents;
+    }
+    if let Some(start_block) = ordhook_config.start_block {
+        if block.block_identifier.index < start_block {
+            return ipfs_contents;
+        }
+    }
+    let mut inscription_ids = vec![];
+    let mut cids = vec![];
+    for inscription in get_inscriptions_revealed_in_block(block, None).iter() {
+        let Ok(content) = hex::decode(inscription.content_bytes.trim_start_matches("0x")) else {
+            continue;
+        };
+        let Some(cid) = parse_ipfs_cid(&content) else {
+            continue;
+        };
+        inscription_ids.push(inscription.inscription_id.clone());
+        cids.push(cid);
+    }
+    if cids.is_empty() {
+        return ipfs_contents;
+    }
+    let max_bytes = ordhook_config.max_stored_content_bytes.unwrap_or(u64::MAX);
+    let resolved_contents = resolve_ipfs_contents(&cids, max_bytes);
+    for ((inscription_id, cid), resolved_content) in
+        inscription_ids.into_iter().zip(cids).zip(resolved_contents)
+    {
+        match resolved_content {
+            Ok(resolved_content) => {
+                ipfs_contents.insert(inscription_id, (cid, resolved_content));
+            }
+            Err(e) => ctx.try_log(|logger| {
+                warn!(
+                    logger,
+                    "Unable to resolve the IPFS content of inscription {inscription_id}: {e}"
+                )
+            }),
+        }
+    }
+    ipfs_contents
 }
 
 pub fn process_block(
@@ -271,11 +741,23 @@ pub fn process_block(
     next_blocks: &Vec<BitcoinBlockData>,
     sequence_cursor: &mut SequenceCursor,
     cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
-    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    cache_l2: &Arc<TraversalsCache>,
     inscriptions_db_tx: &Transaction,
+    ipfs_contents: &IpfsContents,
     ordhook_config: &OrdhookConfig,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), ProcessingError> {
+    // Below `start_block`, only the transfers of the inscriptions already indexed are tracked.
+    if let Some(start_block) = ordhook_config.start_block {
+        if block.block_identifier.index < start_block {
+            for tx in block.transactions.iter_mut() {
+                tx.metadata
+                    .ordinal_operations
+                    .retain(|op| !matches!(op, OrdinalOperation::InscriptionRevealed(_)));
+            }
+        }
+    }
+
     let any_processable_transactions = parallelize_inscription_data_computations(
         &block,
         &next_blocks,
@@ -292,19 +774,479 @@ pub fn process_block(
         Context::empty()
     };
 
+    let write_started_at = Instant::now();
+
     // Handle inscriptions
     if any_processable_transactions {
-        let _ = augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
+        augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
             block,
             sequence_cursor,
             cache_l1,
             &inscriptions_db_tx,
             &inner_ctx,
-        );
+        )?;
     }
 
     // Handle transfers
-    let _ = augment_block_with_ordinals_transfer_data(block, inscriptions_db_tx, true, &inner_ctx);
+    augment_block_with_ordinals_transfer_data(block, inscriptions_db_tx, true, &inner_ctx)?;
+
+    let inscriptions_revealed = get_inscriptions_revealed_in_block(block, None);
+
+    // Handle inscriptions content
+    if ordhook_config.store_inscription_content {
+        for inscription in inscriptions_revealed.iter() {
+            let Ok(mut content) = hex::decode(inscription.content_bytes.trim_start_matches("0x"))
+            else {
+                continue;
+            };
+            let mut content_source = ContentSource::Inline;
+            if let Some((cid, resolved_content)) = ipfs_contents.get(&inscription.inscription_id) {
+                content = resolved_content.clone();
+                content_source = ContentSource::Ipfs(cid.clone());
+            }
+            if let Some(max_bytes) = ordhook_config.max_stored_content_bytes {
+                if content.len() as u64 > max_bytes {
+                    inner_ctx.try_log(|logger| {
+                        warn!(
+                            logger,
+                            "Content of inscription {} not stored: {} bytes over the {} bytes limit",
+                            inscription.inscription_id,
+                            content.len(),
+                            max_bytes
+                        )
+                    });
+                    continue;
+                }
+            }
+            store_inscription_content(
+                &inscription.inscription_id,
+                &content,
+                &content_source,
+                ordhook_config.compress_content,
+                inscriptions_db_tx,
+                &inner_ctx,
+            )?;
+        }
+    }
+
+    // Handle BRC-20 operations
+    for inscription in inscriptions_revealed.iter() {
+        if let Some(operation) = parse_brc20_operation(inscription) {
+            insert_entry_in_brc20_operations(
+                &inscription.inscription_id,
+                block.block_identifier.index,
+                &operation,
+                inscriptions_db_tx,
+                &inner_ctx,
+            )?;
+        }
+    }
+
+    metrics::DB_WRITE_LATENCY.observe(write_started_at.elapsed().as_secs_f64());
+    metrics::BLOCKS_PROCESSED.inc();
+    metrics::INSCRIPTIONS_INDEXED.inc_by(inscriptions_revealed.len() as u64);
+    metrics::TRANSFERS_INDEXED.inc_by(get_inscriptions_transferred_in_block(block).len() as u64);
+    metrics::L2_CACHE_SIZE.set(cache_l2.len() as i64);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chainhook_sdk::{
+        indexer::bitcoin::{parse_downloaded_block, standardize_bitcoin_block},
+        types::BitcoinNetwork,
+    };
+
+    use super::*;
+    use crate::{
+        core::{
+            checkpoint::read_checkpoint,
+            protocol::inscription_parsing::parse_inscriptions_from_witness,
+        },
+        db::{
+            find_last_block_inserted, format_outpoint_to_watch, get_default_ordhook_db_file_path,
+            initialize_ordhook_db, insert_entry_in_blocks, insert_ordinal_transfer_in_locations_tx,
+            BlockBytesCursor, OrdinalLocation,
+        },
+        utils::test_dir::TestWorkingDir,
+    };
+
+    /// Every row of every table of hord.sqlite, by table.
+    fn snapshot_rows(conn: &Connection) -> BTreeMap<String, BTreeSet<String>> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let tables = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut snapshot = BTreeMap::new();
+        for table in tables {
+            let mut stmt = conn.prepare(&format!("SELECT * FROM {table}")).unwrap();
+            let column_count = stmt.column_count();
+            let rows = stmt
+                .query_map([], |row| {
+                    let values = (0..column_count)
+                        .map(|i| row.get_ref(i).map(|value| format!("{:?}", value)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(values.join(", "))
+                })
+                .unwrap()
+                .collect::<Result<BTreeSet<_>, _>>()
+                .unwrap();
+            snapshot.insert(table, rows);
+        }
+        snapshot
+    }
+
+    fn describe_changes(
+        before: &BTreeMap<String, BTreeSet<String>>,
+        after: &BTreeMap<String, BTreeSet<String>>,
+    ) -> Vec<String> {
+        let empty = BTreeSet::new();
+        let mut changes = vec![];
+        for (table, rows) in after.iter() {
+            let previous_rows = before.get(table).unwrap_or(&empty);
+            for row in previous_rows.difference(rows) {
+                changes.push(format!("{table}: - ({row})"));
+            }
+            for row in rows.difference(previous_rows) {
+                changes.push(format!("{table}: + ({row})"));
+            }
+        }
+        changes
+    }
+
+    /// Run `process_block` the way `process_blocks` does when activities are already present:
+    /// the transaction is always rolled back. Returns whether hord.sqlite was left untouched,
+    /// along with the rows that would have been written (`-` / `+`, an update being both).
+    fn test_process_block_rollback(
+        block: BitcoinBlockData,
+        conn: &mut Connection,
+        ctx: &Context,
+    ) -> (bool, Vec<String>) {
+        let ordhook_config = Config::mainnet_default().get_ordhook_config();
+        let cache_l2 = Arc::new(new_traversals_lazy_cache(
+            ordhook_config.resources.traversal_cache_size,
+            &ordhook_config.resources.cache_eviction_policy,
+        ));
+        let mut block = block;
+        let before = snapshot_rows(conn);
+
+        let inscriptions_db_tx = conn.transaction().unwrap();
+        let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_tx, ordhook_config.chain);
+        let _ = process_block(
+            &mut block,
+            &vec![],
+            &mut sequence_cursor,
+            &mut BTreeMap::new(),
+            &cache_l2,
+            &inscriptions_db_tx,
+            &HashMap::new(),
+            &ordhook_config,
+            ctx,
+        );
+        let changes = describe_changes(&before, &snapshot_rows(&inscriptions_db_tx));
+        inscriptions_db_tx.rollback().unwrap();
+
+        (snapshot_rows(conn) == before, changes)
+    }
+
+    #[test]
+    fn test_rolled_back_transfers_leave_no_rows() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut conn = initialize_ordhook_db(&working_dir, true, &ctx);
+
+        let block = include_str!("../../../db/fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+
+        // Inscription sitting on the first sat spent by the first transaction of the block
+        let spent_output = &block.transactions[1].metadata.inputs[0].previous_output;
+        let genesis_tx_id = spent_output.txid.get_hash_bytes_str().to_string();
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index, genesis_tx_id) VALUES (?1, 5000, 0, 0, 279000, 0, ?2)",
+            rusqlite::params![format!("{genesis_tx_id}i0"), &genesis_tx_id],
+        )
+        .unwrap();
+        insert_ordinal_transfer_in_locations_tx(
+            5000,
+            &format_outpoint_to_watch(&spent_output.txid, spent_output.vout as usize),
+            OrdinalLocation {
+                offset: 0,
+                block_height: 279000,
+                tx_index: 0,
+                is_burned: false,
+                output_value: Some(spent_output.value),
+                address: None,
+            },
+            &conn,
+            &ctx,
+        )
+        .unwrap();
+
+        let (untouched, changes) = test_process_block_rollback(block, &mut conn, &ctx);
+        assert!(untouched, "rows left behind by the rollback");
+        assert!(changes
+            .iter()
+            .any(|change| change.starts_with("locations: +")));
+    }
+
+    #[test]
+    fn test_competing_blocks_roll_back_orphaned_blocks() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut config = Config::mainnet_default();
+        config.storage.working_dir = working_dir.display().to_string();
+        let mut ordhook_config = config.get_ordhook_config();
+        let mut conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        let blocks_db =
+            open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &config.resources, &ctx);
+        let cache_l2 = Arc::new(new_traversals_lazy_cache(
+            ordhook_config.resources.traversal_cache_size,
+            &ordhook_config.resources.cache_eviction_policy,
+        ));
+        let (events_tx, _events_rx) = crossbeam_channel::unbounded();
+        let hot_cache = InscriptionsHotCache::new(10);
+        let hash = |byte: u8| format!("0x{}", hex::encode([byte; 32]));
+
+        let block = include_str!("../../../db/fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+
+        // Indexed chain: the parent of the block, then a stale block at its height and a child
+        let parent = &block.parent_block_identifier;
+        insert_block_hash(parent.index, &parent.hash, &blocks_db, &ctx);
+        insert_block_hash(279671, &hash(0xaa), &blocks_db, &ctx);
+        insert_block_hash(279672, &hash(0xbb), &blocks_db, &ctx);
+        for (index, block_height) in [279670u64, 279671, 279672].iter().enumerate() {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?2, ?2, ?3, 0)",
+                rusqlite::params![format!("{:064x}i0", index), index as u64, block_height],
+            )
+            .unwrap();
+        }
+        let inscriptions_at = |conn: &Connection, block_height: u64| -> u64 {
+            conn.query_row(
+                "SELECT COUNT(*) FROM inscriptions WHERE block_height = ?1",
+                rusqlite::params![block_height],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        let process =
+            |block: &BitcoinBlockData, ordhook_config: &OrdhookConfig, conn: &mut Connection| {
+                process_blocks(
+                    &mut vec![block.clone()],
+                    &mut None,
+                    &cache_l2,
+                    conn,
+                    &blocks_db,
+                    ordhook_config,
+                    &[],
+                    Some(&hot_cache),
+                    &events_tx,
+                    &ctx,
+                )
+            };
+
+        // Reverting 2 blocks exceeds the limit: nothing is touched
+        ordhook_config.max_rollback_depth = 1;
+        assert!(matches!(
+            process(&block, &ordhook_config, &mut conn),
+            Err(ProcessingError::RollbackTooDeep { depth: 2, .. })
+        ));
+        assert_eq!(inscriptions_at(&conn, 279671), 1);
+        assert_eq!(
+            find_block_hash_at_block_height(279671, &blocks_db),
+            Some(hash(0xaa))
+        );
+
+        // A failing rollback aborts the batch, leaving every orphaned block in place
+        ordhook_config.max_rollback_depth = 6;
+        conn.execute(
+            "ALTER TABLE brc20_operations RENAME TO brc20_operations_moved",
+            [],
+        )
+        .unwrap();
+        assert!(matches!(
+            process(&block, &ordhook_config, &mut conn),
+            Err(ProcessingError::BlockError { .. })
+        ));
+        assert_eq!(inscriptions_at(&conn, 279671), 1);
+        assert_eq!(inscriptions_at(&conn, 279672), 1);
+        assert_eq!(
+            find_block_hash_at_block_height(279672, &blocks_db),
+            Some(hash(0xbb))
+        );
+        conn.execute(
+            "ALTER TABLE brc20_operations_moved RENAME TO brc20_operations",
+            [],
+        )
+        .unwrap();
+        hot_cache.insert(
+            format!("{:064x}i0", 1),
+            serde_json::json!({ "block_height": 279671 }),
+        );
+
+        process(&block, &ordhook_config, &mut conn).unwrap();
+        assert!(hot_cache.is_empty());
+        assert_eq!(inscriptions_at(&conn, 279670), 1);
+        assert_eq!(inscriptions_at(&conn, 279671), 0);
+        assert_eq!(inscriptions_at(&conn, 279672), 0);
+        assert_eq!(
+            find_last_block_hash(&blocks_db),
+            Some((279671, block.block_identifier.hash.clone()))
+        );
+
+        // Replaying the indexed block is not a re-org
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, 3, 3, 3, 279671, 0)",
+            rusqlite::params![format!("{:064x}i0", 3)],
+        )
+        .unwrap();
+        process(&block, &ordhook_config, &mut conn).unwrap();
+        assert_eq!(inscriptions_at(&conn, 279671), 1);
+
+        // Competing child: built on another block than the one indexed at 279671
+        let mut child = block.clone();
+        child.block_identifier = BlockIdentifier {
+            index: 279672,
+            hash: hash(0xdd),
+        };
+        child.parent_block_identifier = BlockIdentifier {
+            index: 279671,
+            hash: hash(0xcc),
+        };
+        process(&child, &ordhook_config, &mut conn).unwrap();
+        assert_eq!(inscriptions_at(&conn, 279670), 1);
+        assert_eq!(inscriptions_at(&conn, 279671), 0);
+        assert_eq!(find_block_hash_at_block_height(279671, &blocks_db), None);
+        assert_eq!(find_last_block_hash(&blocks_db), Some((279672, hash(0xdd))));
+
+        drop(blocks_db);
+    }
+
+    #[test]
+    fn test_failing_block_is_not_committed() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut config = Config::mainnet_default();
+        config.storage.working_dir = working_dir.display().to_string();
+        let ordhook_config = config.get_ordhook_config();
+        let mut conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        let blocks_db =
+            open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &config.resources, &ctx);
+        let cache_l2 = Arc::new(new_traversals_lazy_cache(
+            ordhook_config.resources.traversal_cache_size,
+            &ordhook_config.resources.cache_eviction_policy,
+        ));
+        let (events_tx, _events_rx) = crossbeam_channel::unbounded();
+
+        let block = include_str!("../../../db/fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block_bytes = BlockBytesCursor::from_full_block(&block).unwrap();
+        insert_entry_in_blocks(279671, &block_bytes, true, &blocks_db, &ctx);
+        blocks_db.flush().unwrap();
+        let mut block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+
+        // Inscription revealed by a transaction missing from the archived block: its traversal fails
+        let txid = "ab".repeat(32);
+        let tapscript = hex::decode("0063036f726401010a746578742f706c61696e0002686968").unwrap();
+        let reveal = parse_inscriptions_from_witness(0, vec![tapscript, vec![0xc0]], &txid, false)
+            .unwrap()
+            .remove(0);
+        let mut tx = block.transactions[1].clone();
+        tx.transaction_identifier = TransactionIdentifier::new(&txid);
+        tx.metadata.ordinal_operations = vec![OrdinalOperation::InscriptionRevealed(reveal)];
+        block.transactions.push(tx);
+
+        let result = process_blocks(
+            &mut vec![block],
+            &mut None,
+            &cache_l2,
+            &mut conn,
+            &blocks_db,
+            &ordhook_config,
+            &[],
+            None,
+            &events_tx,
+            &ctx,
+        );
+        assert!(matches!(
+            result,
+            Err(ProcessingError::BlockError { block_height: 279671, ref error })
+                if matches!(**error, ProcessingError::TraversalFailed { .. })
+        ));
+        assert_eq!(find_block_hash_at_block_height(279671, &blocks_db), None);
+        assert_eq!(read_checkpoint(&working_dir).unwrap(), None);
+        let inscriptions: u64 = conn
+            .query_row("SELECT COUNT(*) FROM inscriptions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(inscriptions, 0);
+
+        drop(blocks_db);
+    }
+
+    #[test]
+    fn test_pause_snapshot_resume_roundtrip() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let output_dir = working_dir.join("snapshot");
+        let mut config = Config::mainnet_default();
+        config.storage.working_dir = working_dir.display().to_string();
+        let _ = initialize_ordhook_db(&working_dir, true, &ctx);
+
+        let block = include_str!("../../../db/fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block_bytes = BlockBytesCursor::from_full_block(&block).unwrap();
+
+        let controller = start_inscription_indexing_processor(&config, &ctx, vec![], None);
+        controller
+            .commands_tx
+            .send(PostProcessorCommand::ProcessBlocks(
+                vec![(279671, block_bytes.clone())],
+                vec![],
+            ))
+            .unwrap();
+
+        let paused_processor = controller.pause().unwrap();
+        // Enqueued while paused: left out of the snapshot, processed once resumed
+        controller
+            .commands_tx
+            .send(PostProcessorCommand::ProcessBlocks(
+                vec![(279672, block_bytes)],
+                vec![],
+            ))
+            .unwrap();
+        let size = paused_processor.snapshot(&output_dir).unwrap();
+        assert!(size > 0);
+        assert!(get_default_ordhook_db_file_path(&output_dir).exists());
+        let snapshot_blocks_db =
+            open_ordhook_db_conn_rocks_db_loop(false, &output_dir, &config.resources, &ctx);
+        assert_eq!(find_last_block_inserted(&snapshot_blocks_db), 279671);
+        drop(snapshot_blocks_db);
+
+        paused_processor.resume();
+        controller
+            .commands_tx
+            .send(PostProcessorCommand::Drain)
+            .unwrap();
+        assert!(controller
+            .wait_until_synced(Duration::from_secs(30))
+            .is_ok());
+        controller.thread_handle.join().unwrap();
+
+        let blocks_db =
+            open_ordhook_db_conn_rocks_db_loop(false, &working_dir, &config.resources, &ctx);
+        assert_eq!(find_last_block_inserted(&blocks_db), 279672);
+
+        drop(blocks_db);
+    }
+}
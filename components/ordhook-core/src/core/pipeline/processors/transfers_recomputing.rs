@@ -5,10 +5,12 @@ use std::{
 
 use chainhook_sdk::{types::BitcoinBlockData, utils::Context};
 use crossbeam_channel::{Sender, TryRecvError};
+use rusqlite::Transaction;
 
 use crate::{
     config::Config,
     core::{
+        error::ProcessingError,
         pipeline::{PostProcessorCommand, PostProcessorController, PostProcessorEvent},
         protocol::{
             inscription_sequencing::consolidate_block_with_pre_computed_ordinals_data,
@@ -33,9 +35,14 @@ pub fn start_transfers_recomputing_processor(
     let ctx = ctx.clone();
     let handle: JoinHandle<()> = hiro_system_kit::thread_named("Inscription indexing runloop")
         .spawn(move || {
-            let mut inscriptions_db_conn_rw =
-                open_readwrite_ordhook_db_conn(&config.expected_cache_path(), &ctx).unwrap();
+            let mut inscriptions_db_conn_rw = open_readwrite_ordhook_db_conn(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                &ctx,
+            )
+            .unwrap();
             let mut empty_cycles = 0;
+            let mut draining = false;
 
             loop {
                 let mut blocks = match commands_rx.try_recv() {
@@ -47,8 +54,20 @@ pub fn start_transfers_recomputing_processor(
                         let _ = events_tx.send(PostProcessorEvent::Terminated);
                         break;
                     }
+                    Ok(PostProcessorCommand::SetTargetHeight(_))
+                    | Ok(PostProcessorCommand::SetChainTip(_)) => continue,
+                    // Only the inscription indexing processor can be paused
+                    Ok(PostProcessorCommand::Pause(_)) => continue,
+                    Ok(PostProcessorCommand::Drain) => {
+                        draining = true;
+                        continue;
+                    }
                     Err(e) => match e {
                         TryRecvError::Empty => {
+                            if draining {
+                                let _ = events_tx.send(PostProcessorEvent::Drained);
+                                break;
+                            }
                             empty_cycles += 1;
                             if empty_cycles == 10 {
                                 ctx.try_log(|logger| {
@@ -69,33 +88,23 @@ pub fn start_transfers_recomputing_processor(
                 ctx.try_log(|logger| info!(logger, "Processing {} blocks", blocks.len()));
                 let inscriptions_db_tx = inscriptions_db_conn_rw.transaction().unwrap();
 
+                let mut failure = None;
                 for block in blocks.iter_mut() {
-                    consolidate_block_with_pre_computed_ordinals_data(
-                        block,
-                        &inscriptions_db_tx,
-                        false,
-                        &ctx,
-                    );
-
-                    remove_entries_from_locations_at_block_height(
-                        &block.block_identifier.index,
-                        &inscriptions_db_tx,
-                        &ctx,
-                    );
-
-                    insert_entries_from_block_in_inscriptions(block, &inscriptions_db_tx, &ctx);
-
-                    augment_block_with_ordinals_transfer_data(
-                        block,
-                        &inscriptions_db_tx,
-                        true,
-                        &ctx,
-                    );
+                    if let Err(e) = recompute_transfers_in_block(block, &inscriptions_db_tx, &ctx) {
+                        failure = Some(e.at_block(block.block_identifier.index));
+                        break;
+                    }
 
                     if let Some(ref post_processor) = post_processor {
                         let _ = post_processor.send(block.clone());
                     }
                 }
+                if let Some(e) = failure {
+                    // Dropping the transaction discards the changes of the whole batch
+                    ctx.try_log(|logger| error!(logger, "Unable to recompute transfers: {e}"));
+                    let _ = events_tx.send(PostProcessorEvent::Aborted(e));
+                    break;
+                }
                 let _ = inscriptions_db_tx.commit();
             }
         })
@@ -107,3 +116,19 @@ pub fn start_transfers_recomputing_processor(
         thread_handle: handle,
     }
 }
+
+fn recompute_transfers_in_block(
+    block: &mut BitcoinBlockData,
+    inscriptions_db_tx: &Transaction,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    consolidate_block_with_pre_computed_ordinals_data(block, inscriptions_db_tx, false, ctx);
+    remove_entries_from_locations_at_block_height(
+        &block.block_identifier.index,
+        inscriptions_db_tx,
+        ctx,
+    )?;
+    insert_entries_from_block_in_inscriptions(block, inscriptions_db_tx, ctx)?;
+    augment_block_with_ordinals_transfer_data(block, inscriptions_db_tx, true, ctx)?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use chainhook_sdk::types::OrdinalInscriptionRevealData;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Brc20Operation {
+    Deploy {
+        tick: String,
+        max: String,
+        lim: Option<String>,
+        dec: Option<String>,
+    },
+    Mint {
+        tick: String,
+        amt: String,
+    },
+    Transfer {
+        tick: String,
+        amt: String,
+    },
+}
+
+impl Brc20Operation {
+    pub fn get_operation_type(&self) -> &'static str {
+        match self {
+            Brc20Operation::Deploy { .. } => "deploy",
+            Brc20Operation::Mint { .. } => "mint",
+            Brc20Operation::Transfer { .. } => "transfer",
+        }
+    }
+
+    pub fn get_tick(&self) -> &str {
+        match self {
+            Brc20Operation::Deploy { tick, .. }
+            | Brc20Operation::Mint { tick, .. }
+            | Brc20Operation::Transfer { tick, .. } => tick,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Brc20Payload {
+    p: String,
+    op: String,
+    tick: String,
+    max: Option<String>,
+    lim: Option<String>,
+    dec: Option<String>,
+    amt: Option<String>,
+}
+
+/// Decode the BRC-20 operation carried by an inscription, if any. Ticks are case insensitive
+/// and returned lowercased; amounts are kept as the decimal strings inscribed.
+pub fn parse_brc20_operation(inscription: &OrdinalInscriptionRevealData) -> Option<Brc20Operation> {
+    let mime_type = inscription.content_type.split(';').next()?.trim();
+    if mime_type != "text/plain" && mime_type != "application/json" {
+        return None;
+    }
+    let content = hex::decode(inscription.content_bytes.trim_start_matches("0x")).ok()?;
+    let payload: Brc20Payload = serde_json::from_slice(&content).ok()?;
+    if payload.p != "brc-20" {
+        return None;
+    }
+    let tick = payload.tick.to_lowercase();
+    if tick.as_bytes().len() != 4 {
+        return None;
+    }
+    match payload.op.as_str() {
+        "deploy" => Some(Brc20Operation::Deploy {
+            tick,
+            max: payload.max?,
+            lim: payload.lim,
+            dec: payload.dec,
+        }),
+        "mint" => Some(Brc20Operation::Mint {
+            tick,
+            amt: payload.amt?,
+        }),
+        "transfer" => Some(Brc20Operation::Transfer {
+            tick,
+            amt: payload.amt?,
+        }),
+        _ => None,
+    }
+}
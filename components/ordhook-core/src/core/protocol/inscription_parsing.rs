@@ -14,46 +14,100 @@ use std::str::FromStr;
 use crate::ord::envelope::{Envelope, ParsedEnvelope, RawEnvelope};
 use crate::ord::inscription::Inscription;
 use crate::ord::inscription_id::InscriptionId;
-use {chainhook_sdk::bitcoincore_rpc::bitcoin::Witness, std::str};
+use {
+    chainhook_sdk::bitcoincore_rpc::bitcoin::{Script, Witness},
+    std::str,
+};
+
+const TAPROOT_ANNEX_PREFIX: u8 = 0x50;
+
+/// Envelopes violating the protocol rules (malformed fields, unusual placement in the transaction,
+/// etc.) are cursed. Reinscriptions are cursed as well, but can only be detected while sequencing.
+pub fn get_curse_type(envelope: &Envelope<Inscription>) -> Option<OrdinalInscriptionCurseType> {
+    if envelope.payload.unrecognized_even_field {
+        Some(OrdinalInscriptionCurseType::UnrecognizedEvenField)
+    } else if envelope.payload.duplicate_field {
+        Some(OrdinalInscriptionCurseType::DuplicateField)
+    } else if envelope.payload.incomplete_field {
+        Some(OrdinalInscriptionCurseType::IncompleteField)
+    } else if envelope.input != 0 {
+        Some(OrdinalInscriptionCurseType::NotInFirstInput)
+    } else if envelope.offset != 0 {
+        Some(OrdinalInscriptionCurseType::NotAtOffsetZero)
+    } else if envelope.payload.pointer.is_some() {
+        Some(OrdinalInscriptionCurseType::Pointer)
+    } else if envelope.pushnum {
+        Some(OrdinalInscriptionCurseType::Pushnum)
+    } else if envelope.stutter {
+        Some(OrdinalInscriptionCurseType::Stutter)
+    } else {
+        None
+    }
+}
+
+pub fn is_cursed_inscription(envelope: &Envelope<Inscription>) -> bool {
+    get_curse_type(envelope).is_some()
+}
+
+/// BIP341: with at least two witness elements, a last element starting with 0x50 is the annex.
+fn get_annex(witness: &Witness) -> Option<&[u8]> {
+    if witness.len() < 2 {
+        return None;
+    }
+    witness
+        .last()
+        .filter(|element| element.first() == Some(&TAPROOT_ANNEX_PREFIX))
+}
+
+fn parse_envelopes_from_witness(
+    input_index: usize,
+    witness_bytes: Vec<Vec<u8>>,
+    index_annex_envelopes: bool,
+) -> Option<Vec<Envelope<Inscription>>> {
+    let witness = Witness::from_slice(&witness_bytes);
+    let tapscript = witness.tapscript();
+    let annex = get_annex(&witness).filter(|_| index_annex_envelopes);
+    if tapscript.is_none() && annex.is_none() {
+        return None;
+    }
+    let mut raw_envelopes = vec![];
+    if let Some(tapscript) = tapscript {
+        raw_envelopes.append(&mut RawEnvelope::from_tapscript(tapscript, input_index).ok()?);
+    }
+    // Envelopes carried in the annex are serialized like in a tapscript, after the 0x50 tag.
+    if let Some(annex) = annex {
+        if let Ok(mut annex_envelopes) =
+            RawEnvelope::from_tapscript(Script::from_bytes(&annex[1..]), input_index)
+        {
+            raw_envelopes.append(&mut annex_envelopes);
+        }
+    }
+    Some(
+        raw_envelopes
+            .into_iter()
+            .map(|e| ParsedEnvelope::from(e))
+            .collect(),
+    )
+}
 
+/// Envelopes carried in the annex are only parsed with `index_annex_envelopes`: ord ignores
+/// annexes, numbering them would make the inscription numbers diverge from ord.
 pub fn parse_inscriptions_from_witness(
     input_index: usize,
     witness_bytes: Vec<Vec<u8>>,
     txid: &str,
+    index_annex_envelopes: bool,
 ) -> Option<Vec<OrdinalInscriptionRevealData>> {
     // Efficient debugging: Isolate one specific transaction
     // if !txid.eq("aa2ab56587c7d6609c95157e6dff37c5c3fa6531702f41229a289a5613887077") {
     //     return None
     // }
 
-    let witness = Witness::from_slice(&witness_bytes);
-    let tapscript = witness.tapscript()?;
-    let envelopes: Vec<Envelope<Inscription>> = RawEnvelope::from_tapscript(tapscript, input_index)
-        .ok()?
-        .into_iter()
-        .map(|e| ParsedEnvelope::from(e))
-        .collect();
+    let envelopes =
+        parse_envelopes_from_witness(input_index, witness_bytes, index_annex_envelopes)?;
     let mut inscriptions = vec![];
     for envelope in envelopes.into_iter() {
-        let curse_type = if envelope.payload.unrecognized_even_field {
-            Some(OrdinalInscriptionCurseType::UnrecognizedEvenField)
-        } else if envelope.payload.duplicate_field {
-            Some(OrdinalInscriptionCurseType::DuplicateField)
-        } else if envelope.payload.incomplete_field {
-            Some(OrdinalInscriptionCurseType::IncompleteField)
-        } else if envelope.input != 0 {
-            Some(OrdinalInscriptionCurseType::NotInFirstInput)
-        } else if envelope.offset != 0 {
-            Some(OrdinalInscriptionCurseType::NotAtOffsetZero)
-        } else if envelope.payload.pointer.is_some() {
-            Some(OrdinalInscriptionCurseType::Pointer)
-        } else if envelope.pushnum {
-            Some(OrdinalInscriptionCurseType::Pushnum)
-        } else if envelope.stutter {
-            Some(OrdinalInscriptionCurseType::Stutter)
-        } else {
-            None
-        };
+        let curse_type = get_curse_type(&envelope);
 
         let inscription_id = InscriptionId {
             txid: Txid::from_str(txid).unwrap(),
@@ -110,6 +164,7 @@ pub fn parse_inscriptions_from_witness(
 
 pub fn parse_inscriptions_from_standardized_tx(
     tx: &BitcoinTransactionData,
+    index_annex_envelopes: bool,
     _ctx: &Context,
 ) -> Vec<OrdinalOperation> {
     let mut operations = vec![];
@@ -124,6 +179,7 @@ pub fn parse_inscriptions_from_standardized_tx(
             input_index,
             witness_bytes,
             tx.transaction_identifier.get_hash_bytes_str(),
+            index_annex_envelopes,
         ) {
             for inscription in inscriptions.into_iter() {
                 operations.push(OrdinalOperation::InscriptionRevealed(inscription));
@@ -135,6 +191,7 @@ pub fn parse_inscriptions_from_standardized_tx(
 
 pub fn parse_inscriptions_in_raw_tx(
     tx: &BitcoinTransactionFullBreakdown,
+    index_annex_envelopes: bool,
     _ctx: &Context,
 ) -> Vec<OrdinalOperation> {
     let mut operations = vec![];
@@ -145,9 +202,12 @@ pub fn parse_inscriptions_in_raw_tx(
                 .map(|w| hex::decode(w).unwrap())
                 .collect();
 
-            if let Some(inscriptions) =
-                parse_inscriptions_from_witness(input_index, witness_bytes, &tx.txid)
-            {
+            if let Some(inscriptions) = parse_inscriptions_from_witness(
+                input_index,
+                witness_bytes,
+                &tx.txid,
+                index_annex_envelopes,
+            ) {
                 for inscription in inscriptions.into_iter() {
                     println!("Parsing inscriptions for txid: {} inscription:{:?}", tx.txid, inscription.clone());
                     operations.push(OrdinalOperation::InscriptionRevealed(inscription));
@@ -178,12 +238,16 @@ pub fn parse_inscriptions_in_raw_tx(
 pub fn parse_inscriptions_and_standardize_block(
     raw_block: BitcoinBlockFullBreakdown,
     network: &BitcoinNetwork,
+    index_annex_envelopes: bool,
     ctx: &Context,
 ) -> Result<BitcoinBlockData, (String, bool)> {
     let mut ordinal_operations = BTreeMap::new();
 
     for tx in raw_block.tx.iter() {
-        ordinal_operations.insert(tx.txid.to_string(), parse_inscriptions_in_raw_tx(&tx, ctx));
+        ordinal_operations.insert(
+            tx.txid.to_string(),
+            parse_inscriptions_in_raw_tx(&tx, index_annex_envelopes, ctx),
+        );
     }
 
     let mut block = standardize_bitcoin_block(raw_block, network, ctx)?;
@@ -198,19 +262,72 @@ pub fn parse_inscriptions_and_standardize_block(
     Ok(block)
 }
 
-pub fn parse_inscriptions_in_standardized_block(block: &mut BitcoinBlockData, ctx: &Context) {
+pub fn parse_inscriptions_in_standardized_block(
+    block: &mut BitcoinBlockData,
+    index_annex_envelopes: bool,
+    ctx: &Context,
+) {
     for tx in block.transactions.iter_mut() {
-        tx.metadata.ordinal_operations = parse_inscriptions_from_standardized_tx(tx, ctx);
+        tx.metadata.ordinal_operations =
+            parse_inscriptions_from_standardized_tx(tx, index_annex_envelopes, ctx);
     }
 }
 
-pub fn get_inscriptions_revealed_in_block(
-    block: &BitcoinBlockData,
-) -> Vec<&OrdinalInscriptionRevealData> {
+/// Raw CBOR metadata carried by the envelope of an inscription revealed by `tx`. The reveal data
+/// only holds the metadata decoded to JSON, which loses byte strings and tags, so the envelope
+/// is read again from the witness of the inscription input.
+pub fn get_inscription_metadata_bytes(
+    tx: &BitcoinTransactionData,
+    inscription_data: &OrdinalInscriptionRevealData,
+) -> Option<Vec<u8>> {
+    inscription_data.metadata.as_ref()?;
+    let input_index = inscription_data.inscription_input_index;
+    // Rank of the envelope among the ones revealed by its input. Annex envelopes follow the
+    // tapscript ones, so parsing them as well doesn't shift the rank when they aren't indexed.
+    let rank = tx
+        .metadata
+        .ordinal_operations
+        .iter()
+        .filter_map(|op| match op {
+            OrdinalOperation::InscriptionRevealed(reveal)
+                if reveal.inscription_input_index == input_index =>
+            {
+                Some(reveal)
+            }
+            _ => None,
+        })
+        .position(|reveal| reveal.inscription_id == inscription_data.inscription_id)?;
+    let witness_bytes = tx
+        .metadata
+        .inputs
+        .get(input_index)?
+        .witness
+        .iter()
+        .map(|w| hex::decode(&w[2..]).ok())
+        .collect::<Option<Vec<_>>>()?;
+    parse_envelopes_from_witness(input_index, witness_bytes, true)?
+        .into_iter()
+        .nth(rank)?
+        .payload
+        .metadata
+}
+
+/// Retrieve the inscriptions revealed in a block, optionally restricted to a set of MIME types.
+/// Content type parameters are ignored when matching (`text/plain;charset=utf-8` matches `text/plain`).
+pub fn get_inscriptions_revealed_in_block<'a>(
+    block: &'a BitcoinBlockData,
+    content_type_filter: Option<&[&str]>,
+) -> Vec<&'a OrdinalInscriptionRevealData> {
     let mut ops = vec![];
     for tx in block.transactions.iter() {
         for op in tx.metadata.ordinal_operations.iter() {
             if let OrdinalOperation::InscriptionRevealed(op) = op {
+                if let Some(content_types) = content_type_filter {
+                    let mime_type = op.content_type.split(';').next().unwrap_or("").trim();
+                    if !content_types.contains(&mime_type) {
+                        continue;
+                    }
+                }
                 ops.push(op);
             }
         }
@@ -231,3 +348,151 @@ pub fn get_inscriptions_transferred_in_block(
     }
     ops
 }
+
+#[cfg(test)]
+mod tests {
+    use chainhook_sdk::bitcoin::{
+        absolute, consensus, opcodes,
+        script::{self, PushBytesBuf},
+        transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+    };
+
+    use super::*;
+
+    fn push_envelope(builder: script::Builder, content_type: &str, body: &[u8]) -> script::Builder {
+        push_envelope_with_fields(builder, &[(1, content_type.as_bytes())], body)
+    }
+
+    /// `OP_FALSE OP_IF "ord" (<tag> <value>)* 0 <body> OP_ENDIF`
+    fn push_envelope_with_fields(
+        mut builder: script::Builder,
+        fields: &[(u8, &[u8])],
+        body: &[u8],
+    ) -> script::Builder {
+        builder = builder
+            .push_opcode(opcodes::OP_FALSE)
+            .push_opcode(opcodes::all::OP_IF);
+        let mut pushes = vec![b"ord".to_vec()];
+        for (tag, value) in fields.iter() {
+            pushes.push(vec![*tag]);
+            pushes.push(value.to_vec());
+        }
+        pushes.push(vec![]);
+        pushes.push(body.to_vec());
+        for data in pushes {
+            let mut buf = PushBytesBuf::new();
+            buf.extend_from_slice(&data).unwrap();
+            builder = builder.push_slice(buf);
+        }
+        builder.push_opcode(opcodes::all::OP_ENDIF)
+    }
+
+    /// Reveal of a batch of 2 inscriptions laid out like `ord wallet batch` does: a single
+    /// input whose tapscript checks the key of the commit output then carries both envelopes,
+    /// the second one pointing at the second output (10,000 sats in).
+    fn batch_reveal_tx() -> Transaction {
+        let mut tapscript = script::Builder::new()
+            .push_slice([0x5a; 32])
+            .push_opcode(opcodes::all::OP_CHECKSIG);
+        tapscript = push_envelope_with_fields(
+            tapscript,
+            &[(1, b"text/plain;charset=utf-8".as_slice())],
+            b"batch #0",
+        );
+        tapscript = push_envelope_with_fields(
+            tapscript,
+            &[
+                (1, b"text/plain;charset=utf-8".as_slice()),
+                (2, 10_000u16.to_le_bytes().as_slice()),
+            ],
+            b"batch #1",
+        );
+        let mut control_block = vec![0xc0];
+        control_block.extend([0x5a; 32]);
+        let p2tr = |value: u64| TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes([vec![0x51, 0x20], vec![0xab; 32]].concat()),
+        };
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_str(
+                        "ec8f2cfb07c9f0d4b7c3e0e8a5e2f9d1a1c6b0e3f5d7c9a8b6e4f2d0c1a3b5e7",
+                    )
+                    .unwrap(),
+                    vout: 0,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_slice(&[
+                    vec![0x01; 64],
+                    tapscript.into_script().into_bytes(),
+                    control_block,
+                ]),
+            }],
+            output: vec![p2tr(10_000), p2tr(10_000)],
+        }
+    }
+
+    #[test]
+    fn parse_batch_inscriptions_from_single_input() {
+        let tx = batch_reveal_tx();
+        // Serialized and hashed like any reveal, rather than given an arbitrary txid
+        let tx = consensus::deserialize::<Transaction>(&consensus::serialize(&tx)).unwrap();
+        let txid = tx.txid().to_string();
+
+        let inscriptions =
+            parse_inscriptions_from_witness(0, tx.input[0].witness.to_vec(), &txid, false).unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        // Parsed ids only carry the input, the sequencer numbers the inscriptions of the tx
+        assert!(inscriptions
+            .iter()
+            .all(|inscription| inscription.inscription_id == format!("{txid}i0")));
+        assert_eq!(
+            inscriptions[0].content_bytes,
+            format!("0x{}", hex::encode(b"batch #0"))
+        );
+        assert_eq!(
+            inscriptions[1].content_bytes,
+            format!("0x{}", hex::encode(b"batch #1"))
+        );
+        assert_eq!(inscriptions[0].inscription_pointer, None);
+        assert_eq!(inscriptions[1].inscription_pointer, Some(10_000));
+        assert_eq!(inscriptions[0].curse_type, None);
+        assert_eq!(
+            inscriptions[1].curse_type,
+            Some(OrdinalInscriptionCurseType::NotAtOffsetZero)
+        );
+    }
+
+    #[test]
+    fn annex_envelopes_are_only_parsed_when_enabled() {
+        let tapscript = push_envelope(script::Builder::new(), "text/plain", b"tapscript");
+        let mut annex = vec![TAPROOT_ANNEX_PREFIX];
+        annex.extend(
+            push_envelope(script::Builder::new(), "text/plain", b"annex")
+                .into_script()
+                .into_bytes(),
+        );
+        let witness_bytes = vec![tapscript.into_script().into_bytes(), vec![0xc0], annex];
+        let txid = "d5bd1c8c2e0e4e2a9f0e8f6fb1d0c6c6f0b1f4f0b8f0e4f7d5c3b2a1a0f9e8d7";
+
+        // Like ord: the annex is skipped, the tapscript preceding it is still parsed
+        let inscriptions =
+            parse_inscriptions_from_witness(0, witness_bytes.clone(), txid, false).unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(
+            inscriptions[0].content_bytes,
+            format!("0x{}", hex::encode(b"tapscript"))
+        );
+
+        let inscriptions = parse_inscriptions_from_witness(0, witness_bytes, txid, true).unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        assert_eq!(
+            inscriptions[1].content_bytes,
+            format!("0x{}", hex::encode(b"annex"))
+        );
+    }
+}
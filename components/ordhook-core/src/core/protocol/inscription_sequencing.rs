@@ -1,6 +1,5 @@
 use std::{
     collections::{BTreeMap, HashMap, HashSet, VecDeque},
-    hash::BuildHasherDefault,
     sync::Arc,
 };
 
@@ -14,23 +13,26 @@ use chainhook_sdk::{
     utils::Context,
 };
 use crossbeam_channel::unbounded;
-use dashmap::DashMap;
-use fxhash::FxHasher;
 use rusqlite::{Connection, Transaction};
+use sha2::{Digest, Sha256};
 
 use crate::{
-    core::{resolve_absolute_pointer, OrdhookConfig},
+    core::{
+        error::ProcessingError, get_worker_pool, resolve_absolute_pointer, OrdhookConfig,
+        TraversalsCache,
+    },
     db::{
+        any_sequence_metadata_between, delete_sequence_state,
         find_blessed_inscription_with_ordinal_number, find_nth_classic_neg_number_at_block_height,
         find_nth_classic_pos_number_at_block_height, find_nth_jubilee_number_at_block_height,
-        format_inscription_id, update_ordinals_db_with_block, update_sequence_metadata_with_block,
-        TransactionBytesCursor, TraversalResult,
+        find_sequence_state, format_inscription_id, open_ordhook_db_conn_rocks_db_loop,
+        rollback_blocks_above_height, store_inscription_content_hash,
+        update_ordinals_db_with_block, update_sequence_metadata_with_block, update_sequence_state,
+        BlockBytesCursor, SequenceState, TraversalResult,
     },
-    ord::height::Height,
+    ord::{chain::Chain, height::Height},
 };
 
-use std::sync::mpsc::channel;
-
 use crate::db::find_all_inscriptions_in_block;
 
 use super::{
@@ -46,9 +48,9 @@ use super::{
 /// This function will:
 /// 1) Limit the number of ordinals numbers to compute by filtering out all the ordinals numbers  pre-computed
 /// and present in the L1 cache.
-/// 2) Create a threadpool, by spawning as many threads as specified by the config to process the batch ordinals to
-/// retrieve
-/// 3) Consume eventual entries in cache L1
+/// 2) Warm up the L2 cache with the transactions spent by the inscriptions, fetched in one batch
+/// 3) Dispatch the batch ordinals to retrieve to the shared work-stealing pool, sized with `resources.worker_threads`
+/// 4) Consume eventual entries in cache L1
 /// 4) Inject the ordinals to compute (random order) in a priority queue
 /// via the command line).
 /// 5) Keep injecting ordinals from next blocks (if any) as long as the ordinals from the current block are not all
@@ -56,10 +58,13 @@ use super::{
 ///
 /// If the block has already been computed in the past (so presence of ordinals number present in the `inscriptions` db)
 /// the transaction is removed from the set to compute, and not injected in L1 either.
+///
+/// Fails with the first traversal of the block that could not be completed: its inscription would be
+/// left without an ordinal number. Failed traversals of the next blocks are only logged.
 /// This behaviour should be refined.
 ///
 /// # Panics
-/// - unability to spawn threads
+/// - unability to build the worker pool
 ///
 /// # Todos / Optimizations
 /// - Pre-computed entries are being consumed from L1, and then re-injected in L1, which is wasting a bunch of cycles.
@@ -68,11 +73,11 @@ pub fn parallelize_inscription_data_computations(
     block: &BitcoinBlockData,
     next_blocks: &Vec<BitcoinBlockData>,
     cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
-    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    cache_l2: &Arc<TraversalsCache>,
     inscriptions_db_tx: &Transaction,
     ordhook_config: &OrdhookConfig,
     ctx: &Context,
-) -> Result<bool, String> {
+) -> Result<bool, ProcessingError> {
     let inner_ctx = if ordhook_config.logs.ordinals_internals {
         ctx.clone()
     } else {
@@ -91,72 +96,65 @@ pub fn parallelize_inscription_data_computations(
 
     let has_transactions_to_process = !transactions_ids.is_empty() || !l1_cache_hits.is_empty();
 
-    let thread_pool_capacity = ordhook_config.resources.get_optimal_thread_pool_capacity();
+    let worker_threads = ordhook_config.resources.worker_threads;
 
     // Nothing to do? early return
     if !has_transactions_to_process {
         return Ok(false);
     }
 
+    prefetch_traversal_ancestors(
+        block,
+        &transactions_ids,
+        cache_l2,
+        ordhook_config,
+        &inner_ctx,
+    );
+
     let expected_traversals = transactions_ids.len() + l1_cache_hits.len();
     let (traversal_tx, traversal_rx) = unbounded();
 
-    let mut tx_thread_pool = vec![];
-    let mut thread_pool_handles = vec![];
-
-    for thread_index in 0..thread_pool_capacity {
-        let (tx, rx) = channel();
-        tx_thread_pool.push(tx);
-
+    let worker_pool = get_worker_pool(worker_threads);
+    let spawn_traversal = |(
+        transaction_id,
+        block_identifier,
+        input_index,
+        inscription_pointer,
+        prioritary,
+    ): (TransactionIdentifier, BlockIdentifier, usize, u64, bool)| {
         let moved_traversal_tx = traversal_tx.clone();
         let moved_ctx = inner_ctx.clone();
         let moved_ordhook_db_path = ordhook_config.db_path.clone();
-        let ulimit = ordhook_config.resources.ulimit;
-        let memory_available = ordhook_config.resources.memory_available;
-
+        let resources = ordhook_config.resources.clone();
+        let verbose_traversal = ordhook_config.logs.verbose_traversal;
         let local_cache = cache_l2.clone();
-
-        let handle = hiro_system_kit::thread_named("Worker")
-            .spawn(move || {
-                while let Ok(Some((
-                    transaction_id,
-                    block_identifier,
-                    input_index,
-                    inscription_pointer,
-                    prioritary,
-                ))) = rx.recv()
-                {
-                    let traversal: Result<(TraversalResult, u64, _), String> =
-                        compute_satoshi_number(
-                            &moved_ordhook_db_path,
-                            &block_identifier,
-                            &transaction_id,
-                            input_index,
-                            inscription_pointer,
-                            &local_cache,
-                            ulimit,
-                            memory_available,
-                            false,
-                            &moved_ctx,
-                        );
-                    let _ = moved_traversal_tx.send((traversal, prioritary, thread_index));
-                }
-            })
-            .expect("unable to spawn thread");
-        thread_pool_handles.push(handle);
-    }
+        worker_pool.spawn(move || {
+            let traversal: Result<(TraversalResult, u64, _), String> = compute_satoshi_number(
+                &moved_ordhook_db_path,
+                &block_identifier,
+                &transaction_id,
+                input_index,
+                inscription_pointer,
+                &local_cache,
+                &resources,
+                false,
+                verbose_traversal,
+                &moved_ctx,
+            );
+            let traversal = traversal.map_err(|reason| ProcessingError::TraversalFailed {
+                tx_id: transaction_id.hash.clone(),
+                reason,
+            });
+            let thread_index = rayon::current_thread_index().unwrap_or(0);
+            let _ = moved_traversal_tx.send((traversal, prioritary, thread_index));
+        });
+    };
 
     // Consume L1 cache: if the traversal was performed in a previous round
     // retrieve it and inject it to the "reduce" worker (by-passing the "map" thread pool)
-    let mut round_robin_thread_index = 0;
     for key in l1_cache_hits.iter() {
         if let Some(entry) = cache_l1.get(key) {
-            let _ = traversal_tx.send((
-                Ok((entry.clone(), key.2, vec![])),
-                true,
-                round_robin_thread_index,
-            ));
-            round_robin_thread_index = (round_robin_thread_index + 1) % thread_pool_capacity;
+            let _ = traversal_tx.send((Ok((entry.clone(), key.2, vec![])), true, 0));
         }
     }
 
@@ -192,15 +190,15 @@ pub fn parallelize_inscription_data_computations(
     }
 
     // Feed each worker from the thread pool with 2 workitems each
-    for thread_index in 0..thread_pool_capacity {
-        let _ = tx_thread_pool[thread_index].send(priority_queue.pop_front());
-    }
-    for thread_index in 0..thread_pool_capacity {
-        let _ = tx_thread_pool[thread_index].send(priority_queue.pop_front());
+    for _ in 0..(2 * worker_threads) {
+        if let Some(w) = priority_queue.pop_front() {
+            spawn_traversal(w);
+        }
     }
 
     let mut next_block_iter = next_blocks.iter();
     let mut traversals_received = 0;
+    let mut failed_traversal = None;
     while let Ok((traversal_result, prioritary, thread_index)) = traversal_rx.recv() {
         if prioritary {
             traversals_received += 1;
@@ -232,6 +230,9 @@ pub fn parallelize_inscription_data_computations(
                 ctx.try_log(|logger| {
                     error!(logger, "Unable to compute inscription's Satoshi: {e}",)
                 });
+                if prioritary && failed_traversal.is_none() {
+                    failed_traversal = Some(e);
+                }
             }
         }
 
@@ -240,10 +241,10 @@ pub fn parallelize_inscription_data_computations(
         }
 
         if let Some(w) = priority_queue.pop_front() {
-            let _ = tx_thread_pool[thread_index].send(Some(w));
+            spawn_traversal(w);
         } else {
             if let Some(w) = warmup_queue.pop_front() {
-                let _ = tx_thread_pool[thread_index].send(Some(w));
+                spawn_traversal(w);
             } else {
                 if let Some(next_block) = next_block_iter.next() {
                     let (transactions_ids, _) =
@@ -269,7 +270,9 @@ pub fn parallelize_inscription_data_computations(
                             false,
                         ));
                     }
-                    let _ = tx_thread_pool[thread_index].send(warmup_queue.pop_front());
+                    if let Some(w) = warmup_queue.pop_front() {
+                        spawn_traversal(w);
+                    }
                 }
             }
         }
@@ -281,10 +284,13 @@ pub fn parallelize_inscription_data_computations(
         )
     });
 
-    // Collect eventual results for incoming blocks
-    for tx in tx_thread_pool.iter() {
-        // Empty the queue
-        if let Ok((traversal_result, _prioritary, thread_index)) = traversal_rx.try_recv() {
+    if let Some(e) = failed_traversal {
+        return Err(e);
+    }
+
+    // Collect eventual results for incoming blocks, traversals still running are dropped
+    while let Ok((traversal_result, _prioritary, thread_index)) = traversal_rx.try_recv() {
+        {
             if let Ok((traversal, inscription_pointer, _)) = traversal_result {
                 inner_ctx.try_log(|logger| {
                     info!(
@@ -308,16 +314,8 @@ pub fn parallelize_inscription_data_computations(
                 );
             }
         }
-        let _ = tx.send(None);
     }
 
-    let ctx_moved = inner_ctx.clone();
-    let _ = hiro_system_kit::thread_named("Garbage collection").spawn(move || {
-        for handle in thread_pool_handles.into_iter() {
-            let _ = handle.join();
-        }
-    });
-
     inner_ctx.try_log(|logger| {
         info!(
             logger,
@@ -328,6 +326,205 @@ pub fn parallelize_inscription_data_computations(
     Ok(has_transactions_to_process)
 }
 
+/// Within a batch of blocks, returns the blocks not spending any output of a transaction revealing an inscription
+/// earlier in the same batch. Such blocks are transferring inscriptions that have not been sequenced yet, and are
+/// left to the sequential path.
+pub fn get_blocks_without_intra_batch_dependencies(
+    blocks: &Vec<BitcoinBlockData>,
+) -> Vec<&BitcoinBlockData> {
+    let mut reveal_transactions: HashSet<&TransactionIdentifier> = HashSet::new();
+    let mut independent_blocks = vec![];
+    for block in blocks.iter() {
+        let depends_on_batch = block.transactions.iter().skip(1).any(|tx| {
+            tx.metadata
+                .inputs
+                .iter()
+                .any(|input| reveal_transactions.contains(&input.previous_output.txid))
+        });
+        if !depends_on_batch {
+            independent_blocks.push(block);
+        }
+        for tx in block.transactions.iter().skip(1) {
+            let reveals_inscription = tx
+                .metadata
+                .ordinal_operations
+                .iter()
+                .any(|op| matches!(op, OrdinalOperation::InscriptionRevealed(_)));
+            if reveals_inscription {
+                reveal_transactions.insert(&tx.transaction_identifier);
+            }
+        }
+    }
+    independent_blocks
+}
+
+/// Compute the ordinals numbers of the inscriptions revealed in a set of blocks, all at once, and store the
+/// results in the cache L1 so that the sequential processing of these blocks only consumes cache hits.
+///
+/// Traversals only read hord.rocksdb, so the order in which they are completed does not matter.
+pub fn prefetch_inscriptions_data_in_blocks(
+    blocks: &Vec<&BitcoinBlockData>,
+    cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
+    cache_l2: &Arc<TraversalsCache>,
+    inscriptions_db_conn: &Connection,
+    ordhook_config: &OrdhookConfig,
+    ctx: &Context,
+) {
+    let inner_ctx = if ordhook_config.logs.ordinals_internals {
+        ctx.clone()
+    } else {
+        Context::empty()
+    };
+
+    let (work_tx, work_rx) = unbounded();
+    let mut expected_traversals = 0;
+    for block in blocks.iter() {
+        let (transactions_ids, _) =
+            get_transactions_to_process(block, cache_l1, inscriptions_db_conn, ctx);
+        for (transaction_id, input_index, inscription_pointer) in transactions_ids.into_iter() {
+            let _ = work_tx.send((
+                transaction_id,
+                block.block_identifier.clone(),
+                input_index,
+                inscription_pointer,
+            ));
+            expected_traversals += 1;
+        }
+    }
+    drop(work_tx);
+
+    if expected_traversals == 0 {
+        return;
+    }
+
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Prefetching {expected_traversals} inscriptions data in {} blocks",
+            blocks.len()
+        )
+    });
+
+    let (traversal_tx, traversal_rx) = unbounded();
+    let worker_pool = get_worker_pool(ordhook_config.resources.worker_threads);
+    while let Ok((transaction_id, block_identifier, input_index, inscription_pointer)) =
+        work_rx.recv()
+    {
+        let moved_traversal_tx = traversal_tx.clone();
+        let moved_ctx = inner_ctx.clone();
+        let moved_ordhook_db_path = ordhook_config.db_path.clone();
+        let resources = ordhook_config.resources.clone();
+        let verbose_traversal = ordhook_config.logs.verbose_traversal;
+        let local_cache = cache_l2.clone();
+        worker_pool.spawn(move || {
+            let traversal: Result<(TraversalResult, u64, _), String> = compute_satoshi_number(
+                &moved_ordhook_db_path,
+                &block_identifier,
+                &transaction_id,
+                input_index,
+                inscription_pointer,
+                &local_cache,
+                &resources,
+                false,
+                verbose_traversal,
+                &moved_ctx,
+            );
+            let _ = moved_traversal_tx.send(traversal);
+        });
+    }
+    // Every traversal holds a sender: the channel is closed once they all completed
+    drop(traversal_tx);
+
+    while let Ok(traversal_result) = traversal_rx.recv() {
+        match traversal_result {
+            Ok((traversal, inscription_pointer, _)) => {
+                cache_l1.insert(
+                    (
+                        traversal.transaction_identifier_inscription.clone(),
+                        traversal.inscription_input_index,
+                        inscription_pointer,
+                    ),
+                    traversal,
+                );
+            }
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    error!(logger, "Unable to compute inscription's Satoshi: {e}",)
+                });
+            }
+        }
+    }
+}
+
+/// Loads in the L2 cache the reveal transactions of `block` along with the transactions they
+/// are spending, so that the first hop of each traversal doesn't hit RocksDB. The blocks
+/// holding the spent transactions are read with a single `multi_get`.
+fn prefetch_traversal_ancestors(
+    block: &BitcoinBlockData,
+    transactions_ids: &HashSet<(TransactionIdentifier, usize, u64)>,
+    cache_l2: &Arc<TraversalsCache>,
+    ordhook_config: &OrdhookConfig,
+    ctx: &Context,
+) {
+    if transactions_ids.is_empty() {
+        return;
+    }
+    let blocks_db = open_ordhook_db_conn_rocks_db_loop(
+        false,
+        &ordhook_config.db_path,
+        &ordhook_config.resources,
+        ctx,
+    );
+    let block_height = block.block_identifier.index as u32;
+    let Some(block_bytes) = cache_l2.find_block_bytes(block_height, 3, &blocks_db, ctx) else {
+        return;
+    };
+    let block_cursor = BlockBytesCursor::new(&block_bytes.as_ref());
+
+    let mut ancestors: BTreeMap<u32, HashSet<[u8; 8]>> = BTreeMap::new();
+    for (transaction_id, input_index, _) in transactions_ids.iter() {
+        let txid = transaction_id.get_8_hash_bytes();
+        let Some(tx) = block_cursor.find_and_serialize_transaction_with_txid(&txid) else {
+            continue;
+        };
+        let Some(input) = tx.inputs.get(*input_index) else {
+            continue;
+        };
+        ancestors
+            .entry(input.block_height)
+            .or_default()
+            .insert(input.txin);
+        cache_l2.insert((block_height, txid), tx);
+    }
+
+    let heights = ancestors.keys().cloned().collect::<Vec<_>>();
+    let results = blocks_db.multi_get(heights.iter().map(|height| height.to_be_bytes()));
+    let mut prefetched = 0;
+    for (height, result) in heights.iter().zip(results) {
+        let Ok(Some(block_bytes)) = result else {
+            continue;
+        };
+        let block_cursor = BlockBytesCursor::new(&block_bytes);
+        for txid in ancestors[height].iter() {
+            // Coinbase transactions end traversals and must stay out of the cache
+            if block_cursor.get_coinbase_txid() == txid {
+                continue;
+            }
+            if let Some(tx) = block_cursor.find_and_serialize_transaction_with_txid(txid) {
+                cache_l2.insert((*height, *txid), tx);
+                prefetched += 1;
+            }
+        }
+    }
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Prefetched {prefetched} ancestor transactions from {} blocks for block #{block_height}",
+            heights.len()
+        )
+    });
+}
+
 /// Given a block, a cache L1, and a readonly DB connection, returns a tuple with the transactions that must be included
 /// for ordinals computation and the list of transactions where we have a cache hit.
 ///
@@ -345,7 +542,7 @@ pub fn parallelize_inscription_data_computations(
 fn get_transactions_to_process(
     block: &BitcoinBlockData,
     cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
-    inscriptions_db_tx: &Transaction,
+    inscriptions_db_tx: &Connection,
     ctx: &Context,
 ) -> (
     HashSet<(TransactionIdentifier, usize, u64)>,
@@ -404,142 +601,143 @@ fn get_transactions_to_process(
     (transactions_ids, l1_cache_hits)
 }
 
-/// Helper caching inscription sequence cursor
+/// Inscription sequence cursor, persisted in the `sequence_state` table.
 ///
-/// When attributing an inscription number to a new inscription, retrieving the next inscription number to use (both for
-/// blessed and cursed sequence) is an expensive operation, challenging to optimize from a SQL point of view.
-/// This structure is wrapping the expensive SQL query and helping us keeping track of the next inscription number to
-/// use.
+/// The last inscription numbers assigned (both for blessed and cursed sequence) are read from and written to
+/// the connection the cursor is wrapping, which is expected to be the transaction the inscriptions are being
+/// written to: a number is only ever consumed if the inscription it was assigned to gets committed.
+/// When the state is missing or stale, it is recomputed from `sequence_metadata` with the expensive SQL queries.
 ///
 pub struct SequenceCursor<'a> {
-    pos_cursor: Option<i64>,
-    neg_cursor: Option<i64>,
-    jubilee_cursor: Option<i64>,
     inscriptions_db_conn: &'a Connection,
-    current_block_height: u64,
+    chain: Chain,
 }
 
 impl<'a> SequenceCursor<'a> {
-    pub fn new(inscriptions_db_conn: &'a Connection) -> SequenceCursor<'a> {
+    pub fn new(inscriptions_db_conn: &'a Connection, chain: Chain) -> SequenceCursor<'a> {
         SequenceCursor {
-            jubilee_cursor: None,
-            pos_cursor: None,
-            neg_cursor: None,
             inscriptions_db_conn,
-            current_block_height: 0,
+            chain,
         }
     }
 
-    pub fn reset(&mut self) {
-        self.pos_cursor = None;
-        self.neg_cursor = None;
-        self.jubilee_cursor = None;
-        self.current_block_height = 0;
+    /// Drop the persisted state, the next numbers will be recomputed from `sequence_metadata`.
+    pub fn invalidate(&mut self, ctx: &Context) -> Result<(), ProcessingError> {
+        delete_sequence_state(&self.inscriptions_db_conn, ctx)
+    }
+
+    /// Discard the inscriptions and transfers indexed after `to_height`, so that indexing can
+    /// resume from `to_height + 1`. The sequence state is discarded along with them.
+    pub fn reset(
+        &mut self,
+        to_height: u64,
+        conn: &mut Connection,
+        ctx: &Context,
+    ) -> Result<(), ProcessingError> {
+        rollback_blocks_above_height(to_height, conn, ctx)
+    }
+
+    /// Retrieve the sequence state to use for assigning numbers at `block_height`, recomputing it if it was
+    /// written for a later block (re-org), or if blocks were indexed since without going through a cursor.
+    fn load_state(&self, block_height: u64, ctx: &Context) -> SequenceState {
+        if let Some(mut state) = find_sequence_state(&self.inscriptions_db_conn, ctx) {
+            if state.block_height == block_height
+                || (state.block_height < block_height
+                    && !any_sequence_metadata_between(
+                        state.block_height,
+                        block_height,
+                        &self.inscriptions_db_conn,
+                        ctx,
+                    ))
+            {
+                state.block_height = block_height;
+                return state;
+            }
+        }
+        SequenceState {
+            block_height,
+            nth_classic_pos_number: find_nth_classic_pos_number_at_block_height(
+                &block_height,
+                &self.inscriptions_db_conn,
+                ctx,
+            ),
+            nth_classic_neg_number: find_nth_classic_neg_number_at_block_height(
+                &block_height,
+                &self.inscriptions_db_conn,
+                ctx,
+            ),
+            nth_jubilee_number: find_nth_jubilee_number_at_block_height(
+                &block_height,
+                &self.inscriptions_db_conn,
+                ctx,
+            ),
+        }
     }
 
     pub fn pick_next(
         &mut self,
         cursed: bool,
         block_height: u64,
-        network: &Network,
         ctx: &Context,
-    ) -> OrdinalInscriptionNumber {
-        if block_height < self.current_block_height {
-            self.reset();
-        }
-        self.current_block_height = block_height;
+    ) -> Result<OrdinalInscriptionNumber, ProcessingError> {
+        let state = self.load_state(block_height, ctx);
+        // Anchor the state to the block being indexed, for the increments to come
+        update_sequence_state(&state, &self.inscriptions_db_conn, ctx)?;
 
         let classic = match cursed {
-            true => self.pick_next_neg_classic(ctx),
-            false => self.pick_next_pos_classic(ctx),
+            true => next_neg_classic(&state),
+            false => next_pos_classic(&state),
         };
 
-        let jubilee = if block_height >= get_jubilee_block_height(&network) {
-            self.pick_next_jubilee_number(ctx)
+        let jubilee = if block_height >= self.chain.jubilee_height() {
+            next_jubilee_number(&state)
         } else {
             classic
         };
-        OrdinalInscriptionNumber { classic, jubilee }
-    }
-
-    fn pick_next_pos_classic(&mut self, ctx: &Context) -> i64 {
-        match self.pos_cursor {
-            None => {
-                match find_nth_classic_pos_number_at_block_height(
-                    &self.current_block_height,
-                    &self.inscriptions_db_conn,
-                    &ctx,
-                ) {
-                    Some(inscription_number) => {
-                        self.pos_cursor = Some(inscription_number);
-                        inscription_number + 1
-                    }
-                    _ => 0,
-                }
-            }
-            Some(value) => value + 1,
-        }
+        Ok(OrdinalInscriptionNumber { classic, jubilee })
     }
 
-    fn pick_next_jubilee_number(&mut self, ctx: &Context) -> i64 {
-        match self.jubilee_cursor {
-            None => {
-                match find_nth_jubilee_number_at_block_height(
-                    &self.current_block_height,
-                    &self.inscriptions_db_conn,
-                    &ctx,
-                ) {
-                    Some(inscription_number) => {
-                        self.jubilee_cursor = Some(inscription_number);
-                        inscription_number + 1
-                    }
-                    _ => 0,
-                }
-            }
-            Some(value) => value + 1,
-        }
+    fn increment<F>(&mut self, ctx: &Context, mutate: F) -> Result<(), ProcessingError>
+    where
+        F: FnOnce(&mut SequenceState),
+    {
+        let Some(mut state) = find_sequence_state(&self.inscriptions_db_conn, ctx) else {
+            ctx.try_log(|logger| error!(logger, "sequence state missing, pick_next not called"));
+            return Ok(());
+        };
+        mutate(&mut state);
+        update_sequence_state(&state, &self.inscriptions_db_conn, ctx)
     }
 
-    fn pick_next_neg_classic(&mut self, ctx: &Context) -> i64 {
-        match self.neg_cursor {
-            None => {
-                match find_nth_classic_neg_number_at_block_height(
-                    &self.current_block_height,
-                    &self.inscriptions_db_conn,
-                    &ctx,
-                ) {
-                    Some(inscription_number) => {
-                        self.neg_cursor = Some(inscription_number);
-                        inscription_number - 1
-                    }
-                    _ => -1,
-                }
-            }
-            Some(value) => value - 1,
-        }
+    pub fn increment_neg_classic(&mut self, ctx: &Context) -> Result<(), ProcessingError> {
+        self.increment(ctx, |state| {
+            state.nth_classic_neg_number = Some(next_neg_classic(state))
+        })
     }
 
-    pub fn increment_neg_classic(&mut self, ctx: &Context) {
-        self.neg_cursor = Some(self.pick_next_neg_classic(ctx));
+    pub fn increment_pos_classic(&mut self, ctx: &Context) -> Result<(), ProcessingError> {
+        self.increment(ctx, |state| {
+            state.nth_classic_pos_number = Some(next_pos_classic(state))
+        })
     }
 
-    pub fn increment_pos_classic(&mut self, ctx: &Context) {
-        self.pos_cursor = Some(self.pick_next_pos_classic(ctx));
+    pub fn increment_jubilee_number(&mut self, ctx: &Context) -> Result<(), ProcessingError> {
+        self.increment(ctx, |state| {
+            state.nth_jubilee_number = Some(next_jubilee_number(state))
+        })
     }
+}
 
-    pub fn increment_jubilee_number(&mut self, ctx: &Context) {
-        self.jubilee_cursor = Some(self.pick_next_jubilee_number(ctx))
-    }
+fn next_pos_classic(state: &SequenceState) -> i64 {
+    state.nth_classic_pos_number.map_or(0, |n| n + 1)
 }
 
-pub fn get_jubilee_block_height(network: &Network) -> u64 {
-    match network {
-        Network::Bitcoin => 824544,
-        Network::Regtest => 110,
-        Network::Signet => 175392,
-        Network::Testnet => 2544192,
-        _ => unreachable!(),
-    }
+fn next_neg_classic(state: &SequenceState) -> i64 {
+    state.nth_classic_neg_number.map_or(-1, |n| n - 1)
+}
+
+fn next_jubilee_number(state: &SequenceState) -> i64 {
+    state.nth_jubilee_number.map_or(0, |n| n + 1)
 }
 
 pub fn get_bitcoin_network(network: &BitcoinNetwork) -> Network {
@@ -563,7 +761,7 @@ pub fn augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     inscriptions_db_tx: &Transaction,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, ProcessingError> {
     // Handle re-inscriptions
     let mut reinscriptions_data = HashMap::new();
     for (_, inscription_data) in inscriptions_data.iter() {
@@ -584,12 +782,27 @@ pub fn augment_block_with_ordinals_inscriptions_data_and_write_to_db_tx(
         inscriptions_data,
         &mut reinscriptions_data,
         &ctx,
-    );
+    )?;
 
     // Store inscriptions
-    update_ordinals_db_with_block(block, inscriptions_db_tx, ctx);
-    update_sequence_metadata_with_block(block, inscriptions_db_tx, ctx);
-    any_events
+    update_ordinals_db_with_block(block, inscriptions_db_tx, ctx)?;
+    update_sequence_metadata_with_block(block, inscriptions_db_tx, ctx)?;
+
+    // Store content hashes, for duplicates detection
+    for inscription_data in get_inscriptions_revealed_in_block(block, None) {
+        let Ok(content) = hex::decode(inscription_data.content_bytes.trim_start_matches("0x"))
+        else {
+            continue;
+        };
+        let content_hash: [u8; 32] = Sha256::digest(&content).into();
+        store_inscription_content_hash(
+            &inscription_data.inscription_id,
+            &content_hash,
+            inscriptions_db_tx,
+            ctx,
+        )?;
+    }
+    Ok(any_events)
 }
 
 /// Given a `BitcoinBlockData` that have been augmented with the functions `parse_inscriptions_in_raw_tx`, `parse_inscriptions_in_standardized_tx`
@@ -606,7 +819,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     reinscriptions_data: &mut HashMap<u64, String>,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, ProcessingError> {
     // Handle sat oveflows
     let mut sats_overflows = VecDeque::new();
     let mut any_event = false;
@@ -630,7 +843,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
             &mut sats_overflows,
             reinscriptions_data,
             ctx,
-        );
+        )?;
     }
 
     // Handle sats overflow
@@ -642,14 +855,14 @@ pub fn augment_block_with_ordinals_inscriptions_data(
         };
         let is_curse = inscription_data.curse_type.is_some();
         let inscription_number =
-            sequence_cursor.pick_next(is_curse, block.block_identifier.index, &network, &ctx);
+            sequence_cursor.pick_next(is_curse, block.block_identifier.index, &ctx)?;
         inscription_data.inscription_number = inscription_number;
 
-        sequence_cursor.increment_jubilee_number(ctx);
+        sequence_cursor.increment_jubilee_number(ctx)?;
         if is_curse {
-            sequence_cursor.increment_neg_classic(ctx);
+            sequence_cursor.increment_neg_classic(ctx)?;
         } else {
-            sequence_cursor.increment_pos_classic(ctx);
+            sequence_cursor.increment_pos_classic(ctx)?;
         };
 
         ctx.try_log(|logger| {
@@ -664,7 +877,7 @@ pub fn augment_block_with_ordinals_inscriptions_data(
             );
         });
     }
-    any_event
+    Ok(any_event)
 }
 
 /// Given a `BitcoinTransactionData` that have been augmented with the functions `parse_inscriptions_in_raw_tx` or
@@ -686,7 +899,7 @@ fn augment_transaction_with_ordinals_inscriptions_data(
     sats_overflows: &mut VecDeque<(usize, usize)>,
     reinscriptions_data: &mut HashMap<u64, String>,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, ProcessingError> {
     let inputs = tx
         .metadata
         .inputs
@@ -717,20 +930,18 @@ fn augment_transaction_with_ordinals_inscriptions_data(
             match inscriptions_data.get(&(transaction_identifier, input_index, relative_offset)) {
                 Some(traversal) => traversal,
                 None => {
-                    let err_msg = format!(
-                        "Unable to retrieve backward traversal result for inscription {}",
-                        tx.transaction_identifier.hash
-                    );
-                    ctx.try_log(|logger| {
-                        error!(logger, "{}", err_msg);
+                    return Err(ProcessingError::TraversalFailed {
+                        tx_id: tx.transaction_identifier.hash.clone(),
+                        reason: format!(
+                            "backward traversal result missing for inscription {inscription_id}"
+                        ),
                     });
-                    std::process::exit(1);
                 }
             };
 
         // Do we need to curse the inscription?
         let mut inscription_number =
-            sequence_cursor.pick_next(is_cursed, block_identifier.index, network, ctx);
+            sequence_cursor.pick_next(is_cursed, block_identifier.index, ctx)?;
         let mut curse_type_override = None;
         if !is_cursed {
             // Is this inscription re-inscribing an existing blessed inscription?
@@ -749,7 +960,7 @@ fn augment_transaction_with_ordinals_inscriptions_data(
 
                 is_cursed = true;
                 inscription_number =
-                    sequence_cursor.pick_next(is_cursed, block_identifier.index, network, ctx);
+                    sequence_cursor.pick_next(is_cursed, block_identifier.index, ctx)?;
                 curse_type_override = Some(OrdinalInscriptionCurseType::Reinscription)
             }
         };
@@ -819,18 +1030,18 @@ fn augment_transaction_with_ordinals_inscriptions_data(
             );
         });
 
-        sequence_cursor.increment_jubilee_number(ctx);
+        sequence_cursor.increment_jubilee_number(ctx)?;
         if is_cursed {
-            sequence_cursor.increment_neg_classic(ctx);
+            sequence_cursor.increment_neg_classic(ctx)?;
         } else {
-            sequence_cursor.increment_pos_classic(ctx);
+            sequence_cursor.increment_pos_classic(ctx)?;
         }
     }
     tx.metadata
         .ordinal_operations
         .append(&mut mutated_operations);
 
-    any_event
+    Ok(any_event)
 }
 
 /// Best effort to re-augment a `BitcoinTransactionData` with data coming from `inscriptions` and `locations` tables.
@@ -927,7 +1138,7 @@ pub fn consolidate_block_with_pre_computed_ordinals_data(
     let coinbase_subsidy = Height(block.block_identifier.index).subsidy();
     let coinbase_txid = &block.transactions[0].transaction_identifier.clone();
     let mut cumulated_fees = 0;
-    let expected_inscriptions_count = get_inscriptions_revealed_in_block(&block).len();
+    let expected_inscriptions_count = get_inscriptions_revealed_in_block(&block, None).len();
     let mut inscriptions_data = loop {
         let results =
             find_all_inscriptions_in_block(&block.block_identifier.index, inscriptions_db_tx, ctx);
@@ -1,3 +1,4 @@
+pub mod brc20;
 pub mod inscription_parsing;
 pub mod inscription_sequencing;
 pub mod satoshi_numbering;
@@ -1,16 +1,13 @@
 use chainhook_sdk::types::{BlockIdentifier, OrdinalInscriptionNumber, TransactionIdentifier};
 use chainhook_sdk::utils::Context;
-use dashmap::DashMap;
-use fxhash::FxHasher;
-use std::hash::BuildHasherDefault;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::db::{
-    find_pinned_block_bytes_at_block_height, open_ordhook_db_conn_rocks_db_loop, BlockBytesCursor,
-};
+use crate::config::ResourcesConfig;
+use crate::core::TraversalsCache;
+use crate::db::{open_ordhook_db_conn_rocks_db_loop, BlockBytesCursor};
 
-use crate::db::{TransactionBytesCursor, TraversalResult};
+use crate::db::TraversalResult;
 use crate::ord::height::Height;
 
 pub fn compute_satoshi_number(
@@ -19,20 +16,17 @@ pub fn compute_satoshi_number(
     transaction_identifier: &TransactionIdentifier,
     inscription_input_index: usize,
     inscription_pointer: u64,
-    traversals_cache: &Arc<
-        DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>,
-    >,
-    ulimit: usize,
-    memory_available: usize,
+    traversals_cache: &Arc<TraversalsCache>,
+    resources: &ResourcesConfig,
     _back_tracking: bool,
+    verbose_traversal: bool,
     ctx: &Context,
 ) -> Result<(TraversalResult, u64, Vec<(u32, [u8; 8], usize)>), String> {
     let mut ordinal_offset = inscription_pointer;
     let ordinal_block_number = block_identifier.index as u32;
     let txid = transaction_identifier.get_8_hash_bytes();
     let mut back_track = vec![];
-    let blocks_db =
-        open_ordhook_db_conn_rocks_db_loop(false, &blocks_db_dir, ulimit, memory_available, &ctx);
+    let blocks_db = open_ordhook_db_conn_rocks_db_loop(false, &blocks_db_dir, resources, &ctx);
 
     let (mut tx_cursor, mut ordinal_block_number) = match traversals_cache
         .get(&(block_identifier.index as u32, txid.clone()))
@@ -48,8 +42,7 @@ pub fn compute_satoshi_number(
             )
         }
         None => loop {
-            match find_pinned_block_bytes_at_block_height(ordinal_block_number, 3, &blocks_db, &ctx)
-            {
+            match traversals_cache.find_block_bytes(ordinal_block_number, 3, &blocks_db, &ctx) {
                 None => {
                     return Err(format!("block #{ordinal_block_number} not in database"));
                 }
@@ -97,10 +90,20 @@ pub fn compute_satoshi_number(
             sats_out += ordinal_offset;
 
             let mut sats_in = 0;
-            for input in tx.inputs.iter() {
+            for (input_index, input) in tx.inputs.iter().enumerate() {
                 sats_in += input.txin_value;
 
                 if sats_out < sats_in {
+                    if verbose_traversal {
+                        log_traversal_step(
+                            transaction_identifier,
+                            hops,
+                            (ordinal_block_number, &tx_cursor),
+                            sats_out,
+                            (input_index, sats_in - input.txin_value, sats_in),
+                            ctx,
+                        );
+                    }
                     ordinal_offset = sats_out - (sats_in - input.txin_value);
                     ordinal_block_number = input.block_height;
                     tx_cursor = (input.txin.clone(), input.vout as usize);
@@ -137,12 +140,7 @@ pub fn compute_satoshi_number(
 
         let pinned_block_bytes = {
             loop {
-                match find_pinned_block_bytes_at_block_height(
-                    ordinal_block_number,
-                    3,
-                    &blocks_db,
-                    &ctx,
-                ) {
+                match traversals_cache.find_block_bytes(ordinal_block_number, 3, &blocks_db, &ctx) {
                     Some(block) => break block,
                     None => {
                         return Err(format!("block #{ordinal_block_number} not in database (traversing {} / {} in progress)", transaction_identifier.hash, block_identifier.index));
@@ -168,6 +166,15 @@ pub fn compute_satoshi_number(
 
             let subsidy = Height(ordinal_block_number.into()).subsidy();
             if ordinal_offset < subsidy {
+                if verbose_traversal {
+                    ctx.try_log(|logger| {
+                        debug!(
+                            logger,
+                            "Traversal {}: hop {hops}, reached the coinbase of block #{ordinal_block_number}, offset {ordinal_offset} within the subsidy",
+                            transaction_identifier.hash
+                        )
+                    });
+                }
                 // Great!
                 break;
             }
@@ -194,10 +201,31 @@ pub fn compute_satoshi_number(
                     total_out += offset_within_fee;
                     let mut sats_in = 0;
 
-                    for input in tx.inputs.into_iter() {
+                    if verbose_traversal {
+                        ctx.try_log(|logger| {
+                            debug!(
+                                logger,
+                                "Traversal {}: hop {hops}, offset {ordinal_offset} of the coinbase of block #{ordinal_block_number} is a fee paid by {}, {offset_within_fee} sats into the fee",
+                                transaction_identifier.hash,
+                                hex::encode(tx.txid)
+                            )
+                        });
+                    }
+                    for (input_index, input) in tx.inputs.into_iter().enumerate() {
                         sats_in += input.txin_value;
 
                         if sats_in > total_out {
+                            if verbose_traversal {
+                                ctx.try_log(|logger| {
+                                    debug!(
+                                        logger,
+                                        "Traversal {}: hop {hops}, fee carried by input {input_index} spending [{}, {sats_in}), next offset {}",
+                                        transaction_identifier.hash,
+                                        sats_in - input.txin_value,
+                                        total_out - (sats_in - input.txin_value)
+                                    )
+                                });
+                            }
                             ordinal_offset = total_out - (sats_in - input.txin_value);
                             ordinal_block_number = input.block_height;
                             tx_cursor = (input.txin.clone(), input.vout as usize);
@@ -237,10 +265,20 @@ pub fn compute_satoshi_number(
             sats_out += ordinal_offset;
 
             let mut sats_in = 0;
-            for input in tx_bytes_cursor.inputs.iter() {
+            for (input_index, input) in tx_bytes_cursor.inputs.iter().enumerate() {
                 sats_in += input.txin_value;
 
                 if sats_out < sats_in {
+                    if verbose_traversal {
+                        log_traversal_step(
+                            transaction_identifier,
+                            hops,
+                            (ordinal_block_number, &tx_cursor),
+                            sats_out,
+                            (input_index, sats_in - input.txin_value, sats_in),
+                            ctx,
+                        );
+                    }
                     back_track.push((ordinal_block_number, tx_cursor.0.clone(), tx_cursor.1));
                     traversals_cache
                         .insert((ordinal_block_number, tx_cursor.0), tx_bytes_cursor.clone());
@@ -289,3 +327,25 @@ pub fn compute_satoshi_number(
         back_track,
     ))
 }
+
+/// Debug trace of a traversal hop: the sat sits `sats_out` sats into the outputs of
+/// `tx_cursor`, which falls in the `[start, end)` range spent by `input_index`.
+fn log_traversal_step(
+    transaction_identifier: &TransactionIdentifier,
+    hops: u32,
+    (block_height, tx_cursor): (u32, &([u8; 8], usize)),
+    sats_out: u64,
+    (input_index, start, end): (usize, u64, u64),
+    ctx: &Context,
+) {
+    ctx.try_log(|logger| {
+        debug!(
+            logger,
+            "Traversal {}: hop {hops}, output {} of {} in block #{block_height} at offset {sats_out}, carried by input {input_index} spending [{start}, {end}), next offset {}",
+            transaction_identifier.hash,
+            tx_cursor.1,
+            hex::encode(tx_cursor.0),
+            sats_out - start
+        )
+    });
+}
@@ -10,23 +10,27 @@ use chainhook_sdk::{
 };
 
 use crate::{
-    core::{compute_next_satpoint_data, SatPosition},
+    core::{compute_next_satpoint_data, error::ProcessingError, SatPosition},
     db::{
         find_inscribed_ordinals_at_wached_outpoint, format_outpoint_to_watch,
-        insert_ordinal_transfer_in_locations_tx, parse_satpoint_to_watch, OrdinalLocation,
+        get_transfer_destination_address, insert_ordinal_transfer_in_locations_tx,
+        parse_satpoint_to_watch, update_current_holder_address_tx, OrdinalLocation, TransferEvent,
     },
     ord::height::Height,
 };
-use rusqlite::Transaction;
+use rusqlite::{Connection, Transaction};
 
-use super::inscription_sequencing::get_bitcoin_network;
+use super::{
+    inscription_parsing::get_inscriptions_transferred_in_block,
+    inscription_sequencing::get_bitcoin_network,
+};
 
 pub fn augment_block_with_ordinals_transfer_data(
     block: &mut BitcoinBlockData,
     inscriptions_db_tx: &Transaction,
     update_db_tx: bool,
     ctx: &Context,
-) -> bool {
+) -> Result<bool, ProcessingError> {
     let mut any_event = false;
 
     let network = get_bitcoin_network(&block.metadata.network);
@@ -49,6 +53,7 @@ pub fn augment_block_with_ordinals_transfer_data(
         if update_db_tx {
             // Store transfers between each iteration
             for transfer_data in transfers.into_iter() {
+                let is_burned = is_burn_transfer(tx, &transfer_data);
                 let (tx, output_index, offset) =
                     parse_satpoint_to_watch(&transfer_data.satpoint_post_transfer);
                 let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
@@ -56,6 +61,9 @@ pub fn augment_block_with_ordinals_transfer_data(
                     offset,
                     block_height: block.block_identifier.index,
                     tx_index: transfer_data.tx_index,
+                    is_burned,
+                    output_value: transfer_data.post_transfer_output_value,
+                    address: get_transfer_destination_address(&transfer_data),
                 };
                 insert_ordinal_transfer_in_locations_tx(
                     transfer_data.ordinal_number,
@@ -63,12 +71,132 @@ pub fn augment_block_with_ordinals_transfer_data(
                     data,
                     inscriptions_db_tx,
                     &ctx,
-                );
+                )?;
+                update_current_holder_address_tx(
+                    transfer_data.ordinal_number,
+                    get_transfer_destination_address(&transfer_data).as_deref(),
+                    inscriptions_db_tx,
+                    &ctx,
+                )?;
             }
         }
     }
 
-    any_event
+    Ok(any_event)
+}
+
+/// Dry run of `augment_block_with_ordinals_transfer_data`: the transfers indexing `block` would
+/// record, computed against the current state of hord.sqlite. Locations are still written between
+/// transactions so that transfers chained within the block are followed, but in a transaction
+/// that is rolled back before returning. Inscriptions revealed in `block` are only tracked if
+/// they were already indexed.
+pub fn simulate_transfer_data(
+    block: &BitcoinBlockData,
+    conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<TransferEvent>, String> {
+    let mut block = block.clone();
+    for tx in block.transactions.iter_mut() {
+        tx.metadata
+            .ordinal_operations
+            .retain(|op| !matches!(op, OrdinalOperation::InscriptionTransferred(_)));
+    }
+
+    let inscriptions_db_tx = conn.unchecked_transaction().map_err(|e| {
+        format!(
+            "unable to begin transaction on hord.sqlite: {}",
+            e.to_string()
+        )
+    })?;
+    augment_block_with_ordinals_transfer_data(&mut block, &inscriptions_db_tx, true, ctx)
+        .map_err(|e| e.to_string())?;
+    inscriptions_db_tx.rollback().map_err(|e| {
+        format!(
+            "unable to rollback transaction on hord.sqlite: {}",
+            e.to_string()
+        )
+    })?;
+
+    let transfers = get_inscriptions_transferred_in_block(&block)
+        .into_iter()
+        .map(|transfer| {
+            let strip_offset = |satpoint: &str| {
+                satpoint
+                    .rsplit_once(':')
+                    .map(|(outpoint, _)| outpoint.to_string())
+                    .unwrap_or_default()
+            };
+            TransferEvent {
+                block_height: block.block_identifier.index,
+                tx_id: block.transactions[transfer.tx_index]
+                    .transaction_identifier
+                    .hash
+                    .clone(),
+                from_output: strip_offset(&transfer.satpoint_pre_transfer),
+                to_output: strip_offset(&transfer.satpoint_post_transfer),
+                sat: transfer.ordinal_number,
+                is_burned: is_burn_transfer(&block.transactions[transfer.tx_index], &transfer),
+                output_value: transfer.post_transfer_output_value,
+            }
+        })
+        .collect();
+    Ok(transfers)
+}
+
+/// Whether `transfer_data`, emitted by `tx`, sends the sat to an OP_RETURN output. Other
+/// unspendable destinations (non standard scripts) are not considered burns.
+pub fn is_burn_transfer(
+    tx: &BitcoinTransactionData,
+    transfer_data: &OrdinalInscriptionTransferData,
+) -> bool {
+    if !matches!(
+        transfer_data.destination,
+        OrdinalInscriptionTransferDestination::Burnt(_)
+    ) {
+        return false;
+    }
+    let (_, output_index, _) = parse_satpoint_to_watch(&transfer_data.satpoint_post_transfer);
+    tx.metadata
+        .outputs
+        .get(output_index)
+        .and_then(|output| ScriptBuf::from_hex(&output.get_script_pubkey_hex()).ok())
+        .map(|script| script.is_op_return())
+        .unwrap_or(false)
+}
+
+/// Destination of a sat sent to the output locked by `script_pub_key_hex`: the address of
+/// the script, or a burn when the script has none. A P2TR address only encodes the tweaked
+/// output key of the script, which already commits to the internal key and script tree.
+pub fn get_transfer_destination(
+    script_pub_key_hex: &str,
+    network: &Network,
+    ctx: &Context,
+) -> OrdinalInscriptionTransferDestination {
+    match ScriptBuf::from_hex(script_pub_key_hex) {
+        Ok(script) => match Address::from_script(&script, network.clone()) {
+            Ok(address) => OrdinalInscriptionTransferDestination::Transferred(address.to_string()),
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    info!(
+                        logger,
+                        "unable to retrieve address from {script_pub_key_hex}: {}",
+                        e.to_string()
+                    )
+                });
+                OrdinalInscriptionTransferDestination::Burnt(script.to_string())
+            }
+        },
+        Err(e) => {
+            ctx.try_log(|logger| {
+                info!(
+                    logger,
+                    "unable to retrieve address from {script_pub_key_hex}: {}",
+                    e.to_string()
+                )
+            });
+            OrdinalInscriptionTransferDestination::Burnt(script_pub_key_hex.to_string())
+        }
+    }
 }
 
 pub fn compute_satpoint_post_transfer(
@@ -103,33 +231,7 @@ pub fn compute_satpoint_post_transfer(
             SatPosition::Output((output_index, offset)) => {
                 let outpoint = format_outpoint_to_watch(&tx.transaction_identifier, output_index);
                 let script_pub_key_hex = tx.metadata.outputs[output_index].get_script_pubkey_hex();
-                let updated_address = match ScriptBuf::from_hex(&script_pub_key_hex) {
-                    Ok(script) => match Address::from_script(&script, network.clone()) {
-                        Ok(address) => {
-                            OrdinalInscriptionTransferDestination::Transferred(address.to_string())
-                        }
-                        Err(e) => {
-                            ctx.try_log(|logger| {
-                                info!(
-                                    logger,
-                                    "unable to retrieve address from {script_pub_key_hex}: {}",
-                                    e.to_string()
-                                )
-                            });
-                            OrdinalInscriptionTransferDestination::Burnt(script.to_string())
-                        }
-                    },
-                    Err(e) => {
-                        ctx.try_log(|logger| {
-                            info!(
-                                logger,
-                                "unable to retrieve address from {script_pub_key_hex}: {}",
-                                e.to_string()
-                            )
-                        });
-                        OrdinalInscriptionTransferDestination::Burnt(script_pub_key_hex.to_string())
-                    }
-                };
+                let updated_address = get_transfer_destination(&script_pub_key_hex, network, ctx);
 
                 (
                     outpoint,
@@ -234,3 +336,82 @@ pub fn augment_transaction_with_ordinals_transfers_data(
 
     transfers
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chainhook_sdk::bitcoincore_rpc_json::bitcoin::{
+        secp256k1::{Secp256k1, XOnlyPublicKey},
+        taproot::{LeafVersion, TapLeafHash, TapNodeHash},
+    };
+
+    use super::*;
+
+    /// `scriptPubKey` vectors of BIP-341: internal key, single leaf script (if any),
+    /// output script and its BIP-350 address.
+    const BIP341_VECTORS: [(&str, Option<&str>, &str, &str); 3] = [
+        (
+            "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+            None,
+            "512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+            "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5",
+        ),
+        (
+            "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+            Some("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac"),
+            "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+            "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586",
+        ),
+        (
+            "93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820",
+            Some("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac"),
+            "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+            "bc1punvppl2stp38f7kwv2u2spltjuvuaayuqsthe34hd2dyy5w4g58qqfuag5",
+        ),
+    ];
+
+    #[test]
+    fn p2tr_outputs_transfer_to_their_bech32m_address() {
+        let ctx = Context::empty();
+        for (_, _, script_pub_key, address) in BIP341_VECTORS {
+            match get_transfer_destination(script_pub_key, &Network::Bitcoin, &ctx) {
+                OrdinalInscriptionTransferDestination::Transferred(destination) => {
+                    assert_eq!(destination, address)
+                }
+                _ => panic!("{script_pub_key} not transferred to {address}"),
+            }
+        }
+    }
+
+    #[test]
+    fn p2tr_output_keys_commit_to_internal_key_and_script_tree() {
+        let secp = Secp256k1::verification_only();
+        for (internal_key, leaf_script, script_pub_key, address) in BIP341_VECTORS {
+            let internal_key = XOnlyPublicKey::from_str(internal_key).unwrap();
+            let merkle_root = leaf_script.map(|leaf_script| {
+                let leaf_script = ScriptBuf::from_hex(leaf_script).unwrap();
+                TapNodeHash::from(TapLeafHash::from_script(
+                    &leaf_script,
+                    LeafVersion::TapScript,
+                ))
+            });
+            let derived = Address::p2tr(&secp, internal_key, merkle_root, Network::Bitcoin);
+            assert_eq!(
+                derived.script_pubkey(),
+                ScriptBuf::from_hex(script_pub_key).unwrap()
+            );
+            assert_eq!(derived.to_string(), address);
+        }
+    }
+
+    #[test]
+    fn non_standard_outputs_are_burnt() {
+        let ctx = Context::empty();
+        // OP_RETURN "ord"
+        assert!(matches!(
+            get_transfer_destination("6a036f7264", &Network::Bitcoin, &ctx),
+            OrdinalInscriptionTransferDestination::Burnt(_)
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+use std::io::{Read, Write};
+
+use chainhook_sdk::utils::Context;
+use rocksdb::DB;
+
+use super::integrity::replay_sat_ranges;
+
+pub const UTXO_SET_SNAPSHOT_VERSION: u32 = 1;
+
+/// First item of a snapshot, followed by `utxo_count` `UtxoSatRanges` items.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoSetSnapshotHeader {
+    pub version: u32,
+    pub block_height: u64,
+    pub utxo_count: u64,
+}
+
+/// Sat ranges `[start, end)` held by a live UTXO. `txid` is truncated to its 8 first bytes,
+/// as in the compacted blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoSatRanges {
+    pub txid: [u8; 8],
+    pub vout: u16,
+    pub sat_ranges: Vec<(u64, u64)>,
+}
+
+/// Rebuilds the sat ranges of the UTXO set at `block_height` from the compacted blocks and
+/// writes them to `writer` as a CBOR sequence: a `UtxoSetSnapshotHeader`, then the UTXOs
+/// sorted by outpoint. Returns the number of UTXOs written.
+pub fn export_utxo_set<W: Write>(
+    block_height: u64,
+    blocks_db: &DB,
+    writer: &mut W,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let mut conflicts = 0;
+    let live = replay_sat_ranges(block_height, blocks_db, ctx, |conflict| {
+        ctx.try_log(|logger| {
+            warn!(
+                logger,
+                "Block #{}: transaction {}: {}",
+                conflict.block_height,
+                conflict.txid,
+                conflict.reason
+            )
+        });
+        conflicts += 1;
+    })?;
+    if conflicts > 0 {
+        return Err(format!(
+            "{conflicts} sat range conflicts found up to block #{block_height}, run `db check-integrity` for details"
+        ));
+    }
+    let mut utxos = live
+        .utxos
+        .into_iter()
+        .map(|((txid, vout), sat_ranges)| UtxoSatRanges {
+            txid,
+            vout,
+            sat_ranges,
+        })
+        .collect::<Vec<_>>();
+    utxos.sort_unstable_by_key(|utxo| (utxo.txid, utxo.vout));
+    write_utxo_set(block_height, &utxos, writer)
+}
+
+pub fn write_utxo_set<W: Write>(
+    block_height: u64,
+    utxos: &[UtxoSatRanges],
+    writer: &mut W,
+) -> Result<u64, String> {
+    let header = UtxoSetSnapshotHeader {
+        version: UTXO_SET_SNAPSHOT_VERSION,
+        block_height,
+        utxo_count: utxos.len() as u64,
+    };
+    ciborium::into_writer(&header, &mut *writer)
+        .map_err(|e| format!("unable to write snapshot header: {e}"))?;
+    for utxo in utxos.iter() {
+        ciborium::into_writer(utxo, &mut *writer)
+            .map_err(|e| format!("unable to write snapshot entry: {e}"))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("unable to write snapshot: {e}"))?;
+    Ok(header.utxo_count)
+}
+
+pub fn read_utxo_set<R: Read>(
+    reader: &mut R,
+) -> Result<(UtxoSetSnapshotHeader, Vec<UtxoSatRanges>), String> {
+    let header: UtxoSetSnapshotHeader = ciborium::from_reader(&mut *reader)
+        .map_err(|e| format!("unable to read snapshot header: {e}"))?;
+    if header.version != UTXO_SET_SNAPSHOT_VERSION {
+        return Err(format!(
+            "snapshot version {} not supported (expected {UTXO_SET_SNAPSHOT_VERSION})",
+            header.version
+        ));
+    }
+    let mut utxos = Vec::with_capacity(header.utxo_count as usize);
+    for _ in 0..header.utxo_count {
+        let utxo: UtxoSatRanges = ciborium::from_reader(&mut *reader)
+            .map_err(|e| format!("unable to read snapshot entry: {e}"))?;
+        utxos.push(utxo);
+    }
+    Ok((header, utxos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let utxos = vec![
+            UtxoSatRanges {
+                txid: [0, 1, 2, 3, 4, 5, 6, 7],
+                vout: 0,
+                sat_ranges: vec![(0, 5_000_000_000)],
+            },
+            UtxoSatRanges {
+                txid: [8, 9, 10, 11, 12, 13, 14, 15],
+                vout: 3,
+                sat_ranges: vec![(10, 20), (5_000_000_100, 5_000_000_150)],
+            },
+        ];
+        let mut bytes = vec![];
+        assert_eq!(write_utxo_set(170, &utxos, &mut bytes), Ok(2));
+
+        let (header, decoded) = read_utxo_set(&mut bytes.as_slice()).unwrap();
+        assert_eq!(header.block_height, 170);
+        assert_eq!(header.utxo_count, 2);
+        assert_eq!(decoded, utxos);
+    }
+}
@@ -1,31 +1,49 @@
 use std::{
+    cell::Cell,
     collections::{BTreeMap, BTreeSet, HashMap},
     io::{Read, Write},
+    ops::{Deref, DerefMut},
     path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use rand::{thread_rng, Rng};
 
-use rocksdb::{DBPinnableSlice, DB};
-use rusqlite::{Connection, OpenFlags, ToSql, Transaction};
+use rocksdb::{checkpoint::Checkpoint, DBPinnableSlice, IteratorMode, WriteBatch, DB};
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection, OpenFlags, Params, Statement, ToSql, Transaction,
+};
 use std::io::Cursor;
 
 use chainhook_sdk::{
     indexer::bitcoin::BitcoinBlockFullBreakdown,
     types::{
         BitcoinBlockData, BlockIdentifier, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
-        OrdinalInscriptionTransferData, TransactionIdentifier,
+        OrdinalInscriptionTransferData, OrdinalInscriptionTransferDestination,
+        TransactionIdentifier,
     },
     utils::Context,
 };
 
 use crate::{
-    core::protocol::inscription_parsing::{
-        get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
+    config::ResourcesConfig,
+    core::compute_transaction_vbytes,
+    core::error::ProcessingError,
+    core::protocol::{
+        brc20::Brc20Operation,
+        inscription_parsing::{
+            get_inscription_metadata_bytes, get_inscriptions_revealed_in_block,
+            get_inscriptions_transferred_in_block,
+        },
+        satoshi_tracking::is_burn_transfer,
+    },
+    ord::{
+        rarity::{classify_sat_rarity, SatRarity},
+        sat::Sat,
     },
-    ord::sat::Sat,
 };
 
 pub fn get_default_ordhook_db_file_path(base_dir: &PathBuf) -> PathBuf {
@@ -45,18 +63,346 @@ pub fn open_readonly_ordhook_db_conn(
 
 pub fn open_readwrite_ordhook_db_conn(
     base_dir: &PathBuf,
+    sqlite_wal: bool,
     ctx: &Context,
 ) -> Result<Connection, String> {
     let db_path = get_default_ordhook_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(&db_path, ctx);
+    set_journal_mode(&conn, sqlite_wal)?;
+    migrate_ordhook_db(&conn, ctx)?;
     Ok(conn)
 }
 
-pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
+/// Version of the hord.sqlite schema created by `initialize_ordhook_db`. A schema change
+/// bumps it and appends the upgrade from the previous version to `SCHEMA_MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+type SchemaMigration = fn(&Connection, &Context) -> Result<(), String>;
+
+/// `SCHEMA_MIGRATIONS[n]` upgrades a database from version `n` to version `n + 1`.
+const SCHEMA_MIGRATIONS: [SchemaMigration; CURRENT_SCHEMA_VERSION as usize] =
+    [migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Upgrade databases created by earlier versions, one schema version at a time. Each upgrade
+/// is committed along with the version it leads to.
+pub fn migrate_ordhook_db(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    create_schema_version_table(conn, ctx)
+        .map_err(|e| format!("unable to migrate hord.sqlite: {e}"))?;
+    let schema_version = match get_schema_version(conn, ctx)? {
+        Some(schema_version) => schema_version,
+        // Fresh database, tables will be created by `initialize_ordhook_db`
+        None if !perform_query_exists(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'inscriptions'",
+            &[],
+            conn,
+            ctx,
+        ) =>
+        {
+            return set_schema_version(CURRENT_SCHEMA_VERSION, conn, ctx);
+        }
+        // Created before the schema versions were tracked
+        None => 0,
+    };
+    if schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "hord.sqlite schema version {schema_version} is newer than the version {CURRENT_SCHEMA_VERSION} supported by this build of ordhook, upgrade ordhook"
+        ));
+    }
+    for (version, migration) in SCHEMA_MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(schema_version as usize)
+    {
+        let next_version = version as u32 + 1;
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+        migration(&tx, ctx)?;
+        set_schema_version(next_version, &tx, ctx)?;
+        tx.commit()
+            .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+        ctx.try_log(|logger| {
+            info!(
+                logger,
+                "hord.sqlite migrated from schema version {version} to {next_version}"
+            )
+        });
+    }
+    Ok(())
+}
+
+fn create_schema_version_table(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    execute_query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+            version INTEGER NOT NULL
+        )",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Schema version of hord.sqlite, `None` until recorded by `migrate_ordhook_db`.
+pub fn get_schema_version(conn: &Connection, ctx: &Context) -> Result<Option<u32>, String> {
+    let mut stmt = prepare_query("SELECT version FROM schema_version WHERE id = 0", conn, ctx)
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(row) => row.get(0).map(Some).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+fn set_schema_version(version: u32, conn: &Connection, ctx: &Context) -> Result<(), String> {
+    execute_query(
+        "INSERT OR REPLACE INTO schema_version (id, version) VALUES (0, ?1)",
+        rusqlite::params![&version],
+        conn,
+        ctx,
+    )
+    .map_err(|e| {
+        format!(
+            "unable to record hord.sqlite schema version: {}",
+            e.to_string()
+        )
+    })?;
+    Ok(())
+}
+
+/// Columns, tables and indexes added before the schema versions were tracked. Databases may
+/// have been partially upgraded by earlier releases, statements must be idempotent.
+fn migrate_v0_to_v1(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    // Sat to inscription reverse index
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS index_inscriptions_on_ordinal_number ON inscriptions(ordinal_number);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    create_brc20_operations_table(conn, ctx)
+        .map_err(|e| format!("unable to migrate hord.sqlite: {e}"))?;
+    // Persisted inscription number cursors
+    create_sequence_state_table(conn, ctx)
+        .map_err(|e| format!("unable to migrate hord.sqlite: {e}"))?;
+    // Parent / children relationships
+    add_inscriptions_column_if_missing("parent_inscription_id", "TEXT", conn, ctx)?;
+    // Metaprotocols
+    add_inscriptions_column_if_missing("metaprotocol", "TEXT", conn, ctx)?;
+    for column in ["parent_inscription_id", "metaprotocol"] {
+        execute_query(
+            &format!(
+                "CREATE INDEX IF NOT EXISTS index_inscriptions_on_{column} ON inscriptions({column});"
+            ),
+            [],
+            conn,
+            ctx,
+        )
+        .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    }
+    // Cursed inscriptions, backfilled from their classic inscription numbers
+    if add_inscriptions_column_if_missing("is_cursed", "INTEGER NOT NULL DEFAULT 0", conn, ctx)? {
+        execute_query(
+            "UPDATE inscriptions SET is_cursed = 1 WHERE classic_inscription_number < 0",
+            [],
+            conn,
+            ctx,
+        )
+        .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    }
+    // Inscriptions content, only filled when `store_inscription_content` is enabled
+    add_inscriptions_column_if_missing("content", "BLOB", conn, ctx)?;
+    // Content compression, NULL for the content stored before this migration (uncompressed)
+    add_inscriptions_column_if_missing("content_encoding", "TEXT", conn, ctx)?;
+    // Content origin, NULL for the content inscribed in the envelope (see `ContentSource`)
+    add_inscriptions_column_if_missing("content_source", "TEXT", conn, ctx)?;
+    // Holders, only known for inscriptions revealed or transferred after this migration
+    add_inscriptions_column_if_missing("current_holder_address", "TEXT", conn, ctx)?;
+    // Fees paid by the genesis transactions
+    add_inscriptions_column_if_missing("fee", "INTEGER", conn, ctx)?;
+    add_inscriptions_column_if_missing("fee_rate", "REAL", conn, ctx)?;
+    // Content types, for statistics
+    add_inscriptions_column_if_missing("content_type", "TEXT", conn, ctx)?;
+    add_inscriptions_column_if_missing("content_length", "INTEGER", conn, ctx)?;
+    // Sat rarities, only known for inscriptions indexed after this migration
+    add_inscriptions_column_if_missing("rarity", "TEXT", conn, ctx)?;
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS index_inscriptions_on_rarity ON inscriptions(rarity);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    // Genesis transactions, backfilled from the inscription ids (`<txid>i<index>`)
+    if add_inscriptions_column_if_missing("genesis_tx_id", "TEXT", conn, ctx)? {
+        execute_query(
+            "UPDATE inscriptions SET genesis_tx_id = substr(inscription_id, 1, instr(inscription_id, 'i') - 1)",
+            [],
+            conn,
+            ctx,
+        )
+        .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    }
+    // Envelope metadata, only known for inscriptions indexed after this migration
+    add_inscriptions_column_if_missing("metadata_cbor", "BLOB", conn, ctx)?;
+    add_inscriptions_column_if_missing("metadata_json", "TEXT", conn, ctx)?;
+    // Sat names, backfilled from the ordinal numbers
+    if add_inscriptions_column_if_missing("sat_name", "TEXT", conn, ctx)? {
+        backfill_sat_names(conn, ctx)?;
+    }
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS index_inscriptions_on_sat_name ON inscriptions(sat_name);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    // Content hashes, only known for inscriptions indexed after this migration
+    add_inscriptions_column_if_missing("content_hash", "BLOB", conn, ctx)?;
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS index_inscriptions_on_content_hash ON inscriptions(content_hash);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    // Burns and output values, only known for locations indexed after this migration
+    add_column_if_missing(
+        "locations",
+        "is_burned",
+        "INTEGER NOT NULL DEFAULT 0",
+        conn,
+        ctx,
+    )?;
+    add_column_if_missing("locations", "output_value", "INTEGER", conn, ctx)?;
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS locations_indexed_on_is_burned ON locations(is_burned) WHERE is_burned = 1;",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    // Inscription number to inscription id lookups. Databases with duplicated numbers are
+    // left untouched, the lookups are still served by the non-unique index.
+    if let Err(e) = execute_query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS unique_index_inscriptions_on_jubilee_inscription_number ON inscriptions(jubilee_inscription_number);",
+        [],
+        conn,
+        ctx,
+    ) {
+        ctx.try_log(|logger| {
+            warn!(
+                logger,
+                "unable to enforce inscription numbers uniqueness in hord.sqlite: {}",
+                e.to_string()
+            )
+        });
+    }
+    Ok(())
+}
+
+/// Addresses receiving the sats, only known for locations indexed after this migration.
+fn migrate_v1_to_v2(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    add_column_if_missing("locations", "address", "TEXT", conn, ctx)?;
+    Ok(())
+}
+
+fn backfill_sat_names(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    let ordinal_numbers = {
+        let mut stmt = prepare_query(
+            "SELECT DISTINCT ordinal_number FROM inscriptions",
+            conn,
+            ctx,
+        )
+        .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, u64>(0))
+            .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?
+    };
+    let mut stmt = prepare_query(
+        "UPDATE inscriptions SET sat_name = ? WHERE ordinal_number = ?",
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    for ordinal_number in ordinal_numbers {
+        stmt.execute(rusqlite::params![
+            InscriptionData::display_name(ordinal_number),
+            ordinal_number
+        ])
+        .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Add a column to an inscriptions table created by a previous version.
+/// Returns true if the column was missing.
+fn add_inscriptions_column_if_missing(
+    column: &str,
+    definition: &str,
+    conn: &Connection,
+    ctx: &Context,
+) -> Result<bool, String> {
+    add_column_if_missing("inscriptions", column, definition, conn, ctx)
+}
+
+fn add_column_if_missing(
+    table: &str,
+    column: &str,
+    definition: &str,
+    conn: &Connection,
+    ctx: &Context,
+) -> Result<bool, String> {
+    if perform_query_exists(
+        &format!("SELECT name FROM pragma_table_info('{table}') WHERE name = ?"),
+        &[&column.to_sql().unwrap()],
+        conn,
+        ctx,
+    ) {
+        return Ok(false);
+    }
+    execute_query(
+        &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to migrate hord.sqlite: {}", e.to_string()))?;
+    Ok(true)
+}
+
+/// WAL + synchronous=NORMAL trades durability of the last commits on power loss for far fewer fsyncs.
+/// Without WAL, we are falling back on SQLite defaults (rollback journal + synchronous=FULL).
+pub fn set_journal_mode(conn: &Connection, sqlite_wal: bool) -> Result<(), String> {
+    let (journal_mode, synchronous) = match sqlite_wal {
+        true => ("WAL", "NORMAL"),
+        false => ("DELETE", "FULL"),
+    };
+    conn.pragma_update(None, "journal_mode", &journal_mode)
+        .map_err(|e| {
+            format!(
+                "unable to set journal_mode={journal_mode}: {}",
+                e.to_string()
+            )
+        })?;
+    conn.pragma_update(None, "synchronous", &synchronous)
+        .map_err(|e| format!("unable to set synchronous={synchronous}: {}", e.to_string()))?;
+    Ok(())
+}
+
+pub fn initialize_ordhook_db(base_dir: &PathBuf, sqlite_wal: bool, ctx: &Context) -> Connection {
     let db_path = get_default_ordhook_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(&db_path, ctx);
+    if let Err(e) = set_journal_mode(&conn, sqlite_wal) {
+        ctx.try_log(|logger| error!(logger, "{}", e));
+    }
     // TODO: introduce initial output
-    if let Err(e) = conn.execute(
+    if let Err(e) = execute_query(
         "CREATE TABLE IF NOT EXISTS inscriptions (
             inscription_id TEXT NOT NULL PRIMARY KEY,
             input_index INTEGER NOT NULL,
@@ -64,11 +410,30 @@ pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
             ordinal_number INTEGER NOT NULL,
             jubilee_inscription_number INTEGER NOT NULL,
             classic_inscription_number INTEGER NOT NULL,
+            parent_inscription_id TEXT,
+            metaprotocol TEXT,
+            is_cursed INTEGER NOT NULL DEFAULT 0,
+            content BLOB,
+            content_encoding TEXT,
+            content_source TEXT,
+            current_holder_address TEXT,
+            fee INTEGER,
+            fee_rate REAL,
+            content_type TEXT,
+            content_length INTEGER,
+            rarity TEXT,
+            content_hash BLOB,
+            genesis_tx_id TEXT,
+            metadata_cbor BLOB,
+            metadata_json TEXT,
+            sat_name TEXT,
             CONSTRAINT inscription_id_uniqueness UNIQUE (inscription_id),
             CONSTRAINT jubilee_inscription_number_uniqueness UNIQUE (inscription_id),
             CONSTRAINT classic_inscription_number_uniqueness UNIQUE (inscription_id)
         )",
         [],
+        &conn,
+        ctx,
     ) {
         ctx.try_log(|logger| {
             warn!(
@@ -78,43 +443,109 @@ pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
             )
         });
     } else {
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS index_inscriptions_on_ordinal_number ON inscriptions(ordinal_number);",
             [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS index_inscriptions_on_sat_name ON inscriptions(sat_name);",
+            [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS index_inscriptions_on_jubilee_inscription_number ON inscriptions(jubilee_inscription_number);",
             [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+
+        if let Err(e) = execute_query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS unique_index_inscriptions_on_jubilee_inscription_number ON inscriptions(jubilee_inscription_number);",
+            [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
 
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS index_inscriptions_on_classic_inscription_number ON inscriptions(classic_inscription_number);",
             [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
 
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS index_inscriptions_on_block_height ON inscriptions(block_height);",
             [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS index_inscriptions_on_parent_inscription_id ON inscriptions(parent_inscription_id);",
+            [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS index_inscriptions_on_metaprotocol ON inscriptions(metaprotocol);",
+            [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS index_inscriptions_on_rarity ON inscriptions(rarity);",
+            [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS index_inscriptions_on_content_hash ON inscriptions(content_hash);",
+            [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
     }
-    if let Err(e) = conn.execute(
+    if let Err(e) = execute_query(
         "CREATE TABLE IF NOT EXISTS locations (
             ordinal_number INTEGER NOT NULL,
             block_height INTEGER NOT NULL,
             tx_index INTEGER NOT NULL,
             outpoint_to_watch TEXT NOT NULL,
             offset INTEGER NOT NULL,
+            is_burned INTEGER NOT NULL DEFAULT 0,
+            output_value INTEGER,
+            address TEXT,
             CONSTRAINT ordinal_number_outpoint_to_watch_offset_uniqueness UNIQUE (ordinal_number, outpoint_to_watch)
         )",
         [],
+        &conn,
+        ctx,
     ) {
         ctx.try_log(|logger| {
             warn!(
@@ -124,27 +555,41 @@ pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
             )
         });
     } else {
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS locations_indexed_on_block_height ON locations(block_height);",
             [],
+            &conn,
+            ctx,
+        ) {
+            ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
+        }
+        if let Err(e) = execute_query(
+            "CREATE INDEX IF NOT EXISTS locations_indexed_on_is_burned ON locations(is_burned) WHERE is_burned = 1;",
+            [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS locations_indexed_on_outpoint_to_watch ON locations(outpoint_to_watch);",
             [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS locations_indexed_on_ordinal_number ON locations(ordinal_number);",
             [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
     }
 
-    if let Err(e) = conn.execute(
+    if let Err(e) = execute_query(
         "CREATE TABLE IF NOT EXISTS sequence_metadata (
             block_height INTEGER NOT NULL,
             nth_classic_pos_number INTEGER NOT NULL,
@@ -152,6 +597,8 @@ pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
             nth_jubilee_number INTEGER NOT NULL
         )",
         [],
+        &conn,
+        ctx,
     ) {
         ctx.try_log(|logger| {
             warn!(
@@ -161,17 +608,76 @@ pub fn initialize_ordhook_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
             )
         });
     } else {
-        if let Err(e) = conn.execute(
+        if let Err(e) = execute_query(
             "CREATE INDEX IF NOT EXISTS sequence_metadata_indexed_on_block_height ON sequence_metadata(block_height);",
             [],
+            &conn,
+            ctx,
         ) {
             ctx.try_log(|logger| warn!(logger, "unable to create hord.sqlite: {}", e.to_string()));
         }
     }
 
+    if let Err(e) = create_brc20_operations_table(&conn, ctx) {
+        ctx.try_log(|logger| warn!(logger, "Unable to create table brc20_operations: {e}"));
+    }
+
+    if let Err(e) = create_sequence_state_table(&conn, ctx) {
+        ctx.try_log(|logger| warn!(logger, "Unable to create table sequence_state: {e}"));
+    }
+
     conn
 }
 
+/// Single row table holding the last inscription numbers assigned, written in the same
+/// transaction as the inscriptions they were assigned to.
+fn create_sequence_state_table(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    execute_query(
+        "CREATE TABLE IF NOT EXISTS sequence_state (
+            id INTEGER NOT NULL PRIMARY KEY CHECK (id = 0),
+            block_height INTEGER NOT NULL,
+            nth_classic_pos_number INTEGER,
+            nth_classic_neg_number INTEGER,
+            nth_jubilee_number INTEGER
+        )",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn create_brc20_operations_table(conn: &Connection, ctx: &Context) -> Result<(), String> {
+    execute_query(
+        "CREATE TABLE IF NOT EXISTS brc20_operations (
+            inscription_id TEXT NOT NULL PRIMARY KEY,
+            block_height INTEGER NOT NULL,
+            operation TEXT NOT NULL,
+            tick TEXT NOT NULL
+        )",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS brc20_operations_indexed_on_tick ON brc20_operations(tick);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    execute_query(
+        "CREATE INDEX IF NOT EXISTS brc20_operations_indexed_on_block_height ON brc20_operations(block_height);",
+        [],
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 pub fn create_or_open_readwrite_db(db_path: &PathBuf, ctx: &Context) -> Connection {
     let open_flags = match std::fs::metadata(&db_path) {
         Err(e) => {
@@ -241,8 +747,6 @@ fn connection_with_defaults_pragma(conn: Connection) -> Connection {
         .expect("unable to enable mmap_size");
     conn.pragma_update(None, "cache_size", 512 * 1024 * 1024)
         .expect("unable to enable cache_size");
-    conn.pragma_update(None, "journal_mode", &"WAL")
-        .expect("unable to enable wal");
     conn
 }
 
@@ -252,7 +756,7 @@ fn get_default_ordhook_db_file_path_rocks_db(base_dir: &PathBuf) -> PathBuf {
     destination_path
 }
 
-fn rocks_db_default_options(ulimit: usize, memory_available: usize) -> rocksdb::Options {
+fn rocks_db_default_options(resources: &ResourcesConfig) -> rocksdb::Options {
     let mut opts = rocksdb::Options::default();
     // Per rocksdb's documentation:
     // If cache_index_and_filter_blocks is false (which is default),
@@ -263,13 +767,21 @@ fn rocks_db_default_options(ulimit: usize, memory_available: usize) -> rocksdb::
     // Setting max_open_files to -1 will get you the best possible performance.
     // Additional documentation:
     // https://betterprogramming.pub/navigating-the-minefield-of-rocksdb-configuration-options-246af1e1d3f9
-    // opts.set_write_buffer_size(64 * 1024 * 1024);
     // opts.set_blob_file_size(1 * 1024 * 1024 * 1024);
     // opts.set_target_file_size_base(64 * 1024 * 1024);
-    opts.set_max_open_files(ulimit as i32);
+    opts.set_max_open_files(resources.ulimit as i32);
     opts.create_if_missing(true);
     // opts.set_allow_mmap_reads(true);
 
+    // Larger / more numerous memtables absorb the bursts of writes performed during the initial sync
+    opts.set_write_buffer_size((resources.rocks_write_buffer_mb * 1024 * 1024) as usize);
+    opts.set_max_write_buffer_number(resources.rocks_max_write_buffers as i32);
+    let mut block_opts = rocksdb::BlockBasedOptions::default();
+    let block_cache =
+        rocksdb::Cache::new_lru_cache((resources.rocks_block_cache_mb * 1024 * 1024) as usize);
+    block_opts.set_block_cache(&block_cache);
+    opts.set_block_based_table_factory(&block_opts);
+
     // set_arena_block_size
 
     // opts.optimize_for_point_lookup(1 * 1024 * 1024 * 1024);
@@ -287,15 +799,17 @@ fn rocks_db_default_options(ulimit: usize, memory_available: usize) -> rocksdb::
 
 pub fn open_readonly_ordhook_db_conn_rocks_db(
     base_dir: &PathBuf,
-    ulimit: usize,
-    memory_available: usize,
+    resources: &ResourcesConfig,
     _ctx: &Context,
 ) -> Result<DB, String> {
     let path = get_default_ordhook_db_file_path_rocks_db(&base_dir);
-    let mut opts = rocks_db_default_options(ulimit, memory_available);
+    let mut opts = rocks_db_default_options(resources);
     opts.set_disable_auto_compactions(true);
     opts.set_max_background_jobs(0);
-    let db = DB::open_for_read_only(&opts, path, false)
+    // Databases created by previous versions don't have the height_to_hash column family
+    let column_families = DB::list_cf(&opts, &path)
+        .map_err(|e| format!("unable to read hord.rocksdb: {}", e.to_string()))?;
+    let db = DB::open_cf_for_read_only(&opts, path, column_families, false)
         .map_err(|e| format!("unable to read hord.rocksdb: {}", e.to_string()))?;
     Ok(db)
 }
@@ -303,16 +817,15 @@ pub fn open_readonly_ordhook_db_conn_rocks_db(
 pub fn open_ordhook_db_conn_rocks_db_loop(
     readwrite: bool,
     base_dir: &PathBuf,
-    ulimit: usize,
-    memory_available: usize,
+    resources: &ResourcesConfig,
     ctx: &Context,
 ) -> DB {
     let mut retries = 0;
     let blocks_db = loop {
         let res = if readwrite {
-            open_readwrite_ordhook_db_conn_rocks_db(&base_dir, ulimit, memory_available, &ctx)
+            open_readwrite_ordhook_db_conn_rocks_db(&base_dir, resources, &ctx)
         } else {
-            open_readonly_ordhook_db_conn_rocks_db(&base_dir, ulimit, memory_available, &ctx)
+            open_readonly_ordhook_db_conn_rocks_db(&base_dir, resources, &ctx)
         };
         match res {
             Ok(db) => break db,
@@ -335,25 +848,203 @@ pub fn open_ordhook_db_conn_rocks_db_loop(
 
 pub fn open_readwrite_ordhook_dbs(
     base_dir: &PathBuf,
-    ulimit: usize,
-    memory_available: usize,
+    resources: &ResourcesConfig,
+    sqlite_wal: bool,
     ctx: &Context,
 ) -> Result<(DB, Connection), String> {
-    let blocks_db =
-        open_ordhook_db_conn_rocks_db_loop(true, &base_dir, ulimit, memory_available, &ctx);
-    let inscriptions_db = open_readwrite_ordhook_db_conn(&base_dir, &ctx)?;
+    let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &base_dir, resources, &ctx);
+    let inscriptions_db = open_readwrite_ordhook_db_conn(&base_dir, sqlite_wal, &ctx)?;
     Ok((blocks_db, inscriptions_db))
 }
 
-fn open_readwrite_ordhook_db_conn_rocks_db(
+/// Copies hord.sqlite (through SQLite's online backup API) and hord.rocksdb (through a
+/// RocksDB checkpoint) into `output_dir` and returns the size of the snapshot in bytes.
+/// The copies are taken from the handles the indexer writes through: it has to be paused in
+/// the meantime, for both databases to be copied at the same block.
+pub fn snapshot_ordhook_db(
+    blocks_db: &DB,
+    inscriptions_db_conn: &Connection,
+    output_dir: &PathBuf,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let started_at = Instant::now();
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("unable to create {}: {e}", output_dir.display()))?;
+    ctx.try_log(|logger| info!(logger, "Copying hord.sqlite"));
+
+    let mut snapshot_conn = Connection::open(get_default_ordhook_db_file_path(output_dir))
+        .map_err(|e| format!("unable to create hord.sqlite snapshot: {e}"))?;
+    let backup = Backup::new(inscriptions_db_conn, &mut snapshot_conn)
+        .map_err(|e| format!("unable to start hord.sqlite backup: {e}"))?;
+    // Copying all the pages in a single step keeps the backup consistent with concurrent writers
+    match backup.step(-1) {
+        Ok(StepResult::Done) => {}
+        Ok(_) => return Err("hord.sqlite is busy, unable to complete backup".to_string()),
+        Err(e) => return Err(format!("unable to backup hord.sqlite: {e}")),
+    }
+    drop(backup);
+
+    ctx.try_log(|logger| info!(logger, "Creating hord.rocksdb checkpoint"));
+    let checkpoint = Checkpoint::new(blocks_db)
+        .map_err(|e| format!("unable to create hord.rocksdb checkpoint: {e}"))?;
+    checkpoint
+        .create_checkpoint(get_default_ordhook_db_file_path_rocks_db(output_dir))
+        .map_err(|e| format!("unable to create hord.rocksdb checkpoint: {e}"))?;
+
+    let size = get_dir_size(output_dir)?;
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Snapshot written to {} ({:.1} MB in {:.1}s)",
+            output_dir.display(),
+            size as f64 / (1024.0 * 1024.0),
+            started_at.elapsed().as_secs_f64()
+        )
+    });
+    Ok(size)
+}
+
+fn get_dir_size(path: &PathBuf) -> Result<u64, String> {
+    let mut size = 0;
+    let entries =
+        std::fs::read_dir(path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|e| format!("unable to read {}: {e}", entry.path().display()))?;
+        size += if metadata.is_dir() {
+            get_dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexStats {
+    pub name: String,
+    pub table: String,
+    /// `sqlite_stat1` entry: approximate rows in the index, then the average number of rows
+    /// matching each prefix of its columns. `None` until `ANALYZE` ran.
+    pub stat: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OrdhookDbStats {
+    pub rocksdb_size: u64,
+    /// hord.sqlite, including its write-ahead log
+    pub sqlite_size: u64,
+    pub tables: Vec<TableStats>,
+    pub indexes: Vec<IndexStats>,
+}
+
+/// Storage used by hord.rocksdb and hord.sqlite, read without writing to either of them.
+pub fn get_ordhook_db_stats(base_dir: &PathBuf, ctx: &Context) -> Result<OrdhookDbStats, String> {
+    let rocksdb_path = get_default_ordhook_db_file_path_rocks_db(base_dir);
+    let rocksdb_size = if rocksdb_path.exists() {
+        get_dir_size(&rocksdb_path)?
+    } else {
+        0
+    };
+    let sqlite_path = get_default_ordhook_db_file_path(base_dir);
+    let mut sqlite_size = 0;
+    for suffix in ["", "-wal", "-shm"] {
+        let path = format!("{}{suffix}", sqlite_path.display());
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            sqlite_size += metadata.len();
+        }
+    }
+
+    let conn = open_readonly_ordhook_db_conn(base_dir, ctx)?;
+    let table_names: Vec<String> = perform_query_set(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        &[],
+        &conn,
+        ctx,
+        |row| row.get(0).unwrap(),
+    );
+    let mut tables = vec![];
+    for name in table_names.into_iter() {
+        let rows: u64 = query_one_row(
+            &format!("SELECT COUNT(*) FROM \"{name}\""),
+            [],
+            &conn,
+            ctx,
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("unable to count rows of {name}: {e}"))?;
+        tables.push(TableStats { name, rows });
+    }
+
+    let has_stats = perform_query_exists(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sqlite_stat1'",
+        &[],
+        &conn,
+        ctx,
+    );
+    let stats: HashMap<String, String> = if has_stats {
+        perform_query_set(
+            "SELECT idx, stat FROM sqlite_stat1 WHERE idx IS NOT NULL",
+            &[],
+            &conn,
+            ctx,
+            |row| {
+                (
+                    row.get::<_, String>(0).unwrap(),
+                    row.get::<_, String>(1).unwrap(),
+                )
+            },
+        )
+        .into_iter()
+        .collect()
+    } else {
+        HashMap::new()
+    };
+    let indexes = perform_query_set(
+        "SELECT name, tbl_name FROM sqlite_master WHERE type = 'index' ORDER BY tbl_name, name",
+        &[],
+        &conn,
+        ctx,
+        |row| {
+            (
+                row.get::<_, String>(0).unwrap(),
+                row.get::<_, String>(1).unwrap(),
+            )
+        },
+    )
+    .into_iter()
+    .map(|(name, table)| IndexStats {
+        stat: stats.get(&name).cloned(),
+        name,
+        table,
+    })
+    .collect();
+
+    Ok(OrdhookDbStats {
+        rocksdb_size,
+        sqlite_size,
+        tables,
+        indexes,
+    })
+}
+
+pub fn open_readwrite_ordhook_db_conn_rocks_db(
     base_dir: &PathBuf,
-    ulimit: usize,
-    memory_available: usize,
+    resources: &ResourcesConfig,
     _ctx: &Context,
 ) -> Result<DB, String> {
     let path = get_default_ordhook_db_file_path_rocks_db(&base_dir);
-    let opts = rocks_db_default_options(ulimit, memory_available);
-    let db = DB::open(&opts, path)
+    let mut opts = rocks_db_default_options(resources);
+    opts.create_missing_column_families(true);
+    let db = DB::open_cf(&opts, path, [HEIGHT_TO_HASH_CF])
         .map_err(|e| format!("unable to read-write hord.rocksdb: {}", e.to_string()))?;
     Ok(db)
 }
@@ -394,10 +1085,43 @@ pub fn insert_entry_in_blocks(
     }
 }
 
-pub fn find_last_block_inserted(blocks_db: &DB) -> u32 {
-    match blocks_db.get(b"metadata::last_insert") {
-        Ok(Some(bytes)) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
-        _ => 0,
+/// Writes a batch of compacted blocks, along with the hashes (0x prefixed) of the blocks fully
+/// transmitted, in a single atomic write: after a crash, either all of them or none are stored.
+pub fn insert_entries_in_blocks(
+    compacted_blocks: &[(u64, Vec<u8>)],
+    block_hashes: &[(u64, String)],
+    update_tip: bool,
+    blocks_db_rw: &DB,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mut batch = WriteBatch::default();
+    for (block_height, block_bytes) in compacted_blocks.iter() {
+        batch.put((*block_height as u32).to_be_bytes(), block_bytes);
+    }
+    if let Some((tip_height, _)) = compacted_blocks.iter().max_by_key(|(height, _)| *height) {
+        if update_tip {
+            batch.put(b"metadata::last_insert", (*tip_height as u32).to_be_bytes());
+        }
+    }
+    if let Some(cf) = blocks_db_rw.cf_handle(HEIGHT_TO_HASH_CF) {
+        for (block_height, block_hash) in block_hashes.iter() {
+            match hex::decode(block_hash.trim_start_matches("0x")) {
+                Ok(hash_bytes) => batch.put_cf(cf, block_height.to_be_bytes(), hash_bytes),
+                Err(e) => {
+                    ctx.try_log(|logger| error!(logger, "invalid block hash {block_hash}: {e}"))
+                }
+            }
+        }
+    }
+    blocks_db_rw
+        .write(batch)
+        .map_err(|e| format!("unable to write compacted blocks to hord.rocksdb: {e}"))
+}
+
+pub fn find_last_block_inserted(blocks_db: &DB) -> u32 {
+    match blocks_db.get(b"metadata::last_insert") {
+        Ok(Some(bytes)) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        _ => 0,
     }
 }
 
@@ -475,6 +1199,19 @@ pub fn run_compaction(blocks_db_rw: &DB, lim: u32) {
     let _ = blocks_db_rw.compact_range(Some(&gen), Some(&lim.to_be_bytes()));
 }
 
+/// Compacts the `slice` blocks stored from `start`, returning the height to resume from, or `None`
+/// once the last block inserted (and the metadata keys sorted after it) has been compacted.
+pub fn run_compaction_slice(blocks_db_rw: &DB, start: u32, slice: u32) -> Option<u32> {
+    let end = start.saturating_add(slice);
+    if end > find_last_block_inserted(blocks_db_rw) {
+        blocks_db_rw.compact_range(Some(start.to_be_bytes()), None::<&[u8]>);
+        None
+    } else {
+        blocks_db_rw.compact_range(Some(start.to_be_bytes()), Some(end.to_be_bytes()));
+        Some(end)
+    }
+}
+
 pub fn find_missing_blocks(blocks_db: &DB, start: u32, end: u32, ctx: &Context) -> Vec<u32> {
     let mut missing_blocks = vec![];
     for i in start..=end {
@@ -489,6 +1226,58 @@ pub fn remove_entry_from_blocks(block_height: u32, blocks_db_rw: &DB, ctx: &Cont
     if let Err(e) = blocks_db_rw.delete(block_height.to_be_bytes()) {
         ctx.try_log(|logger| error!(logger, "{}", e.to_string()));
     }
+    delete_block_hash(block_height as u64, blocks_db_rw, ctx);
+}
+
+/// Column family mapping block heights (u64, big endian) to 32 bytes block hashes.
+pub const HEIGHT_TO_HASH_CF: &str = "height_to_hash";
+
+pub fn insert_block_hash(block_height: u64, block_hash: &str, blocks_db_rw: &DB, ctx: &Context) {
+    let Some(cf) = blocks_db_rw.cf_handle(HEIGHT_TO_HASH_CF) else {
+        return;
+    };
+    let hash_bytes = match hex::decode(block_hash.trim_start_matches("0x")) {
+        Ok(hash_bytes) => hash_bytes,
+        Err(e) => {
+            ctx.try_log(|logger| error!(logger, "invalid block hash {block_hash}: {e}"));
+            return;
+        }
+    };
+    if let Err(e) = blocks_db_rw.put_cf(cf, block_height.to_be_bytes(), hash_bytes) {
+        ctx.try_log(|logger| {
+            error!(
+                logger,
+                "unable to insert hash of block {block_height}: {}",
+                e.to_string()
+            )
+        });
+    }
+}
+
+/// Hash (0x prefixed) of the block stored at a given height, if known.
+pub fn find_block_hash_at_block_height(block_height: u64, blocks_db: &DB) -> Option<String> {
+    let cf = blocks_db.cf_handle(HEIGHT_TO_HASH_CF)?;
+    match blocks_db.get_cf(cf, block_height.to_be_bytes()) {
+        Ok(Some(hash_bytes)) => Some(format!("0x{}", hex::encode(hash_bytes))),
+        _ => None,
+    }
+}
+
+/// Highest height in the hash index, along with the hash (0x prefixed) of its block.
+pub fn find_last_block_hash(blocks_db: &DB) -> Option<(u64, String)> {
+    let cf = blocks_db.cf_handle(HEIGHT_TO_HASH_CF)?;
+    let (key, hash_bytes) = blocks_db.iterator_cf(cf, IteratorMode::End).next()?.ok()?;
+    let block_height = u64::from_be_bytes(key.as_ref().try_into().ok()?);
+    Some((block_height, format!("0x{}", hex::encode(hash_bytes))))
+}
+
+pub fn delete_block_hash(block_height: u64, blocks_db_rw: &DB, ctx: &Context) {
+    let Some(cf) = blocks_db_rw.cf_handle(HEIGHT_TO_HASH_CF) else {
+        return;
+    };
+    if let Err(e) = blocks_db_rw.delete_cf(cf, block_height.to_be_bytes()) {
+        ctx.try_log(|logger| error!(logger, "{}", e.to_string()));
+    }
 }
 
 pub fn delete_blocks_in_block_range(
@@ -506,19 +1295,176 @@ pub fn delete_blocks_in_block_range(
         .expect("unable to insert metadata");
 }
 
+/// Delete the compacted blocks stored below `before_block`, then compact the deleted range
+/// to reclaim disk space. Block hashes and hord.sqlite entries are left untouched.
+pub fn prune_blocks_before(
+    before_block: u32,
+    blocks_db_rw: &DB,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mut batch = WriteBatch::default();
+    batch.delete_range(0u32.to_be_bytes(), before_block.to_be_bytes());
+    blocks_db_rw
+        .write(batch)
+        .map_err(|e| format!("unable to prune hord.rocksdb: {e}"))?;
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Compacting hord.rocksdb below block #{before_block}"
+        )
+    });
+    blocks_db_rw.compact_range(Some(0u32.to_be_bytes()), Some(before_block.to_be_bytes()));
+    Ok(())
+}
+
+/// `metadata_cbor` holds the envelope metadata bytes, stored as is next to their JSON decoding.
 pub fn insert_entry_in_inscriptions(
     inscription_data: &OrdinalInscriptionRevealData,
+    metadata_cbor: Option<&[u8]>,
     block_identifier: &BlockIdentifier,
+    fee_rate: f64,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) {
-    while let Err(e) = inscriptions_db_conn_rw.execute(
-        "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        rusqlite::params![&inscription_data.inscription_id, &inscription_data.ordinal_number, &inscription_data.inscription_number.jubilee, &inscription_data.inscription_number.classic, &block_identifier.index, &inscription_data.inscription_input_index],
-    ) {
-        ctx.try_log(|logger| warn!(logger, "unable to insert inscription in hord.sqlite: {} - {:?}", e.to_string(), inscription_data));
-        std::thread::sleep(std::time::Duration::from_secs(1));
+) -> Result<(), ProcessingError> {
+    let genesis_tx_id = inscription_data
+        .inscription_id
+        .split_once('i')
+        .map_or(inscription_data.inscription_id.as_str(), |(txid, _)| txid);
+    let metadata_json = inscription_data.metadata.as_ref().map(|m| m.to_string());
+    execute_query(
+        "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index, parent_inscription_id, metaprotocol, is_cursed, current_holder_address, fee, fee_rate, content_type, content_length, rarity, genesis_tx_id, metadata_cbor, metadata_json, sat_name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        rusqlite::params![&inscription_data.inscription_id, &inscription_data.ordinal_number, &inscription_data.inscription_number.jubilee, &inscription_data.inscription_number.classic, &block_identifier.index, &inscription_data.inscription_input_index, &inscription_data.parent, &inscription_data.metaprotocol, &inscription_data.curse_type.is_some(), &inscription_data.inscriber_address, &inscription_data.inscription_fee, &fee_rate, &inscription_data.content_type, &inscription_data.content_length, classify_sat_rarity(inscription_data.ordinal_number).as_str(), genesis_tx_id, metadata_cbor, metadata_json, InscriptionData::display_name(inscription_data.ordinal_number)],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+pub fn insert_entry_in_brc20_operations(
+    inscription_id: &str,
+    block_height: u64,
+    operation: &Brc20Operation,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "INSERT OR REPLACE INTO brc20_operations (inscription_id, block_height, operation, tick) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![&inscription_id, &block_height, operation.get_operation_type(), operation.get_tick()],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// Content encoding stored alongside zstd compressed content.
+pub const ZSTD_CONTENT_ENCODING: &str = "zstd";
+
+/// Origin of the stored content of an inscription.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContentSource {
+    /// Bytes inscribed in the envelope
+    Inline,
+    /// Bytes fetched from an IPFS gateway, for the CID (and optional path) inscribed in the envelope
+    Ipfs(String),
+}
+
+const IPFS_CONTENT_SOURCE_PREFIX: &str = "ipfs://";
+
+impl ContentSource {
+    fn to_db_value(&self) -> Option<String> {
+        match self {
+            ContentSource::Inline => None,
+            ContentSource::Ipfs(cid) => Some(format!("{IPFS_CONTENT_SOURCE_PREFIX}{cid}")),
+        }
+    }
+
+    fn from_db_value(value: Option<String>) -> Result<ContentSource, String> {
+        match value {
+            None => Ok(ContentSource::Inline),
+            Some(value) => match value.strip_prefix(IPFS_CONTENT_SOURCE_PREFIX) {
+                Some(cid) => Ok(ContentSource::Ipfs(cid.to_string())),
+                None => Err(format!("unsupported content source {value}")),
+            },
+        }
+    }
+}
+
+/// Store the content of an inscription, compressed with zstd when `compress` is set.
+pub fn store_inscription_content(
+    inscription_id: &str,
+    content: &[u8],
+    content_source: &ContentSource,
+    compress: bool,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    let (content, content_encoding) = match compress {
+        true => (
+            zstd::encode_all(content, 0).map_err(|e| {
+                ProcessingError::SerializationError(format!(
+                    "unable to compress content of {inscription_id}: {}",
+                    e.to_string()
+                ))
+            })?,
+            Some(ZSTD_CONTENT_ENCODING),
+        ),
+        false => (content.to_vec(), None),
+    };
+    execute_query(
+        "UPDATE inscriptions SET content = ?1, content_encoding = ?2, content_source = ?3 WHERE inscription_id = ?4",
+        rusqlite::params![
+                &content,
+                &content_encoding,
+                &content_source.to_db_value(),
+                &inscription_id
+            ],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// Origin of the stored content of an inscription, `None` if the inscription is unknown or if
+/// its content was not stored.
+pub fn get_inscription_content_source(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<ContentSource>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content IS NOT NULL, content_source FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    let Some(row) = rows.next().map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let is_stored: bool = row.get(0).map_err(|e| e.to_string())?;
+    if !is_stored {
+        return Ok(None);
     }
+    let content_source: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+    ContentSource::from_db_value(content_source).map(Some)
+}
+
+/// Attach the SHA-256 hash of its content to an inscription already inserted.
+pub fn store_inscription_content_hash(
+    inscription_id: &str,
+    content_hash: &[u8; 32],
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "UPDATE inscriptions SET content_hash = ?1 WHERE inscription_id = ?2",
+        rusqlite::params![&content_hash[..], &inscription_id],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -526,37 +1472,67 @@ pub struct OrdinalLocation {
     pub offset: u64,
     pub block_height: u64,
     pub tx_index: usize,
+    /// The sat was sent to an OP_RETURN output
+    pub is_burned: bool,
+    /// Value of the output holding the sat, `None` when spent in fees
+    pub output_value: Option<u64>,
+    /// Address of the output holding the sat, `None` when spent in fees or burnt
+    pub address: Option<String>,
+}
+
+/// Fee paid per virtual byte by the genesis transaction of an inscription.
+pub fn get_inscription_fee_rate(
+    inscription_data: &OrdinalInscriptionRevealData,
+    block: &BitcoinBlockData,
+) -> f64 {
+    match block.transactions.get(inscription_data.tx_index) {
+        Some(tx) => inscription_data.inscription_fee as f64 / compute_transaction_vbytes(tx) as f64,
+        None => 0.0,
+    }
 }
 
 pub fn insert_entries_from_block_in_inscriptions(
     block: &BitcoinBlockData,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) {
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+) -> Result<(), ProcessingError> {
+    for inscription_data in get_inscriptions_revealed_in_block(&block, None).iter() {
+        let metadata_cbor = block
+            .transactions
+            .get(inscription_data.tx_index)
+            .and_then(|tx| get_inscription_metadata_bytes(tx, inscription_data));
         insert_entry_in_inscriptions(
             inscription_data,
+            metadata_cbor.as_deref(),
             &block.block_identifier,
+            get_inscription_fee_rate(inscription_data, block),
             inscriptions_db_conn_rw,
             &ctx,
-        );
+        )?;
     }
+    Ok(())
 }
 
 pub fn update_ordinals_db_with_block(
     block: &BitcoinBlockData,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) {
+) -> Result<(), ProcessingError> {
     let mut locations_to_insert = HashMap::new();
 
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+    for inscription_data in get_inscriptions_revealed_in_block(&block, None).iter() {
+        let metadata_cbor = block
+            .transactions
+            .get(inscription_data.tx_index)
+            .and_then(|tx| get_inscription_metadata_bytes(tx, inscription_data));
         insert_entry_in_inscriptions(
             inscription_data,
+            metadata_cbor.as_deref(),
             &block.block_identifier,
+            get_inscription_fee_rate(inscription_data, block),
             inscriptions_db_conn_rw,
             &ctx,
-        );
+        )?;
         let (tx, output_index, offset) =
             parse_satpoint_to_watch(&inscription_data.satpoint_post_inscription);
         let outpoint_to_watch = format_outpoint_to_watch(&tx, output_index);
@@ -566,6 +1542,10 @@ pub fn update_ordinals_db_with_block(
                 offset,
                 block_height: block.block_identifier.index,
                 tx_index: inscription_data.tx_index,
+                is_burned: false,
+                output_value: Some(inscription_data.inscription_output_value)
+                    .filter(|value| *value > 0),
+                address: inscription_data.inscriber_address.clone(),
             },
         );
         if let Some(prev_location) = insertion_res {
@@ -590,6 +1570,12 @@ pub fn update_ordinals_db_with_block(
                 offset,
                 block_height: block.block_identifier.index,
                 tx_index: transfer_data.tx_index,
+                is_burned: is_burn_transfer(
+                    &block.transactions[transfer_data.tx_index],
+                    transfer_data,
+                ),
+                output_value: transfer_data.post_transfer_output_value,
+                address: get_transfer_destination_address(transfer_data),
             },
         );
         if let Some(prev_location) = insertion_res {
@@ -611,15 +1597,16 @@ pub fn update_ordinals_db_with_block(
             location_data,
             &inscriptions_db_conn_rw,
             ctx,
-        );
+        )?;
     }
+    Ok(())
 }
 
 pub fn update_sequence_metadata_with_block(
     block: &BitcoinBlockData,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) {
+) -> Result<(), ProcessingError> {
     let mut nth_classic_pos_number = find_nth_classic_pos_number_at_block_height(
         &block.block_identifier.index,
         inscriptions_db_conn_rw,
@@ -638,20 +1625,84 @@ pub fn update_sequence_metadata_with_block(
         ctx,
     )
     .unwrap_or(0);
-    for inscription_data in get_inscriptions_revealed_in_block(&block).iter() {
+    for inscription_data in get_inscriptions_revealed_in_block(&block, None).iter() {
         nth_classic_pos_number =
             nth_classic_pos_number.max(inscription_data.inscription_number.classic);
         nth_classic_neg_number =
             nth_classic_neg_number.min(inscription_data.inscription_number.classic);
         nth_jubilee_number = nth_jubilee_number.max(inscription_data.inscription_number.jubilee);
     }
-    while let Err(e) = inscriptions_db_conn_rw.execute(
+    execute_query(
         "INSERT INTO sequence_metadata (block_height, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number) VALUES (?1, ?2, ?3, ?4)",
         rusqlite::params![&block.block_identifier.index, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number],
-    ) {
-        ctx.try_log(|logger| warn!(logger, "unable to update sequence_metadata: {}", e.to_string()));
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    }
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// Last inscription numbers assigned while indexing `block_height`, `None` for a sequence
+/// without any inscription yet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceState {
+    pub block_height: u64,
+    pub nth_classic_pos_number: Option<i64>,
+    pub nth_classic_neg_number: Option<i64>,
+    pub nth_jubilee_number: Option<i64>,
+}
+
+pub fn find_sequence_state(db_conn: &Connection, ctx: &Context) -> Option<SequenceState> {
+    let query = "SELECT block_height, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number FROM sequence_state WHERE id = 0";
+    perform_query_one(query, &[], db_conn, ctx, |row| SequenceState {
+        block_height: row.get(0).unwrap(),
+        nth_classic_pos_number: row.get(1).unwrap(),
+        nth_classic_neg_number: row.get(2).unwrap(),
+        nth_jubilee_number: row.get(3).unwrap(),
+    })
+}
+
+pub fn update_sequence_state(
+    state: &SequenceState,
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "INSERT OR REPLACE INTO sequence_state (id, block_height, nth_classic_pos_number, nth_classic_neg_number, nth_jubilee_number) VALUES (0, ?1, ?2, ?3, ?4)",
+        rusqlite::params![&state.block_height, &state.nth_classic_pos_number, &state.nth_classic_neg_number, &state.nth_jubilee_number],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// Drop the persisted sequence state, so that the next inscription numbers get recomputed
+/// from `sequence_metadata`.
+pub fn delete_sequence_state(
+    inscriptions_db_conn_rw: &Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "DELETE FROM sequence_state",
+        [],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// True if blocks strictly between `start_block` and `end_block` have been indexed.
+pub fn any_sequence_metadata_between(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> bool {
+    perform_query_exists(
+        "SELECT 1 FROM sequence_metadata WHERE block_height > ? AND block_height < ? LIMIT 1",
+        &[&start_block.to_sql().unwrap(), &end_block.to_sql().unwrap()],
+        db_conn,
+        ctx,
+    )
 }
 
 pub fn insert_ordinal_transfer_in_locations_tx(
@@ -660,112 +1711,404 @@ pub fn insert_ordinal_transfer_in_locations_tx(
     data: OrdinalLocation,
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) {
-    let mut retry = 0;
-    while let Err(e) = inscriptions_db_conn_rw.execute(
-        "INSERT INTO locations (ordinal_number, outpoint_to_watch, offset, block_height, tx_index) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![&ordinal_number, &outpoint_to_watch, data.offset, data.block_height, &data.tx_index],
-    ) {
-        retry += 1;
-        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
-        std::thread::sleep(std::time::Duration::from_secs(1));
-        if retry > 2 {
-            ctx.try_log(|logger| error!(logger, "unable to insert inscription in location in hord.sqlite: {}", e.to_string()));
-            return
-        }
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "INSERT INTO locations (ordinal_number, outpoint_to_watch, offset, block_height, tx_index, is_burned, output_value, address) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![&ordinal_number, &outpoint_to_watch, data.offset, data.block_height, &data.tx_index, data.is_burned, data.output_value, &data.address],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
+}
+
+/// Address receiving the sat of a transfer, `None` when spent in fees or burnt.
+pub fn get_transfer_destination_address(
+    transfer_data: &OrdinalInscriptionTransferData,
+) -> Option<String> {
+    match transfer_data.destination {
+        OrdinalInscriptionTransferDestination::Transferred(ref address) => Some(address.clone()),
+        OrdinalInscriptionTransferDestination::SpentInFees
+        | OrdinalInscriptionTransferDestination::Burnt(_) => None,
     }
 }
 
-pub fn perform_query_exists(
-    query: &str,
-    args: &[&dyn ToSql],
-    db_conn: &Connection,
+/// Record the address now holding the inscriptions of a sat, `None` when the sat was
+/// spent in fees or sent to a script without address.
+pub fn update_current_holder_address_tx(
+    ordinal_number: u64,
+    address: Option<&str>,
+    inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-) -> bool {
-    let res = perform_query(query, args, db_conn, ctx, |_| true, true);
-    !res.is_empty()
+) -> Result<(), ProcessingError> {
+    execute_query(
+        "UPDATE inscriptions SET current_holder_address = ?1 WHERE ordinal_number = ?2",
+        rusqlite::params![&address, &ordinal_number],
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    Ok(())
 }
 
-pub fn perform_query_one<F, T>(
-    query: &str,
-    args: &[&dyn ToSql],
-    db_conn: &Connection,
+/// Sats that moved between `start_block` and `end_block`, whose holder has to be restored
+/// when these blocks are rolled back.
+fn find_ordinal_numbers_moved_in_block_range(
+    start_block: u64,
+    end_block: u64,
+    inscriptions_db_conn: &Connection,
     ctx: &Context,
-    mapping_func: F,
-) -> Option<T>
-where
-    F: Fn(&rusqlite::Row<'_>) -> T,
-{
-    let mut res = perform_query(query, args, db_conn, ctx, mapping_func, true);
-    match res.is_empty() {
-        true => None,
-        false => Some(res.remove(0)),
-    }
+) -> Result<Vec<u64>, rusqlite::Error> {
+    let mut stmt = prepare_query(
+        "SELECT DISTINCT ordinal_number FROM locations WHERE block_height BETWEEN ?1 AND ?2",
+        inscriptions_db_conn,
+        ctx,
+    )?;
+    let rows = stmt.query_map(rusqlite::params![&start_block, &end_block], |row| {
+        row.get(0)
+    })?;
+    rows.collect()
 }
 
-pub fn perform_query_set<F, T>(
-    query: &str,
-    args: &[&dyn ToSql],
-    db_conn: &Connection,
+/// Point the holder of the inscriptions of `ordinal_numbers` at the address of their latest
+/// remaining location, once the locations written by rolled back blocks are deleted.
+fn restore_current_holder_addresses(
+    ordinal_numbers: &[u64],
+    inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
-    mapping_func: F,
-) -> Vec<T>
-where
-    F: Fn(&rusqlite::Row<'_>) -> T,
-{
-    perform_query(query, args, db_conn, ctx, mapping_func, false)
+) -> Result<(), rusqlite::Error> {
+    let mut stmt = prepare_query(
+        "UPDATE inscriptions SET current_holder_address = (
+            SELECT l.address FROM locations l WHERE l.ordinal_number = ?1
+            ORDER BY l.block_height DESC, l.tx_index DESC LIMIT 1
+        ) WHERE ordinal_number = ?1",
+        inscriptions_db_conn_rw,
+        ctx,
+    )?;
+    for ordinal_number in ordinal_numbers.iter() {
+        stmt.execute(rusqlite::params![ordinal_number])?;
+    }
+    Ok(())
 }
 
-fn perform_query<F, T>(
-    query: &str,
-    args: &[&dyn ToSql],
-    db_conn: &Connection,
-    ctx: &Context,
-    mapping_func: F,
-    stop_at_first: bool,
-) -> Vec<T>
-where
-    F: Fn(&rusqlite::Row<'_>) -> T,
-{
-    let mut results = vec![];
-    loop {
-        let mut stmt = match db_conn.prepare(query) {
-            Ok(stmt) => stmt,
-            Err(e) => {
-                ctx.try_log(|logger| {
-                    warn!(logger, "unable to prepare query {query}: {}", e.to_string())
-                });
-                std::thread::sleep(std::time::Duration::from_secs(5));
-                continue;
-            }
-        };
+/// Threshold above which the queries run through `prepare_query`, `execute_query` and
+/// `query_one_row` get their query plan logged, 0 when disabled.
+static SLOW_QUERY_THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
 
-        match stmt.query(args) {
-            Ok(mut rows) => loop {
-                match rows.next() {
-                    Ok(Some(row)) => {
-                        let r = mapping_func(row);
-                        results.push(r);
-                        if stop_at_first {
-                            break;
-                        }
-                    }
-                    Ok(None) => break,
-                    Err(e) => {
-                        ctx.try_log(|logger| {
-                            warn!(
-                                logger,
-                                "unable to iterate over results from {query}: {}",
-                                e.to_string()
-                            )
-                        });
-                        std::thread::sleep(std::time::Duration::from_secs(5));
-                        continue;
-                    }
-                }
-            },
-            Err(e) => {
-                ctx.try_log(|logger| {
+/// Enable the logging of the query plan of the hord.sqlite queries slower than `threshold_ms`
+/// (`storage.sqlite_slow_query_ms`), process wide.
+pub fn set_slow_query_threshold(threshold_ms: Option<u64>) {
+    SLOW_QUERY_THRESHOLD_MS.store(threshold_ms.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Log the output of `EXPLAIN QUERY PLAN` for `query` if it took longer than the slow query
+/// threshold to run.
+fn log_slow_query(query: &str, elapsed: Duration, db_conn: &Connection, ctx: &Context) {
+    let threshold_ms = SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed);
+    if threshold_ms == 0 || elapsed < Duration::from_millis(threshold_ms) {
+        return;
+    }
+    match explain_query_plan(query, db_conn) {
+        Ok(plan) => ctx.try_log(|logger| {
+            warn!(
+                logger,
+                "Slow query ({}ms): {query}\n{}",
+                elapsed.as_millis(),
+                plan.join("\n")
+            )
+        }),
+        Err(e) => ctx.try_log(|logger| {
+            warn!(
+                logger,
+                "Slow query ({}ms): {query} (unable to explain: {})",
+                elapsed.as_millis(),
+                e.to_string()
+            )
+        }),
+    }
+}
+
+/// Steps of the plan picked by sqlite for `query`. Parameters are left unbound, they don't
+/// change the plan.
+fn explain_query_plan(query: &str, db_conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = db_conn.prepare(&format!("EXPLAIN QUERY PLAN {query}"))?;
+    let rows = stmt.raw_query().mapped(|row| row.get::<_, String>(3));
+    rows.collect()
+}
+
+/// Prepared statement whose executions are timed when slow queries get reported: only the time
+/// spent in sqlite (`execute`, `query_row`, and each step of `query` / `query_map`) adds up, the
+/// caller's work between two rows does not. The total is reported to `log_slow_query` when the
+/// statement is dropped. Other `Statement` methods are reachable as is, untimed.
+pub struct TimedStatement<'conn> {
+    stmt: Statement<'conn>,
+    /// `None` when no slow query threshold is set: the statement is used as is
+    timer: Option<QueryTimer<'conn>>,
+}
+
+struct QueryTimer<'conn> {
+    query: String,
+    db_conn: &'conn Connection,
+    ctx: Context,
+    elapsed: Cell<Duration>,
+}
+
+impl QueryTimer<'_> {
+    fn start(timer: Option<&Self>) -> Option<Instant> {
+        timer.map(|_| Instant::now())
+    }
+
+    fn stop(timer: Option<&Self>, started_at: Option<Instant>) {
+        if let (Some(timer), Some(started_at)) = (timer, started_at) {
+            timer
+                .elapsed
+                .set(timer.elapsed.get() + started_at.elapsed());
+        }
+    }
+}
+
+impl<'conn> TimedStatement<'conn> {
+    pub fn execute<P: Params>(&mut self, params: P) -> rusqlite::Result<usize> {
+        let started_at = QueryTimer::start(self.timer.as_ref());
+        let res = self.stmt.execute(params);
+        QueryTimer::stop(self.timer.as_ref(), started_at);
+        res
+    }
+
+    pub fn query_row<T, P, F>(&mut self, params: P, f: F) -> rusqlite::Result<T>
+    where
+        P: Params,
+        F: FnOnce(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    {
+        let started_at = QueryTimer::start(self.timer.as_ref());
+        let res = self.stmt.query_row(params, f);
+        QueryTimer::stop(self.timer.as_ref(), started_at);
+        res
+    }
+
+    pub fn query<P: Params>(&mut self, params: P) -> rusqlite::Result<TimedRows<'_>> {
+        let timer = self.timer.as_ref();
+        let started_at = QueryTimer::start(timer);
+        let rows = self.stmt.query(params);
+        QueryTimer::stop(timer, started_at);
+        Ok(TimedRows { rows: rows?, timer })
+    }
+
+    pub fn query_map<T, P, F>(
+        &mut self,
+        params: P,
+        f: F,
+    ) -> rusqlite::Result<TimedMappedRows<'_, F>>
+    where
+        P: Params,
+        F: FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    {
+        Ok(TimedMappedRows {
+            rows: self.query(params)?,
+            map: f,
+        })
+    }
+}
+
+impl<'conn> Deref for TimedStatement<'conn> {
+    type Target = Statement<'conn>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stmt
+    }
+}
+
+impl<'conn> DerefMut for TimedStatement<'conn> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.stmt
+    }
+}
+
+impl<'conn> Drop for TimedStatement<'conn> {
+    fn drop(&mut self) {
+        if let Some(timer) = &self.timer {
+            log_slow_query(&timer.query, timer.elapsed.get(), timer.db_conn, &timer.ctx);
+        }
+    }
+}
+
+/// Rows of a `TimedStatement`, each step being timed.
+pub struct TimedRows<'stmt> {
+    rows: rusqlite::Rows<'stmt>,
+    timer: Option<&'stmt QueryTimer<'stmt>>,
+}
+
+impl<'stmt> TimedRows<'stmt> {
+    pub fn next(&mut self) -> rusqlite::Result<Option<&rusqlite::Row<'stmt>>> {
+        let started_at = QueryTimer::start(self.timer);
+        let row = self.rows.next();
+        QueryTimer::stop(self.timer, started_at);
+        row
+    }
+}
+
+/// Rows of a `TimedStatement` mapped by `F`, each step being timed.
+pub struct TimedMappedRows<'stmt, F> {
+    rows: TimedRows<'stmt>,
+    map: F,
+}
+
+impl<T, F> Iterator for TimedMappedRows<'_, F>
+where
+    F: FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+{
+    type Item = rusqlite::Result<T>;
+
+    fn next(&mut self) -> Option<rusqlite::Result<T>> {
+        let map = &mut self.map;
+        self.rows
+            .next()
+            .transpose()
+            .map(|row| row.and_then(|row| map(row)))
+    }
+}
+
+/// Prepare `query` on `db_conn`. Every hord.sqlite statement goes through this function (or
+/// `execute_query` / `query_one_row`) so that slow queries get reported. Without a slow query
+/// threshold, nothing is timed.
+pub fn prepare_query<'conn>(
+    query: &str,
+    db_conn: &'conn Connection,
+    ctx: &Context,
+) -> rusqlite::Result<TimedStatement<'conn>> {
+    if SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed) == 0 {
+        return Ok(TimedStatement {
+            stmt: db_conn.prepare(query)?,
+            timer: None,
+        });
+    }
+    let started_at = Instant::now();
+    let stmt = db_conn.prepare(query)?;
+    Ok(TimedStatement {
+        stmt,
+        timer: Some(QueryTimer {
+            query: query.to_string(),
+            db_conn,
+            ctx: ctx.clone(),
+            elapsed: Cell::new(started_at.elapsed()),
+        }),
+    })
+}
+
+/// Timed equivalent of `Connection::execute`.
+pub fn execute_query<P: Params>(
+    query: &str,
+    params: P,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> rusqlite::Result<usize> {
+    prepare_query(query, db_conn, ctx)?.execute(params)
+}
+
+/// Timed equivalent of `Connection::query_row`.
+pub fn query_one_row<T, P, F>(
+    query: &str,
+    params: P,
+    db_conn: &Connection,
+    ctx: &Context,
+    f: F,
+) -> rusqlite::Result<T>
+where
+    P: Params,
+    F: FnOnce(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+{
+    prepare_query(query, db_conn, ctx)?.query_row(params, f)
+}
+
+pub fn perform_query_exists(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+) -> bool {
+    let res = perform_query(query, args, db_conn, ctx, |_| true, true);
+    !res.is_empty()
+}
+
+pub fn perform_query_one<F, T>(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+    mapping_func: F,
+) -> Option<T>
+where
+    F: Fn(&rusqlite::Row<'_>) -> T,
+{
+    let mut res = perform_query(query, args, db_conn, ctx, mapping_func, true);
+    match res.is_empty() {
+        true => None,
+        false => Some(res.remove(0)),
+    }
+}
+
+pub fn perform_query_set<F, T>(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+    mapping_func: F,
+) -> Vec<T>
+where
+    F: Fn(&rusqlite::Row<'_>) -> T,
+{
+    perform_query(query, args, db_conn, ctx, mapping_func, false)
+}
+
+fn perform_query<F, T>(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+    mapping_func: F,
+    stop_at_first: bool,
+) -> Vec<T>
+where
+    F: Fn(&rusqlite::Row<'_>) -> T,
+{
+    let mut results = vec![];
+    loop {
+        // Each attempt is timed on its own by the `TimedStatement`
+        let mut stmt = match prepare_query(query, db_conn, ctx) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    warn!(logger, "unable to prepare query {query}: {}", e.to_string())
+                });
+                std::thread::sleep(std::time::Duration::from_secs(5));
+                continue;
+            }
+        };
+
+        match stmt.query(args) {
+            Ok(mut rows) => loop {
+                match rows.next() {
+                    Ok(Some(row)) => {
+                        let r = mapping_func(row);
+                        results.push(r);
+                        if stop_at_first {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        ctx.try_log(|logger| {
+                            warn!(
+                                logger,
+                                "unable to iterate over results from {query}: {}",
+                                e.to_string()
+                            )
+                        });
+                        std::thread::sleep(std::time::Duration::from_secs(5));
+                        continue;
+                    }
+                }
+            },
+            Err(e) => {
+                ctx.try_log(|logger| {
                     warn!(logger, "unable to execute query {query}: {}", e.to_string())
                 });
                 std::thread::sleep(std::time::Duration::from_secs(5));
@@ -792,6 +2135,30 @@ pub fn get_any_entry_in_ordinal_activities(
     perform_query_exists(query, args, db_conn, ctx)
 }
 
+/// Same check as `get_any_entry_in_ordinal_activities`, returning the id of an inscription
+/// revealed or transferred at `block_height` for reporting the conflict.
+pub fn get_conflicting_activity(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<String>, ProcessingError> {
+    let queries = [
+        "SELECT inscription_id FROM inscriptions WHERE block_height = ? LIMIT 1",
+        "SELECT i.inscription_id FROM locations AS l
+            INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+            WHERE l.block_height = ? LIMIT 1",
+    ];
+    for query in queries {
+        let mut stmt = prepare_query(query, db_conn, ctx)?;
+        let mut rows = stmt.query(rusqlite::params![&block_height])?;
+        if let Some(row) = rows.next()? {
+            let inscription_id: String = row.get(0)?;
+            return Ok(Some(inscription_id));
+        }
+    }
+    Ok(None)
+}
+
 pub fn find_latest_inscription_block_height(
     db_conn: &Connection,
     ctx: &Context,
@@ -805,6 +2172,80 @@ pub fn find_latest_inscription_block_height(
     Ok(entry)
 }
 
+/// Return the gaps (first and last numbers missing, inclusive) in the sequences assigned to the
+/// inscriptions revealed between `start_block` and `end_block`: jubilee numbers count up from 0,
+/// classic numbers of cursed inscriptions count down from -1. The numbers assigned before `start_block` are the
+/// starting points, so that consecutive checks cover the whole sequences without rescanning them.
+pub fn verify_sequence_integrity(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<(i64, i64)>, String> {
+    let mut gaps = find_gaps_in_inscription_numbers(
+        "jubilee_inscription_number",
+        false,
+        start_block,
+        end_block,
+        db_conn,
+        ctx,
+    )?;
+    gaps.extend(find_gaps_in_inscription_numbers(
+        "classic_inscription_number",
+        true,
+        start_block,
+        end_block,
+        db_conn,
+        ctx,
+    )?);
+    Ok(gaps)
+}
+
+fn find_gaps_in_inscription_numbers(
+    column: &str,
+    cursed: bool,
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<(i64, i64)>, String> {
+    let (filter, previous, order, step) = match cursed {
+        true => (format!("{column} < 0"), "MIN", "DESC", -1),
+        false => (format!("{column} >= 0"), "MAX", "ASC", 1),
+    };
+    let previous_number: Option<i64> = query_one_row(
+        &format!(
+            "SELECT {previous}({column}) FROM inscriptions WHERE {filter} AND block_height < ?"
+        ),
+        rusqlite::params![&start_block],
+        db_conn,
+        ctx,
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())?;
+    let mut expected = previous_number.map_or(if cursed { -1 } else { 0 }, |n| n + step);
+    let mut stmt = prepare_query(
+        &format!(
+            "SELECT DISTINCT {column} FROM inscriptions WHERE {filter} AND block_height BETWEEN ? AND ? ORDER BY {column} {order}"
+        ),
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&start_block, &end_block])
+        .map_err(|e| e.to_string())?;
+    let mut gaps = vec![];
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let inscription_number: i64 = row.get(0).map_err(|e| e.to_string())?;
+        if expected != inscription_number && (expected < inscription_number) != cursed {
+            gaps.push((expected, inscription_number - step));
+        }
+        expected = inscription_number + step;
+    }
+    Ok(gaps)
+}
+
 pub fn find_initial_inscription_transfer_data(
     ordinal_number: &u64,
     db_conn: &Connection,
@@ -877,7 +2318,11 @@ pub fn find_all_transfers_in_block(
     let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
 
     let mut stmt = loop {
-        match db_conn.prepare("SELECT ordinal_number, offset, outpoint_to_watch, tx_index FROM locations WHERE block_height = ? ORDER BY tx_index ASC")
+        match prepare_query(
+            "SELECT ordinal_number, offset, outpoint_to_watch, tx_index FROM locations WHERE block_height = ? ORDER BY tx_index ASC",
+            db_conn,
+            ctx,
+        )
         {
             Ok(stmt) => break stmt,
             Err(e) => {
@@ -1057,6 +2502,218 @@ pub fn compute_nth_classic_neg_number_at_block_height(
     })
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferEvent {
+    pub block_height: u64,
+    pub tx_id: String,
+    pub from_output: String,
+    pub to_output: String,
+    pub sat: u64,
+    /// The sat was sent to an OP_RETURN output
+    pub is_burned: bool,
+    /// Value of `to_output` in sats, `None` when spent in fees or indexed before it was tracked
+    pub output_value: Option<u64>,
+}
+
+/// Retrieve the transfers of an inscription since its genesis, ordered by block height.
+pub fn get_transfer_history(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<TransferEvent>, String> {
+    let Some(inscription) = get_inscription_by_id(inscription_id, db_conn, ctx)? else {
+        return Err(format!(
+            "unable to retrieve inscription for {inscription_id}"
+        ));
+    };
+    // The history starts at the genesis location of this inscription, which earlier movements
+    // of the sat in the same block must not precede.
+    let args: &[&dyn ToSql] = &[
+        &inscription.ordinal_number.to_sql().unwrap(),
+        &inscription.block_height.to_sql().unwrap(),
+        &inscription.genesis_tx_id.to_sql().unwrap(),
+    ];
+    let query = "SELECT outpoint_to_watch, tx_index FROM locations WHERE ordinal_number = ? AND block_height = ? AND substr(outpoint_to_watch, 1, instr(outpoint_to_watch, ':') - 1) = ?";
+    let Some((genesis_output, genesis_tx_index)) =
+        perform_query_one(query, args, db_conn, ctx, |row| {
+            let outpoint_to_watch: String = row.get(0).unwrap();
+            let tx_index: u64 = row.get(1).unwrap();
+            (outpoint_to_watch, tx_index)
+        })
+    else {
+        return Err(format!(
+            "unable to retrieve genesis location for {inscription_id}"
+        ));
+    };
+
+    let args: &[&dyn ToSql] = &[
+        &inscription.ordinal_number.to_sql().unwrap(),
+        &inscription.block_height.to_sql().unwrap(),
+        &genesis_tx_index.to_sql().unwrap(),
+    ];
+    let query = "SELECT l.outpoint_to_watch, l.block_height, l.is_burned, l.output_value, EXISTS (
+            SELECT 1 FROM inscriptions i
+            WHERE i.ordinal_number = l.ordinal_number
+            AND i.genesis_tx_id = substr(l.outpoint_to_watch, 1, instr(l.outpoint_to_watch, ':') - 1)
+        )
+        FROM locations l
+        WHERE l.ordinal_number = ?1
+        AND (l.block_height > ?2 OR (l.block_height = ?2 AND l.tx_index > ?3))
+        ORDER BY l.block_height ASC, l.tx_index ASC";
+    let locations = perform_query_set(query, args, db_conn, ctx, |row| {
+        let outpoint_to_watch: String = row.get(0).unwrap();
+        let block_height: u64 = row.get(1).unwrap();
+        let is_burned: bool = row.get(2).unwrap();
+        let output_value: Option<u64> = row.get(3).unwrap();
+        let is_genesis: bool = row.get(4).unwrap();
+        (
+            outpoint_to_watch,
+            block_height,
+            is_burned,
+            output_value,
+            is_genesis,
+        )
+    });
+    // Every following location is a transfer, except the genesis locations of the inscriptions
+    // revealed later on the same sat.
+    let mut transfers = vec![];
+    let mut from_output = genesis_output;
+    for (to_output, block_height, is_burned, output_value, is_genesis) in locations {
+        if !is_genesis {
+            let (transaction_identifier, _) = parse_outpoint_to_watch(&to_output);
+            transfers.push(TransferEvent {
+                block_height,
+                tx_id: transaction_identifier.hash,
+                from_output: from_output.clone(),
+                to_output: to_output.clone(),
+                sat: inscription.ordinal_number,
+                is_burned,
+                output_value,
+            });
+        }
+        from_output = to_output;
+    }
+    Ok(transfers)
+}
+
+/// Inscriptions sent to an OP_RETURN output, along with the height of the block burning them.
+pub fn get_burned_inscriptions(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<(String, u64)>, String> {
+    let mut stmt = prepare_query(
+        "SELECT i.inscription_id, MIN(l.block_height) FROM locations l
+            INNER JOIN inscriptions i ON i.ordinal_number = l.ordinal_number
+            WHERE l.is_burned = 1
+            GROUP BY i.inscription_id
+            ORDER BY MIN(l.block_height) ASC, i.inscription_id ASC",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| format!("unable to prepare query hord.sqlite: {}", e.to_string()))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("unable to read hord.sqlite rows: {}", e.to_string()))
+}
+
+/// Ordinal activity happening in a block, see `get_ordinals_activity_at_range`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrdinalActivityEvent {
+    Revealed(InscriptionData),
+    Transferred(TransferEvent),
+}
+
+impl OrdinalActivityEvent {
+    pub fn block_height(&self) -> u64 {
+        match self {
+            OrdinalActivityEvent::Revealed(inscription) => inscription.block_height,
+            OrdinalActivityEvent::Transferred(transfer) => transfer.block_height,
+        }
+    }
+}
+
+/// Retrieve the inscriptions revealed and transferred between `from` and `to` (inclusive),
+/// ordered by block height, with the reveals of a block before its transfers.
+pub fn get_ordinals_activity_at_range(
+    from: u64,
+    to: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<OrdinalActivityEvent>, String> {
+    let args: &[&dyn ToSql] = &[&from.to_sql().unwrap(), &to.to_sql().unwrap()];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE block_height BETWEEN ? AND ? ORDER BY block_height ASC, jubilee_inscription_number ASC";
+    let mut events = query_inscriptions_data(query, args, db_conn, ctx)?
+        .into_iter()
+        .map(OrdinalActivityEvent::Revealed)
+        .collect::<Vec<_>>();
+
+    // Every location is a transfer from the previous location of the sat, except the
+    // genesis locations of the inscriptions (matched through their reveal transaction).
+    let query = "SELECT l.ordinal_number, l.block_height, l.outpoint_to_watch, l.is_burned, l.output_value, (
+            SELECT p.outpoint_to_watch FROM locations p
+            WHERE p.ordinal_number = l.ordinal_number
+            AND (p.block_height < l.block_height OR (p.block_height = l.block_height AND p.tx_index < l.tx_index))
+            ORDER BY p.block_height DESC, p.tx_index DESC LIMIT 1
+        )
+        FROM locations l
+        WHERE l.block_height BETWEEN ? AND ?
+        AND NOT EXISTS (
+            SELECT 1 FROM inscriptions i
+            WHERE i.ordinal_number = l.ordinal_number
+            AND i.genesis_tx_id = substr(l.outpoint_to_watch, 1, instr(l.outpoint_to_watch, ':') - 1)
+        )
+        ORDER BY l.block_height ASC, l.tx_index ASC";
+    let mut stmt = prepare_query(query, db_conn, ctx)
+        .map_err(|e| format!("unable to prepare query hord.sqlite: {}", e.to_string()))?;
+    let rows = stmt
+        .query_map(args, |row| {
+            let sat: u64 = row.get(0)?;
+            let block_height: u64 = row.get(1)?;
+            let to_output: String = row.get(2)?;
+            let is_burned: bool = row.get(3)?;
+            let output_value: Option<u64> = row.get(4)?;
+            let from_output: Option<String> = row.get(5)?;
+            Ok((
+                sat,
+                block_height,
+                to_output,
+                is_burned,
+                output_value,
+                from_output,
+            ))
+        })
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))?;
+    for row in rows {
+        let (sat, block_height, to_output, is_burned, output_value, from_output) =
+            row.map_err(|e| format!("unable to read hord.sqlite rows: {}", e.to_string()))?;
+        let Some(from_output) = from_output else {
+            continue;
+        };
+        let (transaction_identifier, _) = parse_outpoint_to_watch(&to_output);
+        events.push(OrdinalActivityEvent::Transferred(TransferEvent {
+            block_height,
+            tx_id: transaction_identifier.hash,
+            from_output,
+            to_output,
+            sat,
+            is_burned,
+            output_value,
+        }));
+    }
+
+    // Stable sort: reveals and transfers keep their order within a block
+    events.sort_by_key(|event| {
+        let kind = match event {
+            OrdinalActivityEvent::Revealed(_) => 0,
+            OrdinalActivityEvent::Transferred(_) => 1,
+        };
+        (event.block_height(), kind)
+    });
+    Ok(events)
+}
+
 pub fn find_blessed_inscription_with_ordinal_number(
     ordinal_number: &u64,
     db_conn: &Connection,
@@ -1070,6 +2727,35 @@ pub fn find_blessed_inscription_with_ordinal_number(
     })
 }
 
+pub fn find_all_inscriptions_with_ordinal_number(
+    ordinal_number: &u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Vec<(TraversalResult, u64)> {
+    let args: &[&dyn ToSql] = &[&ordinal_number.to_sql().unwrap()];
+    let query = "SELECT classic_inscription_number, jubilee_inscription_number, inscription_id, block_height, input_index FROM inscriptions WHERE ordinal_number = ? ORDER BY block_height ASC";
+    perform_query_set(query, args, db_conn, ctx, |row| {
+        let inscription_number = OrdinalInscriptionNumber {
+            classic: row.get(0).unwrap(),
+            jubilee: row.get(1).unwrap(),
+        };
+        let inscription_id: String = row.get(2).unwrap();
+        let block_height: u64 = row.get(3).unwrap();
+        let inscription_input_index: usize = row.get(4).unwrap();
+        let (transaction_identifier_inscription, _) = parse_inscription_id(&inscription_id);
+        (
+            TraversalResult {
+                inscription_number,
+                ordinal_number: *ordinal_number,
+                inscription_input_index,
+                transaction_identifier_inscription,
+                transfers: 0,
+            },
+            block_height,
+        )
+    })
+}
+
 pub fn find_inscription_with_id(
     inscription_id: &str,
     db_conn: &Connection,
@@ -1120,6 +2806,672 @@ pub fn find_inscription_with_id(
     )))
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct InscriptionData {
+    pub inscription_id: String,
+    pub inscription_number: OrdinalInscriptionNumber,
+    pub ordinal_number: u64,
+    pub block_height: u64,
+    pub input_index: usize,
+    pub fee: Option<u64>,
+    pub fee_rate: Option<f64>,
+    pub genesis_tx_id: String,
+    /// Envelope metadata, decoded from CBOR
+    pub metadata_json: Option<String>,
+    /// Name of the sat the inscription was revealed on
+    pub sat_name: String,
+}
+
+impl InscriptionData {
+    /// Canonical name of a sat: its distance to the last sat mined, in bijective base-26
+    /// over `a..=z` (the first sat is `nvtdijuwxlp`, the last one is `a`).
+    pub fn display_name(sat: u64) -> String {
+        Sat(sat).name()
+    }
+}
+
+fn query_inscriptions_data(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let mut stmt = prepare_query(query, db_conn, ctx)
+        .map_err(|e| format!("unable to prepare query hord.sqlite: {}", e.to_string()))?;
+    let rows = stmt
+        .query_map(args, |row| {
+            Ok(InscriptionData {
+                inscription_id: row.get(0)?,
+                inscription_number: OrdinalInscriptionNumber {
+                    classic: row.get(1)?,
+                    jubilee: row.get(2)?,
+                },
+                ordinal_number: row.get(3)?,
+                block_height: row.get(4)?,
+                input_index: row.get(5)?,
+                fee: row.get(6)?,
+                fee_rate: row.get(7)?,
+                genesis_tx_id: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
+                metadata_json: row.get(9)?,
+                sat_name: InscriptionData::display_name(row.get(3)?),
+            })
+        })
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("unable to read hord.sqlite rows: {}", e.to_string()))
+}
+
+fn query_inscriptions_count(
+    query: &str,
+    args: &[&dyn ToSql],
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<u64, String> {
+    query_one_row(query, args, db_conn, ctx, |row| row.get(0))
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))
+}
+
+/// Retrieve a page of the inscriptions revealed in a block with a single query.
+pub fn get_inscriptions_for_block(
+    block_height: u64,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[
+        &block_height.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE block_height = ? ORDER BY jubilee_inscription_number ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Number of inscriptions revealed in a block, used to paginate `get_inscriptions_for_block`.
+pub fn count_inscriptions_for_block(
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
+    let query = "SELECT COUNT(*) FROM inscriptions WHERE block_height = ?";
+    query_inscriptions_count(query, args, db_conn, ctx)
+}
+
+pub fn get_inscription_by_id(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[&inscription_id.to_sql().unwrap()];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE inscription_id = ?";
+    let mut inscriptions = query_inscriptions_data(query, args, db_conn, ctx)?;
+    Ok(inscriptions.pop())
+}
+
+/// Retrieve an inscription from its number. Positive numbers are looked up in the jubilee
+/// numbering, negative ones in the classic numbering, where cursed inscriptions keep their
+/// negative numbers after the jubilee.
+pub fn get_inscription_by_number(
+    number: i64,
+    conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[&number.to_sql().unwrap()];
+    let query = match number < 0 {
+        true => "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE classic_inscription_number = ?",
+        false => "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE jubilee_inscription_number = ?",
+    };
+    let mut inscriptions = query_inscriptions_data(query, args, conn, ctx)?;
+    Ok(inscriptions.pop())
+}
+
+/// Retrieve the raw content bytes of an inscription, `None` if the inscription is unknown
+/// or if its content was not stored.
+pub fn get_inscription_content(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content, content_encoding FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(row) => {
+            let content: Option<Vec<u8>> = row.get(0).map_err(|e| e.to_string())?;
+            let content_encoding: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+            content
+                .map(|content| decode_inscription_content(content, content_encoding))
+                .transpose()
+        }
+        None => Ok(None),
+    }
+}
+
+/// Undo the compression applied by `store_inscription_content`.
+fn decode_inscription_content(
+    content: Vec<u8>,
+    content_encoding: Option<String>,
+) -> Result<Vec<u8>, String> {
+    match content_encoding.as_deref() {
+        None => Ok(content),
+        Some(ZSTD_CONTENT_ENCODING) => zstd::decode_all(&content[..])
+            .map_err(|e| format!("unable to decompress content: {}", e.to_string())),
+        Some(encoding) => Err(format!("unsupported content encoding {encoding}")),
+    }
+}
+
+/// Envelope metadata of an inscription, as the CBOR bytes carried by its envelope.
+pub fn get_inscription_metadata_cbor(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut stmt = prepare_query(
+        "SELECT metadata_cbor FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(row) => row.get(0).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Retrieve the raw content bytes of an inscription along with its content type.
+pub fn get_inscription_content_with_type(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<(Vec<u8>, String)>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content, content_type, content_encoding FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(row) => {
+            let content: Option<Vec<u8>> = row.get(0).map_err(|e| e.to_string())?;
+            let content_type: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+            let content_encoding: Option<String> = row.get(2).map_err(|e| e.to_string())?;
+            content
+                .map(|content| {
+                    let content = decode_inscription_content(content, content_encoding)?;
+                    Ok((content, content_type.unwrap_or_default()))
+                })
+                .transpose()
+        }
+        None => Ok(None),
+    }
+}
+
+/// Retrieve the address currently holding an inscription.
+pub fn get_current_holder(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<String>, String> {
+    let mut stmt = prepare_query(
+        "SELECT current_holder_address FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    match rows.next().map_err(|e| e.to_string())? {
+        Some(row) => row.get(0).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Content types of the inscriptions revealed between `from` and `to` (inclusive), by inscription id.
+pub fn get_content_types_at_range(
+    from: u64,
+    to: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<HashMap<String, String>, String> {
+    let mut stmt = prepare_query(
+        "SELECT inscription_id, content_type FROM inscriptions WHERE block_height BETWEEN ? AND ? AND content_type IS NOT NULL",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![&from, &to], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentTypeStat {
+    pub content_type: String,
+    pub count: u64,
+    pub total_bytes: u64,
+}
+
+/// Number of inscriptions and cumulated content size per content type.
+pub fn get_content_type_stats(
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<ContentTypeStat>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content_type, COUNT(*), SUM(content_length) FROM inscriptions WHERE content_type IS NOT NULL GROUP BY content_type ORDER BY COUNT(*) DESC",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ContentTypeStat {
+                content_type: row.get(0)?,
+                count: row.get(1)?,
+                total_bytes: row.get::<_, Option<u64>>(2)?.unwrap_or(0),
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+/// Retrieve the SHA-256 hash of the content of an inscription, `None` when the inscription is
+/// unknown or was indexed before contents were hashed.
+pub fn get_inscription_content_hash(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<[u8; 32]>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content_hash FROM inscriptions WHERE inscription_id = ?",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(rusqlite::params![&inscription_id])
+        .map_err(|e| e.to_string())?;
+    let Some(row) = rows.next().map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let content_hash: Option<Vec<u8>> = row.get(0).map_err(|e| e.to_string())?;
+    content_hash
+        .map(|content_hash| {
+            content_hash
+                .try_into()
+                .map_err(|_| "invalid content hash in hord.sqlite".to_string())
+        })
+        .transpose()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateGroup {
+    pub content_hash: [u8; 32],
+    pub inscription_ids: Vec<String>,
+}
+
+/// Retrieve the groups of inscriptions sharing the same content, inscriptions being ordered
+/// by inscription number within a group.
+pub fn get_duplicate_inscriptions(
+    conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let mut stmt = prepare_query(
+        "SELECT content_hash, inscription_id FROM inscriptions WHERE content_hash IN (SELECT content_hash FROM inscriptions WHERE content_hash IS NOT NULL GROUP BY content_hash HAVING COUNT(*) > 1) ORDER BY content_hash ASC, jubilee_inscription_number ASC",
+        conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    let mut groups: Vec<DuplicateGroup> = vec![];
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let content_hash: Vec<u8> = row.get(0).map_err(|e| e.to_string())?;
+        let content_hash: [u8; 32] = content_hash
+            .try_into()
+            .map_err(|_| "invalid content hash in hord.sqlite".to_string())?;
+        let inscription_id: String = row.get(1).map_err(|e| e.to_string())?;
+        match groups.last_mut() {
+            Some(group) if group.content_hash == content_hash => {
+                group.inscription_ids.push(inscription_id)
+            }
+            _ => groups.push(DuplicateGroup {
+                content_hash,
+                inscription_ids: vec![inscription_id],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+/// Retrieve the ids of the inscriptions revealed with `inscription_id` as parent.
+pub fn get_children(
+    inscription_id: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<String>, String> {
+    let mut stmt = prepare_query(
+        "SELECT inscription_id FROM inscriptions WHERE parent_inscription_id = ? ORDER BY block_height ASC",
+        db_conn,
+        ctx,
+    )
+    .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params![&inscription_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collection {
+    pub parent: InscriptionData,
+    /// Descendants of the parent, generation by generation
+    pub children: Vec<InscriptionData>,
+}
+
+/// Retrieve the inscriptions descending from `parent_id`, up to `depth` generations below the
+/// direct children (0 only returns the direct children). `None` when the parent is unknown.
+pub fn get_collection(
+    parent_id: &str,
+    depth: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<Collection>, String> {
+    let Some(parent) = get_inscription_by_id(parent_id, db_conn, ctx)? else {
+        return Ok(None);
+    };
+    let args: &[&dyn ToSql] = &[&parent_id.to_sql().unwrap(), &depth.to_sql().unwrap()];
+    let query = "WITH RECURSIVE descendants(inscription_id, generation) AS (
+            SELECT inscription_id, 0 FROM inscriptions WHERE parent_inscription_id = ?
+            UNION ALL
+            SELECT i.inscription_id, d.generation + 1 FROM inscriptions i
+            JOIN descendants d ON i.parent_inscription_id = d.inscription_id
+            WHERE d.generation < ?
+        )
+        SELECT i.inscription_id, i.classic_inscription_number, i.jubilee_inscription_number, i.ordinal_number, i.block_height, i.input_index, i.fee, i.fee_rate, i.genesis_tx_id, i.metadata_json
+        FROM descendants d JOIN inscriptions i ON i.inscription_id = d.inscription_id
+        ORDER BY d.generation ASC, i.jubilee_inscription_number ASC";
+    let children = query_inscriptions_data(query, args, db_conn, ctx)?;
+    Ok(Some(Collection { parent, children }))
+}
+
+/// Retrieve a page of the inscriptions tagged with the metaprotocol `proto`, ordered by block height.
+pub fn get_inscriptions_by_metaprotocol(
+    proto: &str,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[
+        &proto.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE metaprotocol = ? ORDER BY block_height ASC, jubilee_inscription_number ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Retrieve a page of the inscriptions revealed on sats of a given rarity, ordered by block height.
+pub fn get_inscriptions_by_rarity(
+    rarity: SatRarity,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[
+        &rarity.as_str().to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE rarity = ? ORDER BY block_height ASC, jubilee_inscription_number ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Retrieve a page of the inscriptions revealed by a transaction, ordered by inscription number.
+pub fn get_inscriptions_by_tx(
+    tx_id: &str,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let tx_id = tx_id.trim_start_matches("0x");
+    // Inscription ids are `<txid>i<index>`: range scan on the primary key
+    let (first_id, last_id) = (format!("{tx_id}i"), format!("{tx_id}j"));
+    let args: &[&dyn ToSql] = &[
+        &first_id.to_sql().unwrap(),
+        &last_id.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE inscription_id >= ? AND inscription_id < ? ORDER BY jubilee_inscription_number ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Retrieve the first inscription revealed on a given sat.
+pub fn get_inscription_by_sat(
+    sat: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Option<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[&sat.to_sql().unwrap()];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE ordinal_number = ? ORDER BY block_height ASC, jubilee_inscription_number ASC LIMIT 1";
+    let mut inscriptions = query_inscriptions_data(query, args, db_conn, ctx)?;
+    Ok(inscriptions.pop())
+}
+
+/// Retrieve a page of the inscriptions sitting on the sats `start..=end` with a single query.
+pub fn get_inscriptions_for_sat_range(
+    start: u64,
+    end: u64,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[
+        &start.to_sql().unwrap(),
+        &end.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE ordinal_number BETWEEN ? AND ? ORDER BY ordinal_number ASC, block_height ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Retrieve a page of the inscriptions sitting on the sat named `sat_name`.
+pub fn get_inscriptions_for_sat_name(
+    sat_name: &str,
+    limit: u32,
+    offset: u32,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionData>, String> {
+    let args: &[&dyn ToSql] = &[
+        &sat_name.to_sql().unwrap(),
+        &limit.to_sql().unwrap(),
+        &offset.to_sql().unwrap(),
+    ];
+    let query = "SELECT inscription_id, classic_inscription_number, jubilee_inscription_number, ordinal_number, block_height, input_index, fee, fee_rate, genesis_tx_id, metadata_json FROM inscriptions WHERE sat_name = ? ORDER BY block_height ASC, jubilee_inscription_number ASC LIMIT ? OFFSET ?";
+    query_inscriptions_data(query, args, db_conn, ctx)
+}
+
+/// Number of inscriptions sitting on the sat named `sat_name`, used to paginate
+/// `get_inscriptions_for_sat_name`.
+pub fn count_inscriptions_for_sat_name(
+    sat_name: &str,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let args: &[&dyn ToSql] = &[&sat_name.to_sql().unwrap()];
+    let query = "SELECT COUNT(*) FROM inscriptions WHERE sat_name = ?";
+    query_inscriptions_count(query, args, db_conn, ctx)
+}
+
+/// Number of inscriptions sitting on the sats `start..=end`, used to paginate
+/// `get_inscriptions_for_sat_range`.
+pub fn count_inscriptions_for_sat_range(
+    start: u64,
+    end: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let args: &[&dyn ToSql] = &[&start.to_sql().unwrap(), &end.to_sql().unwrap()];
+    let query = "SELECT COUNT(*) FROM inscriptions WHERE ordinal_number BETWEEN ? AND ?";
+    query_inscriptions_count(query, args, db_conn, ctx)
+}
+
+/// Content and current location of an inscription, as streamed by
+/// `for_each_inscription_in_block_range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InscriptionState {
+    pub satpoint: Option<String>,
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    pub current_holder_address: Option<String>,
+}
+
+/// Stream the inscriptions revealed between `start_block` and `end_block` (included), along with their
+/// content type and current location, without loading the whole set in memory.
+pub fn for_each_inscription_in_block_range<F>(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+    mut handler: F,
+) -> Result<(), String>
+where
+    F: FnMut(InscriptionData, InscriptionState) -> Result<(), String>,
+{
+    let args: &[&dyn ToSql] = &[&start_block.to_sql().unwrap(), &end_block.to_sql().unwrap()];
+    let query = "SELECT i.inscription_id, i.classic_inscription_number, i.jubilee_inscription_number, i.ordinal_number, i.block_height, i.input_index, i.fee, i.fee_rate, i.genesis_tx_id, i.metadata_json, i.content_type, i.content_length, i.current_holder_address, (SELECT l.outpoint_to_watch || ':' || l.offset FROM locations l WHERE l.ordinal_number = i.ordinal_number ORDER BY l.block_height DESC, l.tx_index DESC LIMIT 1) FROM inscriptions i WHERE i.block_height BETWEEN ? AND ? ORDER BY i.block_height ASC, i.jubilee_inscription_number ASC";
+    let mut stmt = prepare_query(query, db_conn, ctx)
+        .map_err(|e| format!("unable to prepare query hord.sqlite: {}", e.to_string()))?;
+    let mut rows = stmt
+        .query(args)
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))?;
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| format!("unable to read hord.sqlite rows: {}", e.to_string()))?
+    {
+        let read_row = || -> Result<(InscriptionData, InscriptionState), rusqlite::Error> {
+            Ok((
+                InscriptionData {
+                    inscription_id: row.get(0)?,
+                    inscription_number: OrdinalInscriptionNumber {
+                        classic: row.get(1)?,
+                        jubilee: row.get(2)?,
+                    },
+                    ordinal_number: row.get(3)?,
+                    block_height: row.get(4)?,
+                    input_index: row.get(5)?,
+                    fee: row.get(6)?,
+                    fee_rate: row.get(7)?,
+                    genesis_tx_id: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
+                    metadata_json: row.get(9)?,
+                    sat_name: InscriptionData::display_name(row.get(3)?),
+                },
+                InscriptionState {
+                    content_type: row.get(10)?,
+                    content_length: row.get(11)?,
+                    current_holder_address: row.get(12)?,
+                    satpoint: row.get(13)?,
+                },
+            ))
+        };
+        let (inscription, state) =
+            read_row().map_err(|e| format!("unable to read hord.sqlite row: {}", e.to_string()))?;
+        handler(inscription, state)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct InscriptionTransferData {
+    pub inscription_id: String,
+    pub ordinal_number: u64,
+    pub block_height: u64,
+    pub tx_id: String,
+    pub from_satpoint: String,
+    pub to_satpoint: String,
+    /// Addresses are only known for the locations indexed since they are recorded
+    pub from_address: Option<String>,
+    pub to_address: Option<String>,
+}
+
+/// Stream the transfers of inscriptions that occurred between `start_block` and `end_block` (included).
+/// Genesis locations are skipped: a transfer always has a previous satpoint.
+pub fn for_each_inscription_transfer_in_block_range<F>(
+    start_block: u64,
+    end_block: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+    mut handler: F,
+) -> Result<(), String>
+where
+    F: FnMut(InscriptionTransferData) -> Result<(), String>,
+{
+    let args: &[&dyn ToSql] = &[&start_block.to_sql().unwrap(), &end_block.to_sql().unwrap()];
+    let query = "SELECT i.inscription_id, t.ordinal_number, t.block_height, t.outpoint_to_watch, t.satpoint, t.previous_satpoint, t.address, t.previous_address FROM (
+            SELECT ordinal_number, block_height, tx_index, outpoint_to_watch, outpoint_to_watch || ':' || offset AS satpoint, address,
+                LAG(outpoint_to_watch || ':' || offset) OVER (PARTITION BY ordinal_number ORDER BY block_height ASC, tx_index ASC) AS previous_satpoint,
+                LAG(address) OVER (PARTITION BY ordinal_number ORDER BY block_height ASC, tx_index ASC) AS previous_address
+            FROM locations
+        ) t
+        INNER JOIN inscriptions i ON i.ordinal_number = t.ordinal_number
+        WHERE t.previous_satpoint IS NOT NULL AND t.block_height BETWEEN ? AND ?
+        ORDER BY t.block_height ASC, t.tx_index ASC";
+    let mut stmt = prepare_query(query, db_conn, ctx)
+        .map_err(|e| format!("unable to prepare query hord.sqlite: {}", e.to_string()))?;
+    let mut rows = stmt
+        .query(args)
+        .map_err(|e| format!("unable to query hord.sqlite: {}", e.to_string()))?;
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| format!("unable to read hord.sqlite rows: {}", e.to_string()))?
+    {
+        let read_row = || -> Result<InscriptionTransferData, rusqlite::Error> {
+            let outpoint_to_watch: String = row.get(3)?;
+            let (transaction_identifier, _) = parse_outpoint_to_watch(&outpoint_to_watch);
+            Ok(InscriptionTransferData {
+                inscription_id: row.get(0)?,
+                ordinal_number: row.get(1)?,
+                block_height: row.get(2)?,
+                tx_id: transaction_identifier.hash,
+                to_satpoint: row.get(4)?,
+                from_satpoint: row.get(5)?,
+                to_address: row.get(6)?,
+                from_address: row.get(7)?,
+            })
+        };
+        let transfer =
+            read_row().map_err(|e| format!("unable to read hord.sqlite row: {}", e.to_string()))?;
+        handler(transfer)?;
+    }
+    Ok(())
+}
+
 pub fn find_all_inscriptions_in_block(
     block_height: &u64,
     inscriptions_db_tx: &Connection,
@@ -1128,7 +3480,11 @@ pub fn find_all_inscriptions_in_block(
     let args: &[&dyn ToSql] = &[&block_height.to_sql().unwrap()];
 
     let mut stmt = loop {
-        match inscriptions_db_tx.prepare("SELECT classic_inscription_number, jubilee_inscription_number, ordinal_number, inscription_id, input_index FROM inscriptions where block_height = ?")
+        match prepare_query(
+            "SELECT classic_inscription_number, jubilee_inscription_number, ordinal_number, inscription_id, input_index FROM inscriptions where block_height = ?",
+            inscriptions_db_tx,
+            ctx,
+        )
         {
             Ok(stmt) => break stmt,
             Err(e) => {
@@ -1214,27 +3570,163 @@ pub fn delete_inscriptions_in_block_range(
     inscriptions_db_conn_rw: &Connection,
     ctx: &Context,
 ) {
-    while let Err(e) = inscriptions_db_conn_rw.execute(
+    let moved_ordinal_numbers = loop {
+        match find_ordinal_numbers_moved_in_block_range(
+            start_block as u64,
+            end_block as u64,
+            inscriptions_db_conn_rw,
+            ctx,
+        ) {
+            Ok(ordinal_numbers) => break ordinal_numbers,
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    warn!(logger, "unable to query hord.sqlite: {}", e.to_string())
+                });
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        }
+    };
+    while let Err(e) = execute_query(
         "DELETE FROM inscriptions WHERE block_height >= ?1 AND block_height <= ?2",
         rusqlite::params![&start_block, &end_block],
+        inscriptions_db_conn_rw,
+        ctx,
     ) {
         ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
-    while let Err(e) = inscriptions_db_conn_rw.execute(
+    while let Err(e) = execute_query(
         "DELETE FROM locations WHERE block_height >= ?1 AND block_height <= ?2",
         rusqlite::params![&start_block, &end_block],
+        inscriptions_db_conn_rw,
+        ctx,
+    ) {
+        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) =
+        restore_current_holder_addresses(&moved_ordinal_numbers, inscriptions_db_conn_rw, ctx)
+    {
+        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = execute_query(
+        "DELETE FROM sequence_metadata WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+        inscriptions_db_conn_rw,
+        ctx,
+    ) {
+        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    while let Err(e) = execute_query(
+        "DELETE FROM brc20_operations WHERE block_height >= ?1 AND block_height <= ?2",
+        rusqlite::params![&start_block, &end_block],
+        inscriptions_db_conn_rw,
+        ctx,
     ) {
         ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
-    while let Err(e) = inscriptions_db_conn_rw.execute(
-        "DELETE FROM sequence_metadata WHERE block_height >= ?1 AND block_height <= ?2",
-        rusqlite::params![&start_block, &end_block],
-    ) {
-        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
-        std::thread::sleep(std::time::Duration::from_secs(1));
+    while let Err(e) = execute_query(
+        "DELETE FROM sequence_state WHERE block_height >= ?1",
+        rusqlite::params![&start_block],
+        inscriptions_db_conn_rw,
+        ctx,
+    ) {
+        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Remove every inscription, location and sequence metadata entry written at `block_height`.
+/// Used for discarding the data of an orphaned block: the sequence state is dropped as well,
+/// so that inscription numbers get recomputed from `block_height - 1`, and the sats moved in
+/// the block are handed back to their previous holder.
+pub fn rollback_block_at_height(
+    block_height: u64,
+    inscriptions_db_conn_rw: &mut Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    let inscriptions_db_tx = inscriptions_db_conn_rw.transaction()?;
+    rollback_block_at_height_tx(block_height, &inscriptions_db_tx, ctx)?;
+    inscriptions_db_tx.commit()?;
+    Ok(())
+}
+
+/// Same as `rollback_block_at_height`, within `inscriptions_db_tx`: nothing is discarded until
+/// the caller commits it, so that several blocks can be rolled back atomically.
+pub fn rollback_block_at_height_tx(
+    block_height: u64,
+    inscriptions_db_tx: &Transaction,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Rolling back inscriptions and transfers from block #{block_height}"
+        )
+    });
+    let moved_ordinal_numbers = find_ordinal_numbers_moved_in_block_range(
+        block_height,
+        block_height,
+        inscriptions_db_tx,
+        ctx,
+    )?;
+    for query in [
+        "DELETE FROM inscriptions WHERE block_height = ?1",
+        "DELETE FROM locations WHERE block_height = ?1",
+        "DELETE FROM sequence_metadata WHERE block_height = ?1",
+        "DELETE FROM brc20_operations WHERE block_height = ?1",
+        "DELETE FROM sequence_state WHERE block_height >= ?1",
+    ] {
+        execute_query(
+            query,
+            rusqlite::params![&block_height],
+            inscriptions_db_tx,
+            ctx,
+        )?;
+    }
+    restore_current_holder_addresses(&moved_ordinal_numbers, inscriptions_db_tx, ctx)?;
+    Ok(())
+}
+
+/// Remove every inscription, location and sequence metadata entry written after `block_height`.
+pub fn rollback_blocks_above_height(
+    block_height: u64,
+    inscriptions_db_conn_rw: &mut Connection,
+    ctx: &Context,
+) -> Result<(), ProcessingError> {
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Rolling back inscriptions and transfers above block #{block_height}"
+        )
+    });
+    let inscriptions_db_tx = inscriptions_db_conn_rw.transaction()?;
+    let moved_ordinal_numbers = find_ordinal_numbers_moved_in_block_range(
+        block_height + 1,
+        i64::MAX as u64,
+        &inscriptions_db_tx,
+        ctx,
+    )?;
+    for query in [
+        "DELETE FROM inscriptions WHERE block_height > ?1",
+        "DELETE FROM locations WHERE block_height > ?1",
+        "DELETE FROM sequence_metadata WHERE block_height > ?1",
+        "DELETE FROM brc20_operations WHERE block_height > ?1",
+        "DELETE FROM sequence_state WHERE block_height > ?1",
+    ] {
+        execute_query(
+            query,
+            rusqlite::params![&block_height],
+            &inscriptions_db_tx,
+            ctx,
+        )?;
     }
+    restore_current_holder_addresses(&moved_ordinal_numbers, &inscriptions_db_tx, ctx)?;
+    inscriptions_db_tx.commit()?;
+    Ok(())
 }
 
 pub fn remove_entry_from_inscriptions(
@@ -1242,16 +3734,20 @@ pub fn remove_entry_from_inscriptions(
     inscriptions_db_rw_conn: &Connection,
     ctx: &Context,
 ) {
-    while let Err(e) = inscriptions_db_rw_conn.execute(
+    while let Err(e) = execute_query(
         "DELETE FROM inscriptions WHERE inscription_id = ?1",
         rusqlite::params![&inscription_id],
+        inscriptions_db_rw_conn,
+        ctx,
     ) {
         ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
-    while let Err(e) = inscriptions_db_rw_conn.execute(
+    while let Err(e) = execute_query(
         "DELETE FROM locations WHERE inscription_id = ?1",
         rusqlite::params![&inscription_id],
+        inscriptions_db_rw_conn,
+        ctx,
     ) {
         ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
         std::thread::sleep(std::time::Duration::from_secs(1));
@@ -1262,14 +3758,14 @@ pub fn remove_entries_from_locations_at_block_height(
     block_height: &u64,
     inscriptions_db_rw_conn: &Transaction,
     ctx: &Context,
-) {
-    while let Err(e) = inscriptions_db_rw_conn.execute(
+) -> Result<(), ProcessingError> {
+    execute_query(
         "DELETE FROM locations WHERE block_height = ?1",
         rusqlite::params![&block_height],
-    ) {
-        ctx.try_log(|logger| warn!(logger, "unable to query hord.sqlite: {}", e.to_string()));
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    }
+        inscriptions_db_rw_conn,
+        ctx,
+    )?;
+    Ok(())
 }
 
 pub fn delete_data_in_ordhook_db(
@@ -1301,7 +3797,7 @@ pub fn delete_data_in_ordhook_db(
     Ok(())
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TraversalResult {
     pub inscription_number: OrdinalInscriptionNumber,
     pub inscription_input_index: usize,
@@ -1405,7 +3901,7 @@ pub struct BlockBytesCursor<'a> {
     pub tx_len: u16,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionBytesCursor {
     pub txid: [u8; 8],
     pub inputs: Vec<TransactionInputBytesCursor>,
@@ -1417,6 +3913,13 @@ impl TransactionBytesCursor {
         TXID_LEN + 3 * TransactionInputBytesCursor::get_average_bytes_size() + 3 * SATS_LEN
     }
 
+    /// Memory held by the cursor, including its inputs and outputs.
+    pub fn byte_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.inputs.capacity() * std::mem::size_of::<TransactionInputBytesCursor>()
+            + self.outputs.capacity() * std::mem::size_of::<u64>()
+    }
+
     pub fn get_sat_ranges(&self) -> Vec<(u64, u64)> {
         let mut sats_ranges = vec![];
         let mut bound = 0u64;
@@ -1439,7 +3942,7 @@ impl TransactionBytesCursor {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInputBytesCursor {
     pub txin: [u8; 8],
     pub block_height: u32,
@@ -1762,11 +4265,765 @@ impl<'a> Iterator for TransactionBytesCursorIterator<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        core::protocol::inscription_parsing::parse_inscriptions_from_witness,
+        utils::test_dir::TestWorkingDir,
+    };
     use chainhook_sdk::{
         indexer::bitcoin::{parse_downloaded_block, standardize_bitcoin_block},
-        types::BitcoinNetwork,
+        types::{BitcoinNetwork, OrdinalOperation},
     };
 
+    #[test]
+    fn test_batch_inscription_queries() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        for (index, (block_height, ordinal_number)) in
+            [(100u64, 5_000u64), (100, 7_000), (101, 6_000)]
+                .iter()
+                .enumerate()
+        {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?3, ?4, 0)",
+                rusqlite::params![format!("{:064x}i0", index), ordinal_number, index as i64, block_height],
+            )
+            .unwrap();
+        }
+
+        let inscriptions = get_inscriptions_for_block(100, u32::MAX, 0, &conn, &ctx).unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        assert!(inscriptions.iter().all(|i| i.block_height == 100));
+        assert_eq!(count_inscriptions_for_block(100, &conn, &ctx).unwrap(), 2);
+
+        let page = get_inscriptions_for_block(100, 1, 1, &conn, &ctx).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0], inscriptions[1]);
+
+        let inscriptions =
+            get_inscriptions_for_sat_range(5_500, 7_000, u32::MAX, 0, &conn, &ctx).unwrap();
+        let sats = inscriptions
+            .iter()
+            .map(|i| i.ordinal_number)
+            .collect::<Vec<_>>();
+        assert_eq!(sats, vec![6_000, 7_000]);
+        assert_eq!(
+            count_inscriptions_for_sat_range(5_500, 7_000, &conn, &ctx).unwrap(),
+            2
+        );
+        let page = get_inscriptions_for_sat_range(5_500, 7_000, 10, 1, &conn, &ctx).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].ordinal_number, 7_000);
+
+        let inscriptions =
+            get_inscriptions_by_tx(&format!("0x{:064x}", 1), u32::MAX, 0, &conn, &ctx).unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].inscription_id, format!("{:064x}i0", 1));
+
+        let inscription = get_inscription_by_number(2, &conn, &ctx).unwrap();
+        assert_eq!(
+            inscription.map(|i| i.inscription_id),
+            Some(format!("{:064x}i0", 2))
+        );
+        assert!(get_inscription_by_number(-1, &conn, &ctx)
+            .unwrap()
+            .is_none());
+
+        let inscription = get_inscription_by_sat(6_000, &conn, &ctx).unwrap();
+        assert_eq!(inscription.map(|i| i.block_height), Some(101));
+        assert!(get_inscription_by_sat(6_001, &conn, &ctx)
+            .unwrap()
+            .is_none());
+
+        let parent_inscription_id = format!("{:064x}i0", 0);
+        conn.execute(
+            "UPDATE inscriptions SET parent_inscription_id = ?1 WHERE block_height = 101",
+            rusqlite::params![&parent_inscription_id],
+        )
+        .unwrap();
+        let children = get_children(&parent_inscription_id, &conn, &ctx).unwrap();
+        assert_eq!(children, vec![format!("{:064x}i0", 2)]);
+        conn.execute(
+            "UPDATE inscriptions SET parent_inscription_id = ?1 WHERE jubilee_inscription_number = 1",
+            rusqlite::params![format!("{:064x}i0", 2)],
+        )
+        .unwrap();
+        let collection = get_collection(&parent_inscription_id, 0, &conn, &ctx)
+            .unwrap()
+            .unwrap();
+        assert_eq!(collection.parent.inscription_id, parent_inscription_id);
+        assert_eq!(collection.children.len(), 1);
+        let collection = get_collection(&parent_inscription_id, 1, &conn, &ctx)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            collection
+                .children
+                .iter()
+                .map(|i| i.inscription_id.clone())
+                .collect::<Vec<_>>(),
+            vec![format!("{:064x}i0", 2), format!("{:064x}i0", 1)]
+        );
+        assert!(get_collection(&format!("{:064x}i9", 0), 1, &conn, &ctx)
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            get_inscription_content(&parent_inscription_id, &conn, &ctx).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_inscription_content_source(&parent_inscription_id, &conn, &ctx).unwrap(),
+            None
+        );
+        store_inscription_content(
+            &parent_inscription_id,
+            b"hello",
+            &ContentSource::Inline,
+            false,
+            &conn,
+            &ctx,
+        )
+        .unwrap();
+        assert_eq!(
+            get_inscription_content(&parent_inscription_id, &conn, &ctx).unwrap(),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(
+            get_inscription_content_source(&parent_inscription_id, &conn, &ctx).unwrap(),
+            Some(ContentSource::Inline)
+        );
+        let content = b"hello hello hello hello hello hello hello hello".repeat(16);
+        let content_source = ContentSource::Ipfs(
+            "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/hello.txt".into(),
+        );
+        store_inscription_content(
+            &parent_inscription_id,
+            &content,
+            &content_source,
+            true,
+            &conn,
+            &ctx,
+        )
+        .unwrap();
+        assert_eq!(
+            get_inscription_content_source(&parent_inscription_id, &conn, &ctx).unwrap(),
+            Some(content_source)
+        );
+        let stored_size: usize = conn
+            .query_row(
+                "SELECT length(content) FROM inscriptions WHERE inscription_id = ?",
+                rusqlite::params![&parent_inscription_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(stored_size < content.len());
+        assert_eq!(
+            get_inscription_content(&parent_inscription_id, &conn, &ctx).unwrap(),
+            Some(content)
+        );
+
+        assert_eq!(
+            get_current_holder(&parent_inscription_id, &conn, &ctx).unwrap(),
+            None
+        );
+        update_current_holder_address_tx(5_000, Some("bc1qholder"), &conn, &ctx).unwrap();
+        assert_eq!(
+            get_current_holder(&parent_inscription_id, &conn, &ctx).unwrap(),
+            Some("bc1qholder".to_string())
+        );
+
+        conn.execute(
+            "UPDATE inscriptions SET content_type = 'text/plain', content_length = 10",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            get_content_type_stats(&conn, &ctx).unwrap(),
+            vec![ContentTypeStat {
+                content_type: "text/plain".into(),
+                count: 3,
+                total_bytes: 30,
+            }]
+        );
+
+        let mut exported = vec![];
+        for_each_inscription_in_block_range(100, 100, &conn, &ctx, |inscription, state| {
+            exported.push((inscription.ordinal_number, state));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(exported.len(), 2);
+        for (ordinal_number, state) in exported.iter() {
+            assert_eq!(state.content_type.as_deref(), Some("text/plain"));
+            assert_eq!(state.content_length, Some(10));
+            let holder = (*ordinal_number == 5_000).then_some("bc1qholder");
+            assert_eq!(state.current_holder_address.as_deref(), holder);
+        }
+
+        assert!(get_duplicate_inscriptions(&conn, &ctx).unwrap().is_empty());
+        let content_hash = [7u8; 32];
+        for index in [0, 2] {
+            store_inscription_content_hash(
+                &format!("{:064x}i0", index),
+                &content_hash,
+                &conn,
+                &ctx,
+            )
+            .unwrap();
+        }
+        store_inscription_content_hash(&format!("{:064x}i0", 1), &[8u8; 32], &conn, &ctx).unwrap();
+        assert_eq!(
+            get_duplicate_inscriptions(&conn, &ctx).unwrap(),
+            vec![DuplicateGroup {
+                content_hash,
+                inscription_ids: vec![format!("{:064x}i0", 0), format!("{:064x}i0", 2)],
+            }]
+        );
+
+        assert!(verify_sequence_integrity(0, 101, &conn, &ctx)
+            .unwrap()
+            .is_empty());
+        conn.execute(
+            "DELETE FROM inscriptions WHERE jubilee_inscription_number = 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            verify_sequence_integrity(0, 101, &conn, &ctx).unwrap(),
+            vec![(1, 1)]
+        );
+        // Checks resume from the numbers assigned below their range
+        assert!(verify_sequence_integrity(100, 100, &conn, &ctx)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            verify_sequence_integrity(101, 101, &conn, &ctx).unwrap(),
+            vec![(1, 1)]
+        );
+        for (index, classic_inscription_number) in [(3i64, -1i64), (4, -4)] {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?2, ?3, 102, 0)",
+                rusqlite::params![format!("{:064x}i0", index), index, classic_inscription_number],
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            verify_sequence_integrity(102, 102, &conn, &ctx).unwrap(),
+            vec![(-2, -3)]
+        );
+    }
+
+    #[test]
+    fn test_sat_name_lookup() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        for (index, ordinal_number) in [0u64, 0, Sat::LAST.0].iter().enumerate() {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?3, ?3, 0)",
+                rusqlite::params![format!("{:064x}i0", index), ordinal_number, index as i64],
+            )
+            .unwrap();
+        }
+        // Rows inserted before the sat_name column existed are backfilled by the migration
+        backfill_sat_names(&conn, &ctx).unwrap();
+
+        let inscriptions =
+            get_inscriptions_for_sat_name("nvtdijuwxlp", u32::MAX, 0, &conn, &ctx).unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        assert!(inscriptions.iter().all(|i| i.sat_name == "nvtdijuwxlp"));
+        assert_eq!(
+            count_inscriptions_for_sat_name("nvtdijuwxlp", &conn, &ctx).unwrap(),
+            2
+        );
+        let inscriptions = get_inscriptions_for_sat_name("a", u32::MAX, 0, &conn, &ctx).unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].ordinal_number, Sat::LAST.0);
+        assert_eq!(
+            count_inscriptions_for_sat_name("b", &conn, &ctx).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_metadata_cbor_is_stored_as_is() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        // {"data": h'deadbeef', "time": 1(1694498816)}: JSON has no byte strings nor tags
+        let metadata = hex::decode("a2646461746144deadbeef6474696d65c11a65000000").unwrap();
+        let mut tapscript = hex::decode("0063036f726401010a746578742f706c61696e0105").unwrap();
+        tapscript.push(metadata.len() as u8);
+        tapscript.extend_from_slice(&metadata);
+        tapscript.extend(hex::decode("0002686968").unwrap());
+        let witness = vec![tapscript, vec![0xc0]];
+
+        let block = include_str!("./fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+        let mut tx = block.transactions[1].clone();
+        let txid = tx.transaction_identifier.get_hash_bytes_str().to_string();
+        let reveal = parse_inscriptions_from_witness(0, witness.clone(), &txid, false)
+            .unwrap()
+            .remove(0);
+        assert!(reveal.metadata.is_some());
+        tx.metadata.inputs[0].witness = witness
+            .iter()
+            .map(|item| format!("0x{}", hex::encode(item)))
+            .collect();
+        tx.metadata.ordinal_operations =
+            vec![OrdinalOperation::InscriptionRevealed(reveal.clone())];
+
+        let metadata_cbor = get_inscription_metadata_bytes(&tx, &reveal);
+        assert_eq!(metadata_cbor.as_deref(), Some(metadata.as_slice()));
+        insert_entry_in_inscriptions(
+            &reveal,
+            metadata_cbor.as_deref(),
+            &block.block_identifier,
+            0.0,
+            &conn,
+            &ctx,
+        )
+        .unwrap();
+        assert_eq!(
+            get_inscription_metadata_cbor(&reveal.inscription_id, &conn, &ctx).unwrap(),
+            Some(metadata)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_inscription_number_fails_the_block() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, 5000, 7, 7, 100, 0)",
+            rusqlite::params![format!("{:064x}i0", 0)],
+        )
+        .unwrap();
+
+        let block = include_str!("./fixtures/blocks_json/279671.json");
+        let block = parse_downloaded_block(block.as_bytes().to_vec()).unwrap();
+        let mut block = standardize_bitcoin_block(block, &BitcoinNetwork::Mainnet, &ctx).unwrap();
+        let txid = block.transactions[1]
+            .transaction_identifier
+            .get_hash_bytes_str()
+            .to_string();
+        let tapscript = hex::decode("0063036f726401010a746578742f706c61696e0002686968").unwrap();
+        let mut reveal =
+            parse_inscriptions_from_witness(0, vec![tapscript, vec![0xc0]], &txid, false)
+                .unwrap()
+                .remove(0);
+        reveal.inscription_number.jubilee = 7;
+        reveal.inscription_number.classic = 8;
+        block.transactions[1].metadata.ordinal_operations =
+            vec![OrdinalOperation::InscriptionRevealed(reveal)];
+
+        // The unique index rejects the number: the error is returned instead of retried
+        let err = update_ordinals_db_with_block(&block, &conn, &ctx).unwrap_err();
+        assert!(matches!(
+            err,
+            ProcessingError::DatabaseError(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error {
+                    code: rusqlite::ErrorCode::ConstraintViolation,
+                    ..
+                },
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_ordinals_activity_at_range() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        let genesis_tx_id = format!("{:064x}", 1);
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index, genesis_tx_id) VALUES (?1, 5000, 0, 0, 100, 0, ?2)",
+            rusqlite::params![format!("{genesis_tx_id}i0"), &genesis_tx_id],
+        )
+        .unwrap();
+        for (block_height, tx_index, txid) in [(100u64, 1usize, 1u64), (101, 3, 2), (101, 4, 3)] {
+            insert_ordinal_transfer_in_locations_tx(
+                5000,
+                &format!("{:064x}:0", txid),
+                OrdinalLocation {
+                    offset: 0,
+                    block_height,
+                    tx_index,
+                    is_burned: txid == 3,
+                    output_value: Some(546),
+                    address: Some(format!("bc1q{txid}")).filter(|_| txid != 3),
+                },
+                &conn,
+                &ctx,
+            )
+            .unwrap();
+        }
+
+        let events = get_ordinals_activity_at_range(100, 101, &conn, &ctx).unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], OrdinalActivityEvent::Revealed(_)));
+        let OrdinalActivityEvent::Transferred(ref transfer) = events[2] else {
+            panic!("expected a transfer");
+        };
+        assert_eq!(transfer.from_output, format!("{:064x}:0", 2));
+        assert_eq!(transfer.to_output, format!("{:064x}:0", 3));
+        assert_eq!(transfer.sat, 5000);
+        assert!(transfer.is_burned);
+        assert_eq!(transfer.output_value, Some(546));
+        assert_eq!(
+            get_burned_inscriptions(&conn, &ctx).unwrap(),
+            vec![(format!("{genesis_tx_id}i0"), 101)]
+        );
+
+        let mut transfers = vec![];
+        for_each_inscription_transfer_in_block_range(100, 101, &conn, &ctx, |transfer| {
+            transfers.push(transfer);
+            Ok(())
+        })
+        .unwrap();
+        // The first location is the genesis one
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].from_address.as_deref(), Some("bc1q1"));
+        assert_eq!(transfers[0].to_address.as_deref(), Some("bc1q2"));
+        assert_eq!(transfers[1].from_address.as_deref(), Some("bc1q2"));
+        assert_eq!(transfers[1].to_address, None);
+
+        let events = get_ordinals_activity_at_range(101, 101, &conn, &ctx).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.block_height()).collect::<Vec<_>>(),
+            vec![101, 101]
+        );
+
+        // Block 101 only transfers the inscription
+        assert_eq!(
+            get_conflicting_activity(101, &conn, &ctx).unwrap(),
+            Some(format!("{genesis_tx_id}i0"))
+        );
+        assert_eq!(get_conflicting_activity(102, &conn, &ctx).unwrap(), None);
+    }
+
+    #[test]
+    fn test_transfer_history() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        let txid = |n: u64| format!("{:064x}", n);
+        let outpoint = |n: u64| format!("{:064x}:0", n);
+        // Sat 5000 is inscribed in tx 1, moved by tx 2 in block 100 before being reinscribed by
+        // tx 3 later in that block, moved by tx 4, reinscribed by tx 5 and finally moved by tx 6.
+        for (index, (genesis, block_height)) in
+            [(1u64, 90u64), (3, 100), (5, 102)].iter().enumerate()
+        {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index, genesis_tx_id) VALUES (?1, 5000, ?2, ?2, ?3, 0, ?4)",
+                rusqlite::params![format!("{}i0", txid(*genesis)), index as i64, block_height, txid(*genesis)],
+            )
+            .unwrap();
+        }
+        for (block_height, tx_index, tx) in [
+            (90u64, 1usize, 1u64),
+            (100, 1, 2),
+            (100, 2, 3),
+            (101, 1, 4),
+            (102, 1, 5),
+            (103, 1, 6),
+        ] {
+            insert_ordinal_transfer_in_locations_tx(
+                5000,
+                &outpoint(tx),
+                OrdinalLocation {
+                    offset: 0,
+                    block_height,
+                    tx_index,
+                    is_burned: false,
+                    output_value: Some(546),
+                    address: None,
+                },
+                &conn,
+                &ctx,
+            )
+            .unwrap();
+        }
+        let history = |genesis: u64| {
+            get_transfer_history(&format!("{}i0", txid(genesis)), &conn, &ctx)
+                .unwrap()
+                .into_iter()
+                .map(|t| (t.block_height, t.from_output, t.to_output))
+                .collect::<Vec<_>>()
+        };
+
+        // The reveals of tx 3 and tx 5 are not transfers of the first inscription
+        assert_eq!(
+            history(1),
+            vec![
+                (100, outpoint(1), outpoint(2)),
+                (101, outpoint(3), outpoint(4)),
+                (103, outpoint(5), outpoint(6)),
+            ]
+        );
+        // The history of the reinscription starts at its own genesis location, not at the move
+        // of the sat earlier in its genesis block
+        assert_eq!(
+            history(3),
+            vec![
+                (101, outpoint(3), outpoint(4)),
+                (103, outpoint(5), outpoint(6)),
+            ]
+        );
+        assert_eq!(history(5), vec![(103, outpoint(5), outpoint(6))]);
+        assert!(get_transfer_history(&format!("{}i0", txid(7)), &conn, &ctx).is_err());
+    }
+
+    #[test]
+    fn test_database_errors_are_structured() {
+        let ctx = Context::empty();
+        // No schema: every statement fails
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let err = get_conflicting_activity(100, &conn, &ctx).unwrap_err();
+        assert!(matches!(err, ProcessingError::DatabaseError(_)));
+        assert!(std::error::Error::source(&err).is_some());
+
+        let err = rollback_block_at_height(100, &mut conn, &ctx).unwrap_err();
+        assert!(matches!(err, ProcessingError::DatabaseError(_)));
+
+        let err = store_inscription_content(
+            &format!("{:064x}i0", 1),
+            b"ord",
+            &ContentSource::Inline,
+            true,
+            &conn,
+            &ctx,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ProcessingError::DatabaseError(_)));
+        assert!(err.to_string().starts_with("hord.sqlite error: "));
+    }
+
+    #[test]
+    fn test_schema_version_tracking() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        // Created without recording its schema version, as by earlier releases
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        migrate_ordhook_db(&conn, &ctx).unwrap();
+        assert_eq!(
+            get_schema_version(&conn, &ctx).unwrap(),
+            Some(CURRENT_SCHEMA_VERSION)
+        );
+        migrate_ordhook_db(&conn, &ctx).unwrap();
+
+        set_schema_version(CURRENT_SCHEMA_VERSION + 1, &conn, &ctx).unwrap();
+        assert!(migrate_ordhook_db(&conn, &ctx).is_err());
+    }
+
+    #[test]
+    fn test_slow_query_plans() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+
+        let query = "SELECT inscription_id FROM inscriptions WHERE ordinal_number = ?";
+        let plan = explain_query_plan(query, &conn).unwrap();
+        assert!(plan
+            .iter()
+            .any(|step| step.contains("index_inscriptions_on_ordinal_number")));
+
+        // Statements are usable as is, and reported when dropped
+        let mut stmt = prepare_query(query, &conn, &ctx).unwrap();
+        assert_eq!(
+            stmt.query_map([0], |row| row.get::<_, String>(0))
+                .unwrap()
+                .count(),
+            0
+        );
+        drop(stmt);
+        assert_eq!(
+            execute_query(
+                "DELETE FROM inscriptions WHERE ordinal_number = ?",
+                [0],
+                &conn,
+                &ctx
+            )
+            .unwrap(),
+            0
+        );
+        let count: u64 = query_one_row(
+            "SELECT COUNT(*) FROM inscriptions",
+            [],
+            &conn,
+            &ctx,
+            |row| row.get(0),
+        )
+        .unwrap();
+        assert_eq!(count, 0);
+
+        // Only timed with a threshold, leaving out the time spent between two rows
+        assert!(prepare_query(query, &conn, &ctx).unwrap().timer.is_none());
+        set_slow_query_threshold(Some(60_000));
+        let mut stmt = prepare_query("SELECT 1 UNION ALL SELECT 2", &conn, &ctx).unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while rows.next().unwrap().is_some() {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        drop(rows);
+        assert!(stmt.timer.as_ref().unwrap().elapsed.get() < Duration::from_millis(50));
+        set_slow_query_threshold(None);
+    }
+
+    #[test]
+    fn test_db_stats() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        migrate_ordhook_db(&conn, &ctx).unwrap();
+        drop(conn);
+
+        let stats = get_ordhook_db_stats(&working_dir, &ctx).unwrap();
+        assert!(stats.sqlite_size > 0);
+        assert_eq!(stats.rocksdb_size, 0);
+        let rows = |name: &str| {
+            stats
+                .tables
+                .iter()
+                .find(|table| table.name == name)
+                .map(|table| table.rows)
+        };
+        assert_eq!(rows("schema_version"), Some(1));
+        assert_eq!(rows("inscriptions"), Some(0));
+        // Not analyzed yet
+        assert!(!stats.indexes.is_empty());
+        assert!(stats.indexes.iter().all(|index| index.stat.is_none()));
+    }
+
+    #[test]
+    fn test_sequence_state_rollback() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        assert_eq!(find_sequence_state(&conn, &ctx), None);
+
+        let state = SequenceState {
+            block_height: 100,
+            nth_classic_pos_number: Some(10),
+            nth_classic_neg_number: None,
+            nth_jubilee_number: Some(10),
+        };
+        update_sequence_state(&state, &conn, &ctx).unwrap();
+        assert_eq!(find_sequence_state(&conn, &ctx), Some(state.clone()));
+
+        rollback_blocks_above_height(100, &mut conn, &ctx).unwrap();
+        assert_eq!(find_sequence_state(&conn, &ctx), Some(state));
+        rollback_block_at_height(100, &mut conn, &ctx).unwrap();
+        assert_eq!(find_sequence_state(&conn, &ctx), None);
+    }
+
+    #[test]
+    fn test_rollback_restores_current_holder_address() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let mut conn = initialize_ordhook_db(&working_dir, true, &ctx);
+        let inscription_id = format!("{:064x}i0", 0);
+        conn.execute(
+            "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, 5000, 0, 0, 100, 0)",
+            rusqlite::params![&inscription_id],
+        )
+        .unwrap();
+        // Revealed to bc1qa at #100, then transferred to bc1qb at #101 and to bc1qc at #102
+        for (block_height, address) in [(100, "bc1qa"), (101, "bc1qb"), (102, "bc1qc")] {
+            insert_ordinal_transfer_in_locations_tx(
+                5_000,
+                &format!("{:064x}:0", block_height),
+                OrdinalLocation {
+                    offset: 0,
+                    block_height,
+                    tx_index: 1,
+                    is_burned: false,
+                    output_value: Some(546),
+                    address: Some(address.to_string()),
+                },
+                &conn,
+                &ctx,
+            )
+            .unwrap();
+            update_current_holder_address_tx(5_000, Some(address), &conn, &ctx).unwrap();
+        }
+        let holder = |conn: &Connection| get_current_holder(&inscription_id, conn, &ctx).unwrap();
+        assert_eq!(holder(&conn).as_deref(), Some("bc1qc"));
+
+        rollback_block_at_height(102, &mut conn, &ctx).unwrap();
+        assert_eq!(holder(&conn).as_deref(), Some("bc1qb"));
+
+        rollback_blocks_above_height(100, &mut conn, &ctx).unwrap();
+        assert_eq!(holder(&conn).as_deref(), Some("bc1qa"));
+    }
+
+    #[test]
+    fn test_block_hash_index_roundtrip() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = crate::config::Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+        let hash = |byte: u8| format!("0x{}", hex::encode([byte; 32]));
+
+        assert_eq!(find_block_hash_at_block_height(100, &blocks_db), None);
+        assert_eq!(find_last_block_hash(&blocks_db), None);
+
+        insert_block_hash(100, &hash(1), &blocks_db, &ctx);
+        insert_block_hash(101, &hash(2), &blocks_db, &ctx);
+        // Keys are big endian: the last entry is the highest block
+        insert_block_hash(99, &hash(3), &blocks_db, &ctx);
+        assert_eq!(
+            find_block_hash_at_block_height(100, &blocks_db),
+            Some(hash(1))
+        );
+        assert_eq!(find_last_block_hash(&blocks_db), Some((101, hash(2))));
+
+        // A competing block replaces the hash stored at its height
+        insert_block_hash(101, &hash(4), &blocks_db, &ctx);
+        assert_eq!(
+            find_block_hash_at_block_height(101, &blocks_db),
+            Some(hash(4))
+        );
+
+        delete_block_hash(101, &blocks_db, &ctx);
+        assert_eq!(find_block_hash_at_block_height(101, &blocks_db), None);
+        assert_eq!(find_last_block_hash(&blocks_db), Some((100, hash(1))));
+
+        remove_entry_from_blocks(100, &blocks_db, &ctx);
+        assert_eq!(find_block_hash_at_block_height(100, &blocks_db), None);
+        assert_eq!(find_last_block_hash(&blocks_db), Some((99, hash(3))));
+
+        drop(blocks_db);
+    }
+
+    #[test]
+    fn test_compaction_slices_reach_the_tip() {
+        let ctx = Context::empty();
+        let working_dir = TestWorkingDir::new();
+        let resources = crate::config::Config::devnet_default().resources;
+        let blocks_db = open_ordhook_db_conn_rocks_db_loop(true, &working_dir, &resources, &ctx);
+        for block_height in 0..25u32 {
+            insert_entry_in_blocks(block_height, &[block_height as u8], true, &blocks_db, &ctx);
+        }
+
+        let mut cursor = Some(0);
+        let mut slices = 0;
+        while let Some(start) = cursor {
+            cursor = run_compaction_slice(&blocks_db, start, 10);
+            slices += 1;
+        }
+        assert_eq!(slices, 3);
+        assert_eq!(blocks_db.get(24u32.to_be_bytes()).unwrap(), Some(vec![24]));
+        assert_eq!(find_last_block_inserted(&blocks_db), 24);
+
+        drop(blocks_db);
+    }
+
     #[test]
     fn test_block_cursor_roundtrip() {
         let ctx = Context::empty();
@@ -7,6 +7,9 @@ extern crate hiro_system_kit;
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+extern crate lazy_static;
+
 extern crate serde;
 
 pub extern crate chainhook_sdk;
@@ -16,6 +19,7 @@ pub mod config;
 pub mod core;
 pub mod db;
 pub mod download;
+pub mod metrics;
 pub mod ord;
 pub mod scan;
 pub mod service;
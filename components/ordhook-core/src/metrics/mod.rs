@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chainhook_sdk::utils::Context;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header::CONTENT_TYPE, Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{
+    core::{Collector, Desc},
+    proto, register_gauge, register_histogram, register_int_counter, register_int_gauge, Encoder,
+    Gauge, Histogram, IntCounter, IntGauge, TextEncoder,
+};
+
+use crate::core::pipeline::processing_duration::{DurationSummary, ProcessingDurationWindow};
+
+lazy_static! {
+    pub static ref BLOCKS_PROCESSED: IntCounter = register_int_counter!(
+        "ordhook_blocks_processed_total",
+        "Number of blocks processed by the inscription indexer"
+    )
+    .unwrap();
+    pub static ref INSCRIPTIONS_INDEXED: IntCounter = register_int_counter!(
+        "ordhook_inscriptions_indexed_total",
+        "Number of inscriptions revealed and indexed"
+    )
+    .unwrap();
+    pub static ref TRANSFERS_INDEXED: IntCounter = register_int_counter!(
+        "ordhook_transfers_indexed_total",
+        "Number of inscription transfers indexed"
+    )
+    .unwrap();
+    pub static ref L2_CACHE_SIZE: IntGauge = register_int_gauge!(
+        "ordhook_l2_cache_size",
+        "Number of transactions held in the traversals L2 cache"
+    )
+    .unwrap();
+    pub static ref BLOCKS_PROCESSED_PER_SECOND: Gauge = register_gauge!(
+        "ordhook_blocks_processed_per_second",
+        "Rolling average of the number of blocks processed per second"
+    )
+    .unwrap();
+    pub static ref API_HOT_CACHE_HITS: IntCounter = register_int_counter!(
+        "ordhook_api_hot_cache_hits_total",
+        "Number of inscription lookups of the Query Api served from the hot cache"
+    )
+    .unwrap();
+    pub static ref API_HOT_CACHE_MISSES: IntCounter = register_int_counter!(
+        "ordhook_api_hot_cache_misses_total",
+        "Number of inscription lookups of the Query Api hitting hord.sqlite"
+    )
+    .unwrap();
+    pub static ref API_HOT_CACHE_HIT_RATE: Gauge = register_gauge!(
+        "ordhook_api_hot_cache_hit_rate",
+        "Share of the inscription lookups of the Query Api served from the hot cache"
+    )
+    .unwrap();
+    pub static ref DB_WRITE_LATENCY: Histogram = register_histogram!(
+        "ordhook_db_write_latency_seconds",
+        "Time spent writing the inscriptions and transfers of a block to hord.sqlite"
+    )
+    .unwrap();
+    pub static ref BLOCK_PROCESSING_DURATION: BlockProcessingDuration = {
+        let summary = BlockProcessingDuration::new(
+            "ordhook_block_processing_duration_seconds",
+            "Time spent processing a block, quantiles over the last 1000 blocks",
+        );
+        prometheus::register(Box::new(summary.clone())).unwrap();
+        summary
+    };
+}
+
+#[derive(Default)]
+struct BlockProcessingDurationState {
+    window: ProcessingDurationWindow,
+    count: u64,
+    sum: f64,
+}
+
+/// Prometheus summary of the block processing durations: the `prometheus` crate has no
+/// summary type, the quantiles are computed over a `ProcessingDurationWindow`.
+#[derive(Clone)]
+pub struct BlockProcessingDuration {
+    desc: Desc,
+    state: Arc<Mutex<BlockProcessingDurationState>>,
+}
+
+impl BlockProcessingDuration {
+    fn new(name: &str, help: &str) -> BlockProcessingDuration {
+        BlockProcessingDuration {
+            desc: Desc::new(name.into(), help.into(), vec![], HashMap::new()).unwrap(),
+            state: Arc::new(Mutex::new(BlockProcessingDurationState::default())),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.window.record(duration);
+        state.count += 1;
+        state.sum += duration.as_secs_f64();
+    }
+
+    /// Number of blocks observed since startup.
+    pub fn count(&self) -> u64 {
+        self.state.lock().unwrap().count
+    }
+
+    pub fn summary(&self) -> Option<DurationSummary> {
+        self.state.lock().unwrap().window.summary()
+    }
+}
+
+impl Collector for BlockProcessingDuration {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        let state = self.state.lock().unwrap();
+        let mut summary = proto::Summary::default();
+        summary.set_sample_count(state.count);
+        summary.set_sample_sum(state.sum);
+        if let Some(durations) = state.window.summary() {
+            let quantiles = [
+                (0.0, durations.min),
+                (0.5, durations.p50),
+                (0.95, durations.p95),
+                (0.99, durations.p99),
+                (1.0, durations.max),
+            ]
+            .into_iter()
+            .map(|(quantile, duration)| {
+                let mut entry = proto::Quantile::default();
+                entry.set_quantile(quantile);
+                entry.set_value(duration.as_secs_f64());
+                entry
+            })
+            .collect::<Vec<_>>();
+            summary.set_quantile(quantiles.into());
+        }
+        let mut metric = proto::Metric::default();
+        metric.set_summary(summary);
+        let mut family = proto::MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(proto::MetricType::SUMMARY);
+        family.set_metric(vec![metric].into());
+        vec![family]
+    }
+}
+
+pub async fn start_metrics_server(port: u16, ctx: Context) -> Result<(), String> {
+    let address = SocketAddr::from(([0, 0, 0, 0], port));
+    let make_svc = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(serve_request)) });
+    let server = Server::try_bind(&address)
+        .map_err(|e| format!("unable to bind {address}: {}", e.to_string()))?
+        .serve(make_svc);
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Prometheus metrics exposed on http://{address}/metrics"
+        )
+    });
+    server.await.map_err(|e| e.to_string())
+}
+
+async fn serve_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        let mut response = Response::new(Body::from("not found"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    let response = match encoder.encode(&prometheus::gather(), &mut buffer) {
+        Ok(_) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, encoder.format_type())
+            .body(Body::from(buffer))
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(e.to_string()))
+            .unwrap(),
+    };
+    Ok(response)
+}
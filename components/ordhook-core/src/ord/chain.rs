@@ -3,7 +3,17 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chainhook_sdk::bitcoincore_rpc::bitcoin::{self, Address, Block, Network, Script};
+use chainhook_sdk::bitcoincore_rpc::bitcoin::{
+    self, absolute,
+    block::{Header, Version},
+    blockdata::{
+        opcodes,
+        script::{self, PushBytesBuf},
+    },
+    hashes::Hash,
+    transaction, Address, Amount, Block, BlockHash, CompactTarget, Network, OutPoint, Script,
+    Sequence, Transaction, TxIn, TxOut, Witness,
+};
 
 use super::*;
 
@@ -13,6 +23,10 @@ pub enum Chain {
     #[default]
     Mainnet,
     Testnet,
+    /// Unknown to chainhook-sdk and to the bundled `bitcoin` crate: observed as `Testnet`, which
+    /// shares its address encoding. Its subsidy schedule is mainnet's, so sat numbering, rarity
+    /// and genesis locations apply unchanged.
+    Testnet4,
     Signet,
     Regtest,
 }
@@ -30,7 +44,7 @@ impl Chain {
     pub(crate) fn network(self) -> Network {
         match self {
             Self::Mainnet => Network::Bitcoin,
-            Self::Testnet => Network::Testnet,
+            Self::Testnet | Self::Testnet4 => Network::Testnet,
             Self::Signet => Network::Signet,
             Self::Regtest => Network::Regtest,
         }
@@ -42,13 +56,14 @@ impl Chain {
             Self::Regtest => 18443,
             Self::Signet => 38332,
             Self::Testnet => 18332,
+            Self::Testnet4 => 48332,
         }
     }
 
     pub(crate) fn inscription_content_size_limit(self) -> Option<usize> {
         match self {
             Self::Mainnet | Self::Regtest => None,
-            Self::Testnet | Self::Signet => Some(1024),
+            Self::Testnet | Self::Testnet4 | Self::Signet => Some(1024),
         }
     }
 
@@ -58,11 +73,26 @@ impl Chain {
             Self::Regtest => 0,
             Self::Signet => 112402,
             Self::Testnet => 2413343,
+            Self::Testnet4 => 0,
+        }
+    }
+
+    /// Height from which cursed inscriptions are numbered along with the blessed ones.
+    pub fn jubilee_height(self) -> u64 {
+        match self {
+            Self::Mainnet => 824544,
+            Self::Regtest => 110,
+            Self::Signet => 175392,
+            Self::Testnet => 2544192,
+            Self::Testnet4 => 0,
         }
     }
 
     pub(crate) fn genesis_block(self) -> Block {
-        bitcoin::blockdata::constants::genesis_block(self.network())
+        match self {
+            Self::Testnet4 => testnet4_genesis_block(),
+            _ => bitcoin::blockdata::constants::genesis_block(self.network()),
+        }
     }
 
     pub fn address_from_script(self, script: &Script) -> Result<Address, bitcoin::address::Error> {
@@ -73,6 +103,7 @@ impl Chain {
         match self {
             Self::Mainnet => data_dir.to_owned(),
             Self::Testnet => data_dir.join("testnet3"),
+            Self::Testnet4 => data_dir.join("testnet4"),
             Self::Signet => data_dir.join("signet"),
             Self::Regtest => data_dir.join("regtest"),
         }
@@ -89,7 +120,91 @@ impl Display for Chain {
                 Self::Regtest => "regtest",
                 Self::Signet => "signet",
                 Self::Testnet => "testnet",
+                Self::Testnet4 => "testnet4",
             }
         )
     }
 }
+
+/// Testnet4 genesis block, as defined in Bitcoin Core's chainparams (BIP94).
+fn testnet4_genesis_block() -> Block {
+    // The 4 is pushed as data, not as OP_4, like in the mainnet genesis coinbase
+    let script_sig = script::Builder::new()
+        .push_int(486604799)
+        .push_slice([4u8])
+        .push_slice(
+            PushBytesBuf::try_from(
+                b"03/May/2024 000000000000000000001ebd58c244970b3aa9d783bb001011fbe8ea8e98e00e"
+                    .to_vec(),
+            )
+            .unwrap(),
+        )
+        .into_script();
+    let script_pubkey = script::Builder::new()
+        .push_slice([0u8; 33])
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script();
+    let coinbase = Transaction {
+        version: transaction::Version::ONE,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::MAX,
+            witness: Witness::default(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(50 * COIN_VALUE),
+            script_pubkey,
+        }],
+    };
+    Block {
+        header: Header {
+            version: Version::ONE,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: coinbase.txid().to_raw_hash().into(),
+            time: 1714777860,
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 393743547,
+        },
+        txdata: vec![coinbase],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ord::{
+        height::Height,
+        rarity::{classify_sat_rarity, SatRarity},
+        sat::sat_to_genesis_location,
+    };
+
+    #[test]
+    fn testnet4_genesis_block_hash() {
+        let block = Chain::Testnet4.genesis_block();
+        assert_eq!(
+            block.header.merkle_root.to_string(),
+            "7aa0a7ae1e223414cb807e40cd57e667b718e42aaf9306db9102fe28912b7b4e"
+        );
+        assert_eq!(
+            block.block_hash().to_string(),
+            "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043"
+        );
+        assert_eq!(
+            block.txdata[0].output[0].value.to_sat(),
+            Height(0).subsidy()
+        );
+    }
+
+    #[test]
+    fn testnet4_follows_the_mainnet_subsidy_schedule() {
+        assert_eq!(Height(209_999).subsidy(), 50 * COIN_VALUE);
+        assert_eq!(Height(210_000).subsidy(), 25 * COIN_VALUE);
+
+        let first_halving_sat = Height(210_000).starting_sat().n();
+        assert_eq!(sat_to_genesis_location(first_halving_sat), (210_000, 0));
+        assert_eq!(classify_sat_rarity(first_halving_sat), SatRarity::Epic);
+        assert_eq!(sat_to_genesis_location(first_halving_sat + 1), (210_000, 1));
+    }
+}
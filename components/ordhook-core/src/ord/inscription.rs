@@ -7,11 +7,12 @@ use super::{inscription_id::InscriptionId, media::Media};
 use {
     super::*,
     chainhook_sdk::bitcoin::{
+        absolute,
         blockdata::{
             opcodes,
             script::{self, PushBytesBuf},
         },
-        ScriptBuf,
+        transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
     },
     std::str,
 };
@@ -51,7 +52,8 @@ impl Inscription {
         bytes
     }
 
-    pub(crate) fn append_reveal_script_to_builder(
+    /// Append the envelope of this inscription (`OP_FALSE OP_IF ... OP_ENDIF`) to a tapscript.
+    pub fn append_reveal_script_to_builder(
         &self,
         mut builder: script::Builder,
     ) -> script::Builder {
@@ -262,6 +264,40 @@ impl Inscription {
         Some(u64::from_le_bytes(pointer))
     }
 
+    /// Virtual size of a transaction revealing this inscription alone: one taproot script-path
+    /// input (Schnorr signature, `<pubkey> OP_CHECKSIG` followed by the envelope, control block)
+    /// and one P2TR output.
+    pub fn reveal_transaction_vsize(&self) -> usize {
+        let builder = script::Builder::new()
+            .push_slice([0u8; 32])
+            .push_opcode(opcodes::all::OP_CHECKSIG);
+        let reveal_script = self.append_reveal_script_to_builder(builder).into_script();
+
+        let mut witness = chainhook_sdk::bitcoin::Witness::new();
+        witness.push([0u8; 64]);
+        witness.push(reveal_script);
+        witness.push([0u8; 33]);
+
+        let mut p2tr_script = vec![0x51, 0x20];
+        p2tr_script.extend_from_slice(&[0u8; 32]);
+
+        let reveal_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness,
+            }],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::from_bytes(p2tr_script),
+            }],
+        };
+        reveal_tx.vsize()
+    }
+
     #[cfg(test)]
     pub(crate) fn to_witness(&self) -> chainhook_sdk::bitcoin::Witness {
         let builder = script::Builder::new();
@@ -657,4 +693,15 @@ mod tests {
             envelope(&[b"ord", &[2], &[1, 2, 3]]),
         );
     }
+
+    #[test]
+    fn reveal_transaction_vsize_discounts_witness_data() {
+        let small = inscription("text/plain", "").reveal_transaction_vsize();
+        let large = inscription("text/plain", [0; 4000]).reveal_transaction_vsize();
+        // Witness bytes weigh a quarter, plus a few bytes of push opcodes per 520 bytes chunk
+        assert!(large - small >= 1000);
+        assert!(large - small <= 1010);
+        // 94 bytes of non-witness data: one input, one P2TR output
+        assert!(small > 94);
+    }
 }
@@ -0,0 +1,120 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use super::{sat::Sat, *};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum SatRarity {
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+    Mythic,
+}
+
+impl SatRarity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SatRarity::Common => "common",
+            SatRarity::Uncommon => "uncommon",
+            SatRarity::Rare => "rare",
+            SatRarity::Epic => "epic",
+            SatRarity::Legendary => "legendary",
+            SatRarity::Mythic => "mythic",
+        }
+    }
+}
+
+impl Display for SatRarity {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SatRarity {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "common" => Ok(SatRarity::Common),
+            "uncommon" => Ok(SatRarity::Uncommon),
+            "rare" => Ok(SatRarity::Rare),
+            "epic" => Ok(SatRarity::Epic),
+            "legendary" => Ok(SatRarity::Legendary),
+            "mythic" => Ok(SatRarity::Mythic),
+            _ => Err(format!("invalid rarity: {s}")),
+        }
+    }
+}
+
+/// Rarity of a sat, derived from its degree: the first sat ever mined (mythic), or the first sat
+/// of a cycle (legendary), of a halving epoch (epic), of a difficulty adjustment period (rare),
+/// or of a block (uncommon).
+pub fn classify_sat_rarity(sat: u64) -> SatRarity {
+    let sat = Sat(sat);
+    if sat.is_common() {
+        return SatRarity::Common;
+    }
+    let height = sat.height().n();
+    let hour = height / (CYCLE_EPOCHS * SUBSIDY_HALVING_INTERVAL);
+    let minute = height % SUBSIDY_HALVING_INTERVAL;
+    let second = height % DIFFCHANGE_INTERVAL;
+    match (hour, minute, second) {
+        (0, 0, 0) => SatRarity::Mythic,
+        (_, 0, 0) => SatRarity::Legendary,
+        (_, 0, _) => SatRarity::Epic,
+        (_, _, 0) => SatRarity::Rare,
+        _ => SatRarity::Uncommon,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rarity() {
+        assert_eq!(classify_sat_rarity(0), SatRarity::Mythic);
+        assert_eq!(classify_sat_rarity(1), SatRarity::Common);
+
+        assert_eq!(classify_sat_rarity(50 * COIN_VALUE - 1), SatRarity::Common);
+        assert_eq!(classify_sat_rarity(50 * COIN_VALUE), SatRarity::Uncommon);
+        assert_eq!(classify_sat_rarity(50 * COIN_VALUE + 1), SatRarity::Common);
+
+        assert_eq!(
+            classify_sat_rarity(50 * COIN_VALUE * DIFFCHANGE_INTERVAL - 1),
+            SatRarity::Common
+        );
+        assert_eq!(
+            classify_sat_rarity(50 * COIN_VALUE * DIFFCHANGE_INTERVAL),
+            SatRarity::Rare
+        );
+
+        assert_eq!(
+            classify_sat_rarity(50 * COIN_VALUE * SUBSIDY_HALVING_INTERVAL - 1),
+            SatRarity::Common
+        );
+        assert_eq!(
+            classify_sat_rarity(50 * COIN_VALUE * SUBSIDY_HALVING_INTERVAL),
+            SatRarity::Epic
+        );
+
+        assert_eq!(classify_sat_rarity(2067187500000000), SatRarity::Legendary);
+    }
+
+    #[test]
+    fn from_str_and_display() {
+        for rarity in [
+            SatRarity::Common,
+            SatRarity::Uncommon,
+            SatRarity::Rare,
+            SatRarity::Epic,
+            SatRarity::Legendary,
+            SatRarity::Mythic,
+        ] {
+            assert_eq!(rarity.to_string().parse::<SatRarity>(), Ok(rarity));
+        }
+        assert!("foo".parse::<SatRarity>().is_err());
+    }
+}
@@ -8,7 +8,7 @@ pub struct Sat(pub u64);
 
 impl Sat {
     pub(crate) const LAST: Self = Self(Self::SUPPLY - 1);
-    pub(crate) const SUPPLY: u64 = 2099999997690000;
+    pub const SUPPLY: u64 = 2099999997690000;
 
     pub(crate) fn n(self) -> u64 {
         self.0
@@ -46,7 +46,7 @@ impl Sat {
         (self.0 - epoch.starting_sat().0) % epoch.subsidy() != 0
     }
 
-    pub(crate) fn name(self) -> String {
+    pub fn name(self) -> String {
         let mut x = Self::SUPPLY - self.0;
         let mut name = String::new();
         while x > 0 {
@@ -62,6 +62,18 @@ impl Sat {
     }
 }
 
+/// Block height where a sat was minted, and its offset in the subsidy of that block's coinbase.
+/// The offset is a u64: the subsidy of the first epochs exceeds u32::MAX sats.
+pub fn sat_to_genesis_location(sat: u64) -> (u64, u64) {
+    let sat = Sat(sat);
+    (sat.height().n(), sat.third())
+}
+
+/// Halving epoch in which a sat was minted.
+pub fn sat_to_epoch(sat: u64) -> u64 {
+    Sat(sat).epoch().0
+}
+
 impl PartialEq<u64> for Sat {
     fn eq(&self, other: &u64) -> bool {
         self.0 == *other
@@ -191,6 +203,21 @@ mod tests {
         assert_eq!(sat, 101);
     }
 
+    #[test]
+    fn genesis_location() {
+        assert_eq!(sat_to_genesis_location(0), (0, 0));
+        assert_eq!(
+            sat_to_genesis_location(50 * COIN_VALUE - 1),
+            (0, 50 * COIN_VALUE - 1)
+        );
+        assert_eq!(sat_to_genesis_location(50 * COIN_VALUE + 1), (1, 1));
+        assert_eq!(
+            sat_to_genesis_location(Epoch(1).starting_sat().n()),
+            (SUBSIDY_HALVING_INTERVAL, 0)
+        );
+        assert_eq!(sat_to_epoch(Epoch(1).starting_sat().n()), 1);
+    }
+
     #[test]
     fn third() {
         assert_eq!(Sat(0).third(), 0);
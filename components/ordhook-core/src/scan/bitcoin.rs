@@ -9,16 +9,12 @@ use crate::download::download_ordinals_dataset_if_required;
 use crate::service::observers::{
     open_readwrite_observers_db_conn_or_panic, update_observer_progress,
 };
-use chainhook_sdk::bitcoincore_rpc::RpcApi;
-use chainhook_sdk::bitcoincore_rpc::{Auth, Client};
+use crate::utils::rpc::RateLimitedRpcClient;
 use chainhook_sdk::chainhooks::bitcoin::{
     evaluate_bitcoin_chainhooks_on_chain_event, handle_bitcoin_hook_action,
     BitcoinChainhookOccurrence, BitcoinTriggerChainhook,
 };
 use chainhook_sdk::chainhooks::types::BitcoinChainhookSpecification;
-use chainhook_sdk::indexer::bitcoin::{
-    build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
-};
 use chainhook_sdk::observer::{gather_proofs, DataHandlerEvent, EventObserverConfig};
 use chainhook_sdk::types::{
     BitcoinBlockData, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
@@ -34,17 +30,12 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
 ) -> Result<(), String> {
     // let _ = download_ordinals_dataset_if_required(config, ctx).await;
 
-    let auth = Auth::UserPass(
-        config.network.bitcoind_rpc_username.clone(),
-        config.network.bitcoind_rpc_password.clone(),
-    );
-
-    let bitcoin_rpc = match Client::new(&config.network.bitcoind_rpc_url, auth) {
-        Ok(con) => con,
-        Err(message) => {
-            return Err(format!("Bitcoin RPC error: {}", message.to_string()));
-        }
+    let event_observer_config = match event_observer_config_override {
+        Some(config_override) => config_override.clone(),
+        None => config.get_event_observer_config(),
     };
+    let bitcoin_config = event_observer_config.get_bitcoin_config();
+    let http_client = RateLimitedRpcClient::new(config.bitcoin.rpc_request_per_second);
     let mut floating_end_block = false;
 
     let mut block_heights_to_scan = if let Some(ref blocks) = predicate_spec.blocks {
@@ -61,13 +52,10 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         };
         let (end_block, update_end_block) = match predicate_spec.end_block {
             Some(end_block) => (end_block, false),
-            None => match bitcoin_rpc.get_blockchain_info() {
-                Ok(result) => (result.blocks, true),
+            None => match http_client.get_block_count(&bitcoin_config).await {
+                Ok(block_count) => (block_count, true),
                 Err(e) => {
-                    return Err(format!(
-                        "unable to retrieve Bitcoin chain tip ({})",
-                        e.to_string()
-                    ));
+                    return Err(format!("unable to retrieve Bitcoin chain tip ({})", e));
                 }
             },
         };
@@ -83,13 +71,7 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
     let mut actions_triggered = 0;
     let mut err_count = 0;
 
-    let event_observer_config = match event_observer_config_override {
-        Some(config_override) => config_override.clone(),
-        None => config.get_event_observer_config(),
-    };
-    let bitcoin_config = event_observer_config.get_bitcoin_config();
     let mut number_of_blocks_scanned = 0;
-    let http_client = build_http_client();
 
     while let Some(current_block_height) = block_heights_to_scan.pop_front() {
         // let mut inscriptions_db_conn =
@@ -102,19 +84,16 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         //     continue;
         // }
 
-        let block_hash = retrieve_block_hash_with_retry(
-            &http_client,
-            &current_block_height,
-            &bitcoin_config,
-            ctx,
-        )
-        .await?;
-        let block_breakdown =
-            download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
-                .await?;
+        let block_hash = http_client
+            .retrieve_block_hash_with_retry(&current_block_height, &bitcoin_config, ctx)
+            .await?;
+        let block_breakdown = http_client
+            .download_and_parse_block_with_retry(&block_hash, &bitcoin_config, ctx)
+            .await?;
         let mut block = match parse_inscriptions_and_standardize_block(
             block_breakdown,
             &event_observer_config.bitcoin_network,
+            config.index_annex_envelopes,
             ctx,
         ) {
             Ok(data) => data,
@@ -137,7 +116,7 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
         //     );
         // }
 
-        let inscriptions_revealed = get_inscriptions_revealed_in_block(&block)
+        let inscriptions_revealed = get_inscriptions_revealed_in_block(&block, None)
             .iter()
             .map(|d| d.get_inscription_number().to_string())
             .collect::<Vec<String>>();
@@ -168,8 +147,11 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             return Err(format!("Scan aborted (consecutive action errors >= 3)"));
         }
         {
-            let observers_db_conn =
-                open_readwrite_observers_db_conn_or_panic(&config.expected_cache_path(), &ctx);
+            let observers_db_conn = open_readwrite_observers_db_conn_or_panic(
+                &config.expected_cache_path(),
+                config.storage.sqlite_wal,
+                &ctx,
+            );
             update_observer_progress(
                 &predicate_spec.uuid,
                 current_block_height,
@@ -178,16 +160,16 @@ pub async fn scan_bitcoin_chainstate_via_rpc_using_predicate(
             )
         }
         if block_heights_to_scan.is_empty() && floating_end_block {
-            let new_tip = match bitcoin_rpc.get_blockchain_info() {
-                Ok(result) => match predicate_spec.end_block {
+            let new_tip = match http_client.get_block_count(&bitcoin_config).await {
+                Ok(block_count) => match predicate_spec.end_block {
                     Some(end_block) => {
-                        if end_block > result.blocks {
-                            result.blocks
+                        if end_block > block_count {
+                            block_count
                         } else {
                             end_block
                         }
                     }
-                    None => result.blocks,
+                    None => block_count,
                 },
                 Err(_e) => {
                     continue;
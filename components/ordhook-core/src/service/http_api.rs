@@ -17,11 +17,19 @@ use std::error::Error;
 use super::observers::{
     find_all_observers, find_observer_with_uuid, open_readonly_observers_db_conn, ObserverReport,
 };
+use super::SnapshotRequest;
+
+#[derive(Deserialize)]
+struct SnapshotParams {
+    /// Absolute path, on the host running the indexer
+    output_dir: PathBuf,
+}
 
 pub async fn start_predicate_api_server(
     port: u16,
     observers_db_dir_path: PathBuf,
     observer_commands_tx: Sender<ObserverCommand>,
+    snapshot_tx: crossbeam_channel::Sender<SnapshotRequest>,
     ctx: Context,
 ) -> Result<(), Box<dyn Error>> {
     let log_level = LogLevel::Off;
@@ -49,6 +57,7 @@ pub async fn start_predicate_api_server(
         handle_get_predicate,
         handle_create_predicate,
         handle_delete_bitcoin_predicate,
+        handle_create_snapshot,
     ];
 
     let background_job_tx_mutex = Arc::new(Mutex::new(observer_commands_tx.clone()));
@@ -58,6 +67,7 @@ pub async fn start_predicate_api_server(
     let ignite = rocket::custom(control_config)
         .manage(background_job_tx_mutex)
         .manage(observers_db_dir_path)
+        .manage(snapshot_tx)
         .manage(ctx_cloned)
         .mount("/", routes)
         .ignite()
@@ -215,6 +225,38 @@ fn handle_delete_bitcoin_predicate(
     }))
 }
 
+#[post("/v1/snapshot", format = "application/json", data = "<params>")]
+fn handle_create_snapshot(
+    params: Json<SnapshotParams>,
+    snapshot_tx: &State<crossbeam_channel::Sender<SnapshotRequest>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP POST /v1/snapshot"));
+    let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+    let request = SnapshotRequest {
+        output_dir: params.into_inner().output_dir,
+        reply_tx,
+    };
+    let res = snapshot_tx
+        .send(request)
+        .map_err(|_| "indexer stopped".to_string())
+        .and_then(|_| {
+            reply_rx
+                .recv()
+                .map_err(|_| "indexer stopped before taking the snapshot".to_string())?
+        });
+    match res {
+        Ok(size) => Json(json!({
+            "status": 200,
+            "result": { "size": size },
+        })),
+        Err(e) => Json(json!({
+            "status": 500,
+            "message": e,
+        })),
+    }
+}
+
 fn serialized_predicate_with_status(
     predicate: &ChainhookSpecification,
     report: &ObserverReport,
@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+use chainhook_sdk::types::{BitcoinBlockData, TransactionIdentifier};
+use chainhook_sdk::utils::Context;
+use crossbeam_channel::Sender;
+use rusqlite::Connection;
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::broadcast;
+
+use crate::core::protocol::inscription_parsing::{
+    get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
+};
+use crate::db::{
+    find_all_inscriptions_with_ordinal_number, get_content_types_at_range,
+    get_ordinals_activity_at_range, open_readonly_ordhook_db_conn, InscriptionData,
+    OrdinalActivityEvent, TransferEvent,
+};
+
+const INSCRIPTION_EVENTS_CAPACITY: usize = 1024;
+
+/// Something that happened to a sat in a given transaction: an inscription being revealed on it,
+/// or the sat being transferred.
+pub trait OrdinalEvent {
+    fn block_height(&self) -> u64;
+    fn tx_id(&self) -> &str;
+    fn sat(&self) -> u64;
+    fn to_json(&self) -> JsonValue;
+}
+
+impl OrdinalEvent for InscriptionData {
+    fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    fn tx_id(&self) -> &str {
+        &self.genesis_tx_id
+    }
+
+    fn sat(&self) -> u64 {
+        self.ordinal_number
+    }
+
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "inscription_id": self.inscription_id,
+            "inscription_number": {
+                "classic": self.inscription_number.classic,
+                "jubilee": self.inscription_number.jubilee,
+            },
+            "ordinal_number": self.ordinal_number,
+            "sat_name": self.sat_name,
+            "block_height": self.block_height,
+            "genesis_block_height": self.block_height,
+            "genesis_tx_id": self.genesis_tx_id,
+            "fee": self.fee,
+            "fee_rate": self.fee_rate,
+            "metadata": self
+                .metadata_json
+                .as_ref()
+                .and_then(|metadata| serde_json::from_str::<JsonValue>(metadata).ok()),
+        })
+    }
+}
+
+impl OrdinalEvent for TransferEvent {
+    fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    fn tx_id(&self) -> &str {
+        &self.tx_id
+    }
+
+    fn sat(&self) -> u64 {
+        self.sat
+    }
+
+    fn to_json(&self) -> JsonValue {
+        json!({
+            "block_height": self.block_height,
+            "tx_id": self.tx_id,
+            "from_output": self.from_output,
+            "to_output": self.to_output,
+            "sat": self.sat,
+            "is_burned": self.is_burned,
+            "output_value": self.output_value,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InscriptionEventType {
+    Revealed,
+    Transferred,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct InscriptionEvent {
+    pub block_height: u64,
+    pub inscription_id: String,
+    pub event_type: InscriptionEventType,
+    /// Transaction revealing or transferring the inscription
+    pub tx_id: String,
+    pub sat: u64,
+    /// Only known for reveals
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+impl OrdinalEvent for InscriptionEvent {
+    fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    fn tx_id(&self) -> &str {
+        &self.tx_id
+    }
+
+    fn sat(&self) -> u64 {
+        self.sat
+    }
+
+    fn to_json(&self) -> JsonValue {
+        serde_json::to_value(self).unwrap_or(JsonValue::Null)
+    }
+}
+
+/// Inscription events of a block committed to hord.sqlite.
+#[derive(Clone, Debug, Serialize)]
+pub struct InscriptionEventsBatch {
+    pub block_height: u64,
+    pub events: Vec<InscriptionEvent>,
+}
+
+impl InscriptionEventsBatch {
+    pub fn to_json(&self) -> JsonValue {
+        json!({
+            "block_height": self.block_height,
+            "events": self.events.iter().map(OrdinalEvent::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+pub fn new_inscription_events_channel() -> broadcast::Sender<InscriptionEventsBatch> {
+    let (events_tx, _) = broadcast::channel(INSCRIPTION_EVENTS_CAPACITY);
+    events_tx
+}
+
+pub fn collect_inscription_events(
+    block: &BitcoinBlockData,
+    ordhook_db_dir_path: &PathBuf,
+    ctx: &Context,
+) -> Vec<InscriptionEvent> {
+    let block_height = block.block_identifier.index;
+    let mut events = vec![];
+    for reveal in get_inscriptions_revealed_in_block(block, None) {
+        events.push(InscriptionEvent {
+            block_height,
+            inscription_id: reveal.inscription_id.clone(),
+            event_type: InscriptionEventType::Revealed,
+            tx_id: block.transactions[reveal.tx_index]
+                .transaction_identifier
+                .hash
+                .clone(),
+            sat: reveal.ordinal_number,
+            content_type: Some(reveal.content_type.clone()),
+        });
+    }
+    let transfers = get_inscriptions_transferred_in_block(block);
+    if transfers.is_empty() {
+        return events;
+    }
+    // Transfers are tracked by sat: resolve the inscriptions sitting on each of them
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            ctx.try_log(|logger| error!(logger, "Unable to collect transfers: {e}"));
+            return events;
+        }
+    };
+    for transfer in transfers {
+        for (traversal, _) in
+            find_all_inscriptions_with_ordinal_number(&transfer.ordinal_number, &db_conn, ctx)
+        {
+            events.push(InscriptionEvent {
+                block_height,
+                inscription_id: traversal.get_inscription_id(),
+                event_type: InscriptionEventType::Transferred,
+                tx_id: block.transactions[transfer.tx_index]
+                    .transaction_identifier
+                    .hash
+                    .clone(),
+                sat: transfer.ordinal_number,
+                content_type: None,
+            });
+        }
+    }
+    events
+}
+
+/// Inscription events of the blocks between `from` and `to` (inclusive) already committed to
+/// hord.sqlite, as they were published when indexing them. Blocks without events are omitted.
+pub fn collect_inscription_events_at_range(
+    from: u64,
+    to: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> Result<Vec<InscriptionEventsBatch>, String> {
+    let mut content_types = get_content_types_at_range(from, to, db_conn, ctx)?;
+    let mut batches: Vec<InscriptionEventsBatch> = vec![];
+    for activity in get_ordinals_activity_at_range(from, to, db_conn, ctx)? {
+        let block_height = activity.block_height();
+        let events = match activity {
+            OrdinalActivityEvent::Revealed(inscription) => vec![InscriptionEvent {
+                block_height,
+                content_type: content_types.remove(&inscription.inscription_id),
+                inscription_id: inscription.inscription_id,
+                event_type: InscriptionEventType::Revealed,
+                tx_id: TransactionIdentifier::new(&inscription.genesis_tx_id).hash,
+                sat: inscription.ordinal_number,
+            }],
+            OrdinalActivityEvent::Transferred(transfer) => {
+                find_all_inscriptions_with_ordinal_number(&transfer.sat, db_conn, ctx)
+                    .into_iter()
+                    .map(|(traversal, _)| InscriptionEvent {
+                        block_height,
+                        inscription_id: traversal.get_inscription_id(),
+                        event_type: InscriptionEventType::Transferred,
+                        tx_id: transfer.tx_id.clone(),
+                        sat: transfer.sat,
+                        content_type: None,
+                    })
+                    .collect()
+            }
+        };
+        match batches.last_mut() {
+            Some(batch) if batch.block_height == block_height => batch.events.extend(events),
+            _ => batches.push(InscriptionEventsBatch {
+                block_height,
+                events,
+            }),
+        }
+    }
+    Ok(batches)
+}
+
+/// Publish the inscriptions revealed and transferred in a block that was just committed to hord.sqlite.
+pub fn publish_inscription_events(
+    block: &BitcoinBlockData,
+    events_tx: &broadcast::Sender<InscriptionEventsBatch>,
+    ordhook_db_dir_path: &PathBuf,
+    ctx: &Context,
+) {
+    // Nobody listening, no need to hit the db
+    if events_tx.receiver_count() == 0 {
+        return;
+    }
+    let events = collect_inscription_events(block, ordhook_db_dir_path, ctx);
+    if events.is_empty() {
+        return;
+    }
+    let _ = events_tx.send(InscriptionEventsBatch {
+        block_height: block.block_identifier.index,
+        events,
+    });
+}
+
+/// Spawn a thread publishing the inscription events of the blocks received, before
+/// forwarding them to `next_block_post_processor`.
+pub fn start_inscription_events_forwarding(
+    events_tx: broadcast::Sender<InscriptionEventsBatch>,
+    next_block_post_processor: Option<Sender<BitcoinBlockData>>,
+    ordhook_db_dir_path: PathBuf,
+    ctx: &Context,
+) -> Sender<BitcoinBlockData> {
+    let (block_tx, block_rx) = crossbeam_channel::unbounded::<BitcoinBlockData>();
+    let ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Inscription events forwarding")
+        .spawn(move || {
+            while let Ok(block) = block_rx.recv() {
+                publish_inscription_events(&block, &events_tx, &ordhook_db_dir_path, &ctx);
+                if let Some(ref tx) = next_block_post_processor {
+                    let _ = tx.send(block);
+                }
+            }
+        })
+        .expect("unable to spawn thread");
+    block_tx
+}
@@ -1,36 +1,56 @@
 mod http_api;
+pub mod inscription_events;
 pub mod observers;
+mod query_api;
 mod runloops;
+pub mod webhooks;
+pub mod websocket;
 
-use crate::config::{Config, PredicatesApi};
-use crate::core::pipeline::download_and_pipeline_blocks;
+use crate::config::{Config, PredicatesApi, QueryApi};
+use crate::core::checkpoint::{reconcile_dbs_with_checkpoint, write_checkpoint};
 use crate::core::pipeline::processors::block_archiving::start_block_archiving_processor;
 use crate::core::pipeline::processors::inscription_indexing::process_block;
 use crate::core::pipeline::processors::start_inscription_indexing_processor;
 use crate::core::pipeline::processors::transfers_recomputing::start_transfers_recomputing_processor;
+use crate::core::pipeline::{
+    download_and_pipeline_blocks, PausedProcessor, PostProcessorCommand, PostProcessorRegistry,
+};
 use crate::core::protocol::inscription_parsing::{
     get_inscriptions_revealed_in_block, get_inscriptions_transferred_in_block,
     parse_inscriptions_in_standardized_block,
 };
 use crate::core::protocol::inscription_sequencing::SequenceCursor;
-use crate::core::{new_traversals_lazy_cache, should_sync_ordhook_db, should_sync_rocks_db};
+use crate::core::{
+    check_rollback_depth, get_chain_tip_height, new_traversals_lazy_cache, should_sync_ordhook_db,
+    should_sync_rocks_db, TraversalsCache,
+};
 use crate::db::{
-    delete_data_in_ordhook_db, insert_entry_in_blocks, open_ordhook_db_conn_rocks_db_loop,
-    open_readwrite_ordhook_db_conn, open_readwrite_ordhook_dbs, update_ordinals_db_with_block,
-    BlockBytesCursor, TransactionBytesCursor,
+    delete_data_in_ordhook_db, find_block_hash_at_block_height, insert_block_hash,
+    insert_entry_in_blocks, open_ordhook_db_conn_rocks_db_loop, open_readwrite_ordhook_db_conn,
+    open_readwrite_ordhook_dbs, snapshot_ordhook_db, update_ordinals_db_with_block,
+    BlockBytesCursor,
 };
 use crate::db::{
     find_last_block_inserted, find_missing_blocks, run_compaction,
     update_sequence_metadata_with_block,
 };
+use crate::metrics::start_metrics_server;
 use crate::scan::bitcoin::process_block_with_predicates;
 use crate::service::http_api::start_predicate_api_server;
+use crate::service::inscription_events::{
+    new_inscription_events_channel, publish_inscription_events,
+    start_inscription_events_forwarding, InscriptionEventsBatch,
+};
 use crate::service::observers::{
     create_and_consolidate_chainhook_config_with_predicates, insert_entry_in_observers,
     open_readwrite_observers_db_conn, remove_entry_from_observers, update_observer_progress,
     update_observer_streaming_enabled, ObserverReport,
 };
+use crate::service::query_api::start_query_api_server;
 use crate::service::runloops::start_bitcoin_scan_runloop;
+use crate::service::webhooks::start_webhook_delivery;
+use crate::service::websocket::start_websocket_server;
+use crate::utils::hot_cache::{HotCache, InscriptionsHotCache};
 use chainhook_sdk::chainhooks::bitcoin::BitcoinChainhookOccurrencePayload;
 use chainhook_sdk::chainhooks::types::{
     BitcoinChainhookSpecification, ChainhookConfig, ChainhookFullSpecification,
@@ -43,23 +63,44 @@ use chainhook_sdk::observer::{
 use chainhook_sdk::types::{BitcoinBlockData, BlockIdentifier};
 use chainhook_sdk::utils::{BlockHeights, Context};
 use crossbeam_channel::unbounded;
-use crossbeam_channel::{select, Sender};
-use dashmap::DashMap;
-use fxhash::FxHasher;
+use crossbeam_channel::{select, Receiver, Sender};
+use rocksdb::DB;
 
-use std::collections::BTreeMap;
-use std::hash::BuildHasherDefault;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 
 pub struct Service {
     pub config: Config,
     pub ctx: Context,
+    pub inscription_events_tx: Option<broadcast::Sender<InscriptionEventsBatch>>,
+    /// Inscriptions served from memory by the query API, cleared on re-orgs
+    pub inscriptions_hot_cache: Option<Arc<InscriptionsHotCache>>,
+    /// Processors receiving every block indexed, on top of the ones started by the service
+    pub post_processors: PostProcessorRegistry,
+    /// Snapshots requested through the control API, taken in between two blocks by the
+    /// inscription indexing processor or the observer sidecar, whichever is indexing
+    pub snapshot_requests: (Sender<SnapshotRequest>, Receiver<SnapshotRequest>),
+}
+
+pub struct SnapshotRequest {
+    pub output_dir: PathBuf,
+    /// Size of the snapshot, in bytes
+    pub reply_tx: Sender<Result<u64, String>>,
 }
 
 impl Service {
     pub fn new(config: Config, ctx: Context) -> Self {
-        Self { config, ctx }
+        Self {
+            config,
+            ctx,
+            inscription_events_tx: None,
+            inscriptions_hot_cache: None,
+            post_processors: PostProcessorRegistry::new(),
+            snapshot_requests: unbounded(),
+        }
     }
 
     pub async fn run(
@@ -73,6 +114,78 @@ impl Service {
     ) -> Result<(), String> {
         let mut event_observer_config = self.config.get_event_observer_config();
 
+        if self.config.is_query_api_enabled() || !self.config.webhooks.is_empty() {
+            self.inscription_events_tx = Some(new_inscription_events_channel());
+        }
+
+        if let QueryApi::On(ref api_config) = self.config.query_api {
+            self.inscriptions_hot_cache = Some(Arc::new(HotCache::new(api_config.hot_cache_size)));
+        }
+
+        if let (QueryApi::On(ref api_config), Some(ref events_tx)) =
+            (&self.config.query_api, &self.inscription_events_tx)
+        {
+            let events_tx = events_tx.clone();
+            info!(
+                self.ctx.expect_logger(),
+                "Listening on {}:{} for WebSocket subscriptions",
+                api_config.host,
+                api_config.websocket_port
+            );
+            let ctx = self.ctx.clone();
+            let api_config = api_config.clone();
+            let _ = hiro_system_kit::thread_named("WebSocket Server").spawn(move || {
+                let future = start_websocket_server(api_config, events_tx, ctx.clone());
+                if let Err(e) = hiro_system_kit::nestable_block_on(future) {
+                    ctx.try_log(|logger| error!(logger, "Unable to start WebSocket server: {e}"));
+                }
+            });
+        }
+
+        if let Some(ref events_tx) = self.inscription_events_tx {
+            for webhook in self.config.webhooks.iter() {
+                start_webhook_delivery(webhook.clone(), events_tx.subscribe(), &self.ctx);
+            }
+        }
+
+        if let Some(metrics_port) = self.config.metrics_port {
+            let ctx = self.ctx.clone();
+            let _ = hiro_system_kit::thread_named("Metrics Server").spawn(move || {
+                let future = start_metrics_server(metrics_port, ctx.clone());
+                if let Err(e) = hiro_system_kit::nestable_block_on(future) {
+                    ctx.try_log(|logger| error!(logger, "Unable to start metrics server: {e}"));
+                }
+            });
+        }
+
+        // Create the chainhook runloop tx/rx comms. Predicates registered before the observer
+        // starts are queued.
+        let (observer_command_tx, observer_command_rx) = channel();
+
+        // The control API is up during the catch-up, for snapshots to be taken meanwhile
+        if let PredicatesApi::On(ref api_config) = self.config.http_api {
+            info!(
+                self.ctx.expect_logger(),
+                "Listening on port {} for chainhook predicate registrations", api_config.http_port
+            );
+            let ctx = self.ctx.clone();
+            let api_config = api_config.clone();
+            let moved_observer_command_tx = observer_command_tx.clone();
+            let snapshot_tx = self.snapshot_requests.0.clone();
+            let db_dir_path = self.config.expected_cache_path();
+            // Test and initialize a database connection
+            let _ = hiro_system_kit::thread_named("HTTP Predicate API").spawn(move || {
+                let future = start_predicate_api_server(
+                    api_config.http_port,
+                    db_dir_path,
+                    moved_observer_command_tx,
+                    snapshot_tx,
+                    ctx,
+                );
+                let _ = hiro_system_kit::nestable_block_on(future);
+            });
+        }
+
         let block_post_processor = if stream_indexing_to_observers && !observer_specs.is_empty() {
             let mut chainhook_config: ChainhookConfig = ChainhookConfig::new();
             let specs = observer_specs.clone();
@@ -98,10 +211,8 @@ impl Service {
         );
 
         // Sidecar channels setup
-        let observer_sidecar = self.set_up_observer_sidecar_runloop()?;
+        let (observer_sidecar, sidecar_failure_rx) = self.set_up_observer_sidecar_runloop()?;
 
-        // Create the chainhook runloop tx/rx comms
-        let (observer_command_tx, observer_command_rx) = channel();
         let (observer_event_tx, observer_event_rx) = crossbeam_channel::unbounded();
         let ordhook_config = self.config.get_ordhook_config();
         let inner_ctx = if ordhook_config.logs.chainhook_internals {
@@ -138,12 +249,11 @@ impl Service {
             inner_ctx,
         );
 
-        // If HTTP Predicates API is on, we start:
-        // - Thread pool in charge of performing replays
-        // - API server
+        // Start the thread pool in charge of performing replays
         self.start_main_runloop_with_dynamic_predicates(
             &observer_command_tx,
             observer_event_rx,
+            &sidecar_failure_rx,
             predicate_activity_relayer,
         )?;
         Ok(())
@@ -198,20 +308,26 @@ impl Service {
         &self,
         _observer_command_tx: &std::sync::mpsc::Sender<ObserverCommand>,
         observer_event_rx: crossbeam_channel::Receiver<ObserverEvent>,
+        sidecar_failure_rx: &crossbeam_channel::Receiver<String>,
         predicate_activity_relayer: Option<
             crossbeam_channel::Sender<BitcoinChainhookOccurrencePayload>,
         >,
     ) -> Result<(), String> {
         loop {
-            let event = match observer_event_rx.recv() {
-                Ok(cmd) => cmd,
-                Err(e) => {
-                    error!(
-                        self.ctx.expect_logger(),
-                        "Error: broken channel {}",
-                        e.to_string()
-                    );
-                    break;
+            let event = select! {
+                recv(observer_event_rx) -> msg => match msg {
+                    Ok(cmd) => cmd,
+                    Err(e) => {
+                        error!(
+                            self.ctx.expect_logger(),
+                            "Error: broken channel {}",
+                            e.to_string()
+                        );
+                        break;
+                    }
+                },
+                recv(sidecar_failure_rx) -> msg => {
+                    return Err(msg.unwrap_or_else(|_| "observer sidecar stopped".into()));
                 }
             };
             match event {
@@ -234,6 +350,7 @@ impl Service {
         &self,
         observer_command_tx: &std::sync::mpsc::Sender<ObserverCommand>,
         observer_event_rx: crossbeam_channel::Receiver<ObserverEvent>,
+        sidecar_failure_rx: &crossbeam_channel::Receiver<String>,
         predicate_activity_relayer: Option<
             crossbeam_channel::Sender<BitcoinChainhookOccurrencePayload>,
         >,
@@ -253,37 +370,49 @@ impl Service {
             })
             .expect("unable to spawn thread");
 
-        if let PredicatesApi::On(ref api_config) = self.config.http_api {
+        if let (QueryApi::On(ref api_config), Some(ref hot_cache)) =
+            (&self.config.query_api, &self.inscriptions_hot_cache)
+        {
             info!(
                 self.ctx.expect_logger(),
-                "Listening on port {} for chainhook predicate registrations", api_config.http_port
+                "Listening on {}:{} for inscription queries", api_config.host, api_config.port
             );
+            let hot_cache = hot_cache.clone();
             let ctx = self.ctx.clone();
             let api_config = api_config.clone();
-            let moved_observer_command_tx = observer_command_tx.clone();
             let db_dir_path = self.config.expected_cache_path();
-            // Test and initialize a database connection
-            let _ = hiro_system_kit::thread_named("HTTP Predicate API").spawn(move || {
-                let future = start_predicate_api_server(
-                    api_config.http_port,
+            let events_tx = self.inscription_events_tx.clone();
+            let _ = hiro_system_kit::thread_named("HTTP Query API").spawn(move || {
+                let future = start_query_api_server(
+                    api_config,
                     db_dir_path,
-                    moved_observer_command_tx,
-                    ctx,
+                    hot_cache,
+                    events_tx,
+                    ctx.clone(),
                 );
-                let _ = hiro_system_kit::nestable_block_on(future);
+                if let Err(e) = hiro_system_kit::nestable_block_on(future) {
+                    ctx.try_log(|logger| {
+                        error!(logger, "Unable to start query API: {}", e.to_string())
+                    });
+                }
             });
         }
 
         loop {
-            let event = match observer_event_rx.recv() {
-                Ok(cmd) => cmd,
-                Err(e) => {
-                    error!(
-                        self.ctx.expect_logger(),
-                        "Error: broken channel {}",
-                        e.to_string()
-                    );
-                    break;
+            let event = select! {
+                recv(observer_event_rx) -> msg => match msg {
+                    Ok(cmd) => cmd,
+                    Err(e) => {
+                        error!(
+                            self.ctx.expect_logger(),
+                            "Error: broken channel {}",
+                            e.to_string()
+                        );
+                        break;
+                    }
+                },
+                recv(sidecar_failure_rx) -> msg => {
+                    return Err(msg.unwrap_or_else(|_| "observer sidecar stopped".into()));
                 }
             };
             match event {
@@ -293,6 +422,7 @@ impl Service {
                     // - contract-id
                     let observers_db_conn = match open_readwrite_observers_db_conn(
                         &self.config.expected_cache_path(),
+                        self.config.storage.sqlite_wal,
                         &self.ctx,
                     ) {
                         Ok(con) => con,
@@ -317,6 +447,7 @@ impl Service {
                 ObserverEvent::PredicateEnabled(spec) => {
                     let observers_db_conn = match open_readwrite_observers_db_conn(
                         &self.config.expected_cache_path(),
+                        self.config.storage.sqlite_wal,
                         &self.ctx,
                     ) {
                         Ok(con) => con,
@@ -339,6 +470,7 @@ impl Service {
                 ObserverEvent::PredicateDeregistered(spec) => {
                     let observers_db_conn = match open_readwrite_observers_db_conn(
                         &self.config.expected_cache_path(),
+                        self.config.storage.sqlite_wal,
                         &self.ctx,
                     ) {
                         Ok(con) => con,
@@ -357,6 +489,7 @@ impl Service {
                     if let Some(ref tip) = data.apply.last() {
                         let observers_db_conn = match open_readwrite_observers_db_conn(
                             &self.config.expected_cache_path(),
+                            self.config.storage.sqlite_wal,
                             &self.ctx,
                         ) {
                             Ok(con) => con,
@@ -422,41 +555,93 @@ impl Service {
         Ok((event_observer_config, data_rx))
     }
 
-    pub fn set_up_observer_sidecar_runloop(&self) -> Result<ObserverSidecar, String> {
+    /// Starts the thread augmenting the blocks streamed by the observer. The receiver returned
+    /// yields the error the sidecar stopped on, e.g. a re-org deeper than `max_rollback_depth`.
+    pub fn set_up_observer_sidecar_runloop(
+        &self,
+    ) -> Result<(ObserverSidecar, crossbeam_channel::Receiver<String>), String> {
         let (block_mutator_in_tx, block_mutator_in_rx) = crossbeam_channel::unbounded();
         let (block_mutator_out_tx, block_mutator_out_rx) = crossbeam_channel::unbounded();
         let (chain_event_notifier_tx, chain_event_notifier_rx) = crossbeam_channel::unbounded();
+        let (sidecar_failure_tx, sidecar_failure_rx) = crossbeam_channel::bounded(1);
         let observer_sidecar = ObserverSidecar {
             bitcoin_blocks_mutator: Some((block_mutator_in_tx, block_mutator_out_rx)),
             bitcoin_chain_event_notifier: Some(chain_event_notifier_tx),
         };
-        let cache_l2 = Arc::new(new_traversals_lazy_cache(100_000));
+        let cache_l2 = Arc::new(
+            new_traversals_lazy_cache(100_000, &self.config.resources.cache_eviction_policy)
+                .with_block_cache(self.config.resources.block_cache_size)
+                .with_max_bytes(self.config.resources.max_cache_bytes),
+        );
         let ctx = self.ctx.clone();
         let config = self.config.clone();
+        let inscription_events_tx = self.inscription_events_tx.clone();
+        let inscriptions_hot_cache = self.inscriptions_hot_cache.clone();
+        let snapshot_rx = self.snapshot_requests.1.clone();
 
         let _ = hiro_system_kit::thread_named("Observer Sidecar Runloop").spawn(move || loop {
             select! {
                 recv(block_mutator_in_rx) -> msg => {
                     if let Ok((mut blocks_to_mutate, blocks_ids_to_rollback)) = msg {
-                        chainhook_sidecar_mutate_blocks(
+                        if let Err(e) = chainhook_sidecar_mutate_blocks(
                             &mut blocks_to_mutate,
                             &blocks_ids_to_rollback,
                             &cache_l2,
                             &config,
                             &ctx,
-                        );
+                        ) {
+                            ctx.try_log(|logger| error!(logger, "Re-org handling: {e}, aborting"));
+                            let _ = sidecar_failure_tx.send(e);
+                            break;
+                        }
+                        if !blocks_ids_to_rollback.is_empty() {
+                            if let Some(ref hot_cache) = inscriptions_hot_cache {
+                                hot_cache.clear();
+                            }
+                        }
                         let _ = block_mutator_out_tx.send(blocks_to_mutate);
+                        if cache_l2.exceeds_max_bytes() {
+                            let evicted = cache_l2.collect_garbage();
+                            ctx.try_log(|logger| info!(logger, "Evicted {evicted} cache L2 entries exceeding resources.max_cache_bytes"));
+                        }
                     }
                 }
                 recv(chain_event_notifier_rx) -> msg => {
                     if let Ok(command) = msg {
-                        chainhook_sidecar_mutate_ordhook_db(command, &config, &ctx)
+                        let is_rollback = matches!(command, HandleBlock::UndoBlock(_));
+                        if let Err(e) = chainhook_sidecar_mutate_ordhook_db(command, &inscription_events_tx, &cache_l2, &config, &ctx) {
+                            ctx.try_log(|logger| error!(logger, "Re-org handling: {e}, aborting"));
+                            let _ = sidecar_failure_tx.send(e);
+                            break;
+                        }
+                        if is_rollback {
+                            if let Some(ref hot_cache) = inscriptions_hot_cache {
+                                hot_cache.clear();
+                            }
+                        }
+                    }
+                }
+                recv(snapshot_rx) -> msg => {
+                    if let Ok(request) = msg {
+                        let res = open_readwrite_ordhook_dbs(
+                            &config.expected_cache_path(),
+                            &config.resources,
+                            config.storage.sqlite_wal,
+                            &ctx,
+                        )
+                        .and_then(|(blocks_db, inscriptions_db_conn)| {
+                            snapshot_ordhook_db(&blocks_db, &inscriptions_db_conn, &request.output_dir, &ctx)
+                        });
+                        if let Err(ref e) = res {
+                            ctx.try_log(|logger| error!(logger, "Unable to take snapshot: {e}"));
+                        }
+                        let _ = request.reply_tx.send(res);
                     }
                 }
             }
         });
 
-        Ok(observer_sidecar)
+        Ok((observer_sidecar, sidecar_failure_rx))
     }
 
     pub async fn catch_up_with_chain_tip(
@@ -471,8 +656,7 @@ impl Service {
                     let blocks_db = open_ordhook_db_conn_rocks_db_loop(
                         false,
                         &self.config.expected_cache_path(),
-                        self.config.resources.ulimit,
-                        self.config.resources.memory_available,
+                        &self.config.resources,
                         &self.ctx,
                     );
                     let tip = find_last_block_inserted(&blocks_db);
@@ -504,8 +688,7 @@ impl Service {
                 let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
                     false,
                     &self.config.expected_cache_path(),
-                    self.config.resources.ulimit,
-                    self.config.resources.memory_available,
+                    &self.config.resources,
                     &self.ctx,
                 );
                 info!(self.ctx.expect_logger(), "Running database compaction",);
@@ -516,13 +699,15 @@ impl Service {
                 let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
                     false,
                     &self.config.expected_cache_path(),
-                    self.config.resources.ulimit,
-                    self.config.resources.memory_available,
+                    &self.config.resources,
                     &self.ctx,
                 );
 
-                let inscriptions_db_conn_rw =
-                    open_readwrite_ordhook_db_conn(&self.config.expected_cache_path(), &self.ctx)?;
+                let inscriptions_db_conn_rw = open_readwrite_ordhook_db_conn(
+                    &self.config.expected_cache_path(),
+                    self.config.storage.sqlite_wal,
+                    &self.ctx,
+                )?;
 
                 delete_data_in_ordhook_db(
                     767430,
@@ -540,6 +725,9 @@ impl Service {
         &self,
         block_post_processor: Option<crossbeam_channel::Sender<BitcoinBlockData>>,
     ) -> Result<u64, String> {
+        // Discard any data written after the last checkpoint (e.g. crash in the middle of a batch)
+        reconcile_dbs_with_checkpoint(&self.config, &self.ctx)?;
+
         // First, make sure that rocksdb and sqlite are aligned.
         // If rocksdb.chain_tip.height <= sqlite.chain_tip.height
         // Perform some block compression until that height.
@@ -575,15 +763,39 @@ impl Service {
         // Start predicate processor
         let mut last_block_processed = 0;
         while let Some((start_block, end_block, speed)) =
-            should_sync_ordhook_db(&self.config, &self.ctx)?
+            should_sync_ordhook_db(&self.config, &self.ctx).await?
         {
             if last_block_processed == end_block {
                 break;
             }
+            let block_post_processor = match self.inscription_events_tx {
+                Some(ref events_tx) => Some(start_inscription_events_forwarding(
+                    events_tx.clone(),
+                    block_post_processor.clone(),
+                    self.config.expected_cache_path(),
+                    &self.ctx,
+                )),
+                None => block_post_processor.clone(),
+            };
+            let mut post_processors = self.post_processors.clone();
+            if let Some(block_post_processor) = block_post_processor {
+                post_processors.register_sender(block_post_processor);
+            }
             let blocks_post_processor = start_inscription_indexing_processor(
                 &self.config,
                 &self.ctx,
-                block_post_processor.clone(),
+                post_processors.into_senders(),
+                self.inscriptions_hot_cache.clone(),
+            );
+            if let Ok(chain_tip_height) = get_chain_tip_height(&self.config).await {
+                let _ = blocks_post_processor
+                    .commands_tx
+                    .send(PostProcessorCommand::SetChainTip(chain_tip_height));
+            }
+            let snapshot_relay_stop_tx = start_snapshot_relay(
+                self.snapshot_requests.1.clone(),
+                blocks_post_processor.commands_tx.clone(),
+                &self.ctx,
             );
 
             self.ctx.try_log(|logger| {
@@ -605,6 +817,7 @@ impl Service {
                 &self.ctx,
             )
             .await?;
+            drop(snapshot_relay_stop_tx);
 
             last_block_processed = end_block;
         }
@@ -637,17 +850,51 @@ impl Service {
     }
 }
 
-fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ctx: &Context) {
+/// Forwards the snapshot requests to the inscription indexing processor listening on
+/// `commands_tx`, until the sender returned is dropped.
+fn start_snapshot_relay(
+    snapshot_rx: Receiver<SnapshotRequest>,
+    commands_tx: Sender<PostProcessorCommand>,
+    ctx: &Context,
+) -> Sender<()> {
+    let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(0);
+    let ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Snapshot relay")
+        .spawn(move || loop {
+            select! {
+                recv(snapshot_rx) -> msg => {
+                    let Ok(request) = msg else { break };
+                    let res = PausedProcessor::pause(&commands_tx)
+                        .and_then(|paused_processor| paused_processor.snapshot(&request.output_dir));
+                    if let Err(ref e) = res {
+                        ctx.try_log(|logger| error!(logger, "Unable to take snapshot: {e}"));
+                    }
+                    let _ = request.reply_tx.send(res);
+                }
+                recv(stop_rx) -> _ => break,
+            }
+        })
+        .expect("unable to spawn thread");
+    stop_tx
+}
+
+fn chainhook_sidecar_mutate_ordhook_db(
+    command: HandleBlock,
+    inscription_events_tx: &Option<broadcast::Sender<InscriptionEventsBatch>>,
+    cache_l2: &Arc<TraversalsCache>,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
     let (blocks_db_rw, inscriptions_db_conn_rw) = match open_readwrite_ordhook_dbs(
         &config.expected_cache_path(),
-        config.resources.ulimit,
-        config.resources.memory_available,
+        &config.resources,
+        config.storage.sqlite_wal,
         &ctx,
     ) {
         Ok(dbs) => dbs,
         Err(e) => {
             ctx.try_log(|logger| error!(logger, "Unable to open readwtite connection: {e}",));
-            return;
+            return Ok(());
         }
     };
 
@@ -659,6 +906,23 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                     "Re-org handling: reverting changes in block #{}", block.block_identifier.index
                 )
             });
+            if !is_stored_block(&block.block_identifier, &blocks_db_rw) {
+                ctx.try_log(|logger| {
+                    warn!(
+                        logger,
+                        "Re-org handling: block {} is not the block stored at this height, skipping",
+                        block.block_identifier
+                    )
+                });
+                return Ok(());
+            }
+            check_rollback_depth(
+                block.block_identifier.index,
+                find_last_block_inserted(&blocks_db_rw) as u64,
+                config.max_rollback_depth,
+            )
+            .map_err(|e| e.to_string())?;
+            cache_l2.forget_block(block.block_identifier.index as u32);
             if let Err(e) = delete_data_in_ordhook_db(
                 block.block_identifier.index,
                 block.block_identifier.index,
@@ -672,6 +936,11 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                         "Unable to rollback bitcoin block {}: {e}", block.block_identifier
                     )
                 });
+            } else if let Err(e) = write_checkpoint(
+                &config.expected_cache_path(),
+                &block.parent_block_identifier,
+            ) {
+                ctx.try_log(|logger| error!(logger, "Unable to write checkpoint: {e}"));
             }
         }
         HandleBlock::ApplyBlock(block) => {
@@ -686,9 +955,10 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                             e.to_string()
                         )
                     });
-                    return;
+                    return Ok(());
                 }
             };
+            cache_l2.forget_block(block.block_identifier.index as u32);
             insert_entry_in_blocks(
                 block.block_identifier.index as u32,
                 &block_bytes,
@@ -696,13 +966,40 @@ fn chainhook_sidecar_mutate_ordhook_db(command: HandleBlock, config: &Config, ct
                 &blocks_db_rw,
                 &ctx,
             );
+            insert_block_hash(
+                block.block_identifier.index,
+                &block.block_identifier.hash,
+                &blocks_db_rw,
+                &ctx,
+            );
             let _ = blocks_db_rw.flush();
 
-            update_ordinals_db_with_block(&block, &inscriptions_db_conn_rw, ctx);
+            update_ordinals_db_with_block(&block, &inscriptions_db_conn_rw, ctx)
+                .map_err(|e| e.at_block(block.block_identifier.index).to_string())?;
+
+            update_sequence_metadata_with_block(&block, &inscriptions_db_conn_rw, &ctx)
+                .map_err(|e| e.at_block(block.block_identifier.index).to_string())?;
 
-            update_sequence_metadata_with_block(&block, &inscriptions_db_conn_rw, &ctx);
+            if let Err(e) = write_checkpoint(&config.expected_cache_path(), &block.block_identifier)
+            {
+                ctx.try_log(|logger| error!(logger, "Unable to write checkpoint: {e}"));
+            }
+
+            if let Some(events_tx) = inscription_events_tx {
+                publish_inscription_events(&block, events_tx, &config.expected_cache_path(), ctx);
+            }
         }
     }
+    Ok(())
+}
+
+/// Whether `block_identifier` is the block indexed at its height. A block missing from the
+/// height_to_hash index was never indexed, or was already rolled back.
+fn is_stored_block(block_identifier: &BlockIdentifier, blocks_db: &DB) -> bool {
+    match find_block_hash_at_block_height(block_identifier.index, blocks_db) {
+        Some(hash) => hash.eq(&block_identifier.hash),
+        None => false,
+    }
 }
 
 pub fn start_observer_forwarding(
@@ -743,28 +1040,47 @@ pub fn start_observer_forwarding(
 pub fn chainhook_sidecar_mutate_blocks(
     blocks_to_mutate: &mut Vec<BitcoinBlockDataCached>,
     blocks_ids_to_rollback: &Vec<BlockIdentifier>,
-    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    cache_l2: &Arc<TraversalsCache>,
     config: &Config,
     ctx: &Context,
-) {
+) -> Result<(), String> {
     let mut updated_blocks_ids = vec![];
 
     let (blocks_db_rw, mut inscriptions_db_conn_rw) = match open_readwrite_ordhook_dbs(
         &config.expected_cache_path(),
-        config.resources.ulimit,
-        config.resources.memory_available,
+        &config.resources,
+        config.storage.sqlite_wal,
         &ctx,
     ) {
         Ok(dbs) => dbs,
         Err(e) => {
             ctx.try_log(|logger| error!(logger, "Unable to open readwtite connection: {e}",));
-            return;
+            return Ok(());
         }
     };
 
+    if let Some(lowest_block_id) = blocks_ids_to_rollback.iter().map(|b| b.index).min() {
+        check_rollback_depth(
+            lowest_block_id,
+            find_last_block_inserted(&blocks_db_rw) as u64,
+            config.max_rollback_depth,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
     let inscriptions_db_tx = inscriptions_db_conn_rw.transaction().unwrap();
 
     for block_id_to_rollback in blocks_ids_to_rollback.iter() {
+        if !is_stored_block(block_id_to_rollback, &blocks_db_rw) {
+            ctx.try_log(|logger| {
+                warn!(
+                    logger,
+                    "Re-org handling: block {} is not the block stored at this height, skipping",
+                    block_id_to_rollback
+                )
+            });
+            continue;
+        }
         if let Err(e) = delete_data_in_ordhook_db(
             block_id_to_rollback.index,
             block_id_to_rollback.index,
@@ -799,6 +1115,7 @@ pub fn chainhook_sidecar_mutate_blocks(
             }
         };
 
+        cache_l2.forget_block(cache.block.block_identifier.index as u32);
         insert_entry_in_blocks(
             cache.block.block_identifier.index as u32,
             &block_bytes,
@@ -806,18 +1123,31 @@ pub fn chainhook_sidecar_mutate_blocks(
             &blocks_db_rw,
             &ctx,
         );
+        insert_block_hash(
+            cache.block.block_identifier.index,
+            &cache.block.block_identifier.hash,
+            &blocks_db_rw,
+            &ctx,
+        );
         let _ = blocks_db_rw.flush();
 
         if cache.processed_by_sidecar {
-            update_ordinals_db_with_block(&cache.block, &inscriptions_db_tx, &ctx);
-            update_sequence_metadata_with_block(&cache.block, &inscriptions_db_tx, &ctx);
+            update_ordinals_db_with_block(&cache.block, &inscriptions_db_tx, &ctx)
+                .map_err(|e| e.at_block(cache.block.block_identifier.index).to_string())?;
+            update_sequence_metadata_with_block(&cache.block, &inscriptions_db_tx, &ctx)
+                .map_err(|e| e.at_block(cache.block.block_identifier.index).to_string())?;
         } else {
             updated_blocks_ids.push(format!("{}", cache.block.block_identifier.index));
 
-            parse_inscriptions_in_standardized_block(&mut cache.block, &ctx);
+            parse_inscriptions_in_standardized_block(
+                &mut cache.block,
+                ordhook_config.index_annex_envelopes,
+                &ctx,
+            );
 
             let mut cache_l1 = BTreeMap::new();
-            let mut sequence_cursor = SequenceCursor::new(&inscriptions_db_tx);
+            let mut sequence_cursor =
+                SequenceCursor::new(&inscriptions_db_tx, ordhook_config.chain);
 
             let _ = process_block(
                 &mut cache.block,
@@ -826,11 +1156,13 @@ pub fn chainhook_sidecar_mutate_blocks(
                 &mut cache_l1,
                 &cache_l2,
                 &inscriptions_db_tx,
+                // Rolled back below, the contents are not stored
+                &HashMap::new(),
                 &ordhook_config,
                 &ctx,
             );
 
-            let inscriptions_revealed = get_inscriptions_revealed_in_block(&cache.block)
+            let inscriptions_revealed = get_inscriptions_revealed_in_block(&cache.block, None)
                 .iter()
                 .map(|d| d.get_inscription_number().to_string())
                 .collect::<Vec<String>>();
@@ -851,4 +1183,5 @@ pub fn chainhook_sidecar_mutate_blocks(
         }
     }
     let _ = inscriptions_db_tx.rollback();
+    Ok(())
 }
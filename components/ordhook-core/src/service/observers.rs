@@ -20,7 +20,7 @@ use crate::{
     config::Config,
     db::{
         create_or_open_readwrite_db, open_existing_readonly_db, perform_query_one,
-        perform_query_set,
+        perform_query_set, set_journal_mode,
     },
     scan::bitcoin::process_block_with_predicates,
 };
@@ -100,15 +100,21 @@ pub fn open_readonly_observers_db_conn(
 
 pub fn open_readwrite_observers_db_conn(
     base_dir: &PathBuf,
+    sqlite_wal: bool,
     ctx: &Context,
 ) -> Result<Connection, String> {
     let db_path = get_default_observers_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(&db_path, ctx);
+    set_journal_mode(&conn, sqlite_wal)?;
     Ok(conn)
 }
 
-pub fn open_readwrite_observers_db_conn_or_panic(base_dir: &PathBuf, ctx: &Context) -> Connection {
-    let conn = match open_readwrite_observers_db_conn(base_dir, ctx) {
+pub fn open_readwrite_observers_db_conn_or_panic(
+    base_dir: &PathBuf,
+    sqlite_wal: bool,
+    ctx: &Context,
+) -> Connection {
+    let conn = match open_readwrite_observers_db_conn(base_dir, sqlite_wal, ctx) {
         Ok(con) => con,
         Err(message) => {
             error!(ctx.expect_logger(), "Storage: {}", message.to_string());
@@ -118,11 +124,16 @@ pub fn open_readwrite_observers_db_conn_or_panic(base_dir: &PathBuf, ctx: &Conte
     conn
 }
 
-pub fn initialize_observers_db(base_dir: &PathBuf, ctx: &Context) -> Connection {
+pub fn initialize_observers_db(
+    base_dir: &PathBuf,
+    sqlite_wal: bool,
+    ctx: &Context,
+) -> Result<Connection, String> {
     let db_path = get_default_observers_db_file_path(&base_dir);
     let conn = create_or_open_readwrite_db(&db_path, ctx);
+    set_journal_mode(&conn, sqlite_wal)?;
     // TODO: introduce initial output
-    if let Err(e) = conn.execute(
+    conn.execute(
         "CREATE TABLE IF NOT EXISTS observers (
             uuid TEXT NOT NULL PRIMARY KEY,
             spec TEXT NOT NULL,
@@ -130,16 +141,9 @@ pub fn initialize_observers_db(base_dir: &PathBuf, ctx: &Context) -> Connection
             last_block_height_update INTEGER NOT NULL
         )",
         [],
-    ) {
-        ctx.try_log(|logger| {
-            warn!(
-                logger,
-                "Unable to create table inscriptions: {}",
-                e.to_string()
-            )
-        });
-    }
-    conn
+    )
+    .map_err(|e| format!("unable to create table observers: {e}"))?;
+    Ok(conn)
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
@@ -278,7 +282,11 @@ pub fn create_and_consolidate_chainhook_config_with_predicates(
         ));
     }
 
-    let observers_db_conn = initialize_observers_db(&config.expected_cache_path(), ctx);
+    let observers_db_conn = initialize_observers_db(
+        &config.expected_cache_path(),
+        config.storage.sqlite_wal,
+        ctx,
+    )?;
 
     let mut observers_to_catchup = vec![];
     let mut observers_to_clean_up = vec![];
@@ -0,0 +1,744 @@
+use std::{net::IpAddr, path::PathBuf, sync::Arc};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chainhook_sdk::utils::Context;
+use rocket::config::{self, Config, LogLevel};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::{json, Json, Value as JsonValue};
+use rocket::{Build, Rocket, Shutdown, State};
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use tokio::sync::broadcast;
+
+use super::inscription_events::{
+    collect_inscription_events_at_range, InscriptionEvent, InscriptionEventsBatch, OrdinalEvent,
+};
+use crate::config::QueryApiConfig;
+use crate::db::{
+    count_inscriptions_for_block, count_inscriptions_for_sat_name,
+    count_inscriptions_for_sat_range, find_inscription_with_id, get_collection,
+    get_content_type_stats, get_inscription_by_id, get_inscription_content_hash,
+    get_inscription_content_with_type, get_inscriptions_for_block, get_inscriptions_for_sat_name,
+    get_inscriptions_for_sat_range, open_readonly_ordhook_db_conn, InscriptionData,
+    TraversalResult,
+};
+use crate::metrics;
+use crate::utils::hot_cache::InscriptionsHotCache;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 60;
+/// Inscription contents are immutable, they can be cached for as long as clients allow.
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000";
+
+pub async fn start_query_api_server(
+    api_config: QueryApiConfig,
+    ordhook_db_dir_path: PathBuf,
+    hot_cache: Arc<InscriptionsHotCache>,
+    events_tx: Option<broadcast::Sender<InscriptionEventsBatch>>,
+    ctx: Context,
+) -> Result<(), Box<dyn Error>> {
+    let log_level = LogLevel::Off;
+
+    let mut shutdown_config = config::Shutdown::default();
+    shutdown_config.ctrlc = false;
+    shutdown_config.grace = 1;
+    shutdown_config.mercy = 1;
+
+    let address: IpAddr = api_config.host.parse()?;
+
+    let control_config = Config {
+        port: api_config.port,
+        workers: 1,
+        address,
+        keep_alive: 5,
+        temp_dir: std::env::temp_dir().into(),
+        log_level,
+        cli_colors: false,
+        shutdown: shutdown_config,
+        ..Config::default()
+    };
+
+    let ignite = build_query_api(
+        control_config,
+        ordhook_db_dir_path,
+        hot_cache,
+        events_tx,
+        ctx,
+    )
+    .ignite()
+    .await?;
+
+    let _ = std::thread::spawn(move || {
+        let _ = hiro_system_kit::nestable_block_on(ignite.launch());
+    });
+    Ok(())
+}
+
+fn build_query_api(
+    control_config: Config,
+    ordhook_db_dir_path: PathBuf,
+    hot_cache: Arc<InscriptionsHotCache>,
+    events_tx: Option<broadcast::Sender<InscriptionEventsBatch>>,
+    ctx: Context,
+) -> Rocket<Build> {
+    let routes = routes![
+        handle_get_inscription,
+        handle_get_inscriptions_with_sat,
+        handle_get_inscriptions_in_block,
+        handle_get_collection,
+        handle_get_content_type_stats,
+        handle_get_inscription_content,
+        handle_get_events,
+    ];
+
+    rocket::custom(control_config)
+        .manage(ordhook_db_dir_path)
+        .manage(hot_cache)
+        .manage(events_tx)
+        .manage(ctx)
+        .mount("/", routes)
+}
+
+#[get("/inscriptions/<inscription_id>", format = "application/json")]
+fn handle_get_inscription(
+    inscription_id: &str,
+    ordhook_db_dir_path: &State<PathBuf>,
+    hot_cache: &State<Arc<InscriptionsHotCache>>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP GET /inscriptions/{inscription_id}"));
+    let cached = hot_cache.get(&inscription_id.to_string());
+    match cached {
+        Some(_) => metrics::API_HOT_CACHE_HITS.inc(),
+        None => metrics::API_HOT_CACHE_MISSES.inc(),
+    }
+    if let Some(hit_rate) = hot_cache.hit_rate() {
+        metrics::API_HOT_CACHE_HIT_RATE.set(hit_rate);
+    }
+    if let Some(inscription) = cached {
+        return Json(json!({
+            "status": 200,
+            "result": *inscription,
+        }));
+    }
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    match find_inscription_with_id(inscription_id, &db_conn, ctx) {
+        Ok(Some((traversal, block_height))) => {
+            let inscription = serialized_inscription(&traversal, block_height, &db_conn, ctx);
+            hot_cache.insert(inscription_id.to_string(), inscription.clone());
+            Json(json!({
+                "status": 200,
+                "result": inscription,
+            }))
+        }
+        _ => Json(json!({
+            "status": 404,
+            "message": format!("inscription {inscription_id} not found"),
+        })),
+    }
+}
+
+#[get(
+    "/inscriptions?<sat>&<name>&<page>&<page_size>",
+    format = "application/json"
+)]
+fn handle_get_inscriptions_with_sat(
+    sat: Option<u64>,
+    name: Option<&str>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    let (page, page_size, offset) = pagination(page, page_size);
+    let inscriptions = match (sat, name) {
+        (Some(sat), _) => {
+            ctx.try_log(|logger| info!(logger, "Handling HTTP GET /inscriptions?sat={sat}"));
+            get_inscriptions_for_sat_range(sat, sat, page_size, offset, &db_conn, ctx).and_then(
+                |inscriptions| {
+                    let total_count = count_inscriptions_for_sat_range(sat, sat, &db_conn, ctx)?;
+                    Ok((inscriptions, total_count))
+                },
+            )
+        }
+        (None, Some(name)) => {
+            ctx.try_log(|logger| info!(logger, "Handling HTTP GET /inscriptions?name={name}"));
+            get_inscriptions_for_sat_name(name, page_size, offset, &db_conn, ctx).and_then(
+                |inscriptions| {
+                    let total_count = count_inscriptions_for_sat_name(name, &db_conn, ctx)?;
+                    Ok((inscriptions, total_count))
+                },
+            )
+        }
+        (None, None) => {
+            return Json(json!({
+                "status": 400,
+                "message": "either sat or name must be provided",
+            }))
+        }
+    };
+    serialized_inscriptions_page(inscriptions, page, page_size)
+}
+
+#[get(
+    "/block/<block_height>/inscriptions?<page>&<page_size>",
+    format = "application/json"
+)]
+fn handle_get_inscriptions_in_block(
+    block_height: u64,
+    page: Option<u32>,
+    page_size: Option<u32>,
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| {
+        info!(
+            logger,
+            "Handling HTTP GET /block/{block_height}/inscriptions"
+        )
+    });
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    let (page, page_size, offset) = pagination(page, page_size);
+    let inscriptions = get_inscriptions_for_block(block_height, page_size, offset, &db_conn, ctx)
+        .and_then(|inscriptions| {
+            let total_count = count_inscriptions_for_block(block_height, &db_conn, ctx)?;
+            Ok((inscriptions, total_count))
+        });
+    serialized_inscriptions_page(inscriptions, page, page_size)
+}
+
+#[get("/collections/<parent_id>?<depth>", format = "application/json")]
+fn handle_get_collection(
+    parent_id: &str,
+    depth: Option<u32>,
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP GET /collections/{parent_id}"));
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    match get_collection(parent_id, depth.unwrap_or(0), &db_conn, ctx) {
+        Ok(Some(collection)) => Json(json!({
+            "status": 200,
+            "result": {
+                "parent": collection.parent.to_json(),
+                "children": collection
+                    .children
+                    .iter()
+                    .map(OrdinalEvent::to_json)
+                    .collect::<Vec<_>>(),
+            },
+        })),
+        Ok(None) => Json(json!({
+            "status": 404,
+            "message": format!("inscription {parent_id} not found"),
+        })),
+        Err(e) => Json(json!({
+            "status": 500,
+            "message": e,
+        })),
+    }
+}
+
+/// Resolves the `page` (zero-based) and `page_size` query parameters into
+/// `(page, page_size, offset)`, capping the page size to `MAX_PAGE_SIZE`.
+fn pagination(page: Option<u32>, page_size: Option<u32>) -> (u32, u32, u32) {
+    let page = page.unwrap_or(0);
+    let page_size = page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    (page, page_size, page.saturating_mul(page_size))
+}
+
+fn serialized_inscriptions_page(
+    inscriptions: Result<(Vec<InscriptionData>, u64), String>,
+    page: u32,
+    page_size: u32,
+) -> Json<JsonValue> {
+    match inscriptions {
+        Ok((inscriptions, total_count)) => Json(json!({
+            "status": 200,
+            "page": page,
+            "page_size": page_size,
+            "total_count": total_count,
+            "result": inscriptions
+                .iter()
+                .map(OrdinalEvent::to_json)
+                .collect::<Vec<_>>(),
+        })),
+        Err(e) => Json(json!({
+            "status": 500,
+            "message": e,
+        })),
+    }
+}
+
+#[get("/stats/content-types", format = "application/json")]
+fn handle_get_content_type_stats(
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+) -> Json<JsonValue> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP GET /stats/content-types"));
+    let db_conn = match open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx) {
+        Ok(db_conn) => db_conn,
+        Err(e) => {
+            return Json(json!({
+                "status": 500,
+                "message": e,
+            }))
+        }
+    };
+    match get_content_type_stats(&db_conn, ctx) {
+        Ok(stats) => Json(json!({
+            "status": 200,
+            "result": stats
+                .iter()
+                .map(|stat| json!({
+                    "content_type": stat.content_type,
+                    "count": stat.count,
+                    "total_bytes": stat.total_bytes,
+                }))
+                .collect::<Vec<_>>(),
+        })),
+        Err(e) => Json(json!({
+            "status": 500,
+            "message": e,
+        })),
+    }
+}
+
+/// Value of the `If-None-Match` request header, if any.
+struct IfNoneMatch<'r>(Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(request.headers().get_one("If-None-Match")))
+    }
+}
+
+impl IfNoneMatch<'_> {
+    /// Whether the client already holds the representation tagged `etag`. Weak comparison, as
+    /// recommended for `If-None-Match`.
+    fn matches(&self, etag: &str) -> bool {
+        self.0.map_or(false, |value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+        })
+    }
+}
+
+#[derive(Responder)]
+enum InscriptionContentResponse {
+    Cacheable(Vec<u8>, ContentType, Header<'static>, Header<'static>),
+    /// Recursive references resolved inline: referenced inscriptions indexed later would change
+    /// the response, so it is not tagged.
+    Inlined(Vec<u8>, ContentType),
+    #[response(status = 304)]
+    NotModified((), Header<'static>, Header<'static>),
+}
+
+impl InscriptionContentResponse {
+    fn not_modified(etag: String) -> InscriptionContentResponse {
+        InscriptionContentResponse::NotModified(
+            (),
+            Header::new("ETag", etag),
+            Header::new("Cache-Control", IMMUTABLE_CACHE_CONTROL),
+        )
+    }
+}
+
+fn content_etag(content_hash: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(content_hash))
+}
+
+#[get("/content/<inscription_id>?<resolve_recursion>")]
+fn handle_get_inscription_content(
+    inscription_id: &str,
+    resolve_recursion: Option<bool>,
+    if_none_match: IfNoneMatch<'_>,
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+) -> Result<InscriptionContentResponse, Status> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP GET /content/{inscription_id}"));
+    let db_conn = open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx)
+        .map_err(|_| Status::InternalServerError)?;
+    let resolve_recursion = resolve_recursion.unwrap_or(false);
+    let content_hash = get_inscription_content_hash(inscription_id, &db_conn, ctx)
+        .map_err(|_| Status::InternalServerError)?;
+    if let Some(etag) = content_hash.as_ref().map(|hash| content_etag(hash)) {
+        if !resolve_recursion && if_none_match.matches(&etag) {
+            return Ok(InscriptionContentResponse::not_modified(etag));
+        }
+    }
+    let (content, content_type) = get_inscription_content_with_type(inscription_id, &db_conn, ctx)
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+    let parsed_content_type =
+        ContentType::parse_flexible(&content_type).unwrap_or(ContentType::Binary);
+    if resolve_recursion && content_type.starts_with("text/html") {
+        let content = match String::from_utf8(content) {
+            Ok(html) => inline_recursive_references(&html, |inscription_id| {
+                get_inscription_content_with_type(inscription_id, &db_conn, ctx).unwrap_or(None)
+            })
+            .into_bytes(),
+            Err(e) => e.into_bytes(),
+        };
+        return Ok(InscriptionContentResponse::Inlined(
+            content,
+            parsed_content_type,
+        ));
+    }
+    // Inscriptions indexed before contents were hashed are tagged with the hash of their content
+    let etag = match content_hash {
+        Some(content_hash) => content_etag(&content_hash),
+        None => content_etag(&Sha256::digest(&content)),
+    };
+    if if_none_match.matches(&etag) {
+        return Ok(InscriptionContentResponse::not_modified(etag));
+    }
+    Ok(InscriptionContentResponse::Cacheable(
+        content,
+        parsed_content_type,
+        Header::new("ETag", etag),
+        Header::new("Cache-Control", IMMUTABLE_CACHE_CONTROL),
+    ))
+}
+
+/// Value of the `Last-Event-ID` request header, sent by SSE clients when reconnecting.
+struct LastEventId(Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let last_event_id = request
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|value| value.trim().parse().ok());
+        Outcome::Success(LastEventId(last_event_id))
+    }
+}
+
+fn sse_event(event: &InscriptionEvent) -> Event {
+    Event::data(event.to_json().to_string()).id(event.block_height.to_string())
+}
+
+/// Server-Sent Events stream of the inscriptions revealed and transferred, one event per
+/// inscription, identified by its block height. Clients reconnecting with `Last-Event-ID` first
+/// receive the events of the blocks indexed since. Lagging clients are disconnected, for them to
+/// catch up the same way.
+#[get("/events")]
+fn handle_get_events(
+    last_event_id: LastEventId,
+    events_tx: &State<Option<broadcast::Sender<InscriptionEventsBatch>>>,
+    ordhook_db_dir_path: &State<PathBuf>,
+    ctx: &State<Context>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    ctx.try_log(|logger| info!(logger, "Handling HTTP GET /events"));
+    let Some(events_tx) = events_tx.inner() else {
+        return Err(Status::ServiceUnavailable);
+    };
+    // Subscribe before replaying, for the blocks committed in the meantime not to be missed
+    let mut events_rx = events_tx.subscribe();
+    let replayed_batches = match last_event_id.0 {
+        Some(last_block_height) => {
+            let db_conn = open_readonly_ordhook_db_conn(ordhook_db_dir_path, ctx)
+                .map_err(|_| Status::InternalServerError)?;
+            collect_inscription_events_at_range(
+                last_block_height + 1,
+                i64::MAX as u64,
+                &db_conn,
+                ctx,
+            )
+            .map_err(|_| Status::InternalServerError)?
+        }
+        None => vec![],
+    };
+    let replayed_up_to = replayed_batches
+        .last()
+        .map(|batch| batch.block_height)
+        .or(last_event_id.0);
+    let ctx = ctx.inner().clone();
+    Ok(EventStream! {
+        for batch in replayed_batches {
+            for event in batch.events.iter() {
+                yield sse_event(event);
+            }
+        }
+        loop {
+            let batch = tokio::select! {
+                batch = events_rx.recv() => match batch {
+                    Ok(batch) => batch,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        ctx.try_log(|logger| warn!(logger, "SSE client lagging, {skipped} blocks dropped, disconnecting"));
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            if matches!(replayed_up_to, Some(block_height) if batch.block_height <= block_height) {
+                continue;
+            }
+            for event in batch.events.iter() {
+                yield sse_event(event);
+            }
+        }
+    })
+}
+
+/// Rewrites the `src="/content/<id>"` and `fetch('/content/<id>')` references of a recursive
+/// inscription into data URLs, so that clients without recursion support can render it.
+/// Only direct references are inlined; unknown inscriptions are left untouched.
+fn inline_recursive_references<F>(html: &str, mut resolve: F) -> String
+where
+    F: FnMut(&str) -> Option<(Vec<u8>, String)>,
+{
+    const CONTENT_PATH: &str = "/content/";
+    let mut inlined = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(position) = rest.find(CONTENT_PATH) {
+        let (before, after) = rest.split_at(position);
+        inlined.push_str(before);
+        let reference = before.chars().last().and_then(|quote| {
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let opener = before[..before.len() - 1].trim_end();
+            if !opener.ends_with("src=") && !opener.ends_with("fetch(") {
+                return None;
+            }
+            let inscription_id = &after[CONTENT_PATH.len()..];
+            let inscription_id = &inscription_id[..inscription_id.find(quote)?];
+            if !is_inscription_id(inscription_id) {
+                return None;
+            }
+            let (content, content_type) = resolve(inscription_id)?;
+            Some((
+                inscription_id.len(),
+                format!("data:{content_type};base64,{}", BASE64.encode(content)),
+            ))
+        });
+        match reference {
+            Some((inscription_id_len, data_url)) => {
+                inlined.push_str(&data_url);
+                rest = &after[CONTENT_PATH.len() + inscription_id_len..];
+            }
+            None => {
+                inlined.push_str(CONTENT_PATH);
+                rest = &after[CONTENT_PATH.len()..];
+            }
+        }
+    }
+    inlined.push_str(rest);
+    inlined
+}
+
+fn is_inscription_id(value: &str) -> bool {
+    match value.split_once('i') {
+        Some((txid, index)) => {
+            txid.len() == 64
+                && txid.chars().all(|c| c.is_ascii_hexdigit())
+                && !index.is_empty()
+                && index.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+fn serialized_inscription(
+    traversal: &TraversalResult,
+    block_height: u64,
+    db_conn: &Connection,
+    ctx: &Context,
+) -> JsonValue {
+    let inscription_data =
+        get_inscription_by_id(&traversal.get_inscription_id(), db_conn, ctx).unwrap_or(None);
+    json!({
+        "inscription_id": traversal.get_inscription_id(),
+        "inscription_number": {
+            "classic": traversal.inscription_number.classic,
+            "jubilee": traversal.inscription_number.jubilee,
+        },
+        "ordinal_number": traversal.ordinal_number,
+        "sat_name": InscriptionData::display_name(traversal.ordinal_number),
+        "block_height": block_height,
+        "genesis_block_height": block_height,
+        "genesis_tx_id": inscription_data.as_ref().map(|i| i.genesis_tx_id.clone()),
+        "fee": inscription_data.as_ref().and_then(|i| i.fee),
+        "fee_rate": inscription_data.as_ref().and_then(|i| i.fee_rate),
+        "metadata": inscription_data
+            .as_ref()
+            .and_then(|i| serialized_metadata(&i.metadata_json)),
+    })
+}
+
+fn serialized_metadata(metadata_json: &Option<String>) -> Option<JsonValue> {
+    metadata_json
+        .as_ref()
+        .and_then(|metadata| serde_json::from_str(metadata).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::Accept;
+    use rocket::local::blocking::Client;
+
+    use super::*;
+    use crate::db::initialize_ordhook_db;
+    use crate::utils::hot_cache::HotCache;
+    use crate::utils::test_dir::TestWorkingDir;
+
+    fn inscription_id(index: usize) -> String {
+        format!("{:064x}i0", index)
+    }
+
+    /// Query Api serving an hord.sqlite holding 3 inscriptions: 2 revealed in block 100 on
+    /// sats 5000 and 7000, and 1 revealed in block 101 on sat 5000 (reinscription).
+    fn test_client(working_dir: &PathBuf) -> Client {
+        let ctx = Context::empty();
+        let conn = initialize_ordhook_db(working_dir, true, &ctx);
+        for (index, (block_height, ordinal_number)) in
+            [(100u64, 5_000u64), (100, 7_000), (101, 5_000)]
+                .iter()
+                .enumerate()
+        {
+            conn.execute(
+                "INSERT INTO inscriptions (inscription_id, ordinal_number, jubilee_inscription_number, classic_inscription_number, block_height, input_index) VALUES (?1, ?2, ?3, ?3, ?4, 0)",
+                rusqlite::params![inscription_id(index), ordinal_number, index as i64, block_height],
+            )
+            .unwrap();
+        }
+        let control_config = Config {
+            log_level: LogLevel::Off,
+            ..Config::debug_default()
+        };
+        let rocket = build_query_api(
+            control_config,
+            working_dir.clone(),
+            Arc::new(HotCache::new(10)),
+            None,
+            ctx,
+        );
+        Client::tracked(rocket).expect("unable to build the Query Api")
+    }
+
+    fn get_json(client: &Client, uri: &str) -> JsonValue {
+        client
+            .get(uri)
+            .header(Accept::JSON)
+            .dispatch()
+            .into_json()
+            .expect("response is not json")
+    }
+
+    #[test]
+    fn test_get_inscription() {
+        let working_dir = TestWorkingDir::new();
+        let client = test_client(&working_dir);
+
+        let response = get_json(&client, &format!("/inscriptions/{}", inscription_id(1)));
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["result"]["inscription_id"], inscription_id(1));
+        assert_eq!(response["result"]["inscription_number"]["classic"], 1);
+        assert_eq!(response["result"]["ordinal_number"], 7_000);
+        assert_eq!(response["result"]["block_height"], 100);
+
+        // Served from the hot cache the second time
+        let cached = get_json(&client, &format!("/inscriptions/{}", inscription_id(1)));
+        assert_eq!(cached, response);
+
+        let response = get_json(&client, &format!("/inscriptions/{}", inscription_id(3)));
+        assert_eq!(response["status"], 404);
+    }
+
+    #[test]
+    fn test_get_inscriptions_with_sat() {
+        let working_dir = TestWorkingDir::new();
+        let client = test_client(&working_dir);
+
+        let response = get_json(&client, "/inscriptions?sat=5000");
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["total_count"], 2);
+        let inscriptions = response["result"].as_array().unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        assert!(inscriptions
+            .iter()
+            .all(|inscription| inscription["ordinal_number"] == 5_000));
+
+        let response = get_json(&client, "/inscriptions?sat=5000&page=1&page_size=1");
+        assert_eq!(response["page"], 1);
+        assert_eq!(response["page_size"], 1);
+        assert_eq!(response["total_count"], 2);
+        assert_eq!(response["result"].as_array().unwrap().len(), 1);
+
+        let response = get_json(&client, "/inscriptions?sat=6000");
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["total_count"], 0);
+        assert!(response["result"].as_array().unwrap().is_empty());
+
+        let response = get_json(&client, "/inscriptions");
+        assert_eq!(response["status"], 400);
+    }
+
+    #[test]
+    fn test_get_inscriptions_in_block() {
+        let working_dir = TestWorkingDir::new();
+        let client = test_client(&working_dir);
+
+        let response = get_json(&client, "/block/100/inscriptions");
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["total_count"], 2);
+        let inscriptions = response["result"].as_array().unwrap();
+        assert_eq!(inscriptions.len(), 2);
+        assert!(inscriptions
+            .iter()
+            .all(|inscription| inscription["block_height"] == 100));
+
+        let response = get_json(&client, "/block/102/inscriptions");
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["total_count"], 0);
+    }
+}
@@ -47,8 +47,11 @@ pub fn start_bitcoin_scan_runloop(
                     });
 
                     // Update predicate
-                    let mut observers_db_conn =
-                        open_readwrite_observers_db_conn_or_panic(&db_base_dir, &moved_ctx);
+                    let mut observers_db_conn = open_readwrite_observers_db_conn_or_panic(
+                        &db_base_dir,
+                        moved_config.storage.sqlite_wal,
+                        &moved_ctx,
+                    );
                     update_observer_streaming_enabled(
                         &predicate_spec.uuid,
                         false,
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use chainhook_sdk::utils::Context;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::Sha256;
+use tokio::sync::broadcast;
+
+use super::inscription_events::InscriptionEventsBatch;
+use crate::config::WebhookConfig;
+
+pub const SIGNATURE_HEADER: &str = "X-Ordhook-Signature";
+
+/// Hex encoded HMAC-SHA256 of `payload`, keyed with the webhook secret.
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Spawn a thread delivering the inscription events of each committed block to `webhook`.
+/// Deliveries are performed in the background: a slow or failing webhook only delays its own
+/// queue, and blocks are dropped if it falls too far behind the indexer.
+pub fn start_webhook_delivery(
+    webhook: WebhookConfig,
+    mut events_rx: broadcast::Receiver<InscriptionEventsBatch>,
+    ctx: &Context,
+) {
+    let ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Webhook delivery")
+        .spawn(move || {
+            let future = async {
+                let client = Client::new();
+                loop {
+                    let batch = match events_rx.recv().await {
+                        Ok(batch) => batch,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            ctx.try_log(|logger| {
+                                warn!(
+                                    logger,
+                                    "Webhook {} lagging, {skipped} blocks dropped", webhook.url
+                                )
+                            });
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    if let Err(e) = deliver_batch(&client, &webhook, &batch, &ctx).await {
+                        ctx.try_log(|logger| {
+                            error!(
+                                logger,
+                                "Unable to deliver block #{} to webhook {}: {e}",
+                                batch.block_height,
+                                webhook.url
+                            )
+                        });
+                    }
+                }
+            };
+            hiro_system_kit::nestable_block_on(future);
+        })
+        .expect("unable to spawn thread");
+}
+
+async fn deliver_batch(
+    client: &Client,
+    webhook: &WebhookConfig,
+    batch: &InscriptionEventsBatch,
+    ctx: &Context,
+) -> Result<(), String> {
+    let payload = batch.to_json().to_string().into_bytes();
+    let signature = sign_payload(&webhook.secret, &payload);
+
+    let mut attempt = 0;
+    loop {
+        let res = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(payload.clone())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        let err = match res {
+            Ok(_) => return Ok(()),
+            Err(e) => e.to_string(),
+        };
+        if attempt >= webhook.retry_count {
+            return Err(format!("{err} (after {} retries)", webhook.retry_count));
+        }
+        let backoff = Duration::from_secs(1 << attempt.min(6));
+        ctx.try_log(|logger| {
+            warn!(
+                logger,
+                "Webhook {} failed ({err}), retrying in {}s",
+                webhook.url,
+                backoff.as_secs()
+            )
+        });
+        tokio::time::sleep(backoff).await;
+        attempt += 1;
+    }
+}
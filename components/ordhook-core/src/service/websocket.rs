@@ -0,0 +1,141 @@
+use std::net::SocketAddr;
+
+use chainhook_sdk::utils::Context;
+use futures_util::{SinkExt, StreamExt};
+use rocket::http::RawStr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::inscription_events::{InscriptionEvent, InscriptionEventsBatch, OrdinalEvent};
+use crate::config::QueryApiConfig;
+
+#[derive(Clone, Debug, Default)]
+struct InscriptionEventsFilter {
+    sat_min: Option<u64>,
+    sat_max: Option<u64>,
+    content_type: Option<String>,
+}
+
+impl InscriptionEventsFilter {
+    fn from_query(query: &str) -> Result<Self, String> {
+        let mut filter = InscriptionEventsFilter::default();
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = RawStr::new(value)
+                .url_decode()
+                .map_err(|e| format!("invalid query parameter {key}: {e}"))?;
+            match key {
+                "sat_min" => {
+                    filter.sat_min = Some(value.parse().map_err(|_| "invalid sat_min")?);
+                }
+                "sat_max" => {
+                    filter.sat_max = Some(value.parse().map_err(|_| "invalid sat_max")?);
+                }
+                "content_type" => filter.content_type = Some(value.to_string()),
+                _ => return Err(format!("unknown query parameter {key}")),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Events without a content type (transfers) are discarded when filtering by content type.
+    fn matches(&self, event: &InscriptionEvent) -> bool {
+        if matches!(self.sat_min, Some(sat_min) if event.sat() < sat_min) {
+            return false;
+        }
+        if matches!(self.sat_max, Some(sat_max) if event.sat() > sat_max) {
+            return false;
+        }
+        match (&self.content_type, &event.content_type) {
+            (None, _) => true,
+            (Some(expected), Some(content_type)) => content_type.starts_with(expected),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+pub async fn start_websocket_server(
+    api_config: QueryApiConfig,
+    events_tx: broadcast::Sender<InscriptionEventsBatch>,
+    ctx: Context,
+) -> Result<(), String> {
+    let address = format!("{}:{}", api_config.host, api_config.websocket_port);
+    let listener = TcpListener::bind(&address)
+        .await
+        .map_err(|e| format!("unable to bind {address}: {}", e.to_string()))?;
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                ctx.try_log(|logger| warn!(logger, "Unable to accept connection: {e}"));
+                continue;
+            }
+        };
+        let events_rx = events_tx.subscribe();
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_websocket_connection(stream, peer, events_rx, &ctx).await {
+                ctx.try_log(|logger| debug!(logger, "WebSocket connection {peer} closed: {e}"));
+            }
+        });
+    }
+}
+
+async fn handle_websocket_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    mut events_rx: broadcast::Receiver<InscriptionEventsBatch>,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mut filter = InscriptionEventsFilter::default();
+    let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        if request.uri().path() != "/ws" {
+            return Err(error_response(StatusCode::NOT_FOUND, "not found".into()));
+        }
+        filter = InscriptionEventsFilter::from_query(request.uri().query().unwrap_or(""))
+            .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))?;
+        Ok(response)
+    };
+    let ws_stream = tokio_tungstenite::accept_hdr_async(stream, callback)
+        .await
+        .map_err(|e| e.to_string())?;
+    ctx.try_log(|logger| info!(logger, "Handling WebSocket /ws from {peer} ({:?})", filter));
+
+    let (mut ws_tx, mut ws_rx) = ws_stream.split();
+    loop {
+        tokio::select! {
+            batch = events_rx.recv() => {
+                let batch = match batch {
+                    Ok(batch) => batch,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        ctx.try_log(|logger| warn!(logger, "WebSocket client {peer} lagging, {skipped} blocks dropped"));
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                for event in batch.events.iter().filter(|e| filter.matches(e)) {
+                    let payload = event.to_json().to_string();
+                    ws_tx.send(Message::Text(payload)).await.map_err(|e| e.to_string())?;
+                }
+            }
+            msg = ws_rx.next() => {
+                match msg {
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.to_string()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn error_response(status: StatusCode, message: String) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message));
+    *response.status_mut() = status;
+    response
+}
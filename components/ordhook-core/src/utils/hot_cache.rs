@@ -0,0 +1,260 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use arc_swap::ArcSwap;
+use serde_json::Value as JsonValue;
+
+/// Frequencies saturate at 15, the halving keeps them comparable over time.
+const MAX_FREQUENCY: u8 = 15;
+/// Lookups recorded, per entry of capacity, before the frequencies are halved.
+const SAMPLE_SIZE_PER_ENTRY: usize = 10;
+
+/// Inscriptions served from memory by `GET /inscriptions/<id>`, keyed by inscription id.
+pub type InscriptionsHotCache = HotCache<String, JsonValue>;
+
+/// Approximate, aging count of the lookups of every key, cached or not: a count-min sketch of
+/// 4 rows of 4-bit counters, halved every `SAMPLE_SIZE_PER_ENTRY * capacity` lookups. Counters
+/// are atomic, for lookups to be recorded without locking.
+struct FrequencySketch {
+    counters: Vec<AtomicU8>,
+    mask: usize,
+    hasher: RandomState,
+    additions: AtomicUsize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    const ROWS: usize = 4;
+
+    fn new(capacity: usize) -> FrequencySketch {
+        let width = capacity.max(16).next_power_of_two();
+        FrequencySketch {
+            counters: (0..Self::ROWS * width).map(|_| AtomicU8::new(0)).collect(),
+            mask: width - 1,
+            hasher: RandomState::new(),
+            additions: AtomicUsize::new(0),
+            sample_size: SAMPLE_SIZE_PER_ENTRY * capacity.max(1),
+        }
+    }
+
+    /// Index of the counter of `key` in each row.
+    fn indexes<K: Hash>(&self, key: &K) -> [usize; FrequencySketch::ROWS] {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut indexes = [0; Self::ROWS];
+        for (row, index) in indexes.iter_mut().enumerate() {
+            let row_hash = (hash >> (16 * row)) as usize;
+            *index = row * (self.mask + 1) + (row_hash & self.mask);
+        }
+        indexes
+    }
+
+    fn frequency<K: Hash>(&self, key: &K) -> u8 {
+        self.indexes(key)
+            .iter()
+            .map(|index| self.counters[*index].load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn increment<K: Hash>(&self, key: &K) {
+        for index in self.indexes(key) {
+            let _ = self.counters[index].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| {
+                (c < MAX_FREQUENCY).then_some(c + 1)
+            });
+        }
+        // Only the lookup completing the sample halves the counts
+        if self.additions.fetch_add(1, Ordering::Relaxed) + 1 == self.sample_size {
+            for counter in self.counters.iter() {
+                let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| Some(c / 2));
+            }
+            self.additions
+                .fetch_sub(self.sample_size - self.sample_size / 2, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Cache keeping up to `capacity` values. Lookups are lock-free: they read the current snapshot
+/// of the map, held in an `ArcSwap`. Writes copy the snapshot, O(capacity), and swap the copy in.
+/// Once full, a new value is only admitted if its key was looked up more often, lately, than the
+/// least queried key, which it evicts: a burst of one-off queries can't flush the values queried
+/// all the time.
+pub struct HotCache<K: Hash + Eq, V> {
+    entries: ArcSwap<HashMap<K, Arc<V>>>,
+    /// Serializes the writes, for concurrent copies not to lose each other's updates
+    writer: Mutex<()>,
+    frequencies: FrequencySketch,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Eq + Hash + Clone, V> HotCache<K, V> {
+    /// A cache of `capacity` 0 stores nothing.
+    pub fn new(capacity: usize) -> HotCache<K, V> {
+        HotCache {
+            entries: ArcSwap::from_pointee(HashMap::with_capacity(capacity)),
+            writer: Mutex::new(()),
+            frequencies: FrequencySketch::new(capacity),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        if self.capacity == 0 {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        self.frequencies.increment(key);
+        let value = self.entries.load().get(key).cloned();
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let _writer = self.writer.lock().unwrap();
+        let entries = self.entries.load_full();
+        let mut coldest = None;
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let Some((coldest_key, coldest_frequency)) = entries
+                .keys()
+                .map(|cached| (cached, self.frequencies.frequency(cached)))
+                .min_by_key(|(_, frequency)| *frequency)
+            else {
+                return;
+            };
+            if self.frequencies.frequency(&key) <= coldest_frequency {
+                return;
+            }
+            coldest = Some(coldest_key);
+        }
+        let mut updated = HashMap::clone(&entries);
+        if let Some(coldest) = coldest {
+            updated.remove(coldest);
+        }
+        updated.insert(key, Arc::new(value));
+        self.entries.store(Arc::new(updated));
+    }
+
+    /// Drops every value, e.g. once the blocks they were read from were rolled back.
+    pub fn clear(&self) {
+        let _writer = self.writer.lock().unwrap();
+        self.entries.store(Arc::new(HashMap::new()));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.load().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Share of the lookups served from the cache, `None` before the first lookup.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let lookups = hits + self.misses.load(Ordering::Relaxed);
+        match lookups {
+            0 => None,
+            _ => Some(hits as f64 / lookups as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looks `key` up, inserting `value` on a miss, the way the query API uses the cache.
+    fn query(cache: &HotCache<&'static str, u32>, key: &'static str, value: u32) {
+        if cache.get(&key).is_none() {
+            cache.insert(key, value);
+        }
+    }
+
+    #[test]
+    fn evicts_the_least_queried_entry() {
+        let cache = HotCache::new(2);
+        query(&cache, "a", 1);
+        query(&cache, "b", 2);
+        query(&cache, "a", 1);
+        // "c" has to be queried more than "b" to take its place
+        query(&cache, "c", 3);
+        assert_eq!(cache.len(), 2);
+        query(&cache, "c", 3);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a").as_deref(), Some(&1));
+        assert_eq!(cache.get(&"c").as_deref(), Some(&3));
+        assert_eq!(cache.hit_rate(), Some(3.0 / 8.0));
+    }
+
+    #[test]
+    fn new_entries_do_not_evict_the_hot_ones() {
+        let cache = HotCache::new(1);
+        for _ in 0..5 {
+            query(&cache, "hot", 1);
+        }
+        for (key, value) in [("x", 2), ("y", 3), ("z", 4)] {
+            query(&cache, key, value);
+        }
+        assert_eq!(cache.get(&"hot").as_deref(), Some(&1));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn frequencies_age() {
+        let frequencies = FrequencySketch::new(1);
+        for _ in 0..9 {
+            frequencies.increment(&"a");
+        }
+        assert_eq!(frequencies.frequency(&"a"), 9);
+        // The 10th lookup of the sample halves every count
+        frequencies.increment(&"a");
+        assert_eq!(frequencies.frequency(&"a"), 5);
+    }
+
+    #[test]
+    fn clear_drops_every_entry() {
+        let cache = HotCache::new(2);
+        query(&cache, "a", 1);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[test]
+    fn readers_keep_their_snapshot() {
+        let cache = HotCache::new(2);
+        query(&cache, "a", 1);
+        let snapshot = cache.entries.load_full();
+        query(&cache, "b", 2);
+        cache.clear();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot.get(&"a").map(|value| **value), Some(1));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let cache = HotCache::new(0);
+        cache.insert("a", 1);
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.hit_rate(), Some(0.0));
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use futures::{stream, StreamExt};
+use reqwest::Client as HttpClient;
+
+pub const IPFS_GATEWAY_URL: &str = "https://ipfs.io/ipfs";
+const IPFS_GATEWAY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time spent fetching all the contents referenced in a block, whatever their number.
+pub const IPFS_BLOCK_DEADLINE: Duration = Duration::from_secs(15);
+/// Requests in flight to the gateway at any time.
+const IPFS_CONCURRENT_FETCHES: usize = 16;
+/// Largest content downloaded from the gateway, whatever `storage.max_stored_content_bytes`.
+pub const IPFS_CONTENT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+lazy_static! {
+    static ref IPFS_HTTP_CLIENT: HttpClient = HttpClient::builder()
+        .timeout(IPFS_GATEWAY_TIMEOUT)
+        .build()
+        .expect("unable to build IPFS http client");
+}
+
+/// CID (with its optional path) referenced by an inscription whose whole content is
+/// `ipfs://<cid>[/path]` or a bare CID, e.g. `ipfs://bafy.../image.png` or `Qm...`.
+pub fn parse_ipfs_cid(content: &[u8]) -> Option<String> {
+    let content = std::str::from_utf8(content).ok()?.trim();
+    let reference = content.strip_prefix("ipfs://").unwrap_or(content);
+    let cid = reference.split('/').next()?;
+    if is_cid_v0(cid) || is_cid_v1(cid) {
+        Some(reference.to_string())
+    } else {
+        None
+    }
+}
+
+/// Base58 encoded sha2-256 multihash.
+fn is_cid_v0(cid: &str) -> bool {
+    cid.len() == 46
+        && cid.starts_with("Qm")
+        && cid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l'))
+}
+
+/// Multibase base32 (`b` prefix), the encoding used by the gateways and the IPFS CLI.
+fn is_cid_v1(cid: &str) -> bool {
+    cid.len() >= 59
+        && cid.starts_with('b')
+        && cid
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+}
+
+/// Fetch the content referenced by `cid` from the public IPFS gateway, giving up on the
+/// contents larger than `max_bytes`.
+pub async fn fetch_ipfs_content(
+    client: &HttpClient,
+    cid: &str,
+    max_bytes: u64,
+) -> Result<Vec<u8>, String> {
+    let url = format!("{IPFS_GATEWAY_URL}/{cid}");
+    let mut res = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("unable to reach {url}: {e}"))?;
+    if !res.status().is_success() {
+        return Err(format!("{url} answered {}", res.status()));
+    }
+    let too_large = || format!("content of {url} over the {max_bytes} bytes limit");
+    if res.content_length().map_or(false, |len| len > max_bytes) {
+        return Err(too_large());
+    }
+    let mut content = vec![];
+    while let Some(chunk) = res
+        .chunk()
+        .await
+        .map_err(|e| format!("unable to download {url}: {e}"))?
+    {
+        if (content.len() + chunk.len()) as u64 > max_bytes {
+            return Err(too_large());
+        }
+        content.extend_from_slice(&chunk);
+    }
+    Ok(content)
+}
+
+/// Blocking, batched version of `fetch_ipfs_content` for the indexing threads: the contents
+/// are fetched concurrently, under a single `IPFS_BLOCK_DEADLINE` for the whole batch. Results
+/// are returned in the order of `cids`, the contents missing the deadline being errors.
+pub fn resolve_ipfs_contents(cids: &[String], max_bytes: u64) -> Vec<Result<Vec<u8>, String>> {
+    let max_bytes = max_bytes.min(IPFS_CONTENT_MAX_BYTES);
+    hiro_system_kit::nestable_block_on(async {
+        let deadline = tokio::time::Instant::now() + IPFS_BLOCK_DEADLINE;
+        stream::iter(cids.iter())
+            .map(|cid| async move {
+                tokio::time::timeout_at(
+                    deadline,
+                    fetch_ipfs_content(&IPFS_HTTP_CLIENT, cid, max_bytes),
+                )
+                .await
+                .unwrap_or_else(|_| {
+                    Err(format!(
+                        "{cid} not fetched within the {}s block deadline",
+                        IPFS_BLOCK_DEADLINE.as_secs()
+                    ))
+                })
+            })
+            .buffered(IPFS_CONCURRENT_FETCHES)
+            .collect()
+            .await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cid_references() {
+        let cid_v0 = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+        let cid_v1 = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+        assert_eq!(parse_ipfs_cid(cid_v0.as_bytes()), Some(cid_v0.to_string()));
+        assert_eq!(
+            parse_ipfs_cid(format!("ipfs://{cid_v1}/image.png\n").as_bytes()),
+            Some(format!("{cid_v1}/image.png"))
+        );
+        assert_eq!(parse_ipfs_cid(b"hello world"), None);
+        assert_eq!(parse_ipfs_cid(b"ipfs://not-a-cid"), None);
+        assert_eq!(parse_ipfs_cid(&[0xff, 0xfe]), None);
+    }
+}
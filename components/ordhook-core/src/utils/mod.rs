@@ -1,9 +1,32 @@
+pub mod hot_cache;
+pub mod ipfs;
+pub mod rpc;
+#[cfg(test)]
+pub(crate) mod test_dir;
+
 use std::{
     fs,
     io::{Read, Write},
     path::PathBuf,
 };
 
+use slog::{Drain, FnValue, Logger, PushFnValue};
+
+/// Logger serializing each record as a single-line JSON object with `timestamp`, `level`
+/// and `message` fields, followed by the key-values of the record (e.g. `block_height`).
+pub fn new_json_logger() -> Logger {
+    let drain = slog_json::Json::new(std::io::stdout())
+        .add_key_value(slog::o!(
+            "timestamp" => FnValue(|_| chrono::Utc::now().to_rfc3339()),
+            "level" => FnValue(|record| record.level().as_str()),
+            "message" => PushFnValue(|record, serializer| serializer.emit(record.msg())),
+        ))
+        .build()
+        .fuse();
+    let drain = slog_async::Async::new(drain).build().fuse();
+    Logger::root(drain, slog::o!())
+}
+
 pub fn read_file_content_at_path(file_path: &PathBuf) -> Result<Vec<u8>, String> {
     use std::fs::File;
     use std::io::BufReader;
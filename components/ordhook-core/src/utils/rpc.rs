@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chainhook_sdk::bitcoincore_rpc::bitcoin::{BlockHash, Txid};
+use chainhook_sdk::bitcoincore_rpc::json::{GetBlockHeaderResult, GetRawTransactionResult};
+use chainhook_sdk::bitcoincore_rpc::jsonrpc::Response;
+use chainhook_sdk::indexer::bitcoin::{
+    build_http_client, parse_downloaded_block, BitcoinBlockFullBreakdown,
+};
+use chainhook_sdk::observer::BitcoinConfig;
+use chainhook_sdk::utils::Context;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use reqwest::Client as HttpClient;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value as JsonValue};
+
+lazy_static! {
+    /// Clients created with the same rate share one budget, so that the requests sent by the
+    /// different parts of the process add up to `bitcoin.rpc_request_per_second`.
+    static ref SHARED_LIMITERS: Mutex<HashMap<NonZeroU32, Arc<DefaultDirectRateLimiter>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Http client used for every request sent to bitcoind, throttled to
+/// `bitcoin.rpc_request_per_second` when set, to avoid being answered with
+/// `429 Too Many Requests` by rate-limited RPC providers during the initial sync.
+/// Every attempt waits on the limiter, retries included.
+/// Cloning is cheap, and all the clones share the same budget.
+#[derive(Clone)]
+pub struct RateLimitedRpcClient {
+    http_client: HttpClient,
+    limiter: Option<Arc<DefaultDirectRateLimiter>>,
+}
+
+impl RateLimitedRpcClient {
+    pub fn new(requests_per_second: Option<u32>) -> RateLimitedRpcClient {
+        RateLimitedRpcClient {
+            http_client: build_http_client(),
+            limiter: requests_per_second.and_then(NonZeroU32::new).map(|rps| {
+                SHARED_LIMITERS
+                    .lock()
+                    .unwrap()
+                    .entry(rps)
+                    .or_insert_with(|| Arc::new(RateLimiter::direct(Quota::per_second(rps))))
+                    .clone()
+            }),
+        }
+    }
+
+    async fn throttle(&self) {
+        if let Some(ref limiter) = self.limiter {
+            limiter.until_ready().await;
+        }
+    }
+
+    /// Sends a single JSON-RPC request, once the limiter allows it.
+    async fn send(
+        &self,
+        method: &str,
+        params: JsonValue,
+        bitcoin_config: &BitcoinConfig,
+    ) -> Result<(reqwest::StatusCode, Vec<u8>), String> {
+        self.throttle().await;
+        let body = json!({
+            "jsonrpc": "1.0",
+            "id": "ordhook",
+            "method": method,
+            "params": params
+        });
+        let response = self
+            .http_client
+            .post(&bitcoin_config.rpc_url)
+            .basic_auth(&bitcoin_config.username, Some(&bitcoin_config.password))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("unable to send request ({e})"))?;
+        let status = response.status();
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| format!("unable to get bytes ({e})"))?
+            .to_vec();
+        Ok((status, bytes))
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: JsonValue,
+        bitcoin_config: &BitcoinConfig,
+    ) -> Result<T, String> {
+        let (status, bytes) = self.send(method, params, bitcoin_config).await?;
+        // bitcoind answers RPC errors with an error status and a JSON-RPC payload
+        serde_json::from_slice::<Response>(&bytes)
+            .map_err(|_| format!("unable to parse response ({status})"))?
+            .result::<T>()
+            .map_err(|e| format!("unable to parse response ({e})"))
+    }
+
+    async fn wait_before_retry(description: &str, errors_count: u32, e: &str, ctx: &Context) {
+        if errors_count > 3 {
+            ctx.try_log(|logger| {
+                warn!(
+                    logger,
+                    "unable to {description} ({e}): will retry in a few seconds (attempt #{errors_count})",
+                )
+            });
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    pub async fn get_block_count(&self, bitcoin_config: &BitcoinConfig) -> Result<u64, String> {
+        self.call("getblockcount", json!([]), bitcoin_config).await
+    }
+
+    pub async fn get_raw_transaction_info(
+        &self,
+        txid: &Txid,
+        bitcoin_config: &BitcoinConfig,
+    ) -> Result<GetRawTransactionResult, String> {
+        self.call(
+            "getrawtransaction",
+            json!([txid.to_string(), true]),
+            bitcoin_config,
+        )
+        .await
+    }
+
+    pub async fn get_block_header_info(
+        &self,
+        block_hash: &BlockHash,
+        bitcoin_config: &BitcoinConfig,
+    ) -> Result<GetBlockHeaderResult, String> {
+        self.call(
+            "getblockheader",
+            json!([block_hash.to_string(), true]),
+            bitcoin_config,
+        )
+        .await
+    }
+
+    pub async fn retrieve_block_hash_with_retry(
+        &self,
+        block_height: &u64,
+        bitcoin_config: &BitcoinConfig,
+        ctx: &Context,
+    ) -> Result<String, String> {
+        let mut errors_count = 0;
+        loop {
+            match self
+                .call("getblockhash", json!([block_height]), bitcoin_config)
+                .await
+            {
+                Ok(block_hash) => return Ok(block_hash),
+                Err(e) => {
+                    errors_count += 1;
+                    let description = format!("retrieve block hash #{block_height}");
+                    Self::wait_before_retry(&description, errors_count, &e, ctx).await;
+                }
+            }
+        }
+    }
+
+    async fn download_block_with_retry(
+        &self,
+        block_hash: &str,
+        bitcoin_config: &BitcoinConfig,
+        ctx: &Context,
+    ) -> Vec<u8> {
+        let mut errors_count = 0;
+        loop {
+            let res = match self
+                .send("getblock", json!([block_hash, 3]), bitcoin_config)
+                .await
+            {
+                Ok((status, bytes)) if status.is_success() => return bytes,
+                Ok((status, _)) => format!("status {status}"),
+                Err(e) => e,
+            };
+            errors_count += 1;
+            let description = format!("fetch block {block_hash}");
+            Self::wait_before_retry(&description, errors_count, &res, ctx).await;
+        }
+    }
+
+    pub async fn try_download_block_bytes_with_retry(
+        self,
+        block_height: u64,
+        bitcoin_config: BitcoinConfig,
+        ctx: Context,
+    ) -> Result<Vec<u8>, String> {
+        let block_hash = self
+            .retrieve_block_hash_with_retry(&block_height, &bitcoin_config, &ctx)
+            .await?;
+        Ok(self
+            .download_block_with_retry(&block_hash, &bitcoin_config, &ctx)
+            .await)
+    }
+
+    pub async fn download_and_parse_block_with_retry(
+        &self,
+        block_hash: &str,
+        bitcoin_config: &BitcoinConfig,
+        ctx: &Context,
+    ) -> Result<BitcoinBlockFullBreakdown, String> {
+        let mut errors_count = 0;
+        loop {
+            let bytes = self
+                .download_block_with_retry(block_hash, bitcoin_config, ctx)
+                .await;
+            match parse_downloaded_block(bytes) {
+                Ok(block) => return Ok(block),
+                Err(e) => {
+                    errors_count += 1;
+                    let description = format!("parse block {block_hash}");
+                    Self::wait_before_retry(&description, errors_count, &e, ctx).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::{Duration, Instant};
+
+    use chainhook_sdk::types::{BitcoinBlockSignaling, BitcoinNetwork};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Answers each incoming request with the next of `responses`, returns the url to reach it
+    /// and the number of requests served.
+    async fn serve(responses: Vec<(u16, &'static str)>) -> (String, Arc<Mutex<usize>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let served = Arc::new(Mutex::new(0));
+        let counter = served.clone();
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0u8; 1024];
+                // Requests are small: read until the end of the headers and the json body
+                while !request.ends_with(b"}") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let response = format!(
+                    "HTTP/1.1 {status} -\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                *counter.lock().unwrap() += 1;
+            }
+        });
+        (url, served)
+    }
+
+    fn bitcoin_config(rpc_url: String) -> BitcoinConfig {
+        BitcoinConfig {
+            username: "user".into(),
+            password: "pass".into(),
+            rpc_url,
+            network: BitcoinNetwork::Regtest,
+            bitcoin_block_signaling: BitcoinBlockSignaling::ZeroMQ("".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_requests_answered_with_too_many_requests() {
+        let (url, served) = serve(vec![
+            (429, "Too Many Requests"),
+            (200, r#"{"result":"00ab","error":null,"id":"ordhook"}"#),
+        ])
+        .await;
+        let client = RateLimitedRpcClient::new(Some(50));
+        let block_hash = client
+            .retrieve_block_hash_with_retry(&1, &bitcoin_config(url), &Context::empty())
+            .await
+            .unwrap();
+        assert_eq!(block_hash, "00ab");
+        assert_eq!(*served.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn surfaces_rpc_errors() {
+        let (url, _) = serve(vec![(
+            500,
+            r#"{"result":null,"error":{"code":-5,"message":"No such mempool or blockchain transaction"},"id":"ordhook"}"#,
+        )])
+        .await;
+        let client = RateLimitedRpcClient::new(None);
+        let txid = Txid::from_str(&"00".repeat(32)).unwrap();
+        let err = client
+            .get_raw_transaction_info(&txid, &bitcoin_config(url))
+            .await
+            .unwrap_err();
+        assert!(err.contains("No such mempool or blockchain transaction"));
+    }
+
+    #[tokio::test]
+    async fn clients_with_the_same_rate_share_the_same_budget() {
+        let start = Instant::now();
+        for _ in 0..10 {
+            RateLimitedRpcClient::new(Some(7)).throttle().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn throttles_requests_to_the_configured_rate() {
+        // The bucket starts full: the first second worth of requests goes through at once,
+        // the next ones are spaced by 1 / requests_per_second
+        let client = RateLimitedRpcClient::new(Some(20));
+        let start = Instant::now();
+        for _ in 0..40 {
+            client.throttle().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(900));
+
+        let client = RateLimitedRpcClient::new(None);
+        let start = Instant::now();
+        for _ in 0..40 {
+            client.throttle().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_same_budget() {
+        let client = RateLimitedRpcClient::new(Some(10));
+        let start = Instant::now();
+        for _ in 0..10 {
+            client.throttle().await;
+        }
+        let clone = client.clone();
+        for _ in 0..5 {
+            clone.throttle().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,36 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// Unique directory under the system temp dir, removed on drop so that a panicking test
+/// doesn't leave it behind. The directory itself is created by whatever is opened in it.
+pub(crate) struct TestWorkingDir(PathBuf);
+
+impl TestWorkingDir {
+    pub(crate) fn new() -> TestWorkingDir {
+        let mut path = std::env::temp_dir();
+        path.push(format!("ordhook-test-{}", uuid::Uuid::new_v4()));
+        TestWorkingDir(path)
+    }
+}
+
+impl Deref for TestWorkingDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TestWorkingDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestWorkingDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
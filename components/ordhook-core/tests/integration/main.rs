@@ -0,0 +1,10 @@
+//! Integration tests running the indexer against regtest block fixtures, and against synthetic
+//! testnet4 blocks built on the testnet4 genesis block fixture.
+//! Indexing the first 100 real testnet4 blocks is not covered yet: their fixtures have to be
+//! fetched from a synced testnet4 node, see `fixtures/testnet4/README.md`.
+//! Regtest fixtures can be regenerated with `cargo run -p ordhook --example make-regtest-fixtures`.
+
+mod regtest;
+#[path = "../../src/utils/test_dir.rs"]
+mod test_dir;
+mod testnet4;
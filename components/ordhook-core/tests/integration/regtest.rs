@@ -0,0 +1,151 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use chainhook_sdk::{
+    indexer::bitcoin::{parse_downloaded_block, BitcoinBlockFullBreakdown},
+    types::BitcoinNetwork,
+    utils::Context,
+};
+use ordhook::{
+    config::Config,
+    core::{
+        new_traversals_lazy_cache,
+        pipeline::processors::inscription_indexing::process_blocks,
+        protocol::inscription_parsing::{
+            get_inscriptions_transferred_in_block, parse_inscriptions_and_standardize_block,
+        },
+    },
+    db::{
+        get_inscription_by_id, initialize_ordhook_db, insert_entry_in_blocks,
+        open_ordhook_db_conn_rocks_db_loop, open_readwrite_ordhook_db_conn, BlockBytesCursor,
+    },
+};
+use serde_json::Value as JsonValue;
+
+use crate::test_dir::TestWorkingDir;
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/integration/fixtures/regtest"
+    ))
+}
+
+/// Manifest and blocks `1..=tip` written by `make-regtest-fixtures`.
+fn load_fixtures() -> (JsonValue, Vec<BitcoinBlockFullBreakdown>) {
+    let mut manifest_path = fixtures_dir();
+    manifest_path.push("manifest.json");
+    let manifest_bytes = fs::read(&manifest_path).unwrap_or_else(|_| {
+        panic!(
+            "{} not found, generate the fixtures with `cargo run -p ordhook --example make-regtest-fixtures`",
+            manifest_path.display()
+        )
+    });
+    let manifest: JsonValue = serde_json::from_slice(&manifest_bytes).expect("invalid manifest");
+    let tip = manifest["tip"].as_u64().expect("invalid manifest tip");
+    let blocks = (1..=tip)
+        .map(|block_height| {
+            let mut block_path = fixtures_dir();
+            block_path.push(format!("{block_height}.json"));
+            let bytes = fs::read(&block_path).expect("missing block fixture");
+            parse_downloaded_block(bytes).expect("unable to decode block")
+        })
+        .collect();
+    (manifest, blocks)
+}
+
+#[test]
+fn process_blocks_reveals_and_transfers_regtest_inscriptions() {
+    let (manifest, raw_blocks) = load_fixtures();
+    let ctx = Context::empty();
+    let working_dir = TestWorkingDir::new();
+    fs::create_dir_all(&working_dir).unwrap();
+    let mut config = Config::devnet_default();
+    config.storage.working_dir = working_dir.display().to_string();
+
+    // Traversals read the compacted blocks from hord.rocksdb
+    let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
+        true,
+        &config.expected_cache_path(),
+        &config.resources,
+        &ctx,
+    );
+    let mut blocks = vec![];
+    for raw_block in raw_blocks {
+        let block_bytes = BlockBytesCursor::from_full_block(&raw_block).unwrap();
+        insert_entry_in_blocks(
+            raw_block.height as u32,
+            &block_bytes,
+            true,
+            &blocks_db_rw,
+            &ctx,
+        );
+        let block = parse_inscriptions_and_standardize_block(
+            raw_block,
+            &BitcoinNetwork::Regtest,
+            false,
+            &ctx,
+        )
+        .expect("unable to standardize block");
+        blocks.push(block);
+    }
+    blocks_db_rw.flush().unwrap();
+
+    let _ = initialize_ordhook_db(
+        &config.expected_cache_path(),
+        config.storage.sqlite_wal,
+        &ctx,
+    );
+    let mut inscriptions_db_conn_rw =
+        open_readwrite_ordhook_db_conn(&config.expected_cache_path(), false, &ctx).unwrap();
+    let cache_l2 = Arc::new(new_traversals_lazy_cache(
+        config.resources.traversal_cache_size,
+        &config.resources.cache_eviction_policy,
+    ));
+    let (events_tx, _events_rx) = crossbeam_channel::unbounded();
+    let processed_blocks = process_blocks(
+        &mut blocks,
+        &mut None,
+        &cache_l2,
+        &mut inscriptions_db_conn_rw,
+        &blocks_db_rw,
+        &config.get_ordhook_config(),
+        &[],
+        None,
+        &events_tx,
+        &ctx,
+    )
+    .expect("unable to process blocks");
+
+    let expected_inscriptions = manifest["inscriptions"].as_array().unwrap();
+    let mut numbers = vec![];
+    for expected in expected_inscriptions {
+        let inscription_id = expected["inscription_id"].as_str().unwrap();
+        let inscription = get_inscription_by_id(inscription_id, &inscriptions_db_conn_rw, &ctx)
+            .unwrap()
+            .unwrap_or_else(|| panic!("inscription {inscription_id} not indexed"));
+        assert_eq!(
+            Some(inscription.block_height),
+            expected["block_height"].as_u64()
+        );
+        numbers.push(inscription.inscription_number.jubilee);
+    }
+    numbers.sort();
+    assert_eq!(
+        numbers,
+        (0..expected_inscriptions.len() as i64).collect::<Vec<_>>()
+    );
+
+    for expected in manifest["transfers"].as_array().unwrap() {
+        let inscription_id = expected["inscription_id"].as_str().unwrap();
+        let inscription = get_inscription_by_id(inscription_id, &inscriptions_db_conn_rw, &ctx)
+            .unwrap()
+            .unwrap();
+        let block = processed_blocks
+            .iter()
+            .find(|b| Some(b.block_identifier.index) == expected["block_height"].as_u64())
+            .expect("missing transfer block");
+        assert!(get_inscriptions_transferred_in_block(block)
+            .iter()
+            .any(|transfer| transfer.ordinal_number == inscription.ordinal_number));
+    }
+}
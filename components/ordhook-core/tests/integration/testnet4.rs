@@ -0,0 +1,291 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use chainhook_sdk::{
+    indexer::bitcoin::{parse_downloaded_block, BitcoinBlockFullBreakdown},
+    types::BitcoinNetwork,
+    utils::Context,
+};
+use ordhook::{
+    config::Config,
+    core::{
+        new_traversals_lazy_cache, pipeline::processors::inscription_indexing::process_blocks,
+        protocol::inscription_parsing::parse_inscriptions_and_standardize_block,
+    },
+    db::{
+        get_inscription_by_id, initialize_ordhook_db, insert_entry_in_blocks,
+        open_ordhook_db_conn_rocks_db_loop, open_readwrite_ordhook_db_conn, BlockBytesCursor,
+    },
+    ord::{
+        chain::Chain,
+        height::Height,
+        rarity::{classify_sat_rarity, SatRarity},
+        sat::sat_to_genesis_location,
+    },
+};
+use serde_json::{json, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+use crate::test_dir::TestWorkingDir;
+
+/// Hash of the testnet4 genesis block (BIP94).
+const GENESIS_HASH: &str = "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043";
+const COINBASE_TXID: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+const REVEAL_TXID: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+
+/// `OP_FALSE OP_IF "ord" 1 "text/plain" 0 <body> OP_ENDIF` tapscript, followed by a control block.
+fn envelope_witness(body: &str) -> JsonValue {
+    let mut script = vec![0x00, 0x63, 0x03];
+    script.extend_from_slice(b"ord");
+    script.extend_from_slice(&[0x01, 0x01, 0x0a]);
+    script.extend_from_slice(b"text/plain");
+    script.extend_from_slice(&[0x00, body.len() as u8]);
+    script.extend_from_slice(body.as_bytes());
+    script.push(0x68);
+    json!([hex::encode(script), format!("c0{}", "00".repeat(32))])
+}
+
+fn p2tr_output(value: f64, n: u32) -> JsonValue {
+    json!({
+        "value": value,
+        "n": n,
+        "scriptPubKey": {
+            "asm": "",
+            "hex": format!("5120{}", "ab".repeat(32)),
+        },
+    })
+}
+
+fn coinbase_input() -> JsonValue {
+    json!({ "coinbase": "51", "sequence": 4294967295u32 })
+}
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/integration/fixtures/testnet4"
+    ))
+}
+
+/// Hash, in RPC (reversed) byte order, of `bytes` hashed twice with SHA-256.
+fn double_sha256_hex(bytes: &[u8]) -> String {
+    let mut hash: [u8; 32] = Sha256::digest(Sha256::digest(bytes)).into();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// Header of a `getblock` response, serialized the way it is hashed.
+fn serialized_header(block: &JsonValue) -> Vec<u8> {
+    let hash_bytes = |field: &str| {
+        let mut bytes = match block[field].as_str() {
+            Some(hash) => hex::decode(hash).unwrap(),
+            None => vec![0; 32],
+        };
+        bytes.reverse();
+        bytes
+    };
+    let mut header = vec![];
+    header.extend((block["version"].as_i64().unwrap() as i32).to_le_bytes());
+    header.extend(hash_bytes("previousblockhash"));
+    header.extend(hash_bytes("merkleroot"));
+    header.extend((block["time"].as_u64().unwrap() as u32).to_le_bytes());
+    header.extend(
+        u32::from_str_radix(block["bits"].as_str().unwrap(), 16)
+            .unwrap()
+            .to_le_bytes(),
+    );
+    header.extend((block["nonce"].as_u64().unwrap() as u32).to_le_bytes());
+    header
+}
+
+fn block(height: u64, txs: Vec<JsonValue>) -> BitcoinBlockFullBreakdown {
+    let response = json!({
+        "result": {
+            "hash": format!("{:064x}", height),
+            "height": height,
+            "tx": txs,
+            "time": 1714777860 + height * 600,
+            "nonce": 0,
+            "previousblockhash": match height {
+                1 => GENESIS_HASH.to_string(),
+                _ => format!("{:064x}", height - 1),
+            },
+            "confirmations": 1,
+        },
+        "error": null,
+        "id": "ordhook",
+    });
+    parse_downloaded_block(serde_json::to_vec(&response).unwrap()).expect("invalid block")
+}
+
+/// Synthetic blocks built on the testnet4 genesis block: they are not mined, their hashes are
+/// placeholders. They stand in for the real blocks 1 to 100, not committed yet (see the fixtures
+/// README). Block 1 mints 2 outputs of 25 BTC, both spent in block 2 by a transaction revealing an
+/// inscription in each input. The second one is cursed (not in the first input).
+fn testnet4_blocks() -> Vec<BitcoinBlockFullBreakdown> {
+    let block_1 = block(
+        1,
+        vec![json!({
+            "txid": COINBASE_TXID,
+            "vin": [coinbase_input()],
+            "vout": [p2tr_output(25.0, 0), p2tr_output(25.0, 1)],
+        })],
+    );
+    let spend = |vout: u32, body: &str| {
+        json!({
+            "txid": COINBASE_TXID,
+            "vout": vout,
+            "scriptSig": { "hex": "" },
+            "txinwitness": envelope_witness(body),
+            "prevout": { "height": 1, "value": 25.0 },
+            "sequence": 4294967293u32,
+        })
+    };
+    let block_2 = block(
+        2,
+        vec![
+            json!({
+                "txid": format!("{:064x}", 0xc0),
+                "vin": [coinbase_input()],
+                "vout": [p2tr_output(50.0001, 0)],
+            }),
+            json!({
+                "txid": REVEAL_TXID,
+                "vin": [spend(0, "blessed"), spend(1, "cursed")],
+                "vout": [p2tr_output(49.9999, 0)],
+            }),
+        ],
+    );
+    vec![block_1, block_2]
+}
+
+/// The genesis fixture (`getblock <hash> 3`) hashes to the testnet4 genesis block, and its
+/// coinbase carries the first 50 BTC worth of sats.
+#[test]
+fn testnet4_genesis_fixture_matches_the_chain() {
+    let mut fixture_path = fixtures_dir();
+    fixture_path.push("0.json");
+    let fixture: JsonValue =
+        serde_json::from_slice(&fs::read(&fixture_path).expect("missing genesis fixture"))
+            .expect("invalid genesis fixture");
+    let genesis = &fixture["result"];
+    assert_eq!(genesis["hash"], GENESIS_HASH);
+    assert_eq!(double_sha256_hex(&serialized_header(genesis)), GENESIS_HASH);
+
+    let coinbase = &genesis["tx"][0];
+    let coinbase_bytes = hex::decode(coinbase["hex"].as_str().unwrap()).unwrap();
+    assert_eq!(double_sha256_hex(&coinbase_bytes), coinbase["txid"]);
+    assert_eq!(genesis["merkleroot"], coinbase["txid"]);
+
+    // Sats 0 to 50 BTC (excluded) were minted in the genesis coinbase, then 50 BTC per block
+    // until the first halving, at block 210,000 like on mainnet
+    let subsidy = (coinbase["vout"][0]["value"].as_f64().unwrap() * 100_000_000.0) as u64;
+    assert_eq!(subsidy, 50 * 100_000_000);
+    assert_eq!(Height(0).starting_sat().0, 0);
+    assert_eq!(Height(1).starting_sat().0, subsidy);
+    assert_eq!(sat_to_genesis_location(0), (0, 0));
+    assert_eq!(sat_to_genesis_location(subsidy - 1), (0, subsidy - 1));
+    assert_eq!(classify_sat_rarity(0), SatRarity::Mythic);
+    let first_halving_sat = Height(210_000).starting_sat().0;
+    assert_eq!(first_halving_sat, 210_000 * subsidy);
+    assert_eq!(sat_to_genesis_location(first_halving_sat), (210_000, 0));
+    assert_eq!(classify_sat_rarity(first_halving_sat), SatRarity::Epic);
+}
+
+#[test]
+fn process_blocks_numbers_testnet4_inscriptions() {
+    let ctx = Context::empty();
+    let working_dir = TestWorkingDir::new();
+    fs::create_dir_all(&working_dir).unwrap();
+    let mut config = Config::testnet_default();
+    config.chain = Chain::Testnet4;
+    config.storage.working_dir = working_dir.display().to_string();
+    assert_eq!(config.validate(), Ok(()));
+
+    let blocks_db_rw = open_ordhook_db_conn_rocks_db_loop(
+        true,
+        &config.expected_cache_path(),
+        &config.resources,
+        &ctx,
+    );
+    let mut blocks = vec![];
+    for raw_block in testnet4_blocks() {
+        let block_bytes = BlockBytesCursor::from_full_block(&raw_block).unwrap();
+        insert_entry_in_blocks(
+            raw_block.height as u32,
+            &block_bytes,
+            true,
+            &blocks_db_rw,
+            &ctx,
+        );
+        let block = parse_inscriptions_and_standardize_block(
+            raw_block,
+            &BitcoinNetwork::Testnet,
+            false,
+            &ctx,
+        )
+        .expect("unable to standardize block");
+        blocks.push(block);
+    }
+    blocks_db_rw.flush().unwrap();
+
+    let _ = initialize_ordhook_db(
+        &config.expected_cache_path(),
+        config.storage.sqlite_wal,
+        &ctx,
+    );
+    let mut inscriptions_db_conn_rw =
+        open_readwrite_ordhook_db_conn(&config.expected_cache_path(), false, &ctx).unwrap();
+    let cache_l2 = Arc::new(new_traversals_lazy_cache(
+        config.resources.traversal_cache_size,
+        &config.resources.cache_eviction_policy,
+    ));
+    let (events_tx, _events_rx) = crossbeam_channel::unbounded();
+    process_blocks(
+        &mut blocks,
+        &mut None,
+        &cache_l2,
+        &mut inscriptions_db_conn_rw,
+        &blocks_db_rw,
+        &config.get_ordhook_config(),
+        &[],
+        None,
+        &events_tx,
+        &ctx,
+    )
+    .expect("unable to process blocks");
+
+    let inscription = |index: u32| {
+        get_inscription_by_id(
+            &format!("{REVEAL_TXID}i{index}"),
+            &inscriptions_db_conn_rw,
+            &ctx,
+        )
+        .unwrap()
+        .unwrap_or_else(|| panic!("inscription {REVEAL_TXID}i{index} not indexed"))
+    };
+
+    // Sats are numbered with the mainnet subsidy schedule
+    let blessed = inscription(0);
+    assert_eq!(blessed.ordinal_number, Height(1).starting_sat().0);
+    assert_eq!(sat_to_genesis_location(blessed.ordinal_number), (1, 0));
+    assert_eq!(
+        classify_sat_rarity(blessed.ordinal_number),
+        SatRarity::Uncommon
+    );
+    assert_eq!(blessed.inscription_number.classic, 0);
+    assert_eq!(blessed.inscription_number.jubilee, 0);
+
+    let cursed = inscription(1);
+    assert_eq!(
+        sat_to_genesis_location(cursed.ordinal_number),
+        (1, 25 * 100_000_000)
+    );
+    assert_eq!(
+        classify_sat_rarity(cursed.ordinal_number),
+        SatRarity::Common
+    );
+    // The jubilee is active from the testnet4 genesis: cursed inscriptions are numbered along
+    // with the blessed ones
+    assert_eq!(cursed.inscription_number.classic, -1);
+    assert_eq!(cursed.inscription_number.jubilee, 1);
+}
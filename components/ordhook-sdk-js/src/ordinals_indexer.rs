@@ -56,7 +56,7 @@ impl OrdinalsIndexingRunloop {
 
     // Set-up the observer sidecar - used for augmenting the bitcoin blocks with
     // ordinals informations
-    let observer_sidecar = service
+    let (observer_sidecar, sidecar_failure_rx) = service
       .set_up_observer_sidecar_runloop()
       .expect("unable to setup indexer");
     // Prepare internal predicate
@@ -165,7 +165,11 @@ impl OrdinalsIndexingRunloop {
             let (command_tx, event_rx) =
               hiro_system_kit::nestable_block_on(future).expect("unable to start indexer");
             // Blocking call
-            let _ = service.start_main_runloop(&command_tx, event_rx, None);
+            if let Err(e) =
+              service.start_main_runloop(&command_tx, event_rx, &sidecar_failure_rx, None)
+            {
+              panic!("Runloop error: {e}");
+            }
             break;
           }
           IndexerCommand::ReplayBlocks(blocks) => {